@@ -0,0 +1,1221 @@
+// PBS Admin - AI report, progress note and PII commands
+
+use std::time::Duration;
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::{check_rate_limit, validate_read_path, validate_write_path};
+use crate::audio::detect_audio_duration;
+use crate::documents::run_pandoc_from_stdin;
+use crate::settings::resolve_openai_api_key;
+use crate::transcription::{format_duration_clock, transcribe_audio};
+
+// ============================================================================
+// ANTHROPIC AI API
+// ============================================================================
+
+/// Request body for Anthropic Claude API
+#[derive(Debug, Serialize)]
+struct AnthropicRequest {
+    model: String,
+    max_tokens: u32,
+    system: Vec<AnthropicSystemMessage>,
+    messages: Vec<AnthropicMessage>,
+}
+
+#[derive(Debug, Serialize)]
+struct AnthropicSystemMessage {
+    #[serde(rename = "type")]
+    msg_type: String,
+    text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache_control: Option<AnthropicCacheControl>,
+}
+
+#[derive(Debug, Serialize)]
+struct AnthropicCacheControl {
+    #[serde(rename = "type")]
+    control_type: String,
+}
+
+#[derive(Debug, Serialize)]
+struct AnthropicMessage {
+    role: String,
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicResponse {
+    content: Vec<AnthropicContentBlock>,
+    usage: AnthropicUsage,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicContentBlock {
+    #[serde(rename = "type")]
+    block_type: String,
+    text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicUsage {
+    input_tokens: u32,
+    output_tokens: u32,
+}
+
+/// Log a failed Anthropic API call: rate limits and overload/server errors (worth retrying)
+/// are warnings, anything else (bad key, invalid request) is an error
+fn log_anthropic_api_error(status: reqwest::StatusCode, body: &str) {
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        warn!("Anthropic API error ({}): {}", status, body);
+    } else {
+        error!("Anthropic API error ({}): {}", status, body);
+    }
+}
+
+/// Generate AI report using Anthropic Claude API
+/// API key can be passed directly or read from environment variable
+#[tauri::command]
+pub(crate) async fn generate_ai_report(
+    system_prompt: String,
+    user_prompt: String,
+    max_tokens: u32,
+    api_key: Option<String>,
+    model: Option<String>,
+) -> Result<serde_json::Value, String> {
+    // Rate limit: 1 report per 60 seconds
+    check_rate_limit("ai_report", Duration::from_secs(60))?;
+
+    // Use provided API key, or fall back to environment variable
+    let api_key = if let Some(key) = api_key {
+        if key.is_empty() {
+            return Err("API key cannot be empty".to_string());
+        }
+        key
+    } else {
+        // Fall back to environment variable (for development)
+        // Load .env file from project root
+        let exe_dir = std::env::current_exe()
+            .ok()
+            .and_then(|p| p.parent().map(|p| p.to_path_buf()));
+
+        if let Some(dir) = exe_dir {
+            // Try loading from various locations
+            let _ = dotenvy::from_path(dir.join(".env"));
+            let _ = dotenvy::from_path(dir.join("../.env"));
+            let _ = dotenvy::from_path(dir.join("../../.env"));
+        }
+        // Also try current directory and standard locations
+        let _ = dotenvy::dotenv();
+
+        std::env::var("VITE_ANTHROPIC_API_KEY")
+            .or_else(|_| std::env::var("ANTHROPIC_API_KEY"))
+            .map_err(|_| "Anthropic API key not configured. Please add your API key in Settings > API Keys.".to_string())?
+    };
+
+    let model = model.unwrap_or_else(|| "claude-opus-4-8".to_string());
+
+    info!("Generating AI report with model: {}", model);
+    info!("System prompt length: {} chars", system_prompt.len());
+    info!("User prompt length: {} chars", user_prompt.len());
+    info!("Max tokens: {}", max_tokens);
+
+    // Build request
+    let request = AnthropicRequest {
+        model: model.to_string(),
+        max_tokens,
+        system: vec![AnthropicSystemMessage {
+            msg_type: "text".to_string(),
+            text: system_prompt,
+            cache_control: Some(AnthropicCacheControl {
+                control_type: "ephemeral".to_string(),
+            }),
+        }],
+        messages: vec![AnthropicMessage {
+            role: "user".to_string(),
+            content: user_prompt,
+        }],
+    };
+
+    // Send request to Anthropic API
+    let client = reqwest::Client::new();
+    let response = client
+        .post("https://api.anthropic.com/v1/messages")
+        .header("x-api-key", &api_key)
+        .header("anthropic-version", "2023-06-01")
+        .header("Content-Type", "application/json")
+        .json(&request)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send request to Anthropic API: {}", e))?;
+
+    // Check response status
+    let status = response.status();
+    let response_text = response.text().await
+        .unwrap_or_else(|_| "Unknown error".to_string());
+
+    if !status.is_success() {
+        log_anthropic_api_error(status, &response_text);
+        return Err(format!("Anthropic API error ({}): {}", status, response_text));
+    }
+
+    // Parse response
+    let api_response: AnthropicResponse = serde_json::from_str(&response_text)
+        .map_err(|e| format!("Failed to parse Anthropic response: {}. Response: {}", e, response_text))?;
+
+    // Extract text content
+    let content = api_response.content
+        .iter()
+        .filter_map(|block| {
+            if block.block_type == "text" {
+                block.text.clone()
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    if content.is_empty() {
+        return Err("No text content in Anthropic API response".to_string());
+    }
+
+    info!("AI report generated successfully!");
+    info!("Input tokens: {}, Output tokens: {}",
+        api_response.usage.input_tokens, api_response.usage.output_tokens);
+
+    Ok(serde_json::json!({
+        "success": true,
+        "content": content,
+        "usage": {
+            "input_tokens": api_response.usage.input_tokens,
+            "output_tokens": api_response.usage.output_tokens,
+            "total_tokens": api_response.usage.input_tokens + api_response.usage.output_tokens
+        }
+    }))
+}
+
+/// Generate AI content with Claude's built-in web_search tool enabled.
+///
+/// Used for tasks that need live grounding — e.g. looking up current Australian
+/// medication brand names. Returns the model's final text plus the list of
+/// source URLs it consulted (from web_search_tool_result blocks and inline
+/// citations), so callers can surface citations the way the Perplexity-backed
+/// flow used to.
+#[tauri::command]
+pub(crate) async fn generate_ai_report_with_search(
+    system_prompt: String,
+    user_prompt: String,
+    max_tokens: u32,
+    api_key: Option<String>,
+    model: Option<String>,
+    max_searches: Option<u32>,
+) -> Result<serde_json::Value, String> {
+    // Light backstop only — the frontend aiService rate limiter does the real
+    // throttling. A long cooldown here would break the batched medication flow.
+    check_rate_limit("ai_report_search", Duration::from_secs(2))?;
+
+    // Use provided API key, or fall back to environment variable
+    let api_key = if let Some(key) = api_key {
+        if key.is_empty() {
+            return Err("API key cannot be empty".to_string());
+        }
+        key
+    } else {
+        let exe_dir = std::env::current_exe()
+            .ok()
+            .and_then(|p| p.parent().map(|p| p.to_path_buf()));
+
+        if let Some(dir) = exe_dir {
+            let _ = dotenvy::from_path(dir.join(".env"));
+            let _ = dotenvy::from_path(dir.join("../.env"));
+            let _ = dotenvy::from_path(dir.join("../../.env"));
+        }
+        let _ = dotenvy::dotenv();
+
+        std::env::var("VITE_ANTHROPIC_API_KEY")
+            .or_else(|_| std::env::var("ANTHROPIC_API_KEY"))
+            .map_err(|_| "Anthropic API key not configured. Please add your API key in Settings > API Keys.".to_string())?
+    };
+
+    let model = model.unwrap_or_else(|| "claude-opus-4-8".to_string());
+    let max_searches = max_searches.unwrap_or(5);
+
+    info!("Generating AI report (web search) with model: {}", model);
+    info!("System prompt length: {} chars", system_prompt.len());
+    info!("User prompt length: {} chars", user_prompt.len());
+    info!("Max tokens: {}, Max searches: {}", max_tokens, max_searches);
+
+    // Build request with the web_search server tool, localised to Australia so
+    // pharmacy/PBS results are AU-relevant.
+    let request_body = serde_json::json!({
+        "model": model,
+        "max_tokens": max_tokens,
+        "system": [{
+            "type": "text",
+            "text": system_prompt,
+        }],
+        "messages": [{
+            "role": "user",
+            "content": user_prompt,
+        }],
+        "tools": [{
+            "type": "web_search_20250305",
+            "name": "web_search",
+            "max_uses": max_searches,
+            "user_location": {
+                "type": "approximate",
+                "country": "AU"
+            }
+        }]
+    });
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post("https://api.anthropic.com/v1/messages")
+        .header("x-api-key", &api_key)
+        .header("anthropic-version", "2023-06-01")
+        .header("Content-Type", "application/json")
+        .json(&request_body)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send request to Anthropic API: {}", e))?;
+
+    let status = response.status();
+    let response_text = response
+        .text()
+        .await
+        .unwrap_or_else(|_| "Unknown error".to_string());
+
+    if !status.is_success() {
+        log_anthropic_api_error(status, &response_text);
+        return Err(format!("Anthropic API error ({}): {}", status, response_text));
+    }
+
+    // Parse loosely as Value — web-search responses interleave text blocks,
+    // server_tool_use blocks, and web_search_tool_result blocks.
+    let api_response: serde_json::Value = serde_json::from_str(&response_text)
+        .map_err(|e| format!("Failed to parse Anthropic response: {}. Response: {}", e, response_text))?;
+
+    let empty: Vec<serde_json::Value> = Vec::new();
+    let content_blocks = api_response
+        .get("content")
+        .and_then(|c| c.as_array())
+        .unwrap_or(&empty);
+
+    let mut text_parts: Vec<String> = Vec::new();
+    let mut sources: Vec<String> = Vec::new();
+
+    for block in content_blocks {
+        let block_type = block.get("type").and_then(|t| t.as_str()).unwrap_or("");
+
+        if block_type == "text" {
+            if let Some(t) = block.get("text").and_then(|t| t.as_str()) {
+                text_parts.push(t.to_string());
+            }
+            // Inline citations attached to the text block
+            if let Some(cites) = block.get("citations").and_then(|c| c.as_array()) {
+                for cite in cites {
+                    if let Some(url) = cite.get("url").and_then(|u| u.as_str()) {
+                        sources.push(url.to_string());
+                    }
+                }
+            }
+        } else if block_type == "web_search_tool_result" {
+            // The `content` field is an array of search results, each with a url
+            if let Some(results) = block.get("content").and_then(|c| c.as_array()) {
+                for r in results {
+                    if let Some(url) = r.get("url").and_then(|u| u.as_str()) {
+                        sources.push(url.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    // Dedupe sources while preserving first-seen order
+    let mut seen = std::collections::HashSet::new();
+    sources.retain(|url| seen.insert(url.clone()));
+
+    let content = text_parts.join("\n");
+
+    if content.is_empty() {
+        return Err("No text content in Anthropic API response".to_string());
+    }
+
+    let usage = api_response.get("usage");
+    let input_tokens = usage
+        .and_then(|u| u.get("input_tokens"))
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0);
+    let output_tokens = usage
+        .and_then(|u| u.get("output_tokens"))
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0);
+
+    info!("AI report (web search) generated successfully!");
+    info!(
+        "Input tokens: {}, Output tokens: {}, Sources: {}",
+        input_tokens,
+        output_tokens,
+        sources.len()
+    );
+
+    Ok(serde_json::json!({
+        "success": true,
+        "content": content,
+        "sources": sources,
+        "usage": {
+            "input_tokens": input_tokens,
+            "output_tokens": output_tokens,
+            "total_tokens": input_tokens + output_tokens
+        }
+    }))
+}
+
+// ============================================================================
+// OPENAI PROGRESS NOTES
+// ============================================================================
+
+/// Accumulates an OpenAI Chat Completions server-sent event stream.
+/// Bytes are buffered until a full line arrives, so chunks may split lines (or UTF-8 characters).
+#[derive(Default)]
+struct ChatStreamAccumulator {
+    pending: Vec<u8>,
+    text: String,
+    usage: Option<serde_json::Value>,
+    done: bool,
+}
+
+impl ChatStreamAccumulator {
+    fn push(&mut self, chunk: &[u8]) -> Result<(), String> {
+        self.pending.extend_from_slice(chunk);
+
+        while let Some(newline) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=newline).collect();
+            let line = String::from_utf8_lossy(&line);
+            self.handle_line(line.trim())?;
+        }
+        Ok(())
+    }
+
+    fn handle_line(&mut self, line: &str) -> Result<(), String> {
+        if self.done {
+            return Ok(()); // the rest of a chunk after [DONE]
+        }
+        let data = match line.strip_prefix("data:") {
+            Some(data) => data.trim(),
+            None => return Ok(()), // blank separators, comments, other SSE fields
+        };
+
+        if data == "[DONE]" {
+            self.done = true;
+            return Ok(());
+        }
+
+        let event: serde_json::Value = serde_json::from_str(data)
+            .map_err(|e| format!("Failed to parse stream event: {}", e))?;
+
+        if let Some(error) = event.get("error") {
+            return Err(format!("OpenAI API error: {}", error));
+        }
+        if let Some(content) = event["choices"][0]["delta"]["content"].as_str() {
+            self.text.push_str(content);
+        }
+        if event["usage"].is_object() {
+            self.usage = Some(event["usage"].clone());
+        }
+        Ok(())
+    }
+}
+
+/// Draft a structured SOAP progress note from a consultation transcript with OpenAI (gpt-4o-mini).
+/// The response is streamed and assembled here; an empty `api_key` uses the keychain-stored key.
+#[tauri::command]
+pub(crate) async fn generate_progress_note(
+    transcript: String,
+    template_prompt: String,
+    api_key: String,
+) -> Result<String, String> {
+    // Rate limit: 1 note per 30 seconds
+    check_rate_limit("progress_note", Duration::from_secs(30))?;
+
+    if transcript.trim().is_empty() {
+        return Err("Transcript cannot be empty".to_string());
+    }
+
+    let api_key = resolve_openai_api_key(if api_key.is_empty() { None } else { Some(api_key) })?;
+
+    let (note, _tokens_used) = stream_progress_note(&transcript, &template_prompt, &api_key).await?;
+    Ok(note)
+}
+
+/// Stream a SOAP note draft from gpt-4o-mini. Returns the note text and total tokens used.
+async fn stream_progress_note(transcript: &str, template_prompt: &str, api_key: &str) -> Result<(String, u32), String> {
+    let system_prompt = format!(
+        "You are assisting a veterinary behaviour clinician. Draft a structured progress note \
+         in SOAP format (Subjective, Objective, Assessment, Plan) based only on the consultation \
+         transcript provided.\n\n{}",
+        template_prompt
+    );
+
+    let body = serde_json::json!({
+        "model": "gpt-4o-mini",
+        "stream": true,
+        "stream_options": { "include_usage": true },
+        "messages": [
+            { "role": "system", "content": system_prompt },
+            { "role": "user", "content": transcript },
+        ],
+    });
+
+    info!("Generating progress note (transcript: {} chars)", transcript.len());
+
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(300))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+    let mut response = client
+        .post("https://api.openai.com/v1/chat/completions")
+        .header("Authorization", format!("Bearer {}", api_key))
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send request to OpenAI: {}", e))?;
+
+    if !response.status().is_success() {
+        let error_text = response.text().await
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(format!("OpenAI API error: {}", error_text));
+    }
+
+    let mut stream = ChatStreamAccumulator::default();
+    while let Some(chunk) = response.chunk().await
+        .map_err(|e| format!("Failed to read response stream: {}", e))?
+    {
+        stream.push(&chunk)?;
+        if stream.done {
+            break;
+        }
+    }
+
+    let mut tokens_used = 0;
+    if let Some(usage) = &stream.usage {
+        info!(
+            "Progress note complete. Tokens - prompt: {}, completion: {}, total: {}",
+            usage["prompt_tokens"], usage["completion_tokens"], usage["total_tokens"]
+        );
+        tokens_used = usage["total_tokens"].as_u64().unwrap_or(0) as u32;
+    }
+
+    if stream.text.trim().is_empty() {
+        return Err("OpenAI returned an empty progress note".to_string());
+    }
+
+    Ok((stream.text, tokens_used))
+}
+
+/// Client and session details for a generated session summary
+#[derive(Debug, Deserialize)]
+pub(crate) struct ClientInfo {
+    client_name: String,
+    pet_name: Option<String>,
+    session_date: String,
+    clinician_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct SessionSummaryResult {
+    transcript: String,
+    note_text: String,
+    docx_path: String,
+    duration_seconds: f64,
+    tokens_used: u32,
+}
+
+/// External calls made while producing a session summary, kept behind a trait so the
+/// orchestration can be exercised without FFmpeg, OpenAI or pandoc
+trait SessionSummarySteps {
+    fn detect_duration(&self, audio_path: &str) -> Result<f64, String>;
+    async fn transcribe(&self, audio_path: &str) -> Result<String, String>;
+    async fn draft_note(&self, transcript: &str, template_prompt: &str) -> Result<(String, u32), String>;
+    fn export_docx(&self, markdown: String, output_path: &str, template_path: &str, metadata: serde_json::Value) -> Result<String, String>;
+}
+
+struct OpenAiSessionSummary {
+    api_key: String,
+}
+
+impl SessionSummarySteps for OpenAiSessionSummary {
+    fn detect_duration(&self, audio_path: &str) -> Result<f64, String> {
+        detect_audio_duration(audio_path.to_string())
+    }
+
+    async fn transcribe(&self, audio_path: &str) -> Result<String, String> {
+        // transcribe_audio splits recordings over the upload limit itself
+        let result = transcribe_audio(
+            audio_path.to_string(),
+            None,
+            Some(self.api_key.clone()),
+            None,
+            None,
+            None,
+            None,
+        ).await?;
+        Ok(result.text)
+    }
+
+    async fn draft_note(&self, transcript: &str, template_prompt: &str) -> Result<(String, u32), String> {
+        check_rate_limit("progress_note", Duration::from_secs(30))?;
+        stream_progress_note(transcript, template_prompt, &self.api_key).await
+    }
+
+    fn export_docx(&self, markdown: String, output_path: &str, template_path: &str, metadata: serde_json::Value) -> Result<String, String> {
+        run_pandoc_from_stdin(markdown, output_path.to_string(), Some(template_path.to_string()), Some(metadata))
+    }
+}
+
+/// Prompt context so the note names the client and pet correctly
+fn session_summary_prompt(client_info: &ClientInfo) -> String {
+    let mut prompt = format!("Client: {}\nSession date: {}\n", client_info.client_name, client_info.session_date);
+    if let Some(pet_name) = client_info.pet_name.as_deref().filter(|p| !p.trim().is_empty()) {
+        prompt.push_str(&format!("Pet: {}\n", pet_name));
+    }
+    prompt
+}
+
+/// Run each step in order; errors are prefixed with the step that failed
+async fn run_session_summary<S: SessionSummarySteps>(
+    steps: &S,
+    audio_path: &str,
+    client_info: &ClientInfo,
+    template_path: &str,
+    output_path: &str,
+) -> Result<SessionSummaryResult, String> {
+    // Check inputs before any paid API calls
+    let validated_audio = validate_read_path(audio_path)?;
+    let validated_output = validate_write_path(output_path)?;
+    let is_docx = validated_output
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("docx"))
+        .unwrap_or(false);
+    if !is_docx {
+        return Err(format!("Output path must be a .docx file: {}", output_path));
+    }
+    let audio_path = validated_audio.to_string_lossy().to_string();
+    let output_path = validated_output.to_string_lossy().to_string();
+
+    let duration_seconds = steps.detect_duration(&audio_path)
+        .map_err(|e| format!("Audio detection failed: {}", e))?;
+    info!("Session summary: {:.0}s of audio in {}", duration_seconds, audio_path);
+
+    let transcript = steps.transcribe(&audio_path).await
+        .map_err(|e| format!("Transcription failed: {}", e))?;
+    if transcript.trim().is_empty() {
+        return Err("Transcription failed: no speech found in recording".to_string());
+    }
+
+    let (note_text, tokens_used) = steps.draft_note(&transcript, &session_summary_prompt(client_info)).await
+        .map_err(|e| format!("Note generation failed: {}", e))?;
+
+    let markdown = format!(
+        "**Client:** {}\n**Session date:** {}\n**Clinician:** {}\n**Duration:** {}\n\n{}\n",
+        client_info.client_name,
+        client_info.session_date,
+        client_info.clinician_name,
+        format_duration_clock(duration_seconds),
+        note_text.trim()
+    );
+    let metadata = serde_json::json!({
+        "title": format!("Session Summary - {}", client_info.client_name),
+        "author": client_info.clinician_name,
+        "date": client_info.session_date,
+    });
+    let docx_path = steps.export_docx(markdown, &output_path, template_path, metadata)
+        .map_err(|e| format!("DOCX export failed: {}", e))?;
+
+    info!("Session summary saved to {} ({} tokens)", docx_path, tokens_used);
+    Ok(SessionSummaryResult { transcript, note_text, docx_path, duration_seconds, tokens_used })
+}
+
+/// One-call workflow: detect duration, transcribe (splitting long recordings),
+/// draft the SOAP note and export it to DOCX using `template_path` as the reference document
+#[tauri::command]
+pub(crate) async fn generate_session_summary_docx(
+    audio_path: String,
+    client_info: ClientInfo,
+    template_path: String,
+    output_path: String,
+) -> Result<SessionSummaryResult, String> {
+    let steps = OpenAiSessionSummary { api_key: resolve_openai_api_key(None)? };
+    run_session_summary(&steps, &audio_path, &client_info, &template_path, &output_path).await
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct SoapNote {
+    subjective: String,
+    objective: String,
+    assessment: String,
+    plan: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SoapRules {
+    use_ai: bool,
+    ai_api_key: Option<String>,
+    /// Section name ("subjective", "objective", "assessment", "plan") -> trigger phrases
+    #[serde(default)]
+    keyword_hints: HashMap<String, Vec<String>>,
+}
+
+const SOAP_SECTIONS: [&str; 4] = ["subjective", "objective", "assessment", "plan"];
+
+/// Split text into sentences at . ! ? (followed by whitespace) and line breaks
+fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch == '\n' {
+            sentences.push(std::mem::take(&mut current));
+            continue;
+        }
+        current.push(ch);
+        if matches!(ch, '.' | '!' | '?') && chars.peek().is_none_or(|next| next.is_whitespace()) {
+            sentences.push(std::mem::take(&mut current));
+        }
+    }
+    sentences.push(current);
+
+    sentences.into_iter()
+        .map(|sentence| sentence.trim().to_string())
+        .filter(|sentence| !sentence.is_empty())
+        .collect()
+}
+
+/// Assign each sentence to a SOAP section. A sentence starting with a section header
+/// ("Plan:") or containing one of that section's trigger phrases switches section;
+/// other sentences continue the current one (Subjective at the start).
+fn segment_soap_note(transcript: &str, keyword_hints: &HashMap<String, Vec<String>>) -> Result<SoapNote, String> {
+    let mut hints: Vec<(usize, String)> = Vec::new();
+    for (section, phrases) in keyword_hints {
+        let index = SOAP_SECTIONS.iter()
+            .position(|name| name.eq_ignore_ascii_case(section.trim()))
+            .ok_or_else(|| format!("Unknown SOAP section in keyword hints: {}. Use subjective, objective, assessment or plan.", section))?;
+        hints.extend(phrases.iter()
+            .map(|phrase| phrase.trim().to_lowercase())
+            .filter(|phrase| !phrase.is_empty())
+            .map(|phrase| (index, phrase)));
+    }
+    // Longer phrases first so "treatment plan" beats "plan"
+    hints.sort_by_key(|(_, phrase)| std::cmp::Reverse(phrase.len()));
+
+    let header = regex::Regex::new(r"(?i)^(subjective|objective|assessment|plan)\s*[:\-]\s*")
+        .expect("valid SOAP header regex");
+
+    let mut sections: [Vec<String>; 4] = Default::default();
+    let mut current = 0;
+    for sentence in split_sentences(transcript) {
+        let mut text = sentence.as_str();
+        if let Some(found) = header.captures(text) {
+            let name = found[1].to_lowercase();
+            current = SOAP_SECTIONS.iter().position(|section| *section == name).unwrap_or(current);
+            text = &text[found[0].len()..];
+        } else {
+            let lower = text.to_lowercase();
+            if let Some((index, _)) = hints.iter().find(|(_, phrase)| lower.contains(phrase.as_str())) {
+                current = *index;
+            }
+        }
+        if !text.is_empty() {
+            sections[current].push(text.to_string());
+        }
+    }
+
+    let [subjective, objective, assessment, plan] = sections.map(|sentences| sentences.join(" "));
+    Ok(SoapNote { subjective, objective, assessment, plan })
+}
+
+/// Ask gpt-4o-mini to split the transcript into SOAP sections (JSON mode)
+async fn segment_soap_note_with_ai(base_url: &str, api_key: &str, transcript: &str) -> Result<SoapNote, String> {
+    let body = serde_json::json!({
+        "model": "gpt-4o-mini",
+        "response_format": { "type": "json_object" },
+        "messages": [
+            {
+                "role": "system",
+                "content": "Split this veterinary behaviour consultation transcript into SOAP sections. \
+                            Respond with a JSON object with string fields subjective, objective, assessment \
+                            and plan. Use only information from the transcript; leave a field empty if nothing applies."
+            },
+            { "role": "user", "content": transcript },
+        ],
+    });
+
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(120))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let response = client
+        .post(format!("{}/v1/chat/completions", base_url))
+        .header("Authorization", format!("Bearer {}", api_key))
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send request to OpenAI: {}", e))?;
+
+    if !response.status().is_success() {
+        let error_text = response.text().await
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(format!("OpenAI API error: {}", error_text));
+    }
+
+    let response_json: serde_json::Value = response.json().await
+        .map_err(|e| format!("Failed to parse OpenAI response: {}", e))?;
+    let content = response_json["choices"][0]["message"]["content"]
+        .as_str()
+        .ok_or_else(|| "OpenAI response has no content".to_string())?;
+    serde_json::from_str(content)
+        .map_err(|e| format!("OpenAI returned an invalid SOAP note: {}", e))
+}
+
+/// Structure a raw transcript into SOAP sections, with gpt-4o-mini (`use_ai`) or keyword
+/// heuristics. If the AI call fails the heuristics are used instead.
+#[tauri::command]
+pub(crate) async fn transcription_to_soap_note(transcript: String, rules: SoapRules) -> Result<SoapNote, String> {
+    if transcript.trim().is_empty() {
+        return Err("Transcript cannot be empty".to_string());
+    }
+
+    if rules.use_ai {
+        check_rate_limit("soap_note", Duration::from_secs(30))?;
+        let api_key = resolve_openai_api_key(rules.ai_api_key.filter(|key| !key.is_empty()))?;
+        match segment_soap_note_with_ai("https://api.openai.com", &api_key, &transcript).await {
+            Ok(note) => return Ok(note),
+            Err(e) => warn!("AI SOAP segmentation failed, using keyword rules: {}", e),
+        }
+    }
+
+    segment_soap_note(&transcript, &rules.keyword_hints)
+}
+
+// ============================================================================
+// PII DETECTION AND REDACTION
+// ============================================================================
+
+#[derive(Debug, Serialize, PartialEq)]
+struct PiiFinding {
+    kind: String,
+    value: String,
+    start_char: u32,
+    end_char: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct PiiScanResult {
+    findings: Vec<PiiFinding>,
+}
+
+static PII_PATTERNS: std::sync::OnceLock<Vec<(&'static str, regex::Regex, usize)>> = std::sync::OnceLock::new();
+
+/// PII patterns in priority order; where matches overlap the earlier kind wins.
+/// The third value is the capture group holding the PII itself. Compiled once per process.
+fn pii_patterns() -> &'static [(&'static str, regex::Regex, usize)] {
+    PII_PATTERNS.get_or_init(|| [
+        ("ssn", r"\b\d{3}-\d{2}-\d{4}\b", 0),
+        ("email", r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b", 0),
+        ("phone", r"(?:\+1[\s.-]?)?(?:\(\d{3}\)\s?|\b\d{3}[\s.-])\d{3}[\s.-]\d{4}\b", 0),
+        // Only dates introduced as a birth date; session dates aren't PII on their own
+        ("date_of_birth", r"(?i)\b(?:dob|d\.o\.b\.?|date of birth|birth ?date|born(?: on)?)\s*[:\-]?\s*(\d{1,2}[/.\-]\d{1,2}[/.\-]\d{2,4}|\d{4}-\d{2}-\d{2})", 1),
+        ("zip_code", r"\b\d{5}(?:-\d{4})?\b", 0),
+    ]
+    .into_iter()
+    .map(|(kind, pattern, group)| (kind, regex::Regex::new(pattern).expect("valid PII regex"), group))
+    .collect())
+}
+
+/// Non-overlapping PII matches as (kind, byte range), sorted by position
+fn find_pii(text: &str) -> Vec<(&'static str, std::ops::Range<usize>)> {
+    let mut matches: Vec<(usize, &'static str, std::ops::Range<usize>)> = Vec::new();
+    for (priority, (kind, pattern, group)) in pii_patterns().iter().enumerate() {
+        for captures in pattern.captures_iter(text) {
+            if let Some(found) = captures.get(*group) {
+                matches.push((priority, *kind, found.range()));
+            }
+        }
+    }
+    matches.sort_by_key(|(priority, _, range)| (range.start, *priority));
+
+    let mut accepted: Vec<(&'static str, std::ops::Range<usize>)> = Vec::new();
+    for (priority, kind, range) in &matches {
+        // A higher-priority match covering this one claims the text
+        let overlaps = |other: &std::ops::Range<usize>| range.start < other.end && other.start < range.end;
+        let shadowed = matches.iter()
+            .any(|(other_priority, _, other)| other_priority < priority && overlaps(other));
+        if !shadowed && !accepted.iter().any(|(_, other)| overlaps(other)) {
+            accepted.push((*kind, range.clone()));
+        }
+    }
+    accepted
+}
+
+/// Find SSNs, emails, phone numbers, birth dates and ZIP codes in `text`.
+/// Positions are UTF-16 offsets so they index JavaScript strings directly.
+#[tauri::command]
+pub(crate) fn scan_for_pii(text: String) -> Result<PiiScanResult, String> {
+    let utf16_offset = |byte_index: usize| text[..byte_index].encode_utf16().count() as u32;
+
+    let findings = find_pii(&text)
+        .into_iter()
+        .map(|(kind, range)| PiiFinding {
+            kind: kind.to_string(),
+            value: text[range.clone()].to_string(),
+            start_char: utf16_offset(range.start),
+            end_char: utf16_offset(range.end),
+        })
+        .collect();
+
+    Ok(PiiScanResult { findings })
+}
+
+/// Replace everything scan_for_pii would flag with `replacement` (e.g. "[REDACTED]")
+#[tauri::command]
+pub(crate) fn redact_pii(text: String, replacement: String) -> Result<String, String> {
+    let mut redacted = text.clone();
+    // Replace from the end so earlier ranges stay valid
+    for (_, range) in find_pii(&text).into_iter().rev() {
+        redacted.replace_range(range, &replacement);
+    }
+    Ok(redacted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::Mutex;
+    use crate::system::read_last_lines;
+    use crate::test_support::{path_string, scratch_dir, strings};
+
+    #[test]
+    fn anthropic_api_failures_log_as_warnings_or_errors() {
+        use tracing_subscriber::prelude::*;
+
+        let dir = scratch_dir();
+        let log = dir.path().join("pbs_admin_test.log");
+        let file = fs::File::create(&log).unwrap();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_subscriber::fmt::layer().json().with_ansi(false).with_writer(Mutex::new(file)));
+
+        tracing::subscriber::with_default(subscriber, || {
+            for status in [401, 400, 429, 529, 500] {
+                log_anthropic_api_error(reqwest::StatusCode::from_u16(status).unwrap(), "{}");
+            }
+        });
+
+        let levels: Vec<String> = read_last_lines(&log, 10).unwrap().iter()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["level"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(levels, strings(&["ERROR", "ERROR", "WARN", "WARN", "WARN"]));
+    }
+
+    const PROGRESS_NOTE_SSE_FIXTURE: &str = concat!(
+        ": keep-alive\n\n",
+        "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\",\"content\":\"\"}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{\"content\":\"**Subjective:** Owner reports \"}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{\"content\":\"Max growls at visitors — café trips stopped.\\n\"}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{\"content\":\"**Plan:** Desensitisation.\"}}]}\n\n",
+        "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":120,\"completion_tokens\":30,\"total_tokens\":150}}\n\n",
+        "data: [DONE]\n\n",
+        "data: {\"choices\":[{\"delta\":{\"content\":\"ignored after DONE\"}}]}\n\n",
+    );
+
+    #[test]
+    fn chat_stream_accumulator_assembles_fixture_at_any_chunk_size() {
+        let expected = "**Subjective:** Owner reports Max growls at visitors — café trips stopped.\n**Plan:** Desensitisation.";
+        let bytes = PROGRESS_NOTE_SSE_FIXTURE.as_bytes();
+
+        // Chunk sizes of 1 and 7 split lines and multi-byte characters
+        for chunk_size in [1, 7, 64, bytes.len()] {
+            let mut stream = ChatStreamAccumulator::default();
+            for chunk in bytes.chunks(chunk_size) {
+                stream.push(chunk).unwrap();
+                if stream.done {
+                    break;
+                }
+            }
+            assert!(stream.done);
+            assert_eq!(stream.text, expected, "chunk size {}", chunk_size);
+            assert_eq!(stream.usage.as_ref().unwrap()["total_tokens"], 150);
+        }
+    }
+
+    #[test]
+    fn chat_stream_accumulator_surfaces_errors() {
+        let mut stream = ChatStreamAccumulator::default();
+        let err = stream.push(b"data: {\"error\":{\"message\":\"quota exceeded\"}}\n").unwrap_err();
+        assert!(err.contains("OpenAI API error") && err.contains("quota exceeded"), "{}", err);
+
+        let mut stream = ChatStreamAccumulator::default();
+        let err = stream.push(b"data: {not json}\n").unwrap_err();
+        assert!(err.contains("Failed to parse stream event"), "{}", err);
+
+        // A partial line is held back until its newline arrives
+        let mut stream = ChatStreamAccumulator::default();
+        stream.push(b"data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}").unwrap();
+        assert_eq!(stream.text, "");
+        stream.push(b"\n").unwrap();
+        assert_eq!(stream.text, "Hi");
+    }
+
+    #[tokio::test]
+    async fn generate_progress_note_rejects_empty_transcript() {
+        let err = generate_progress_note("   ".to_string(), String::new(), "sk-test".to_string())
+            .await
+            .unwrap_err();
+        assert_eq!(err, "Transcript cannot be empty");
+    }
+
+    /// Records each step it's asked to run; `fail_at` makes that step return an error
+    #[derive(Default)]
+    struct ScriptedSessionSummary {
+        calls: std::sync::Mutex<Vec<String>>,
+        fail_at: Option<&'static str>,
+        transcript: String,
+    }
+
+    impl ScriptedSessionSummary {
+        fn record(&self, step: &'static str, detail: String) -> Result<(), String> {
+            self.calls.lock().unwrap().push(format!("{}: {}", step, detail));
+            match self.fail_at {
+                Some(failing) if failing == step => Err(format!("{} broke", step)),
+                _ => Ok(()),
+            }
+        }
+
+        fn steps(&self) -> Vec<String> {
+            self.calls.lock().unwrap().iter().map(|c| c.split(':').next().unwrap().to_string()).collect()
+        }
+    }
+
+    impl SessionSummarySteps for ScriptedSessionSummary {
+        fn detect_duration(&self, audio_path: &str) -> Result<f64, String> {
+            self.record("detect", audio_path.to_string())?;
+            Ok(3725.0)
+        }
+
+        async fn transcribe(&self, audio_path: &str) -> Result<String, String> {
+            self.record("transcribe", audio_path.to_string())?;
+            Ok(self.transcript.clone())
+        }
+
+        async fn draft_note(&self, transcript: &str, template_prompt: &str) -> Result<(String, u32), String> {
+            self.record("draft", format!("{} | {}", transcript, template_prompt.replace('\n', " / ")))?;
+            Ok(("Subjective: barking at visitors.\n".to_string(), 812))
+        }
+
+        fn export_docx(&self, markdown: String, output_path: &str, template_path: &str, metadata: serde_json::Value) -> Result<String, String> {
+            self.record("export", format!("{} | {} | {} | {}", output_path, template_path, metadata["title"], markdown))?;
+            Ok(output_path.to_string())
+        }
+    }
+
+    fn sample_client_info() -> ClientInfo {
+        ClientInfo {
+            client_name: "Jane Smith".to_string(),
+            pet_name: Some("Max".to_string()),
+            session_date: "2025-03-14".to_string(),
+            clinician_name: "Dr Glenn".to_string(),
+        }
+    }
+
+    /// An audio file and a .docx output path inside a scratch dir
+    fn session_summary_paths(dir: &Path) -> (String, String) {
+        let audio = dir.join("consult.m4a");
+        fs::write(&audio, b"audio").unwrap();
+        (path_string(&audio), path_string(&dir.join("Summary.docx")))
+    }
+
+    #[tokio::test]
+    async fn session_summary_runs_each_step_in_order() {
+        let dir = scratch_dir();
+        let (audio, output) = session_summary_paths(dir.path());
+        let steps = ScriptedSessionSummary { transcript: "He barks at the door.".to_string(), ..Default::default() };
+
+        let result = run_session_summary(&steps, &audio, &sample_client_info(), "letterhead.docx", &output).await.unwrap();
+
+        assert_eq!(steps.steps(), strings(&["detect", "transcribe", "draft", "export"]));
+        let calls = steps.calls.lock().unwrap().clone();
+        assert!(calls[2].contains("He barks at the door. | Client: Jane Smith / Session date: 2025-03-14 / Pet: Max"), "{}", calls[2]);
+        assert!(calls[3].contains("letterhead.docx | \"Session Summary - Jane Smith\""), "{}", calls[3]);
+        assert!(calls[3].contains("**Duration:** 1:02:05"), "{}", calls[3]);
+        assert!(calls[3].ends_with("Subjective: barking at visitors.\n"));
+
+        assert_eq!(result.transcript, "He barks at the door.");
+        assert_eq!(result.duration_seconds, 3725.0);
+        assert_eq!(result.tokens_used, 812);
+        assert_eq!(PathBuf::from(&result.docx_path).file_name().unwrap(), "Summary.docx");
+    }
+
+    #[tokio::test]
+    async fn session_summary_errors_name_the_failed_step() {
+        let dir = scratch_dir();
+        let (audio, output) = session_summary_paths(dir.path());
+
+        for (step, prefix, ran) in [
+            ("detect", "Audio detection failed: ", 1),
+            ("transcribe", "Transcription failed: ", 2),
+            ("draft", "Note generation failed: ", 3),
+            ("export", "DOCX export failed: ", 4),
+        ] {
+            let steps = ScriptedSessionSummary { fail_at: Some(step), transcript: "words".to_string(), ..Default::default() };
+            let err = run_session_summary(&steps, &audio, &sample_client_info(), "", &output).await.err().unwrap();
+            assert_eq!(err, format!("{}{} broke", prefix, step));
+            assert_eq!(steps.steps().len(), ran, "{}", step);
+        }
+
+        let silent = ScriptedSessionSummary { transcript: "  ".to_string(), ..Default::default() };
+        let err = run_session_summary(&silent, &audio, &sample_client_info(), "", &output).await.err().unwrap();
+        assert_eq!(err, "Transcription failed: no speech found in recording");
+        assert_eq!(silent.steps(), strings(&["detect", "transcribe"]));
+    }
+
+    #[tokio::test]
+    async fn session_summary_checks_paths_before_calling_anything() {
+        let dir = scratch_dir();
+        let (audio, _) = session_summary_paths(dir.path());
+        let steps = ScriptedSessionSummary::default();
+
+        let pdf = path_string(&dir.path().join("Summary.pdf"));
+        let err = run_session_summary(&steps, &audio, &sample_client_info(), "", &pdf).await.err().unwrap();
+        assert!(err.starts_with("Output path must be a .docx file"), "{}", err);
+
+        let missing = path_string(&dir.path().join("missing.m4a"));
+        let err = run_session_summary(&steps, &missing, &sample_client_info(), "", &pdf).await.err().unwrap();
+        assert!(err.starts_with("File does not exist"), "{}", err);
+        assert!(steps.steps().is_empty());
+    }
+
+    const SOAP_TRANSCRIPT_FIXTURE: &str = "Owner reports Max barks at visitors. He started last spring.\n\
+        Objective: Max was calm in the clinic. Heart rate normal!\n\
+        Assessment - territorial aggression likely. Is fear involved? Possibly.\n\
+        Plan: start desensitisation. Recheck in 4 weeks.";
+
+    fn keyword_hints(pairs: &[(&str, &[&str])]) -> HashMap<String, Vec<String>> {
+        pairs.iter().map(|(section, phrases)| (section.to_string(), strings(phrases))).collect()
+    }
+
+    #[test]
+    fn soap_note_splits_on_section_headers() {
+        let note = segment_soap_note(SOAP_TRANSCRIPT_FIXTURE, &HashMap::new()).unwrap();
+
+        assert_eq!(note, SoapNote {
+            subjective: "Owner reports Max barks at visitors. He started last spring.".to_string(),
+            objective: "Max was calm in the clinic. Heart rate normal!".to_string(),
+            assessment: "territorial aggression likely. Is fear involved? Possibly.".to_string(),
+            plan: "start desensitisation. Recheck in 4 weeks.".to_string(),
+        });
+    }
+
+    #[test]
+    fn soap_note_switches_section_on_trigger_phrases() {
+        let transcript = "The owner says he hides during storms. On exam he was panting. \
+            My impression is noise phobia. Going forward we will try a thundershirt. Also 2.5 mg at night.";
+        let hints = keyword_hints(&[
+            ("Objective", &["on exam"]),
+            ("assessment", &["my impression"]),
+            ("plan", &["going forward", "plan"]),
+            ("subjective", &["  "]),
+        ]);
+
+        let note = segment_soap_note(transcript, &hints).unwrap();
+
+        assert_eq!(note.subjective, "The owner says he hides during storms.");
+        assert_eq!(note.objective, "On exam he was panting.");
+        assert_eq!(note.assessment, "My impression is noise phobia.");
+        // "2.5" doesn't end a sentence; with no trigger the last sentence stays in Plan
+        assert_eq!(note.plan, "Going forward we will try a thundershirt. Also 2.5 mg at night.");
+    }
+
+    #[test]
+    fn soap_note_rejects_unknown_hint_sections() {
+        let err = segment_soap_note("text", &keyword_hints(&[("history", &["reports"])])).unwrap_err();
+        assert!(err.starts_with("Unknown SOAP section in keyword hints: history"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn transcription_to_soap_note_uses_keywords_without_ai() {
+        let rules = SoapRules { use_ai: false, ai_api_key: None, keyword_hints: HashMap::new() };
+        let note = transcription_to_soap_note(SOAP_TRANSCRIPT_FIXTURE.to_string(), rules).await.unwrap();
+        assert_eq!(note.plan, "start desensitisation. Recheck in 4 weeks.");
+
+        let rules = SoapRules { use_ai: false, ai_api_key: None, keyword_hints: HashMap::new() };
+        assert_eq!(transcription_to_soap_note(" \n".to_string(), rules).await.unwrap_err(), "Transcript cannot be empty");
+    }
+
+    fn pii_kinds_and_values(text: &str) -> Vec<(String, String)> {
+        scan_for_pii(text.to_string()).unwrap().findings.into_iter().map(|f| (f.kind, f.value)).collect()
+    }
+
+    #[test]
+    fn scan_for_pii_detects_ssn_with_positions() {
+        let text = "Owner SSN 123-45-6789 on file.";
+        assert_eq!(scan_for_pii(text.to_string()).unwrap().findings, vec![PiiFinding {
+            kind: "ssn".to_string(),
+            value: "123-45-6789".to_string(),
+            start_char: 10,
+            end_char: 21,
+        }]);
+        // Longer digit runs are not SSNs
+        assert!(pii_kinds_and_values("Ref 1123-45-67890").iter().all(|(kind, _)| kind != "ssn"));
+    }
+
+    #[test]
+    fn scan_for_pii_detects_us_phone_formats() {
+        let text = "Call (555) 123-4567, 555-234-5678, 555.345.6789 or +1 555 456 7890.";
+        assert_eq!(pii_kinds_and_values(text), vec![
+            ("phone".to_string(), "(555) 123-4567".to_string()),
+            ("phone".to_string(), "555-234-5678".to_string()),
+            ("phone".to_string(), "555.345.6789".to_string()),
+            ("phone".to_string(), "+1 555 456 7890".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn scan_for_pii_detects_email_birth_date_and_zip() {
+        let text = "Email jane.doe@example.com, DOB: 03/14/1985, lives at 12 Main St, Springfield 62704-1234. Seen 2024-05-01.";
+        assert_eq!(pii_kinds_and_values(text), vec![
+            ("email".to_string(), "jane.doe@example.com".to_string()),
+            ("date_of_birth".to_string(), "03/14/1985".to_string()),
+            ("zip_code".to_string(), "62704-1234".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn pii_positions_are_utf16_offsets() {
+        let finding = &scan_for_pii("🐕 SSN 123-45-6789".to_string()).unwrap().findings[0];
+        assert_eq!((finding.start_char, finding.end_char), (7, 18));
+    }
+
+    #[test]
+    fn redact_pii_replaces_every_finding() {
+        let text = "SSN 123-45-6789, phone (555) 123-4567, email a@b.org, DOB 1/2/90.";
+        assert_eq!(
+            redact_pii(text.to_string(), "[REDACTED]".to_string()).unwrap(),
+            "SSN [REDACTED], phone [REDACTED], email [REDACTED], DOB [REDACTED]."
+        );
+        assert_eq!(redact_pii("Nothing sensitive here.".to_string(), "X".to_string()).unwrap(), "Nothing sensitive here.");
+    }
+
+    #[test]
+    fn pii_patterns_are_compiled_once() {
+        let first = pii_patterns();
+        scan_for_pii("SSN 123-45-6789".to_string()).unwrap();
+        assert!(std::ptr::eq(first, pii_patterns()));
+    }
+}
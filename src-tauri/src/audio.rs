@@ -0,0 +1,1093 @@
+// PBS Admin - Audio file commands
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::io::Write;
+use std::process::Command;
+use std::time::Duration;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::{validate_read_path, validate_write_path};
+use crate::files::sanitize_filename_str;
+
+// ============================================================================
+// AUDIO FILES (FFMPEG)
+// ============================================================================
+
+/// Save uploaded audio file to temp directory for processing
+#[tauri::command]
+pub(crate) fn save_temp_audio_file(file_name: String, file_data: Vec<u8>) -> Result<String, String> {
+    // Get system temp directory
+    let temp_dir = std::env::temp_dir();
+    let pbs_temp = temp_dir.join("PBS_Admin");
+
+    // Create PBS_Admin temp folder if it doesn't exist
+    if !pbs_temp.exists() {
+        fs::create_dir_all(&pbs_temp)
+            .map_err(|e| format!("Failed to create temp directory: {}", e))?;
+    }
+
+    // Create unique filename with timestamp
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let safe_name = sanitize_filename_str(&file_name, "_");
+    let temp_file_path = pbs_temp.join(format!("{}_{}", timestamp, safe_name));
+
+    // Write file data
+    match fs::File::create(&temp_file_path) {
+        Ok(mut file) => {
+            file.write_all(&file_data)
+                .map_err(|e| format!("Failed to write temp file: {}", e))?;
+            Ok(temp_file_path.to_string_lossy().to_string())
+        },
+        Err(e) => Err(format!("Failed to create temp file: {}", e)),
+    }
+}
+
+/// Delete files in the PBS_Admin temp folder older than `max_age_seconds`.
+/// Returns the number of files deleted; files that can't be removed (e.g. still open) are skipped.
+#[tauri::command]
+pub(crate) fn cleanup_temp_audio_files(max_age_seconds: u64) -> Result<u32, String> {
+    cleanup_stale_files_in(&std::env::temp_dir().join("PBS_Admin"), max_age_seconds)
+}
+
+/// Delete the files directly inside `pbs_temp` that are older than `max_age_seconds`
+fn cleanup_stale_files_in(pbs_temp: &Path, max_age_seconds: u64) -> Result<u32, String> {
+    if !pbs_temp.exists() {
+        return Ok(0);
+    }
+
+    let max_age = Duration::from_secs(max_age_seconds);
+    let now = std::time::SystemTime::now();
+    let mut deleted: u32 = 0;
+    let mut failed: u32 = 0;
+
+    let entries = fs::read_dir(pbs_temp)
+        .map_err(|e| format!("Failed to read temp directory: {}", e))?;
+
+    for entry in entries.flatten() {
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+
+        let age = metadata.modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok());
+        if !matches!(age, Some(age) if age > max_age) {
+            continue;
+        }
+
+        match fs::remove_file(entry.path()) {
+            Ok(_) => deleted += 1,
+            Err(_) => failed += 1,
+        }
+    }
+
+    if failed > 0 {
+        warn!("{} stale temp file(s) could not be deleted (possibly in use)", failed);
+    }
+    info!("Temp cleanup: deleted {} stale file(s) from {}", deleted, pbs_temp.display());
+
+    Ok(deleted)
+}
+
+/// Check if FFmpeg is available on the system
+#[tauri::command]
+pub(crate) fn check_ffmpeg() -> Result<String, String> {
+    let output = Command::new("ffmpeg")
+        .arg("-version")
+        .output()
+        .map_err(|_| "FFmpeg is not installed or not in PATH. Please install FFmpeg to process large audio files.".to_string())?;
+
+    if !output.status.success() {
+        return Err("FFmpeg found but returned an error.".to_string());
+    }
+
+    let version_str = String::from_utf8_lossy(&output.stdout);
+    let first_line = version_str.lines().next().unwrap_or("unknown").to_string();
+    Ok(first_line)
+}
+
+/// Compress audio file to mono MP3 at specified bitrate using FFmpeg
+/// This reduces file size significantly for transcription
+#[tauri::command]
+pub(crate) fn compress_audio(input_path: String, bitrate: Option<String>) -> Result<String, String> {
+    let br = bitrate.unwrap_or_else(|| "64k".to_string());
+
+    let temp_dir = std::env::temp_dir().join("PBS_Admin");
+    if !temp_dir.exists() {
+        fs::create_dir_all(&temp_dir)
+            .map_err(|e| format!("Failed to create temp directory: {}", e))?;
+    }
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let output_path = temp_dir.join(format!("{}_compressed.mp3", timestamp));
+
+    info!("Compressing audio: {} -> {} (bitrate: {})", input_path, output_path.display(), br);
+
+    let output = Command::new("ffmpeg")
+        .args([
+            "-i", &input_path,
+            "-ac", "1",           // mono
+            "-ab", &br,           // bitrate (e.g., "64k")
+            "-ar", "16000",       // 16kHz sample rate (sufficient for speech)
+            "-y",                 // overwrite output
+            &output_path.to_string_lossy(),
+        ])
+        .output()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("FFmpeg compression failed: {}", stderr));
+    }
+
+    let compressed_size = fs::metadata(&output_path)
+        .map(|m| m.len())
+        .unwrap_or(0);
+
+    info!("Compression complete: {:.1} MB", compressed_size as f64 / 1_048_576.0);
+
+    Ok(output_path.to_string_lossy().to_string())
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct CompressResult {
+    output_path: String,
+    original_bytes: u64,
+    compressed_bytes: u64,
+    reduction_percent: f64,
+}
+
+/// FFmpeg arguments to transcode to mono 16kHz MP3 at `bitrate_kbps`
+fn compress_audio_file_args(input: &Path, output: &Path, bitrate_kbps: u32) -> Vec<String> {
+    vec![
+        "-i".to_string(), input.to_string_lossy().to_string(),
+        "-vn".to_string(),                                  // drop any cover art / video track
+        "-ac".to_string(), "1".to_string(),                 // mono
+        "-ar".to_string(), "16000".to_string(),             // 16kHz (sufficient for speech)
+        "-codec:a".to_string(), "libmp3lame".to_string(),
+        "-b:a".to_string(), format!("{}k", bitrate_kbps),
+        "-y".to_string(),                                   // overwrite output
+        output.to_string_lossy().to_string(),
+    ]
+}
+
+/// Default output for compress_audio_file: `<input stem>_compressed.mp3` next to the input
+fn default_compressed_audio_path(input: &Path) -> Result<PathBuf, String> {
+    let stem = input.file_stem()
+        .ok_or_else(|| format!("Invalid audio file name: {}", input.display()))?
+        .to_string_lossy();
+    Ok(input.with_file_name(format!("{}_compressed.mp3", stem)))
+}
+
+fn compress_audio_file_with(
+    ffmpeg: &std::ffi::OsStr,
+    input_path: &str,
+    output_path: &str,
+    target_bitrate_kbps: u32,
+) -> Result<CompressResult, String> {
+    // Validate both paths are within allowed directories
+    let input = validate_read_path(input_path)?;
+    let original_bytes = fs::metadata(&input)
+        .map_err(|e| format!("Failed to read audio file: {}", e))?
+        .len();
+
+    let output = if output_path.trim().is_empty() {
+        default_compressed_audio_path(&input)?
+    } else {
+        PathBuf::from(output_path)
+    };
+    let output = validate_write_path(&output.to_string_lossy())?;
+    if input == output {
+        return Err("Output path must differ from the input file".to_string());
+    }
+
+    let bitrate_kbps = target_bitrate_kbps.clamp(8, 320);
+    info!("Compressing audio: {} -> {} ({} kbps)", input_path, output.display(), bitrate_kbps);
+
+    let result = Command::new(ffmpeg)
+        .args(compress_audio_file_args(&input, &output, bitrate_kbps))
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => "FFmpeg was not found. Run check_dependencies to see which tools are missing, then install FFmpeg and make sure it is on PATH.".to_string(),
+            _ => format!("Failed to run FFmpeg: {}", e),
+        })?;
+
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        return Err(format!("FFmpeg compression failed: {}", stderr));
+    }
+
+    let compressed_bytes = fs::metadata(&output)
+        .map_err(|e| format!("Compressed file was not created: {}", e))?
+        .len();
+    let reduction_percent = if original_bytes == 0 {
+        0.0
+    } else {
+        (1.0 - compressed_bytes as f64 / original_bytes as f64) * 100.0
+    };
+
+    info!("Compression complete: {:.1} MB -> {:.1} MB ({:.0}% smaller)",
+        original_bytes as f64 / 1_048_576.0, compressed_bytes as f64 / 1_048_576.0, reduction_percent);
+
+    Ok(CompressResult {
+        output_path: output.to_string_lossy().to_string(),
+        original_bytes,
+        compressed_bytes,
+        reduction_percent,
+    })
+}
+
+/// Transcode a recording to mono MP3 at `target_bitrate_kbps` (8-320) to get it under the
+/// transcription size limit. An empty `output_path` writes `<name>_compressed.mp3` next to the input.
+#[tauri::command]
+pub(crate) fn compress_audio_file(input_path: String, output_path: String, target_bitrate_kbps: u32) -> Result<CompressResult, String> {
+    compress_audio_file_with(std::ffi::OsStr::new("ffmpeg"), &input_path, &output_path, target_bitrate_kbps)
+}
+
+/// Output formats for convert_audio_format and the FFmpeg codec arguments for each
+const AUDIO_CONVERSION_CODECS: &[(&str, &[&str])] = &[
+    ("mp3", &["-codec:a", "libmp3lame", "-q:a", "2"]),
+    ("m4a", &["-codec:a", "aac", "-b:a", "128k"]),
+    ("wav", &["-codec:a", "pcm_s16le"]),
+    ("flac", &["-codec:a", "flac"]),
+    ("ogg", &["-codec:a", "libvorbis", "-q:a", "5"]),
+    ("opus", &["-codec:a", "libopus", "-b:a", "64k"]),
+];
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ConvertResult {
+    output_path: String,
+    input_size_bytes: u64,
+    output_size_bytes: u64,
+    duration_seconds: f64,
+}
+
+/// FFmpeg arguments to transcode `input` to `output_format` (audio track only)
+fn convert_audio_format_args(input: &Path, output: &Path, output_format: &str) -> Result<Vec<String>, String> {
+    let codec_args = AUDIO_CONVERSION_CODECS.iter()
+        .find(|(format, _)| format.eq_ignore_ascii_case(output_format))
+        .map(|(_, args)| *args)
+        .ok_or_else(|| {
+            let supported: Vec<&str> = AUDIO_CONVERSION_CODECS.iter().map(|(format, _)| *format).collect();
+            format!("Unsupported output format: {}. Supported formats: {}", output_format, supported.join(", "))
+        })?;
+
+    let mut args = vec!["-i".to_string(), input.to_string_lossy().to_string(), "-vn".to_string()];
+    args.extend(codec_args.iter().map(|arg| arg.to_string()));
+    args.push("-y".to_string());
+    args.push(output.to_string_lossy().to_string());
+    Ok(args)
+}
+
+/// Container duration from ffprobe; fails if ffprobe can't parse the file
+fn probe_audio_duration_with(ffprobe: &std::ffi::OsStr, path: &Path) -> Result<f64, String> {
+    let output = Command::new(ffprobe)
+        .args(["-v", "error", "-show_entries", "format=duration", "-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(path)
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => "ffprobe was not found. Run check_dependencies to see which tools are missing, then install FFmpeg and make sure it is on PATH.".to_string(),
+            _ => format!("Failed to run ffprobe: {}", e),
+        })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("ffprobe could not read {}: {}", path.display(), stderr.trim()));
+    }
+
+    let duration = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    duration.parse::<f64>()
+        .map_err(|_| format!("ffprobe found no duration in {}", path.display()))
+}
+
+fn convert_audio_format_with(
+    ffmpeg: &std::ffi::OsStr,
+    ffprobe: &std::ffi::OsStr,
+    input_path: &str,
+    output_format: &str,
+    output_path: &str,
+) -> Result<ConvertResult, String> {
+    // Validate both ends are within allowed directories
+    let input = validate_read_path(input_path)?;
+    let output = validate_write_path(output_path)?;
+    if input == output {
+        return Err("Output path must differ from the input file".to_string());
+    }
+
+    let args = convert_audio_format_args(&input, &output, output_format)?;
+    let matches_format = output
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case(output_format))
+        .unwrap_or(false);
+    if !matches_format {
+        return Err(format!("Output path must be a .{} file: {}", output_format.to_lowercase(), output_path));
+    }
+
+    // Reject inputs ffprobe can't parse before spending time in ffmpeg
+    let duration_seconds = probe_audio_duration_with(ffprobe, &input)?;
+    let input_size_bytes = fs::metadata(&input)
+        .map_err(|e| format!("Failed to read audio file: {}", e))?
+        .len();
+
+    info!("Converting audio: {} -> {} ({})", input_path, output.display(), output_format);
+    let result = Command::new(ffmpeg)
+        .args(&args)
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => "FFmpeg was not found. Run check_dependencies to see which tools are missing, then install FFmpeg and make sure it is on PATH.".to_string(),
+            _ => format!("Failed to run FFmpeg: {}", e),
+        })?;
+
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        return Err(format!("FFmpeg conversion failed: {}", stderr));
+    }
+
+    let output_size_bytes = fs::metadata(&output)
+        .map_err(|e| format!("Converted file was not created: {}", e))?
+        .len();
+
+    Ok(ConvertResult {
+        output_path: output.to_string_lossy().to_string(),
+        input_size_bytes,
+        output_size_bytes,
+        duration_seconds,
+    })
+}
+
+/// Convert a recording to mp3, m4a, wav, flac, ogg or opus with FFmpeg
+#[tauri::command]
+pub(crate) fn convert_audio_format(input_path: String, output_format: String, output_path: String) -> Result<ConvertResult, String> {
+    convert_audio_format_with(
+        std::ffi::OsStr::new("ffmpeg"),
+        std::ffi::OsStr::new("ffprobe"),
+        &input_path,
+        &output_format,
+        &output_path,
+    )
+}
+
+/// Split audio file into chunks of specified duration (in seconds) using FFmpeg
+/// Returns a list of chunk file paths
+#[tauri::command]
+pub(crate) fn split_audio(input_path: String, chunk_duration_secs: u64) -> Result<Vec<String>, String> {
+    let temp_dir = std::env::temp_dir().join("PBS_Admin").join("chunks");
+    if temp_dir.exists() {
+        // Clean up old chunks
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+    fs::create_dir_all(&temp_dir)
+        .map_err(|e| format!("Failed to create chunks directory: {}", e))?;
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let output_pattern = temp_dir.join(format!("{}_chunk_%03d.mp3", timestamp));
+
+    info!("Splitting audio into {}s chunks: {}", chunk_duration_secs, input_path);
+
+    let output = Command::new("ffmpeg")
+        .args([
+            "-i", &input_path,
+            "-f", "segment",
+            "-segment_time", &chunk_duration_secs.to_string(),
+            "-ac", "1",           // mono
+            "-ab", "64k",         // 64kbps
+            "-ar", "16000",       // 16kHz
+            "-y",
+            &output_pattern.to_string_lossy(),
+        ])
+        .output()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("FFmpeg split failed: {}", stderr));
+    }
+
+    // Collect all generated chunk files (sorted by name)
+    let mut chunk_paths: Vec<String> = fs::read_dir(&temp_dir)
+        .map_err(|e| format!("Failed to read chunks directory: {}", e))?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let path = entry.path();
+            if path.extension().map(|e| e == "mp3").unwrap_or(false) {
+                Some(path.to_string_lossy().to_string())
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    chunk_paths.sort();
+
+    info!("Split into {} chunks", chunk_paths.len());
+
+    if chunk_paths.is_empty() {
+        return Err("FFmpeg produced no output chunks".to_string());
+    }
+
+    Ok(chunk_paths)
+}
+
+/// Get audio file duration in seconds using FFmpeg
+#[tauri::command]
+pub(crate) fn get_audio_duration_ffmpeg(file_path: String) -> Result<f64, String> {
+    get_audio_duration_with(std::ffi::OsStr::new("ffprobe"), &file_path)
+}
+
+pub(crate) fn get_audio_duration_with(ffprobe: &std::ffi::OsStr, file_path: &str) -> Result<f64, String> {
+    let output = Command::new(ffprobe)
+        .args([
+            "-v", "error",
+            "-show_entries", "format=duration",
+            "-of", "default=noprint_wrappers=1:nokey=1",
+            file_path,
+        ])
+        .output()
+        .map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("ffprobe failed: {}", stderr));
+    }
+
+    let duration_str = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    duration_str.parse::<f64>()
+        .map_err(|_| format!("Failed to parse duration: {}", duration_str))
+}
+
+/// Duration of the first audio stream in `ffprobe -print_format json -show_streams` output
+fn parse_ffprobe_audio_duration(ffprobe_json: &str) -> Option<f64> {
+    let parsed: serde_json::Value = serde_json::from_str(ffprobe_json).ok()?;
+    parsed["streams"]
+        .as_array()?
+        .iter()
+        .find(|stream| stream["codec_type"] == "audio")
+        .and_then(|stream| stream["duration"].as_str())
+        .and_then(|duration| duration.parse::<f64>().ok())
+}
+
+/// Rough duration from file size using a typical bitrate for the container
+fn estimate_audio_duration_from_size(file_path: &str, size_bytes: u64) -> f64 {
+    let lower = file_path.to_lowercase();
+    let bytes_per_sec: f64 = if lower.ends_with(".wav") {
+        176_400.0 // 16-bit 44.1kHz stereo PCM
+    } else if lower.ends_with(".flac") {
+        88_000.0 // ~700kbps
+    } else if lower.ends_with(".ogg") || lower.ends_with(".webm") {
+        12_000.0 // ~96kbps
+    } else {
+        16_000.0 // ~128kbps (mp3, m4a)
+    };
+    size_bytes as f64 / bytes_per_sec
+}
+
+/// Get audio duration in seconds with ffprobe (first audio stream).
+/// Falls back to a size-based estimate if ffprobe isn't installed.
+#[tauri::command]
+pub(crate) fn detect_audio_duration(file_path: String) -> Result<f64, String> {
+    detect_audio_duration_with(std::ffi::OsStr::new("ffprobe"), &file_path)
+}
+
+fn detect_audio_duration_with(ffprobe: &std::ffi::OsStr, file_path: &str) -> Result<f64, String> {
+    let size_bytes = fs::metadata(file_path)
+        .map_err(|e| format!("Failed to read audio file: {}", e))?
+        .len();
+
+    let output = match Command::new(ffprobe)
+        .args(["-v", "quiet", "-print_format", "json", "-show_streams", file_path])
+        .output()
+    {
+        Ok(output) => output,
+        Err(_) => {
+            let estimate = estimate_audio_duration_from_size(file_path, size_bytes);
+            warn!("ffprobe not available - estimating duration from file size ({:.0}s)", estimate);
+            return Ok(estimate);
+        }
+    };
+
+    if !output.status.success() {
+        return Err(format!("ffprobe could not read audio file: {}", file_path));
+    }
+
+    parse_ffprobe_audio_duration(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| format!("No audio stream duration found in: {}", file_path))
+}
+
+// ============================================================================
+// AUDIO FORMAT DETECTION
+// ============================================================================
+
+/// Identify common audio containers from their leading magic bytes
+pub(crate) fn sniff_audio_mime(header: &[u8]) -> Option<&'static str> {
+    if header.starts_with(b"ID3") || header.starts_with(&[0xFF, 0xFB]) || header.starts_with(&[0xFF, 0xF3]) || header.starts_with(&[0xFF, 0xF2]) {
+        Some("audio/mpeg")
+    } else if header.len() >= 8 && &header[4..8] == b"ftyp" {
+        Some("audio/mp4")
+    } else if header.starts_with(b"RIFF") && header.len() >= 12 && &header[8..12] == b"WAVE" {
+        Some("audio/wav")
+    } else if header.starts_with(b"OggS") {
+        Some("audio/ogg")
+    } else if header.starts_with(b"fLaC") {
+        Some("audio/flac")
+    } else if header.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        Some("audio/webm")
+    } else {
+        None
+    }
+}
+
+/// Per-request upload limit of the OpenAI transcription API
+pub(crate) const TRANSCRIPTION_MAX_UPLOAD_MB: u32 = 25;
+
+/// Audio formats the OpenAI transcription API accepts: (extension, MIME type).
+/// Where two extensions share a MIME type, the first is the preferred one.
+const SUPPORTED_AUDIO_FORMATS: &[(&str, &str)] = &[
+    ("mp3", "audio/mpeg"),
+    ("mpeg", "audio/mpeg"),
+    ("mpga", "audio/mpeg"),
+    ("m4a", "audio/mp4"),
+    ("mp4", "audio/mp4"),
+    ("wav", "audio/wav"),
+    ("webm", "audio/webm"),
+    ("ogg", "audio/ogg"),
+    ("flac", "audio/flac"),
+];
+
+#[derive(Debug, Serialize)]
+pub(crate) struct AudioFormat {
+    extension: String,
+    mime_type: String,
+    max_size_mb: u32,
+}
+
+/// Audio formats accepted for transcription, so the UI validates against the same list
+#[tauri::command]
+pub(crate) fn list_audio_formats_supported() -> Vec<AudioFormat> {
+    SUPPORTED_AUDIO_FORMATS.iter()
+        .map(|(extension, mime_type)| AudioFormat {
+            extension: extension.to_string(),
+            mime_type: mime_type.to_string(),
+            max_size_mb: TRANSCRIPTION_MAX_UPLOAD_MB,
+        })
+        .collect()
+}
+
+/// Guess an audio MIME type from the file extension
+pub(crate) fn audio_mime_from_extension(file_name: &str) -> Option<&'static str> {
+    let extension = Path::new(file_name).extension()?.to_str()?.to_lowercase();
+    SUPPORTED_AUDIO_FORMATS.iter()
+        .find(|(ext, _)| *ext == extension)
+        .map(|(_, mime)| *mime)
+}
+
+/// Preferred file extension for a supported audio MIME type
+pub(crate) fn audio_extension_for_mime(mime_type: &str) -> Option<&'static str> {
+    SUPPORTED_AUDIO_FORMATS.iter()
+        .find(|(_, mime)| *mime == mime_type)
+        .map(|(ext, _)| *ext)
+}
+
+/// Detect a file's MIME type from its content (first 512 bytes), falling back to its extension
+#[tauri::command]
+pub(crate) fn detect_mime_type(file_path: String) -> Result<String, String> {
+    use std::io::Read;
+
+    let validated_path = validate_read_path(&file_path)?;
+    let file = fs::File::open(&validated_path)
+        .map_err(|e| format!("Failed to open file: {}", e))?;
+    let mut header = Vec::with_capacity(512);
+    file.take(512).read_to_end(&mut header)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+
+    let mime = sniff_audio_mime(&header)
+        .or_else(|| audio_mime_from_extension(&file_path))
+        .unwrap_or("application/octet-stream");
+    Ok(mime.to_string())
+}
+
+/// Below this a file can't hold a complete container header plus any audio
+const MIN_AUDIO_FILE_BYTES: u64 = 128;
+
+#[derive(Debug, Serialize)]
+pub(crate) struct AudioValidationResult {
+    /// File extension of the detected container (e.g. "mp3"), or "unknown"
+    format: String,
+    /// None when ffprobe isn't installed (magic-byte check only)
+    duration_seconds: Option<f64>,
+    is_valid: bool,
+    errors: Vec<String>,
+}
+
+fn validate_audio_file_with(ffprobe: &std::ffi::OsStr, file_path: &str) -> Result<AudioValidationResult, String> {
+    use std::io::Read;
+
+    // Validate path is within allowed directories
+    let validated_path = validate_read_path(file_path)?;
+
+    let size_bytes = fs::metadata(&validated_path)
+        .map_err(|e| format!("Failed to read audio file: {}", e))?
+        .len();
+    let mut header = Vec::with_capacity(16);
+    fs::File::open(&validated_path)
+        .and_then(|file| file.take(16).read_to_end(&mut header))
+        .map_err(|e| format!("Failed to read audio file: {}", e))?;
+
+    let mut errors = Vec::new();
+    let format = match sniff_audio_mime(&header).and_then(audio_extension_for_mime) {
+        Some(ext) => ext.to_string(),
+        None => {
+            errors.push("File content doesn't match a supported audio format".to_string());
+            "unknown".to_string()
+        }
+    };
+    if size_bytes == 0 {
+        errors.push("File is empty".to_string());
+    } else if size_bytes < MIN_AUDIO_FILE_BYTES {
+        errors.push(format!("File is truncated ({} bytes)", size_bytes));
+    }
+
+    // ffprobe reports container/stream damage on stderr; skip it if the header is already bad
+    let mut duration_seconds = None;
+    if errors.is_empty() {
+        match Command::new(ffprobe)
+            .args(["-v", "error", "-show_entries", "format=duration", "-of", "default=noprint_wrappers=1:nokey=1", "-i"])
+            .arg(&validated_path)
+            .output()
+        {
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                errors.extend(stderr.lines().filter(|line| !line.trim().is_empty()).map(|line| line.trim().to_string()));
+                if !output.status.success() && errors.is_empty() {
+                    errors.push("ffprobe could not read the file".to_string());
+                }
+                duration_seconds = String::from_utf8_lossy(&output.stdout).trim().parse::<f64>().ok();
+            }
+            Err(e) => warn!("ffprobe not available ({}) - validating audio header only", e),
+        }
+    }
+
+    let is_valid = errors.is_empty();
+    if !is_valid {
+        warn!("Audio validation failed for {}: {}", file_path, errors.join("; "));
+    }
+    Ok(AudioValidationResult { format, duration_seconds, is_valid, errors })
+}
+
+/// Check an audio file is intact before uploading it for transcription
+#[tauri::command]
+pub(crate) fn validate_audio_file(file_path: String) -> Result<AudioValidationResult, String> {
+    validate_audio_file_with(std::ffi::OsStr::new("ffprobe"), &file_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{mock_tool, path_string, scratch_dir, strings, wav_bytes};
+
+    /// Create `path` with its modification time `age` in the past
+    fn backdated_file(path: &Path, age: Duration) {
+        let file = fs::File::create(path).unwrap();
+        file.set_modified(std::time::SystemTime::now() - age).unwrap();
+    }
+
+    #[test]
+    fn stale_temp_files_are_deleted() {
+        let dir = scratch_dir();
+        let day = Duration::from_secs(24 * 60 * 60);
+        backdated_file(&dir.path().join("1700000000_old.m4a"), day * 2);
+        backdated_file(&dir.path().join("1700000001_old.wav"), day + Duration::from_secs(60));
+        backdated_file(&dir.path().join("fresh.m4a"), Duration::from_secs(60));
+        // Subdirectories (and their contents) are left alone
+        let nested = dir.path().join("segments");
+        fs::create_dir(&nested).unwrap();
+        backdated_file(&nested.join("segment_000.mp3"), day * 3);
+
+        let deleted = cleanup_stale_files_in(dir.path(), day.as_secs()).unwrap();
+
+        assert_eq!(deleted, 2);
+        let mut remaining: Vec<String> = fs::read_dir(dir.path()).unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        remaining.sort();
+        assert_eq!(remaining, ["fresh.m4a", "segments"]);
+        assert!(nested.join("segment_000.mp3").exists());
+    }
+
+    #[test]
+    fn cleanup_of_missing_temp_dir_is_a_no_op() {
+        let dir = scratch_dir();
+        assert_eq!(cleanup_stale_files_in(&dir.path().join("missing"), 0).unwrap(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn undeletable_temp_files_are_skipped() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = scratch_dir();
+        let locked = dir.path().join("locked");
+        fs::create_dir(&locked).unwrap();
+        backdated_file(&locked.join("in_use.m4a"), Duration::from_secs(7200));
+        backdated_file(&locked.join("also_in_use.m4a"), Duration::from_secs(7200));
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o555)).unwrap();
+
+        // Root ignores directory permissions, so only check when removal is really blocked
+        let probe = fs::remove_file(locked.join("also_in_use.m4a"));
+        let result = cleanup_stale_files_in(&locked, 3600);
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+
+        if probe.is_err() {
+            assert_eq!(result.unwrap(), 0);
+            assert!(locked.join("in_use.m4a").exists());
+        }
+    }
+
+    /// Two seconds of 16-bit 16 kHz mono silence
+    fn two_second_wav() -> Vec<u8> {
+        let mut fmt = Vec::new();
+        fmt.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        fmt.extend_from_slice(&1u16.to_le_bytes()); // mono
+        fmt.extend_from_slice(&16_000u32.to_le_bytes());
+        fmt.extend_from_slice(&32_000u32.to_le_bytes()); // byte rate
+        fmt.extend_from_slice(&2u16.to_le_bytes()); // block align
+        fmt.extend_from_slice(&16u16.to_le_bytes());
+        wav_bytes(&[(b"fmt ", &fmt), (b"data", &vec![0u8; 64_000])])
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn detect_audio_duration_reads_first_audio_stream_from_ffprobe() {
+        let dir = scratch_dir();
+        let audio = dir.path().join("session.wav");
+        fs::write(&audio, two_second_wav()).unwrap();
+        let ffprobe = mock_tool(dir.path(), "ffprobe", r#"
+[ "$1 $2 $3 $4 $5" = "-v quiet -print_format json -show_streams" ] || exit 3
+echo '{"streams":[{"codec_type":"video","duration":"99.0"},{"codec_type":"audio","duration":"2.000000"}]}'"#);
+
+        let duration = detect_audio_duration_with(ffprobe.as_os_str(), &path_string(&audio)).unwrap();
+        assert!((duration - 2.0).abs() < 1.0, "{}", duration);
+
+        let failing = mock_tool(dir.path(), "ffprobe_fail", "exit 1");
+        let err = detect_audio_duration_with(failing.as_os_str(), &path_string(&audio)).unwrap_err();
+        assert!(err.contains("ffprobe could not read audio file"), "{}", err);
+
+        let no_audio = mock_tool(dir.path(), "ffprobe_video", r#"echo '{"streams":[{"codec_type":"video","duration":"3.0"}]}'"#);
+        let err = detect_audio_duration_with(no_audio.as_os_str(), &path_string(&audio)).unwrap_err();
+        assert!(err.contains("No audio stream duration"), "{}", err);
+    }
+
+    #[test]
+    fn detect_audio_duration_estimates_from_size_without_ffprobe() {
+        let dir = scratch_dir();
+        let wav = dir.path().join("session.wav");
+        fs::write(&wav, vec![0u8; 176_400 * 3]).unwrap();
+        let missing = dir.path().join("no_such_ffprobe");
+
+        let estimate = detect_audio_duration_with(missing.as_os_str(), &path_string(&wav)).unwrap();
+        assert!((estimate - 3.0).abs() < 1e-9, "{}", estimate);
+        assert_eq!(estimate_audio_duration_from_size("talk.MP3", 160_000), 10.0);
+
+        let err = detect_audio_duration_with(missing.as_os_str(), &path_string(&dir.path().join("gone.wav"))).unwrap_err();
+        assert!(err.contains("Failed to read audio file"), "{}", err);
+    }
+
+    #[test]
+    fn detect_audio_duration_with_real_ffprobe_when_installed() {
+        if which::which("ffprobe").is_err() {
+            return;
+        }
+        let dir = scratch_dir();
+        let audio = dir.path().join("two_seconds.wav");
+        fs::write(&audio, two_second_wav()).unwrap();
+        let duration = detect_audio_duration(path_string(&audio)).unwrap();
+        assert!((duration - 2.0).abs() < 1.0, "{}", duration);
+    }
+
+    #[test]
+    fn detect_mime_type_prefers_magic_bytes_over_extension() {
+        let dir = scratch_dir();
+        let cases: [(&str, Vec<u8>, &str); 8] = [
+            ("tagged.m4a", b"ID3\x04\x00rest".to_vec(), "audio/mpeg"),
+            ("frame.wav", vec![0xFF, 0xFB, 0x90, 0x64], "audio/mpeg"),
+            ("voice.mp3", b"\x00\x00\x00\x20ftypM4A ".to_vec(), "audio/mp4"),
+            ("renamed.m4a", wav_bytes(&[(b"fmt ", &[0u8; 16])]), "audio/wav"),
+            ("clip.mp3", b"OggS\x00\x02".to_vec(), "audio/ogg"),
+            ("clip.ogg", b"fLaC\x00\x00\x00\x22".to_vec(), "audio/flac"),
+            ("clip.flac", vec![0x1A, 0x45, 0xDF, 0xA3, 0x01], "audio/webm"),
+            ("riff.mp3", b"RIFF\x10\x00\x00\x00AVI LIST".to_vec(), "audio/mpeg"),
+        ];
+        for (name, bytes, expected) in cases {
+            let path = dir.path().join(name);
+            fs::write(&path, bytes).unwrap();
+            assert_eq!(detect_mime_type(path_string(&path)).unwrap(), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn detect_mime_type_falls_back_to_extension_then_octet_stream() {
+        let dir = scratch_dir();
+        let unknown = [0u8; 600];
+        let flac = dir.path().join("no_magic.FLAC");
+        fs::write(&flac, unknown).unwrap();
+        assert_eq!(detect_mime_type(path_string(&flac)).unwrap(), "audio/flac");
+
+        let other = dir.path().join("notes.bin");
+        fs::write(&other, unknown).unwrap();
+        assert_eq!(detect_mime_type(path_string(&other)).unwrap(), "application/octet-stream");
+
+        let empty = dir.path().join("empty.webm");
+        fs::write(&empty, b"").unwrap();
+        assert_eq!(detect_mime_type(path_string(&empty)).unwrap(), "audio/webm");
+
+        assert!(detect_mime_type(path_string(&dir.path().join("missing.mp3"))).unwrap_err().starts_with("File does not exist"));
+    }
+
+    #[test]
+    fn detect_mime_type_rejects_paths_outside_pbs_admin() {
+        let outside = tempfile::tempdir().unwrap();
+        let audio = outside.path().join("clip.mp3");
+        fs::write(&audio, b"ID3\x04\x00").unwrap();
+        assert!(detect_mime_type(path_string(&audio)).unwrap_err().starts_with("Access denied"));
+    }
+
+    /// Fake ffmpeg that records its arguments one per line and writes 250 bytes to the output (last argument)
+    #[cfg(unix)]
+    fn mock_recording_ffmpeg(dir: &Path) -> PathBuf {
+        mock_tool(dir, "ffmpeg", r#"
+printf '%s\n' "$@" > "$(dirname "$0")/ffmpeg_args.txt"
+for last; do :; done
+head -c 250 /dev/zero > "$last""#)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn compress_audio_file_spawns_mono_mp3_transcode() {
+        let dir = scratch_dir();
+        let bin = tempfile::tempdir().unwrap();
+        let ffmpeg = mock_recording_ffmpeg(bin.path());
+        let input = dir.path().join("consult.wav");
+        fs::write(&input, vec![0u8; 1000]).unwrap();
+
+        let result = compress_audio_file_with(ffmpeg.as_os_str(), &path_string(&input), "", 64).unwrap();
+
+        let output = dir.path().canonicalize().unwrap().join("consult_compressed.mp3");
+        assert_eq!(PathBuf::from(&result.output_path), output);
+        let args: Vec<String> = fs::read_to_string(bin.path().join("ffmpeg_args.txt")).unwrap().lines().map(String::from).collect();
+        assert_eq!(args, vec![
+            "-i".to_string(), path_string(&input.canonicalize().unwrap()),
+            "-vn".to_string(),
+            "-ac".to_string(), "1".to_string(),
+            "-ar".to_string(), "16000".to_string(),
+            "-codec:a".to_string(), "libmp3lame".to_string(),
+            "-b:a".to_string(), "64k".to_string(),
+            "-y".to_string(),
+            path_string(&output),
+        ]);
+        assert_eq!((result.original_bytes, result.compressed_bytes), (1000, 250));
+        assert!((result.reduction_percent - 75.0).abs() < 1e-9);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn compress_audio_file_clamps_bitrate() {
+        let dir = scratch_dir();
+        let bin = tempfile::tempdir().unwrap();
+        let ffmpeg = mock_recording_ffmpeg(bin.path());
+        let input = dir.path().join("consult.m4a");
+        fs::write(&input, b"audio").unwrap();
+        let output = path_string(&dir.path().join("small.mp3"));
+
+        for (requested, used) in [(1, "8k"), (8, "8k"), (320, "320k"), (999, "320k")] {
+            compress_audio_file_with(ffmpeg.as_os_str(), &path_string(&input), &output, requested).unwrap();
+            let args = fs::read_to_string(bin.path().join("ffmpeg_args.txt")).unwrap();
+            assert!(args.contains(&format!("-b:a\n{}\n", used)), "{} -> {}", requested, args);
+        }
+    }
+
+    #[test]
+    fn compress_audio_file_explains_missing_ffmpeg() {
+        let dir = scratch_dir();
+        let input = dir.path().join("consult.wav");
+        fs::write(&input, b"audio").unwrap();
+        let missing = dir.path().join("no_ffmpeg");
+
+        let err = compress_audio_file_with(missing.as_os_str(), &path_string(&input), "", 64).unwrap_err();
+        assert!(err.contains("check_dependencies"), "{}", err);
+
+        let same = compress_audio_file_with(missing.as_os_str(), &path_string(&input), &path_string(&input), 64).unwrap_err();
+        assert_eq!(same, "Output path must differ from the input file");
+    }
+
+    #[test]
+    fn compress_audio_file_rejects_input_outside_pbs_admin() {
+        let outside = tempfile::tempdir().unwrap();
+        let input = outside.path().join("consult.wav");
+        fs::write(&input, b"audio").unwrap();
+        let output = path_string(&scratch_dir().path().join("consult.mp3"));
+        let missing = outside.path().join("no_ffmpeg");
+
+        let err = compress_audio_file_with(missing.as_os_str(), &path_string(&input), &output, 64).unwrap_err();
+        assert!(err.starts_with("Access denied"), "{}", err);
+    }
+
+    #[test]
+    fn supported_audio_formats_include_m4a() {
+        let formats = list_audio_formats_supported();
+        let m4a = formats.iter().find(|f| f.extension == "m4a").unwrap();
+        assert_eq!(m4a.mime_type, "audio/mp4");
+        assert_eq!(m4a.max_size_mb, 25);
+
+        let extensions: Vec<&str> = formats.iter().map(|f| f.extension.as_str()).collect();
+        for required in ["mp3", "mp4", "mpeg", "mpga", "m4a", "wav", "webm"] {
+            assert!(extensions.contains(&required), "{}", required);
+        }
+    }
+
+    #[test]
+    fn audio_mime_lookup_uses_supported_formats() {
+        assert_eq!(audio_mime_from_extension("Session.M4A"), Some("audio/mp4"));
+        assert_eq!(audio_mime_from_extension("notes.mpga"), Some("audio/mpeg"));
+        assert_eq!(audio_mime_from_extension("notes.txt"), None);
+        assert_eq!(audio_mime_from_extension("no_extension"), None);
+
+        // Shared MIME types map back to the first (preferred) extension
+        assert_eq!(audio_extension_for_mime("audio/mpeg"), Some("mp3"));
+        assert_eq!(audio_extension_for_mime("audio/mp4"), Some("m4a"));
+        assert_eq!(audio_extension_for_mime("video/quicktime"), None);
+    }
+
+    #[test]
+    fn zero_byte_audio_is_invalid() {
+        let dir = scratch_dir();
+        let audio = dir.path().join("empty.mp3");
+        fs::write(&audio, b"").unwrap();
+
+        let result = validate_audio_file_with(dir.path().join("no_ffprobe").as_os_str(), &path_string(&audio)).unwrap();
+
+        assert!(!result.is_valid);
+        assert_eq!(result.format, "unknown");
+        assert_eq!(result.errors, strings(&["File content doesn't match a supported audio format", "File is empty"]));
+        assert_eq!(result.duration_seconds, None);
+    }
+
+    #[test]
+    fn truncated_mp3_header_is_invalid() {
+        let dir = scratch_dir();
+        let audio = dir.path().join("cut.mp3");
+        fs::write(&audio, [b"ID3\x04\x00\x00".as_slice(), &[0u8; 20]].concat()).unwrap();
+
+        let result = validate_audio_file_with(dir.path().join("no_ffprobe").as_os_str(), &path_string(&audio)).unwrap();
+
+        assert!(!result.is_valid);
+        assert_eq!(result.format, "mp3");
+        assert_eq!(result.errors, strings(&["File is truncated (26 bytes)"]));
+    }
+
+    #[test]
+    fn audio_without_ffprobe_falls_back_to_magic_bytes() {
+        let dir = scratch_dir();
+        let audio = dir.path().join("session.wav");
+        fs::write(&audio, two_second_wav()).unwrap();
+
+        let result = validate_audio_file_with(dir.path().join("no_ffprobe").as_os_str(), &path_string(&audio)).unwrap();
+
+        assert!(result.is_valid, "{:?}", result.errors);
+        assert_eq!(result.format, "wav");
+        assert_eq!(result.duration_seconds, None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ffprobe_errors_mark_audio_invalid() {
+        let dir = scratch_dir();
+        let audio = dir.path().join("session.wav");
+        fs::write(&audio, two_second_wav()).unwrap();
+
+        let healthy = mock_tool(dir.path(), "ffprobe", "echo 2.000000");
+        let result = validate_audio_file_with(healthy.as_os_str(), &path_string(&audio)).unwrap();
+        assert!(result.is_valid);
+        assert_eq!(result.duration_seconds, Some(2.0));
+
+        let broken = mock_tool(dir.path(), "ffprobe_broken", "echo '[wav] invalid data found' >&2; exit 1");
+        let result = validate_audio_file_with(broken.as_os_str(), &path_string(&audio)).unwrap();
+        assert!(!result.is_valid);
+        assert_eq!(result.errors, strings(&["[wav] invalid data found"]));
+    }
+
+    #[test]
+    fn mp3_conversion_args_use_libmp3lame() {
+        let args = convert_audio_format_args(Path::new("/data/in.m4a"), Path::new("/data/out.mp3"), "MP3").unwrap();
+        assert_eq!(args, strings(&["-i", "/data/in.m4a", "-vn", "-codec:a", "libmp3lame", "-q:a", "2", "-y", "/data/out.mp3"]));
+
+        let opus = convert_audio_format_args(Path::new("in.wav"), Path::new("out.opus"), "opus").unwrap();
+        assert_eq!(&opus[3..7], strings(&["-codec:a", "libopus", "-b:a", "64k"]).as_slice());
+        let wav = convert_audio_format_args(Path::new("in.ogg"), Path::new("out.wav"), "wav").unwrap();
+        assert_eq!(&wav[3..5], strings(&["-codec:a", "pcm_s16le"]).as_slice());
+    }
+
+    #[test]
+    fn unsupported_conversion_format_lists_supported_ones() {
+        assert_eq!(
+            convert_audio_format_args(Path::new("in.wav"), Path::new("out.aiff"), "aiff").unwrap_err(),
+            "Unsupported output format: aiff. Supported formats: mp3, m4a, wav, flac, ogg, opus"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn convert_audio_format_runs_ffmpeg_after_probing() {
+        let dir = scratch_dir();
+        let bin = tempfile::tempdir().unwrap();
+        let ffmpeg = mock_recording_ffmpeg(bin.path());
+        let ffprobe = mock_tool(bin.path(), "ffprobe", "echo 12.5");
+        let input = dir.path().join("consult.m4a");
+        fs::write(&input, vec![0u8; 400]).unwrap();
+        let output = dir.path().join("consult.mp3");
+
+        let result = convert_audio_format_with(ffmpeg.as_os_str(), ffprobe.as_os_str(), &path_string(&input), "mp3", &path_string(&output)).unwrap();
+
+        let canonical = dir.path().canonicalize().unwrap();
+        let args = fs::read_to_string(bin.path().join("ffmpeg_args.txt")).unwrap();
+        assert_eq!(args.lines().collect::<Vec<_>>(), vec![
+            "-i", &path_string(&canonical.join("consult.m4a")), "-vn", "-codec:a", "libmp3lame", "-q:a", "2", "-y",
+            &path_string(&canonical.join("consult.mp3")),
+        ]);
+        assert_eq!((result.input_size_bytes, result.output_size_bytes, result.duration_seconds), (400, 250, 12.5));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn convert_audio_format_rejects_unparseable_input() {
+        let dir = scratch_dir();
+        let bin = tempfile::tempdir().unwrap();
+        let ffmpeg = mock_recording_ffmpeg(bin.path());
+        let ffprobe = mock_tool(bin.path(), "ffprobe", "echo 'Invalid data found when processing input' >&2; exit 1");
+        let input = dir.path().join("notes.m4a");
+        fs::write(&input, "not audio").unwrap();
+
+        let err = convert_audio_format_with(ffmpeg.as_os_str(), ffprobe.as_os_str(), &path_string(&input), "wav", &path_string(&dir.path().join("notes.wav"))).unwrap_err();
+        assert!(err.ends_with("Invalid data found when processing input"), "{}", err);
+        assert!(!bin.path().join("ffmpeg_args.txt").exists());
+
+        let err = convert_audio_format_with(ffmpeg.as_os_str(), ffprobe.as_os_str(), &path_string(&input), "wav", &path_string(&dir.path().join("notes.mp3"))).unwrap_err();
+        assert!(err.starts_with("Output path must be a .wav file"), "{}", err);
+    }
+}
@@ -0,0 +1,759 @@
+// PBS Admin - Database backup, restore and export commands
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use sha2::{Sha256, Digest};
+use tracing::info;
+
+use crate::{is_within_pbs_root, validate_read_path, validate_write_path, write_file_atomically};
+use crate::email::{base64_decode, base64_encode};
+use crate::settings::resolve_pbs_admin_data_dir;
+
+// ============================================================================
+// DATABASE PATH
+// ============================================================================
+
+/// Get the database path (Tauri command for frontend)
+#[tauri::command]
+pub(crate) fn get_database_path() -> Result<String, String> {
+    match dirs::document_dir() {
+        Some(docs_path) => {
+            let data_dir = docs_path.join("PBS_Admin").join("data");
+            if !data_dir.exists() {
+                fs::create_dir_all(&data_dir)
+                    .map_err(|e| format!("Failed to create data directory: {}", e))?;
+            }
+            let db_path = data_dir.join("pbs_admin.db");
+            Ok(db_path.to_string_lossy().to_string())
+        },
+        None => Err("Could not find Documents folder".to_string()),
+    }
+}
+
+/// Internal function to get database path for backup operations
+pub(crate) fn get_database_path_internal() -> Result<PathBuf, String> {
+    match dirs::document_dir() {
+        Some(docs_path) => {
+            let db_path = docs_path.join("PBS_Admin").join("data").join("pbs_admin.db");
+            if !db_path.exists() {
+                return Err(format!("Database not found at: {}", db_path.display()));
+            }
+            Ok(db_path)
+        },
+        None => Err("Could not find Documents folder".to_string()),
+    }
+}
+
+// ============================================================================
+// DATABASE BACKUP AND RESTORE
+// ============================================================================
+
+pub(crate) fn backups_path_in(data_dir: &Path) -> Result<PathBuf, String> {
+    let backups_path = data_dir.join("Backups");
+
+    // Create backups folder if it doesn't exist
+    if !backups_path.exists() {
+        std::fs::create_dir_all(&backups_path)
+            .map_err(|e| format!("Failed to create backups folder: {}", e))?;
+    }
+
+    Ok(backups_path)
+}
+
+/// Get the path to the backups folder
+#[tauri::command]
+pub(crate) fn get_backups_path() -> Result<String, String> {
+    let backups_path = backups_path_in(&resolve_pbs_admin_data_dir()?)?;
+    Ok(backups_path.to_string_lossy().to_string())
+}
+
+
+/// Create a backup of the database
+#[tauri::command]
+pub(crate) fn create_database_backup() -> Result<serde_json::Value, String> {
+    let db_path = get_database_path_internal()?;
+    let backups_path = get_backups_path()?;
+
+    // Generate backup filename with timestamp
+    let timestamp = chrono::Local::now().format("%Y-%m-%d-%H%M%S").to_string();
+    let backup_filename = format!("pbs-admin-backup-{}.db", timestamp);
+    let backup_path = std::path::Path::new(&backups_path).join(&backup_filename);
+
+    // Copy the database file
+    std::fs::copy(&db_path, &backup_path)
+        .map_err(|e| format!("Failed to create backup: {}", e))?;
+
+    // Compute SHA-256 hash of the backup
+    let data = std::fs::read(&backup_path)
+        .map_err(|e| format!("Failed to read backup for hash: {}", e))?;
+    let mut hasher = Sha256::new();
+    hasher.update(&data);
+    let hash = format!("{:x}", hasher.finalize());
+
+    info!("Backup created: {} (SHA-256: {})", backup_path.display(), &hash[..16]);
+
+    Ok(serde_json::json!({
+        "file_path": backup_path.to_string_lossy().to_string(),
+        "file_name": backup_filename,
+        "hash": hash
+    }))
+}
+
+/// Restore database from a backup file
+#[tauri::command]
+pub(crate) fn restore_database_backup(backup_path: String) -> Result<String, String> {
+    let backup_file = std::path::Path::new(&backup_path);
+
+    // Validate backup file exists
+    if !backup_file.exists() {
+        return Err(format!("Backup file not found: {}", backup_path));
+    }
+
+    // Validate it's a .db file
+    if backup_file.extension().and_then(|e| e.to_str()) != Some("db") {
+        return Err("Invalid backup file: must be a .db file".to_string());
+    }
+
+    let db_path = get_database_path_internal()?;
+
+    // Create a safety backup before restoring (in case restore goes wrong)
+    let safety_backup = db_path.with_extension("db.pre-restore-backup");
+    std::fs::copy(&db_path, &safety_backup)
+        .map_err(|e| format!("Failed to create safety backup: {}", e))?;
+
+    // Restore the backup
+    match std::fs::copy(&backup_file, &db_path) {
+        Ok(_) => {
+            // Remove safety backup on success
+            let _ = std::fs::remove_file(&safety_backup);
+            info!("Database restored from: {}", backup_path);
+            Ok("Database restored successfully. Please restart the application.".to_string())
+        }
+        Err(e) => {
+            // Try to restore from safety backup
+            let _ = std::fs::copy(&safety_backup, &db_path);
+            Err(format!("Failed to restore database: {}. Original database preserved.", e))
+        }
+    }
+}
+
+/// List available backup files
+#[tauri::command]
+pub(crate) fn list_database_backups() -> Result<Vec<serde_json::Value>, String> {
+    let backups_path = get_backups_path()?;
+    let backups_dir = std::path::Path::new(&backups_path);
+
+    let mut backups = Vec::new();
+
+    if let Ok(entries) = std::fs::read_dir(backups_dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) == Some("db") {
+                if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
+                    if file_name.starts_with("pbs-admin-backup-") {
+                        let metadata = std::fs::metadata(&path).ok();
+                        let size = metadata.as_ref().map(|m| m.len()).unwrap_or(0);
+                        let created = metadata
+                            .and_then(|m| m.created().ok())
+                            .map(|t| {
+                                let datetime: chrono::DateTime<chrono::Local> = t.into();
+                                datetime.format("%Y-%m-%d %H:%M:%S").to_string()
+                            })
+                            .unwrap_or_default();
+
+                        backups.push(serde_json::json!({
+                            "fileName": file_name,
+                            "filePath": path.to_string_lossy().to_string(),
+                            "createdAt": created,
+                            "sizeBytes": size
+                        }));
+                    }
+                }
+            }
+        }
+    }
+
+    // Sort by filename (which includes timestamp) descending
+    backups.sort_by(|a, b| {
+        let name_a = a["fileName"].as_str().unwrap_or("");
+        let name_b = b["fileName"].as_str().unwrap_or("");
+        name_b.cmp(name_a)
+    });
+
+    Ok(backups)
+}
+
+/// Delete a backup file
+#[tauri::command]
+pub(crate) fn delete_backup_file(backup_path: String) -> Result<String, String> {
+    let backup_file = std::path::Path::new(&backup_path);
+
+    // Validate the file is in the backups folder (security check)
+    let backups_path = get_backups_path()?;
+    if !backup_path.starts_with(&backups_path) {
+        return Err("Cannot delete files outside the backups folder".to_string());
+    }
+
+    // Validate it's a backup file
+    if let Some(file_name) = backup_file.file_name().and_then(|n| n.to_str()) {
+        if !file_name.starts_with("pbs-admin-backup-") {
+            return Err("Cannot delete non-backup files".to_string());
+        }
+    }
+
+    std::fs::remove_file(&backup_file)
+        .map_err(|e| format!("Failed to delete backup: {}", e))?;
+
+    Ok("Backup deleted successfully".to_string())
+}
+
+/// Verify backup file integrity (SHA-256 hash + SQLite magic header check)
+#[tauri::command]
+pub(crate) fn verify_backup_integrity(backup_path: String) -> Result<serde_json::Value, String> {
+    let path = std::path::Path::new(&backup_path);
+
+    if !path.exists() {
+        return Ok(serde_json::json!({
+            "valid": false,
+            "error": "File not found"
+        }));
+    }
+
+    let metadata = std::fs::metadata(path)
+        .map_err(|e| format!("Failed to read file metadata: {}", e))?;
+    let size = metadata.len();
+
+    if size == 0 {
+        return Ok(serde_json::json!({
+            "valid": false,
+            "size": 0,
+            "error": "File is empty"
+        }));
+    }
+
+    // Read file contents
+    let data = std::fs::read(path)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+
+    // Check SQLite magic header: first 16 bytes should be "SQLite format 3\0"
+    let sqlite_magic = b"SQLite format 3\0";
+    let has_valid_header = data.len() >= 16 && &data[..16] == sqlite_magic;
+
+    // Compute SHA-256 hash
+    let mut hasher = Sha256::new();
+    hasher.update(&data);
+    let hash = format!("{:x}", hasher.finalize());
+
+    if !has_valid_header {
+        return Ok(serde_json::json!({
+            "valid": false,
+            "size": size,
+            "hash": hash,
+            "error": "File is not a valid SQLite database"
+        }));
+    }
+
+    Ok(serde_json::json!({
+        "valid": true,
+        "size": size,
+        "hash": hash
+    }))
+}
+
+// ============================================================================
+// DATABASE JSON EXPORT / IMPORT
+// ============================================================================
+
+/// Quote an SQLite identifier (table/column name) for use in generated SQL
+fn quote_sql_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Names of all user tables in the database (SQLite internal tables excluded)
+fn database_table_names(conn: &rusqlite::Connection) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name")
+        .map_err(|e| format!("Failed to list tables: {}", e))?;
+    let tables = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| format!("Failed to list tables: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to list tables: {}", e))?;
+    Ok(tables)
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub(crate) struct ColumnInfo {
+    cid: i32,
+    name: String,
+    #[serde(rename = "type")]
+    type_: String,
+    not_null: bool,
+    default_value: Option<String>,
+    is_primary_key: bool,
+}
+
+/// Column definitions of `table_name` from PRAGMA table_info.
+/// The name must be an existing table - it can't be bound as a parameter, so it's checked first.
+fn describe_table_columns(conn: &rusqlite::Connection, table_name: &str) -> Result<Vec<ColumnInfo>, String> {
+    if !database_table_names(conn)?.iter().any(|table| table == table_name) {
+        return Err(format!("Unknown table: {}", table_name));
+    }
+
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info({})", quote_sql_identifier(table_name)))
+        .map_err(|e| format!("Failed to describe table {}: {}", table_name, e))?;
+    let columns = stmt
+        .query_map([], |row| {
+            Ok(ColumnInfo {
+                cid: row.get(0)?,
+                name: row.get(1)?,
+                type_: row.get(2)?,
+                not_null: row.get::<_, i64>(3)? != 0,
+                default_value: row.get(4)?,
+                // pk is the column's position in the primary key (0 = not part of it)
+                is_primary_key: row.get::<_, i64>(5)? != 0,
+            })
+        })
+        .map_err(|e| format!("Failed to describe table {}: {}", table_name, e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to describe table {}: {}", table_name, e))?;
+    Ok(columns)
+}
+
+fn open_database_read_only() -> Result<rusqlite::Connection, String> {
+    let db_path = get_database_path_internal()?;
+    rusqlite::Connection::open_with_flags(&db_path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open database: {}", e))
+}
+
+/// Table names in the PBS_Admin database (for schema inspection while debugging)
+#[tauri::command]
+pub(crate) fn list_database_tables() -> Result<Vec<String>, String> {
+    database_table_names(&open_database_read_only()?)
+}
+
+/// Column definitions of a table in the PBS_Admin database
+#[tauri::command]
+pub(crate) fn describe_table(table_name: String) -> Result<Vec<ColumnInfo>, String> {
+    describe_table_columns(&open_database_read_only()?, &table_name)
+}
+
+/// Read every row of every table as `{ "table": [ { "column": value, ... } ] }`.
+/// BLOB values are written as `{ "base64": "..." }`.
+pub(crate) fn export_database_json_value(conn: &rusqlite::Connection) -> Result<serde_json::Value, String> {
+    use rusqlite::types::ValueRef;
+
+    let mut export = serde_json::Map::new();
+
+    for table in database_table_names(conn)? {
+        let mut stmt = conn
+            .prepare(&format!("SELECT * FROM {}", quote_sql_identifier(&table)))
+            .map_err(|e| format!("Failed to read table {}: {}", table, e))?;
+        let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+
+        let mut rows = stmt.query([])
+            .map_err(|e| format!("Failed to read table {}: {}", table, e))?;
+        let mut table_rows = Vec::new();
+
+        while let Some(row) = rows.next().map_err(|e| format!("Failed to read table {}: {}", table, e))? {
+            let mut object = serde_json::Map::new();
+            for (index, column) in columns.iter().enumerate() {
+                let value = match row.get_ref(index).map_err(|e| format!("Failed to read column {}: {}", column, e))? {
+                    ValueRef::Null => serde_json::Value::Null,
+                    ValueRef::Integer(i) => serde_json::json!(i),
+                    ValueRef::Real(f) => serde_json::json!(f),
+                    ValueRef::Text(t) => serde_json::Value::String(String::from_utf8_lossy(t).to_string()),
+                    ValueRef::Blob(b) => serde_json::json!({ "base64": base64_encode(b) }),
+                };
+                object.insert(column.clone(), value);
+            }
+            table_rows.push(serde_json::Value::Object(object));
+        }
+
+        export.insert(table, serde_json::Value::Array(table_rows));
+    }
+
+    Ok(serde_json::Value::Object(export))
+}
+
+/// Create `path`'s missing parent folders, but only if they would end up inside the PBS_Admin root
+pub(crate) fn create_parent_dirs_within_pbs(path: &Path) -> Result<(), String> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => return Ok(()),
+    };
+    if parent.exists() {
+        return Ok(());
+    }
+
+    if path.components().any(|c| matches!(c, std::path::Component::ParentDir)) {
+        return Err(format!("Invalid path: {}", path.display()));
+    }
+
+    let existing_ancestor = parent.ancestors()
+        .find(|ancestor| ancestor.exists())
+        .ok_or_else(|| format!("Invalid path: {}", path.display()))?;
+    if !is_within_pbs_root(existing_ancestor) {
+        return Err("Access denied: Path must be within PBS_Admin folder".to_string());
+    }
+
+    fs::create_dir_all(parent)
+        .map_err(|e| format!("Failed to create directory: {}", e))
+}
+
+/// Export the whole database as pretty-printed JSON (a portable, human-readable backup)
+#[tauri::command]
+pub(crate) fn export_database_to_json(output_path: String) -> Result<String, String> {
+    create_parent_dirs_within_pbs(Path::new(&output_path))?;
+    let validated_path = validate_write_path(&output_path)?;
+
+    let db_path = get_database_path_internal()?;
+    let conn = rusqlite::Connection::open_with_flags(&db_path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open database: {}", e))?;
+
+    let export = export_database_json_value(&conn)?;
+    let json = serde_json::to_string_pretty(&export)
+        .map_err(|e| format!("Failed to serialize database: {}", e))?;
+
+    write_file_atomically(&validated_path, json.as_bytes())?;
+
+    let table_count = export.as_object().map(|tables| tables.len()).unwrap_or(0);
+    info!("Exported {} tables to {}", table_count, output_path);
+
+    Ok(validated_path.to_string_lossy().to_string())
+}
+
+/// Convert an exported JSON value back into an SQLite value (inverse of export_database_json_value)
+pub(crate) fn json_to_sql_value(value: &serde_json::Value) -> Result<rusqlite::types::Value, String> {
+    use rusqlite::types::Value;
+
+    match value {
+        serde_json::Value::Null => Ok(Value::Null),
+        serde_json::Value::Bool(b) => Ok(Value::Integer(*b as i64)),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Ok(Value::Integer(i)),
+            None => Ok(Value::Real(n.as_f64().unwrap_or(0.0))),
+        },
+        serde_json::Value::String(s) => Ok(Value::Text(s.clone())),
+        serde_json::Value::Object(map) if map.len() == 1 && map.contains_key("base64") => {
+            let encoded = map["base64"].as_str().ok_or("Invalid BLOB value")?;
+            Ok(Value::Blob(base64_decode(encoded)?))
+        }
+        _ => Err(format!("Unsupported value in backup: {}", value)),
+    }
+}
+
+/// Column names and values of one row to insert
+type ImportRow = (Vec<String>, Vec<rusqlite::types::Value>);
+
+/// Insert backup rows into the database in a single transaction. Everything is
+/// validated against the live schema first, so a bad backup changes nothing.
+fn import_database_json_value(
+    conn: &mut rusqlite::Connection,
+    data: &serde_json::Value,
+    mode: &str,
+) -> Result<u64, String> {
+    let insert_verb = match mode {
+        "append" | "replace" => "INSERT",
+        "upsert" => "INSERT OR REPLACE",
+        other => return Err(format!("Unsupported import mode: {}. Use append, replace, or upsert.", other)),
+    };
+
+    let tables = data.as_object()
+        .ok_or("Invalid backup: expected an object of tables")?;
+
+    // Validate every table, column and value before touching the database
+    let existing_tables = database_table_names(conn)?;
+    let mut prepared: Vec<(&String, Vec<ImportRow>)> = Vec::new();
+
+    for (table, rows) in tables {
+        if !existing_tables.contains(table) {
+            return Err(format!("Invalid backup: table {} does not exist in the database", table));
+        }
+
+        let mut stmt = conn
+            .prepare(&format!("PRAGMA table_info({})", quote_sql_identifier(table)))
+            .map_err(|e| format!("Failed to read schema for {}: {}", table, e))?;
+        let columns: Vec<String> = stmt
+            .query_map([], |row| row.get::<_, String>(1))
+            .map_err(|e| format!("Failed to read schema for {}: {}", table, e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to read schema for {}: {}", table, e))?;
+
+        let rows = rows.as_array()
+            .ok_or_else(|| format!("Invalid backup: {} must be an array of rows", table))?;
+
+        let mut table_rows = Vec::new();
+        for (index, row) in rows.iter().enumerate() {
+            let row = row.as_object()
+                .ok_or_else(|| format!("Invalid backup: row {} of {} is not an object", index + 1, table))?;
+
+            let mut row_columns = Vec::new();
+            let mut row_values = Vec::new();
+            for (column, value) in row {
+                if !columns.contains(column) {
+                    return Err(format!("Invalid backup: column {}.{} does not exist", table, column));
+                }
+                row_columns.push(column.clone());
+                row_values.push(json_to_sql_value(value)
+                    .map_err(|e| format!("Invalid backup: {}.{} row {}: {}", table, column, index + 1, e))?);
+            }
+            table_rows.push((row_columns, row_values));
+        }
+        prepared.push((table, table_rows));
+    }
+
+    let tx = conn.transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+    // Row order in the backup isn't dependency order, so check foreign keys at commit instead
+    tx.execute_batch("PRAGMA defer_foreign_keys = ON")
+        .map_err(|e| format!("Failed to defer foreign keys: {}", e))?;
+
+    if mode == "replace" {
+        for (table, _) in &prepared {
+            tx.execute(&format!("DELETE FROM {}", quote_sql_identifier(table)), [])
+                .map_err(|e| format!("Failed to clear table {}: {}", table, e))?;
+        }
+    }
+
+    let mut inserted: u64 = 0;
+    for (table, rows) in &prepared {
+        for (columns, values) in rows {
+            let column_list = columns.iter().map(|c| quote_sql_identifier(c)).collect::<Vec<_>>().join(", ");
+            let placeholders = vec!["?"; columns.len()].join(", ");
+            let sql = if columns.is_empty() {
+                format!("{} INTO {} DEFAULT VALUES", insert_verb, quote_sql_identifier(table))
+            } else {
+                format!("{} INTO {} ({}) VALUES ({})", insert_verb, quote_sql_identifier(table), column_list, placeholders)
+            };
+
+            tx.execute(&sql, rusqlite::params_from_iter(values.iter()))
+                .map_err(|e| format!("Failed to insert into {}: {}", table, e))?;
+            inserted += 1;
+        }
+    }
+
+    tx.commit()
+        .map_err(|e| format!("Failed to commit import: {}", e))?;
+
+    Ok(inserted)
+}
+
+/// Restore rows from a JSON backup created by export_database_to_json.
+/// Modes: "append" (INSERT), "replace" (clear tables first), "upsert" (INSERT OR REPLACE).
+/// Returns the number of rows inserted.
+#[tauri::command]
+pub(crate) fn import_database_from_json(json_path: String, mode: String) -> Result<u64, String> {
+    let validated_path = validate_read_path(&json_path)?;
+
+    let content = fs::read_to_string(&validated_path)
+        .map_err(|e| format!("Failed to read backup file: {}", e))?;
+    let data: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Invalid backup JSON: {}", e))?;
+
+    let db_path = get_database_path_internal()?;
+    let mut conn = rusqlite::Connection::open(&db_path)
+        .map_err(|e| format!("Failed to open database: {}", e))?;
+
+    // Refuse to write into a database that is already damaged
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|e| format!("Failed to check database integrity: {}", e))?;
+    if integrity != "ok" {
+        return Err(format!("Database integrity check failed: {}", integrity));
+    }
+
+    let inserted = import_database_json_value(&mut conn, &data, &mode)?;
+    info!("Imported {} rows from {} ({})", inserted, json_path, mode);
+
+    Ok(inserted)
+}
+
+/// Snapshot `source` into `destination` with SQLite's online backup API (safe while the
+/// app has the database open in WAL mode), then check the copy with `PRAGMA integrity_check`
+fn backup_sqlite_database(source: &Path, destination: &Path) -> Result<(), String> {
+    let conn = rusqlite::Connection::open_with_flags(source, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open database: {}", e))?;
+    conn.backup(rusqlite::DatabaseName::Main, destination, None)
+        .map_err(|e| format!("Failed to back up database: {}", e))?;
+
+    let integrity: String = rusqlite::Connection::open_with_flags(destination, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .and_then(|backup| backup.query_row("PRAGMA integrity_check", [], |row| row.get(0)))
+        .map_err(|e| format!("Failed to verify backup: {}", e))?;
+    if integrity != "ok" {
+        let _ = fs::remove_file(destination);
+        return Err(format!("Backup failed integrity check: {}", integrity));
+    }
+
+    Ok(())
+}
+
+/// Create a consistent, verified snapshot of the database in `backup_dir`
+/// (named pbs_admin_backup_<YYYYMMDD_HHMMSS>.sqlite)
+#[tauri::command]
+pub(crate) fn backup_database(backup_dir: String) -> Result<String, String> {
+    let file_name = format!("pbs_admin_backup_{}.sqlite", chrono::Local::now().format("%Y%m%d_%H%M%S"));
+    let backup_path = Path::new(&backup_dir).join(file_name);
+
+    create_parent_dirs_within_pbs(&backup_path)?;
+    let validated_path = validate_write_path(&backup_path.to_string_lossy())?;
+    if validated_path.exists() {
+        return Err(format!("Backup already exists: {}", validated_path.display()));
+    }
+
+    let db_path = get_database_path_internal()?;
+    backup_sqlite_database(&db_path, &validated_path)?;
+
+    info!("Database backed up to {}", validated_path.display());
+
+    Ok(validated_path.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{sample_database, scratch_dir, strings};
+
+    #[test]
+    fn database_export_creates_parent_dirs_only_inside_pbs_roots() {
+        let dir = scratch_dir();
+        let nested = dir.path().join("exports/2025/backup.json");
+        create_parent_dirs_within_pbs(&nested).unwrap();
+        assert!(dir.path().join("exports/2025").is_dir());
+
+        let outside = tempfile::tempdir().unwrap();
+        assert!(create_parent_dirs_within_pbs(&outside.path().join("a/b.json")).unwrap_err().contains("Access denied"));
+        assert!(create_parent_dirs_within_pbs(&dir.path().join("x/../../y/b.json")).unwrap_err().contains("Invalid path"));
+    }
+
+    #[test]
+    fn database_json_round_trip_restores_identical_rows() {
+        let dir = scratch_dir();
+        let source = sample_database(&dir.path().join("source.db"));
+        let export = export_database_json_value(&source).unwrap();
+        // Round-trip through the on-disk text format
+        let export: serde_json::Value = serde_json::from_str(&serde_json::to_string_pretty(&export).unwrap()).unwrap();
+
+        let mut target = sample_database(&dir.path().join("target.db"));
+        target.execute_batch("DELETE FROM Pet; DELETE FROM Client; INSERT INTO Client VALUES (3, 'Stale', NULL, NULL);").unwrap();
+
+        assert_eq!(import_database_json_value(&mut target, &export, "replace").unwrap(), 4);
+        assert_eq!(export_database_json_value(&target).unwrap(), export);
+    }
+
+    #[test]
+    fn database_import_modes() {
+        let dir = scratch_dir();
+        let mut conn = sample_database(&dir.path().join("pbs_admin.db"));
+        let clients = serde_json::json!({"Client": [{"id": 1, "name": "Jane Smith"}, {"id": 5, "name": "New"}]});
+
+        // Appending a duplicate primary key fails and rolls back the whole import
+        assert!(import_database_json_value(&mut conn, &clients, "append").unwrap_err().contains("Failed to insert into Client"));
+        let count = |conn: &rusqlite::Connection| -> i64 { conn.query_row("SELECT COUNT(*) FROM Client", [], |r| r.get(0)).unwrap() };
+        assert_eq!(count(&conn), 2);
+
+        assert_eq!(import_database_json_value(&mut conn, &clients, "upsert").unwrap(), 2);
+        let name: String = conn.query_row("SELECT name FROM Client WHERE id = 1", [], |r| r.get(0)).unwrap();
+        assert_eq!(name, "Jane Smith");
+        assert_eq!(count(&conn), 3);
+
+        assert!(import_database_json_value(&mut conn, &clients, "merge").unwrap_err().contains("Unsupported import mode"));
+    }
+
+    #[test]
+    fn database_import_validates_schema_before_writing() {
+        let dir = scratch_dir();
+        let mut conn = sample_database(&dir.path().join("pbs_admin.db"));
+        let before = export_database_json_value(&conn).unwrap();
+
+        let invalid = [
+            (serde_json::json!({"Invoice": []}), "table Invoice does not exist"),
+            (serde_json::json!({"Client": [{"id": 9, "colour": "red"}]}), "column Client.colour does not exist"),
+            (serde_json::json!({"Client": {"id": 9}}), "must be an array"),
+            (serde_json::json!({"Client": [[9]]}), "is not an object"),
+            (serde_json::json!({"Client": [{"id": 9, "name": ["x"]}]}), "Unsupported value"),
+            (serde_json::json!([]), "expected an object of tables"),
+        ];
+        for (data, expected) in invalid {
+            let err = import_database_json_value(&mut conn, &data, "replace").unwrap_err();
+            assert!(err.contains(expected), "{} should contain {}", err, expected);
+        }
+        assert_eq!(export_database_json_value(&conn).unwrap(), before);
+    }
+
+    #[test]
+    fn database_backup_snapshots_a_wal_database() {
+        let dir = scratch_dir();
+        let source = dir.path().join("pbs_admin.db");
+        let conn = sample_database(&source);
+        conn.execute_batch("PRAGMA journal_mode = WAL; INSERT INTO Client VALUES (3, 'Only in WAL', NULL, NULL);").unwrap();
+        let backup = dir.path().join("pbs_admin_backup_20250115_143022.sqlite");
+
+        // The source connection stays open, as it would be in the running app
+        backup_sqlite_database(&source, &backup).unwrap();
+
+        let copy = rusqlite::Connection::open(&backup).unwrap();
+        let integrity: String = copy.query_row("PRAGMA integrity_check", [], |r| r.get(0)).unwrap();
+        assert_eq!(integrity, "ok");
+        assert_eq!(export_database_json_value(&copy).unwrap(), export_database_json_value(&conn).unwrap());
+    }
+
+    #[test]
+    fn database_backup_of_missing_source_fails() {
+        let dir = scratch_dir();
+        let err = backup_sqlite_database(&dir.path().join("missing.db"), &dir.path().join("backup.sqlite")).unwrap_err();
+        assert!(err.contains("Failed to open database"), "{}", err);
+    }
+
+    #[test]
+    fn describe_table_reports_column_definitions() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch("
+            CREATE TABLE Event (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                title TEXT NOT NULL,
+                status TEXT DEFAULT 'scheduled',
+                duration REAL
+            );
+        ").unwrap();
+
+        // AUTOINCREMENT creates sqlite_sequence, which is hidden
+        assert_eq!(database_table_names(&conn).unwrap(), strings(&["Event"]));
+
+        let column = |cid, name: &str, type_: &str, not_null, default_value: Option<&str>, is_primary_key| ColumnInfo {
+            cid,
+            name: name.to_string(),
+            type_: type_.to_string(),
+            not_null,
+            default_value: default_value.map(String::from),
+            is_primary_key,
+        };
+        assert_eq!(describe_table_columns(&conn, "Event").unwrap(), vec![
+            column(0, "id", "INTEGER", false, None, true),
+            column(1, "title", "TEXT", true, None, false),
+            column(2, "status", "TEXT", false, Some("'scheduled'"), false),
+            column(3, "duration", "REAL", false, None, false),
+        ]);
+    }
+
+    #[test]
+    fn describe_table_only_accepts_existing_tables() {
+        let dir = scratch_dir();
+        let conn = sample_database(&dir.path().join("pbs_admin.db"));
+
+        assert_eq!(database_table_names(&conn).unwrap(), strings(&["Client", "Pet"]));
+        assert_eq!(describe_table_columns(&conn, "client").unwrap_err(), "Unknown table: client");
+        assert_eq!(
+            describe_table_columns(&conn, "Client); DROP TABLE Client; --").unwrap_err(),
+            "Unknown table: Client); DROP TABLE Client; --"
+        );
+        assert_eq!(database_table_names(&conn).unwrap().len(), 2);
+
+        let column_info = serde_json::to_value(&describe_table_columns(&conn, "Pet").unwrap()[1]).unwrap();
+        assert_eq!(column_info["type"], "INTEGER");
+        assert_eq!(column_info["not_null"], true);
+    }
+}
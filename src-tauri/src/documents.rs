@@ -0,0 +1,3417 @@
+// PBS Admin - Document generation and conversion commands
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::io::Write;
+use std::process::Command;
+use std::sync::Mutex;
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{validate_read_path, validate_write_path, write_file_atomically};
+use crate::files::list_files;
+use crate::pdf::session_note_date;
+use crate::settings::resolve_pbs_admin_data_dir;
+use crate::system::audited;
+use crate::transcription::format_duration_clock;
+
+// ============================================================================
+// DOCUMENT CONVERSION (PANDOC)
+// ============================================================================
+
+pub(crate) fn templates_path_in(data_dir: &Path) -> Result<PathBuf, String> {
+    let templates_path = data_dir.join("Templates");
+
+    // Ensure the directory exists
+    if !templates_path.exists() {
+        fs::create_dir_all(&templates_path)
+            .map_err(|e| format!("Failed to create templates directory: {}", e))?;
+    }
+
+    Ok(templates_path)
+}
+
+#[tauri::command]
+pub(crate) fn get_templates_path() -> Result<String, String> {
+    let templates_path = templates_path_in(&resolve_pbs_admin_data_dir()?)?;
+    Ok(templates_path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub(crate) fn pandoc_docx_to_markdown(docx_path: String) -> Result<String, String> {
+    if !docx_path.to_lowercase().ends_with(".docx") {
+        return Err("Input file must be a .docx file".to_string());
+    }
+    if !std::path::Path::new(&docx_path).exists() {
+        return Err(format!("File not found: {}", docx_path));
+    }
+
+    let output = Command::new("pandoc")
+        .arg(&docx_path)
+        .arg("-f").arg("docx")
+        .arg("-t").arg("markdown")
+        .arg("--wrap=none")
+        .output()
+        .map_err(|e| format!("Failed to execute pandoc: {}. Is pandoc installed?", e))?;
+
+    if output.status.success() {
+        String::from_utf8(output.stdout)
+            .map_err(|e| format!("Pandoc returned non-UTF8 output: {}", e))
+    } else {
+        let error_msg = String::from_utf8_lossy(&output.stderr);
+        Err(format!("Pandoc docx→markdown failed: {}", error_msg))
+    }
+}
+
+/// Pandoc flags callers may pass on their own. Anything not listed in this section is rejected,
+/// so abbreviations (`--lua=`), attached short options (`-L/x`) and new pandoc options can't slip through.
+const PANDOC_ALLOWED_FLAGS: &[&str] = &[
+    "--toc", "--table-of-contents", "--standalone", "-s", "--number-sections", "-N", "--section-divs",
+];
+
+/// Flags whose value must be one of a fixed set
+const PANDOC_CHOICE_FLAGS: &[(&str, &[&str])] = &[
+    ("--toc-depth", &["1", "2", "3", "4", "5", "6"]),
+    ("--wrap", &["auto", "none", "preserve"]),
+    ("--top-level-division", &["default", "section", "chapter", "part"]),
+];
+
+/// Flags whose value is a file that must live inside the PBS_Admin Templates folder
+const PANDOC_TEMPLATE_FILE_FLAGS: &[&str] = &["--css", "--epub-cover-image"];
+
+/// Validate caller-supplied pandoc arguments before appending them verbatim
+fn validate_pandoc_extra_args(args: &[String]) -> Result<(), String> {
+    let templates_path = resolve_pbs_admin_data_dir()?.join("Templates");
+    validate_pandoc_extra_args_in(args, &templates_path)
+}
+
+/// Check `args` against the allowlist, resolving file arguments against `templates_path`
+fn validate_pandoc_extra_args_in(args: &[String], templates_path: &Path) -> Result<(), String> {
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+
+        if inline_value.is_none() && PANDOC_ALLOWED_FLAGS.contains(&flag) {
+            continue;
+        }
+
+        let choices = PANDOC_CHOICE_FLAGS.iter()
+            .find(|(name, _)| *name == flag)
+            .map(|(_, choices)| *choices);
+        let is_template_file = PANDOC_TEMPLATE_FILE_FLAGS.contains(&flag);
+        if choices.is_none() && !is_template_file {
+            return Err(format!("Pandoc argument not allowed: {}", arg));
+        }
+
+        let value = match inline_value {
+            Some(v) => v,
+            None => iter.next()
+                .cloned()
+                .ok_or_else(|| format!("Pandoc argument {} requires a value", flag))?,
+        };
+
+        if let Some(choices) = choices {
+            if !choices.contains(&value.as_str()) {
+                return Err(format!(
+                    "Invalid value for pandoc argument {}: {} (expected one of: {})",
+                    flag, value, choices.join(", ")
+                ));
+            }
+            continue;
+        }
+
+        // Canonicalize both sides so ../ and symlinks can't escape the Templates folder
+        let allowed = match (templates_path.canonicalize(), Path::new(&value).canonicalize()) {
+            (Ok(templates), Ok(file)) => file.starts_with(&templates) && file.is_file(),
+            _ => false,
+        };
+        if !allowed {
+            return Err(format!(
+                "Pandoc argument {} must reference a file inside the PBS_Admin Templates folder: {}",
+                flag, value
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Validate a markdown -> document conversion and build the pandoc arguments for it
+pub(crate) fn build_pandoc_args(
+    input_path: &str,
+    output_path: &str,
+    template_path: Option<&str>,
+    extra_args: Option<&[String]>,
+) -> Result<Vec<String>, String> {
+    // Validate input file extension
+    let input_lower = input_path.to_lowercase();
+    if !input_lower.ends_with(".md") && !input_lower.ends_with(".markdown") && !input_lower.ends_with(".txt") {
+        return Err("Input file must be a .md, .markdown, or .txt file".to_string());
+    }
+
+    // Validate output file extension
+    let output_lower = output_path.to_lowercase();
+    let allowed_outputs = [".docx", ".pdf", ".html", ".epub", ".tex", ".latex"];
+    if !allowed_outputs.iter().any(|ext| output_lower.ends_with(ext)) {
+        return Err("Output file must be a .docx, .pdf, .html, .epub, or .tex file".to_string());
+    }
+
+    // Validate output path is within allowed directories
+    validate_write_path(output_path)?;
+
+    // Validate any extra pandoc flags before building the command
+    if let Some(args) = extra_args {
+        validate_pandoc_extra_args(args)?;
+    }
+
+    // Add input file
+    let mut args = vec![input_path.to_string()];
+
+    // Use hard_line_breaks extension to preserve markdown line breaks
+    args.push("-f".to_string());
+    args.push("markdown+hard_line_breaks".to_string());
+
+    // Add output file
+    args.push("-o".to_string());
+    args.push(output_path.to_string());
+
+    // Add reference document (template) if provided
+    // Note: The template's letterhead MUST be in the Word Header section (Insert > Header)
+    // not in the document body, for Pandoc --reference-doc to apply it correctly
+    // --reference-doc only applies to DOCX output, so it's skipped for other formats
+    if let Some(template) = template_path.filter(|_| output_lower.ends_with(".docx")) {
+        // Validate template is a .docx file
+        if !template.to_lowercase().ends_with(".docx") {
+            return Err("Template must be a .docx file".to_string());
+        }
+        // Check if template file exists
+        let template_path_obj = std::path::Path::new(template);
+        if template_path_obj.exists() {
+            info!("Using reference document template: {}", template);
+            args.push("--reference-doc".to_string());
+            args.push(template.to_string());
+        } else {
+            return Err(format!(
+                "Template file not found: {}. Please ensure the letterhead template exists in Documents\\PBS_Admin\\Templates\\",
+                template
+            ));
+        }
+    }
+
+    // Append format-specific flags (e.g. --toc, --css=style.css) verbatim
+    if let Some(extra) = extra_args {
+        args.extend(extra.iter().cloned());
+    }
+
+    Ok(args)
+}
+
+#[tauri::command]
+pub(crate) fn run_pandoc(
+    input_path: String,
+    output_path: String,
+    template_path: Option<String>,
+    extra_args: Option<Vec<String>>,
+) -> Result<String, String> {
+    let args = build_pandoc_args(&input_path, &output_path, template_path.as_deref(), extra_args.as_deref())?;
+
+    // Build pandoc command
+    let mut cmd = Command::new("pandoc");
+    cmd.args(&args);
+
+    // Execute command
+    let output = cmd.output()
+        .map_err(|e| format!("Failed to execute pandoc: {}. Is pandoc installed?", e))?;
+
+    // Check if command succeeded
+    if output.status.success() {
+        Ok(output_path.clone())
+    } else {
+        let error_msg = String::from_utf8_lossy(&output.stderr);
+        Err(format!("Pandoc conversion failed: {}", error_msg))
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct BatchPandocInput {
+    input_path: String,
+    output_path: String,
+    template_path: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct BatchPandocResult {
+    input_path: String,
+    output_path: String,
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Run one pandoc conversion for the batch; failures are reported in the result, not raised
+async fn run_pandoc_batch_item(program: &std::ffi::OsStr, input: BatchPandocInput) -> BatchPandocResult {
+    let outcome = async {
+        let args = build_pandoc_args(&input.input_path, &input.output_path, input.template_path.as_deref(), None)?;
+        let output = tokio::process::Command::new(program)
+            .args(&args)
+            .output()
+            .await
+            .map_err(|e| format!("Failed to execute pandoc: {}. Is pandoc installed?", e))?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(format!("Pandoc conversion failed: {}", String::from_utf8_lossy(&output.stderr)))
+        }
+    }.await;
+
+    BatchPandocResult {
+        input_path: input.input_path,
+        output_path: input.output_path,
+        success: outcome.is_ok(),
+        error: outcome.err(),
+    }
+}
+
+/// Run conversions with at most `max_concurrency` pandoc processes at once.
+/// Results come back in input order.
+async fn run_pandoc_batch_with(
+    program: &std::ffi::OsStr,
+    inputs: Vec<BatchPandocInput>,
+    max_concurrency: u32,
+) -> Vec<BatchPandocResult> {
+    use futures::stream::{self, StreamExt};
+
+    let mut results: Vec<(usize, BatchPandocResult)> = stream::iter(inputs.into_iter().enumerate())
+        .map(|(index, input)| async move { (index, run_pandoc_batch_item(program, input).await) })
+        .buffer_unordered(max_concurrency.max(1) as usize)
+        .collect()
+        .await;
+
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Convert many markdown files with pandoc in parallel (e.g. monthly reports).
+/// Each file succeeds or fails independently.
+#[tauri::command]
+pub(crate) async fn run_pandoc_batch(inputs: Vec<BatchPandocInput>, max_concurrency: u32) -> Result<Vec<BatchPandocResult>, String> {
+    if inputs.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let total = inputs.len();
+    let results = run_pandoc_batch_with(std::ffi::OsStr::new("pandoc"), inputs, max_concurrency).await;
+
+    let failed = results.iter().filter(|r| !r.success).count();
+    info!("Pandoc batch finished: {} of {} converted", total - failed, total);
+    Ok(results)
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct PandocInfo {
+    version: String,
+    output_formats: Vec<String>,
+}
+
+/// Pull the version out of the first line of `pandoc --version` (e.g. "pandoc 3.1.13")
+fn parse_pandoc_version(version_output: &str) -> Option<String> {
+    let first_line = version_output.lines().next()?.trim();
+    let version = first_line.strip_prefix("pandoc")?.trim();
+    // Windows builds print "pandoc.exe 3.1.13"
+    let version = version.strip_prefix(".exe").unwrap_or(version).trim();
+    version.split_whitespace().next().map(|v| v.to_string())
+}
+
+/// Check pandoc is installed and report its version and supported output formats
+#[tauri::command]
+pub(crate) fn check_pandoc_installation() -> Result<PandocInfo, String> {
+    check_pandoc_installation_with(std::ffi::OsStr::new("pandoc"))
+}
+
+fn check_pandoc_installation_with(program: &std::ffi::OsStr) -> Result<PandocInfo, String> {
+    let not_installed = "Pandoc is not installed or not in PATH. Download it from https://pandoc.org/installing.html";
+
+    let version_output = Command::new(program)
+        .arg("--version")
+        .output()
+        .map_err(|_| not_installed.to_string())?;
+
+    if !version_output.status.success() {
+        return Err("Pandoc found but returned an error.".to_string());
+    }
+
+    let version_str = String::from_utf8_lossy(&version_output.stdout);
+    let version = parse_pandoc_version(&version_str)
+        .ok_or_else(|| format!("Could not parse pandoc version from: {}", version_str.lines().next().unwrap_or("")))?;
+
+    let formats_output = Command::new(program)
+        .arg("--list-output-formats")
+        .output()
+        .map_err(|e| format!("Failed to execute pandoc: {}", e))?;
+
+    let output_formats = String::from_utf8_lossy(&formats_output.stdout)
+        .lines()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+        .collect();
+
+    Ok(PandocInfo { version, output_formats })
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub(crate) struct PandocFormats {
+    input: Vec<String>,
+    output: Vec<String>,
+}
+
+/// Pandoc's format lists, cached as app state after the first lookup
+#[derive(Default)]
+pub(crate) struct PandocFormatsCache(Mutex<Option<PandocFormats>>);
+
+/// Run `pandoc <flag>` and collect one format per line
+fn list_pandoc_formats_with(pandoc: &std::ffi::OsStr, flag: &str) -> Result<Vec<String>, String> {
+    let output = Command::new(pandoc)
+        .arg(flag)
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => "Pandoc is not installed or not in PATH. Download it from https://pandoc.org/installing.html".to_string(),
+            _ => format!("Failed to execute pandoc: {}", e),
+        })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("pandoc {} failed: {}", flag, stderr.trim()));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+        .collect())
+}
+
+/// Return the cached formats, spawning pandoc only when the cache is empty or `refresh` is set
+fn cached_pandoc_formats_with(
+    cache: &PandocFormatsCache,
+    pandoc: &std::ffi::OsStr,
+    refresh: bool,
+) -> Result<PandocFormats, String> {
+    // Hold the lock across the lookup so concurrent first calls spawn pandoc once
+    let mut cached = cache.0.lock().map_err(|_| "Pandoc format cache is poisoned".to_string())?;
+    if !refresh {
+        if let Some(formats) = cached.as_ref() {
+            return Ok(formats.clone());
+        }
+    }
+
+    let formats = PandocFormats {
+        input: list_pandoc_formats_with(pandoc, "--list-input-formats")?,
+        output: list_pandoc_formats_with(pandoc, "--list-output-formats")?,
+    };
+    *cached = Some(formats.clone());
+    Ok(formats)
+}
+
+/// Pandoc's supported input and output formats (looked up once per session)
+#[tauri::command]
+pub(crate) fn get_pandoc_supported_formats(cache: tauri::State<'_, PandocFormatsCache>) -> Result<PandocFormats, String> {
+    cached_pandoc_formats_with(&cache, std::ffi::OsStr::new("pandoc"), false)
+}
+
+/// Re-query pandoc's formats, e.g. after installing or upgrading pandoc
+#[tauri::command]
+pub(crate) fn refresh_pandoc_formats(cache: tauri::State<'_, PandocFormatsCache>) -> Result<PandocFormats, String> {
+    cached_pandoc_formats_with(&cache, std::ffi::OsStr::new("pandoc"), true)
+}
+
+/// Build a YAML front matter block (delimited by `---`) from a JSON object.
+/// Values are emitted as JSON, which is valid YAML flow syntax, so quoting and escaping
+/// are always well-formed. Document property keys must be plain strings.
+fn build_yaml_front_matter(metadata: &serde_json::Value) -> Result<String, String> {
+    const STRING_KEYS: &[&str] = &["title", "author", "date", "subject"];
+
+    let map = metadata.as_object()
+        .ok_or_else(|| "Metadata must be a JSON object".to_string())?;
+
+    let mut yaml = String::from("---\n");
+    for (key, value) in map {
+        // Keys are written unquoted, so restrict them to simple identifiers
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(format!("Invalid metadata key: {}", key));
+        }
+        if STRING_KEYS.contains(&key.as_str()) && !value.is_string() {
+            return Err(format!("Metadata field '{}' must be a string", key));
+        }
+        let value_str = serde_json::to_string(value)
+            .map_err(|e| format!("Failed to serialize metadata field '{}': {}", key, e))?;
+        yaml.push_str(&format!("{}: {}\n", key, value_str));
+    }
+    yaml.push_str("---\n\n");
+
+    Ok(yaml)
+}
+
+#[tauri::command]
+pub(crate) fn run_pandoc_from_stdin(
+    markdown_content: String,
+    output_path: String,
+    template_path: Option<String>,
+    metadata: Option<serde_json::Value>,
+) -> Result<String, String> {
+    run_pandoc_from_stdin_with(std::ffi::OsStr::new("pandoc"), markdown_content, output_path, template_path, metadata)
+}
+
+pub(crate) fn run_pandoc_from_stdin_with(
+    program: &std::ffi::OsStr,
+    markdown_content: String,
+    output_path: String,
+    template_path: Option<String>,
+    metadata: Option<serde_json::Value>,
+) -> Result<String, String> {
+    use std::process::Stdio;
+
+    // Validate output path is within allowed directories
+    validate_write_path(&output_path)?;
+
+    // Prepend document metadata (title, author, date...) as YAML front matter
+    let markdown_content = match metadata {
+        Some(ref meta) => format!("{}{}", build_yaml_front_matter(meta)?, markdown_content),
+        None => markdown_content,
+    };
+
+    // Build pandoc command with stdin input
+    let mut cmd = Command::new(program);
+    cmd.stdin(Stdio::piped());
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+
+    // Read from stdin (dash means read from stdin)
+    cmd.arg("-");
+
+    // Use hard_line_breaks extension to preserve markdown line breaks
+    cmd.arg("-f");
+    cmd.arg("markdown+hard_line_breaks");
+
+    // Add output file
+    cmd.arg("-o");
+    cmd.arg(&output_path);
+
+    // Add reference document (template) if provided
+    // Note: The template's letterhead MUST be in the Word Header section (Insert > Header)
+    // not in the document body, for Pandoc --reference-doc to apply it correctly
+    if let Some(ref template) = template_path {
+        // Check if template file exists
+        let template_path_obj = std::path::Path::new(template);
+        if template_path_obj.exists() {
+            info!("Using reference document template: {}", template);
+            cmd.arg("--reference-doc");
+            cmd.arg(template);
+        } else {
+            return Err(format!(
+                "Template file not found: {}. Please ensure the letterhead template exists in Documents\\PBS_Admin\\Templates\\",
+                template
+            ));
+        }
+    }
+
+    // Spawn process
+    let mut child = cmd.spawn()
+        .map_err(|e| format!("Failed to spawn pandoc: {}. Is pandoc installed?", e))?;
+
+    // Write markdown content to stdin
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(markdown_content.as_bytes())
+            .map_err(|e| format!("Failed to write to pandoc stdin: {}", e))?;
+    }
+
+    // Wait for process to complete
+    let output = child.wait_with_output()
+        .map_err(|e| format!("Failed to wait for pandoc: {}", e))?;
+
+    // Check if command succeeded
+    if output.status.success() {
+        Ok(output_path.clone())
+    } else {
+        let error_msg = String::from_utf8_lossy(&output.stderr);
+        Err(format!("Pandoc conversion failed: {}", error_msg))
+    }
+}
+
+// ============================================================================
+// TREATMENT PLANS, INVOICES AND FORMATTING
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+struct TreatmentGoal {
+    description: String,
+    target_date: String,
+    interventions: Vec<String>,
+    measurable_outcomes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TreatmentPlan {
+    client_name: String,
+    diagnosis: Vec<String>,
+    goals: Vec<TreatmentGoal>,
+    start_date: String,
+    review_date: String,
+}
+
+/// Markdown body for a treatment plan: plan dates, diagnoses, then numbered goals
+/// with their interventions and measurable outcomes as sub-bullets
+fn build_treatment_plan_markdown(plan: &TreatmentPlan) -> String {
+    let mut markdown = format!(
+        "**Client:** {}\n**Start date:** {}\n**Review date:** {}\n\n## Diagnosis\n\n",
+        plan.client_name, plan.start_date, plan.review_date
+    );
+    if plan.diagnosis.is_empty() {
+        markdown.push_str("_None recorded._\n");
+    }
+    for diagnosis in &plan.diagnosis {
+        markdown.push_str(&format!("- {}\n", diagnosis.trim()));
+    }
+
+    markdown.push_str("\n## Goals\n\n");
+    for (index, goal) in plan.goals.iter().enumerate() {
+        markdown.push_str(&format!("{}. **{}**", index + 1, goal.description.trim()));
+        if !goal.target_date.trim().is_empty() {
+            markdown.push_str(&format!(" (target: {})", goal.target_date.trim()));
+        }
+        markdown.push('\n');
+        for intervention in &goal.interventions {
+            markdown.push_str(&format!("    - *Intervention:* {}\n", intervention.trim()));
+        }
+        for outcome in &goal.measurable_outcomes {
+            markdown.push_str(&format!("    - *Measurable outcome:* {}\n", outcome.trim()));
+        }
+    }
+    markdown
+}
+
+fn generate_treatment_plan_docx_with(
+    pandoc: &std::ffi::OsStr,
+    plan: &TreatmentPlan,
+    template_path: &str,
+    output_path: &str,
+) -> Result<String, String> {
+    if plan.client_name.trim().is_empty() {
+        return Err("Client name is required".to_string());
+    }
+    if plan.goals.is_empty() {
+        return Err("A treatment plan needs at least one goal".to_string());
+    }
+
+    let validated_output = validate_write_path(output_path)?;
+    let is_docx = validated_output
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("docx"))
+        .unwrap_or(false);
+    if !is_docx {
+        return Err(format!("Output path must be a .docx file: {}", output_path));
+    }
+
+    let document_metadata = serde_json::json!({
+        "title": format!("Treatment Plan - {}", plan.client_name),
+        "date": plan.start_date,
+    });
+    let template = Some(template_path.to_string()).filter(|path| !path.trim().is_empty());
+
+    run_pandoc_from_stdin_with(
+        pandoc,
+        build_treatment_plan_markdown(plan),
+        validated_output.to_string_lossy().to_string(),
+        template,
+        Some(document_metadata),
+    )
+}
+
+/// Save a treatment plan as a Word document via pandoc. An empty `template_path`
+/// uses pandoc's default styles instead of the letterhead template.
+#[tauri::command]
+pub(crate) fn generate_treatment_plan_docx(plan: TreatmentPlan, template_path: String, output_path: String) -> Result<String, String> {
+    generate_treatment_plan_docx_with(std::ffi::OsStr::new("pandoc"), &plan, &template_path, &output_path)
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct InvoiceData {
+    client_name: String,
+    client_id: String,
+    clinician_name: String,
+    session_date: String,
+    service_code: String,
+    units: u32,
+    rate_cents: u64,
+    insurance_provider: Option<String>,
+}
+
+/// Invoice total in cents (units x rate), computed in integers so there's no float rounding
+fn invoice_total_cents(invoice: &InvoiceData) -> Result<u64, String> {
+    invoice.rate_cents
+        .checked_mul(invoice.units as u64)
+        .ok_or_else(|| "Invoice total is too large".to_string())
+}
+
+/// Insert thousands separators into a string of digits, e.g. "1234567" -> "1,234,567"
+fn group_thousands(digits: &str) -> String {
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Format cents as dollars with thousands separators, e.g. 123450 -> "$1,234.50"
+fn format_dollars(cents: u64) -> String {
+    format!("${}.{:02}", group_thousands(&(cents / 100).to_string()), cents % 100)
+}
+
+/// Display symbol and number of minor-unit digits for supported currencies
+fn currency_format(currency_code: &str) -> Option<(&'static str, u32)> {
+    match currency_code.trim().to_uppercase().as_str() {
+        "USD" => Some(("$", 2)),
+        "AUD" => Some(("A$", 2)),
+        "NZD" => Some(("NZ$", 2)),
+        "CAD" => Some(("CA$", 2)),
+        "GBP" => Some(("£", 2)),
+        "EUR" => Some(("€", 2)),
+        "JPY" => Some(("¥", 0)),
+        _ => None,
+    }
+}
+
+/// Format an amount in minor units (cents) for display, e.g. 19500 USD -> "$195.00".
+/// Negative amounts use accounting parentheses: "($195.00)".
+#[tauri::command]
+pub(crate) fn format_currency(cents: i64, currency_code: String) -> Result<String, String> {
+    let (symbol, minor_digits) = currency_format(&currency_code)
+        .ok_or_else(|| format!("Unsupported currency code: {}", currency_code))?;
+
+    let amount = rust_decimal::Decimal::new(cents, minor_digits);
+    let formatted = format!("{:.*}", minor_digits as usize, amount.abs());
+    let (whole, fraction) = match formatted.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (formatted.as_str(), None),
+    };
+
+    let mut display = format!("{}{}", symbol, group_thousands(whole));
+    if let Some(fraction) = fraction {
+        display.push('.');
+        display.push_str(fraction);
+    }
+    if amount.is_sign_negative() && !amount.is_zero() {
+        display = format!("({})", display);
+    }
+    Ok(display)
+}
+
+/// Format a duration in seconds as "HH:MM:SS", "minutes" (e.g. "2.5 min") or "human" (e.g. "2h 15m")
+#[tauri::command]
+pub(crate) fn format_duration(seconds: f64, format: String) -> Result<String, String> {
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(format!("Invalid duration: {}", seconds));
+    }
+    let total = seconds.round() as u64;
+    let (hours, minutes, secs) = (total / 3600, (total / 60) % 60, total % 60);
+
+    match format.as_str() {
+        "HH:MM:SS" => Ok(format!("{:02}:{:02}:{:02}", hours, minutes, secs)),
+        "minutes" => Ok(format!("{:.1} min", seconds / 60.0)),
+        "human" => Ok(if hours > 0 {
+            format!("{}h {}m", hours, minutes)
+        } else if minutes > 0 && secs > 0 {
+            format!("{}m {}s", minutes, secs)
+        } else if minutes > 0 {
+            format!("{}m", minutes)
+        } else {
+            format!("{}s", secs)
+        }),
+        other => Err(format!("Unsupported duration format: {}. Use HH:MM:SS, minutes, or human.", other)),
+    }
+}
+
+/// Translate a display pattern ("YYYY-MM-DD", "MM/DD/YYYY", "MMMM D, YYYY", "HH:mm") into
+/// chrono format codes. Patterns already containing '%' are treated as chrono formats.
+fn chrono_format_from_pattern(pattern: &str) -> Result<String, String> {
+    use chrono::format::{Item, StrftimeItems};
+
+    if pattern.trim().is_empty() {
+        return Err("Date format is required".to_string());
+    }
+
+    let chrono_format = if pattern.contains('%') {
+        pattern.to_string()
+    } else {
+        // Longest tokens first so "MMMM" isn't read as "MM" + "MM"
+        const TOKENS: &[(&str, &str)] = &[
+            ("YYYY", "%Y"), ("YY", "%y"),
+            ("MMMM", "%B"), ("MMM", "%b"), ("MM", "%m"), ("M", "%-m"),
+            ("dddd", "%A"), ("ddd", "%a"), ("DD", "%d"), ("D", "%-d"),
+            ("HH", "%H"), ("hh", "%I"), ("h", "%-I"), ("mm", "%M"), ("ss", "%S"), ("A", "%p"),
+        ];
+        let mut converted = String::new();
+        let mut rest = pattern;
+        'outer: while !rest.is_empty() {
+            for (token, code) in TOKENS {
+                if let Some(remaining) = rest.strip_prefix(token) {
+                    converted.push_str(code);
+                    rest = remaining;
+                    continue 'outer;
+                }
+            }
+            let ch = rest.chars().next().unwrap_or_default();
+            if ch.is_ascii_alphabetic() {
+                return Err(format!("Unsupported date format token '{}' in: {}", ch, pattern));
+            }
+            converted.push(ch);
+            rest = &rest[ch.len_utf8()..];
+        }
+        converted
+    };
+
+    if StrftimeItems::new(&chrono_format).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("Invalid date format: {}", pattern));
+    }
+    Ok(chrono_format)
+}
+
+fn parse_timezone(timezone: &str) -> Result<chrono_tz::Tz, String> {
+    timezone.trim().parse::<chrono_tz::Tz>()
+        .map_err(|_| format!("Invalid timezone: {}", timezone))
+}
+
+/// Parse a date (and optional time) in a named IANA timezone into a Unix timestamp (seconds).
+/// Dates without a time are taken as local midnight.
+#[tauri::command]
+pub(crate) fn parse_date(input: String, format: String, timezone: String) -> Result<u64, String> {
+    use chrono::TimeZone;
+
+    let tz = parse_timezone(&timezone)?;
+    let chrono_format = chrono_format_from_pattern(&format)?;
+    let input = input.trim();
+
+    let naive = chrono::NaiveDateTime::parse_from_str(input, &chrono_format).or_else(|_| {
+        chrono::NaiveDate::parse_from_str(input, &chrono_format)
+            .map(|date| date.and_time(chrono::NaiveTime::MIN))
+    }).map_err(|e| format!("Failed to parse date '{}' as {}: {}", input, format, e))?;
+
+    // Ambiguous local times (DST fall-back) resolve to the earlier instant
+    let local = tz.from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(|| format!("{} does not exist in {} (daylight saving gap)", input, timezone))?;
+
+    u64::try_from(local.timestamp())
+        .map_err(|_| format!("Date is before 1970: {}", input))
+}
+
+/// Format a Unix timestamp (seconds) in a named IANA timezone
+#[tauri::command]
+pub(crate) fn format_date(timestamp: u64, format: String, timezone: String) -> Result<String, String> {
+    let tz = parse_timezone(&timezone)?;
+    let chrono_format = chrono_format_from_pattern(&format)?;
+
+    let seconds = i64::try_from(timestamp).map_err(|_| format!("Invalid timestamp: {}", timestamp))?;
+    let utc = chrono::DateTime::from_timestamp(seconds, 0)
+        .ok_or_else(|| format!("Invalid timestamp: {}", timestamp))?;
+    Ok(utc.with_timezone(&tz).format(&chrono_format).to_string())
+}
+
+/// Psychotherapy codes and the minimum minutes each needs (CMS midpoint rule)
+const PSYCHOTHERAPY_CPT_CODES: &[(&str, u32)] = &[("90832", 16), ("90834", 38), ("90837", 53)];
+/// Psychotherapy add-ons reported alongside an E/M visit, same time thresholds
+const PSYCHOTHERAPY_ADD_ON_CPT_CODES: &[(&str, u32)] = &[("90833", 16), ("90836", 38), ("90838", 53)];
+/// Crisis psychotherapy: 90839 covers the first 30-74 minutes, 90840 each additional 30
+const CRISIS_CPT_CODE: &str = "90839";
+const CRISIS_ADD_ON_CPT_CODE: &str = "90840";
+const CRISIS_MIN_MINUTES: u32 = 30;
+
+#[derive(Debug, Serialize, PartialEq)]
+pub(crate) struct BillingCalculation {
+    /// Total units billed (base code plus any add-on units)
+    units: u32,
+    base_code: String,
+    add_on_code: Option<String>,
+    /// Minutes past the last full 15-minute block (8 or more rounds a timed unit up)
+    remaining_minutes: u32,
+}
+
+/// Pick the longest code in `tiers` whose minimum the session meets
+fn select_time_tier(tiers: &[(&'static str, u32)], duration_minutes: u32, cpt_code: &str) -> Result<&'static str, String> {
+    tiers.iter()
+        .rev()
+        .find(|(_, min_minutes)| duration_minutes >= *min_minutes)
+        .map(|(code, _)| *code)
+        .ok_or_else(|| format!(
+            "{} minutes is too short to bill {} (minimum {} minutes)",
+            duration_minutes, cpt_code, tiers[0].1
+        ))
+}
+
+/// Work out billing units for a time-based CPT code. Psychotherapy codes (90832/90834/90837
+/// and the E/M add-ons 90833/90836/90838) are one unit each, using the code that matches
+/// the session length; crisis 90839 adds a 90840 unit per additional 30 minutes.
+#[tauri::command]
+pub(crate) fn calculate_billing_units(duration_minutes: u32, cpt_code: String) -> Result<BillingCalculation, String> {
+    let code = cpt_code.trim();
+    let remaining_minutes = duration_minutes % 15;
+
+    let tiers = if PSYCHOTHERAPY_CPT_CODES.iter().any(|(c, _)| *c == code) {
+        Some(PSYCHOTHERAPY_CPT_CODES)
+    } else if PSYCHOTHERAPY_ADD_ON_CPT_CODES.iter().any(|(c, _)| *c == code) {
+        Some(PSYCHOTHERAPY_ADD_ON_CPT_CODES)
+    } else {
+        None
+    };
+
+    if let Some(tiers) = tiers {
+        let base_code = select_time_tier(tiers, duration_minutes, code)?;
+        if base_code != code {
+            info!("{} minutes bills as {} rather than {}", duration_minutes, base_code, code);
+        }
+        return Ok(BillingCalculation {
+            units: 1,
+            base_code: base_code.to_string(),
+            add_on_code: None,
+            remaining_minutes,
+        });
+    }
+
+    match code {
+        CRISIS_CPT_CODE => {
+            if duration_minutes < CRISIS_MIN_MINUTES {
+                return Err(format!(
+                    "{} minutes is too short to bill {} (minimum {} minutes); use 90832 instead",
+                    duration_minutes, CRISIS_CPT_CODE, CRISIS_MIN_MINUTES
+                ));
+            }
+            // 75-104 minutes is one add-on unit, 105-134 two, and so on
+            let add_on_units = if duration_minutes >= 75 { (duration_minutes - 45) / 30 } else { 0 };
+            Ok(BillingCalculation {
+                units: 1 + add_on_units,
+                base_code: CRISIS_CPT_CODE.to_string(),
+                add_on_code: (add_on_units > 0).then(|| CRISIS_ADD_ON_CPT_CODE.to_string()),
+                remaining_minutes,
+            })
+        }
+        CRISIS_ADD_ON_CPT_CODE => Err(format!(
+            "{} is an add-on code; calculate units for {} and the add-on is included",
+            CRISIS_ADD_ON_CPT_CODE, CRISIS_CPT_CODE
+        )),
+        _ => Err(format!(
+            "Unsupported CPT code: {}. Supported codes: 90832, 90833, 90834, 90836, 90837, 90838, 90839",
+            cpt_code
+        )),
+    }
+}
+
+/// Markdown billing summary for an invoice
+fn build_invoice_markdown(invoice: &InvoiceData) -> Result<String, String> {
+    let total = invoice_total_cents(invoice)?;
+    // Pipes would break the table row
+    let cell = |text: &str| text.replace('|', "\\|");
+
+    let mut markdown = format!(
+        "**Client:** {} (ID: {})\n**Clinician:** {}\n**Session date:** {}\n",
+        invoice.client_name, invoice.client_id, invoice.clinician_name, invoice.session_date
+    );
+    if let Some(provider) = invoice.insurance_provider.as_deref().filter(|p| !p.trim().is_empty()) {
+        markdown.push_str(&format!("**Insurance provider:** {}\n", provider));
+    }
+    markdown.push_str(&format!(
+        "\n## Services\n\n| Service code | Units | Rate | Amount |\n|---|---:|---:|---:|\n| {} | {} | {} | {} |\n\n**Total due: {}**\n",
+        cell(&invoice.service_code),
+        invoice.units,
+        format_dollars(invoice.rate_cents),
+        format_dollars(total),
+        format_dollars(total)
+    ));
+    Ok(markdown)
+}
+
+/// Generate a DOCX invoice from billing data via pandoc (template used as the reference document)
+#[tauri::command]
+pub(crate) fn generate_invoice(invoice: InvoiceData, template_path: String, output_path: String) -> Result<String, String> {
+    generate_invoice_with(std::ffi::OsStr::new("pandoc"), invoice, template_path, output_path)
+}
+
+fn generate_invoice_with(
+    pandoc: &std::ffi::OsStr,
+    invoice: InvoiceData,
+    template_path: String,
+    output_path: String,
+) -> Result<String, String> {
+    let validated_output = validate_write_path(&output_path)?;
+    let is_docx = validated_output
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("docx"))
+        .unwrap_or(false);
+    if !is_docx {
+        return Err(format!("Output path must be a .docx file: {}", output_path));
+    }
+
+    let markdown = build_invoice_markdown(&invoice)?;
+    let document_metadata = serde_json::json!({
+        "title": format!("Invoice - {}", invoice.client_name),
+        "author": invoice.clinician_name,
+        "date": invoice.session_date,
+    });
+    let template = Some(template_path).filter(|t| !t.trim().is_empty());
+
+    info!("Generating invoice for client {} ({})", invoice.client_id, format_dollars(invoice_total_cents(&invoice)?));
+    run_pandoc_from_stdin_with(
+        pandoc,
+        markdown,
+        validated_output.to_string_lossy().to_string(),
+        template,
+        Some(document_metadata),
+    )
+}
+
+/// Convert markdown to an HTML fragment in-process (no pandoc needed) for previews.
+/// With `sanitize`, unsafe markup such as `<script>` and `<iframe>` is stripped.
+#[tauri::command]
+pub(crate) fn convert_markdown_to_html(markdown: String, sanitize: bool) -> Result<String, String> {
+    use pulldown_cmark::{html, Options, Parser};
+
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+    let parser = Parser::new_ext(&markdown, options);
+
+    let mut output = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut output, parser);
+
+    if sanitize {
+        output = ammonia::clean(&output);
+    }
+    Ok(output)
+}
+
+/// Default preview styling, used unless Templates/preview.css exists
+const PREVIEW_CSS: &str = "body { font-family: Calibri, Arial, sans-serif; font-size: 11pt; line-height: 1.4; max-width: 48em; margin: 2em auto; color: #222; }
+h1, h2, h3 { color: #1f3864; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #999; padding: 4px 8px; }
+";
+
+#[derive(Debug, Serialize)]
+pub(crate) struct HtmlPreview {
+    html: String,
+    /// True when pandoc wasn't available and pulldown-cmark rendered a fragment instead
+    used_fallback: bool,
+}
+
+/// Stylesheet to embed in previews: the user's Templates/preview.css, else the default
+fn preview_css_path() -> Result<PathBuf, String> {
+    let custom = templates_path_in(&resolve_pbs_admin_data_dir()?)?.join("preview.css");
+    if custom.exists() {
+        return Ok(custom);
+    }
+
+    let default = std::env::temp_dir().join("PBS_Admin").join("preview.css");
+    if let Some(parent) = default.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create temp directory: {}", e))?;
+    }
+    write_file_atomically(&default, PREVIEW_CSS.as_bytes())?;
+    Ok(default)
+}
+
+/// Sanitize only the `<body>` of a standalone HTML document; the pandoc-generated head
+/// (styles, meta) is kept since ammonia would strip it
+fn sanitize_html_document_body(document: &str) -> String {
+    // ASCII lowercasing keeps byte offsets identical
+    let lower = document.to_ascii_lowercase();
+    let body_start = lower.find("<body").and_then(|open| lower[open..].find('>').map(|end| open + end + 1));
+    let body_end = lower.rfind("</body>");
+
+    match (body_start, body_end) {
+        (Some(start), Some(end)) if start <= end => format!(
+            "{}{}{}",
+            &document[..start],
+            ammonia::clean(&document[start..end]),
+            &document[end..]
+        ),
+        _ => ammonia::clean(document),
+    }
+}
+
+fn run_pandoc_to_html_preview_with(pandoc: &std::ffi::OsStr, markdown: &str, embed_css: bool) -> Result<HtmlPreview, String> {
+    use std::process::Stdio;
+
+    let mut cmd = Command::new(pandoc);
+    cmd.args(["-", "-f", "markdown+hard_line_breaks", "-t", "html5", "--standalone", "--metadata", "pagetitle=Preview"]);
+    if embed_css {
+        cmd.arg("--embed-resources");
+        cmd.arg("--css");
+        cmd.arg(preview_css_path()?);
+    }
+    cmd.stdin(Stdio::piped());
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            warn!("pandoc not installed - rendering preview with pulldown-cmark");
+            return Ok(HtmlPreview {
+                html: convert_markdown_to_html(markdown.to_string(), true)?,
+                used_fallback: true,
+            });
+        }
+        Err(e) => return Err(format!("Failed to spawn pandoc: {}", e)),
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(markdown.as_bytes())
+            .map_err(|e| format!("Failed to write to pandoc stdin: {}", e))?;
+    }
+
+    let output = child.wait_with_output()
+        .map_err(|e| format!("Failed to wait for pandoc: {}", e))?;
+    if !output.status.success() {
+        let error_msg = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Pandoc conversion failed: {}", error_msg));
+    }
+
+    Ok(HtmlPreview {
+        html: sanitize_html_document_body(&String::from_utf8_lossy(&output.stdout)),
+        used_fallback: false,
+    })
+}
+
+/// Render markdown to a standalone, sanitized HTML document with pandoc so previews match
+/// the exported documents. Falls back to an in-process HTML fragment if pandoc is missing.
+#[tauri::command]
+pub(crate) fn run_pandoc_to_html_preview(markdown: String, embed_css: bool) -> Result<HtmlPreview, String> {
+    run_pandoc_to_html_preview_with(std::ffi::OsStr::new("pandoc"), &markdown, embed_css)
+}
+
+/// Convert HTML (e.g. notes imported from older systems) to markdown.
+/// Styles, classes and scripts are dropped; malformed markup converts as far as it parses.
+#[tauri::command]
+pub(crate) fn convert_html_to_markdown(html: String) -> Result<String, String> {
+    let converter = htmd::HtmlToMarkdown::builder()
+        .skip_tags(vec!["script", "style", "head"])
+        .build();
+    converter.convert(&html)
+        .map(|markdown| markdown.trim().to_string())
+        .map_err(|e| format!("Failed to convert HTML to markdown: {}", e))
+}
+
+// ============================================================================
+// DOCX TEMPLATES
+// ============================================================================
+
+/// Read `word/document.xml` out of a DOCX (ZIP) file
+pub(crate) fn read_docx_document_xml(path: &Path) -> Result<String, String> {
+    use std::io::Read;
+
+    let file = fs::File::open(path)
+        .map_err(|e| format!("Failed to open DOCX: {}", e))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .map_err(|e| format!("Not a valid DOCX (ZIP) file: {}", e))?;
+    let mut document = archive.by_name("word/document.xml")
+        .map_err(|_| "Invalid DOCX: word/document.xml not found".to_string())?;
+
+    let mut xml = String::new();
+    document.read_to_string(&mut xml)
+        .map_err(|e| format!("Failed to read word/document.xml: {}", e))?;
+    Ok(xml)
+}
+
+/// Escape text for inclusion in XML content
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+static TEMPLATE_PLACEHOLDER: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+
+/// The `{{KEY}}` pattern (uppercase letters, digits and `_`) shared by filling and listing,
+/// so both agree on what a placeholder is. Lowercase `{{text}}` is left as written.
+fn template_placeholder_regex() -> &'static regex::Regex {
+    TEMPLATE_PLACEHOLDER.get_or_init(|| {
+        regex::Regex::new(r"\{\{([A-Z0-9_]+)\}\}").expect("valid placeholder regex")
+    })
+}
+
+/// Replace `{{KEY}}` placeholders with (XML-escaped) values.
+/// Returns the new XML and the placeholders that had no value (left untouched).
+fn fill_template_placeholders(xml: &str, variables: &HashMap<String, String>) -> (String, Vec<String>) {
+    let mut missing: Vec<String> = Vec::new();
+
+    let filled = template_placeholder_regex().replace_all(xml, |caps: &regex::Captures| {
+        match variables.get(&caps[1]) {
+            Some(value) => xml_escape(value),
+            None => {
+                if !missing.contains(&caps[0].to_string()) {
+                    missing.push(caps[0].to_string());
+                }
+                caps[0].to_string()
+            }
+        }
+    });
+
+    (filled.into_owned(), missing)
+}
+
+#[derive(Serialize)]
+pub(crate) struct FillResult {
+    output_path: String,
+    /// Placeholders found in the template with no matching variable
+    warnings: Vec<String>,
+}
+
+/// Fill `{{KEY}}` placeholders in a DOCX template's body and save the result.
+/// Placeholders must sit in a single text run in Word to be recognised.
+#[tauri::command]
+pub(crate) fn fill_docx_template(
+    template_path: String,
+    output_path: String,
+    variables: HashMap<String, String>,
+) -> Result<FillResult, String> {
+    audited(
+        "fill_docx_template",
+        &format!("{} -> {}", template_path, output_path),
+        fill_docx_template_unaudited(&template_path, &output_path, &variables),
+    )
+}
+
+fn fill_docx_template_unaudited(
+    template_path: &str,
+    output_path: &str,
+    variables: &HashMap<String, String>,
+) -> Result<FillResult, String> {
+    use std::io::Read;
+
+    let validated_template = validate_read_path(template_path)?;
+    let validated_output = validate_write_path(output_path)?;
+
+    let xml = read_docx_document_xml(&validated_template)?;
+    let (filled_xml, missing) = fill_template_placeholders(&xml, variables);
+
+    let file = fs::File::open(&validated_template)
+        .map_err(|e| format!("Failed to open DOCX: {}", e))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .map_err(|e| format!("Not a valid DOCX (ZIP) file: {}", e))?;
+
+    // Rebuild the archive in memory: every part is copied as-is except the document body
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)
+            .map_err(|e| format!("Failed to read DOCX entry: {}", e))?;
+
+        if entry.name() == "word/document.xml" {
+            let mut discard = Vec::new();
+            entry.read_to_end(&mut discard)
+                .map_err(|e| format!("Failed to read word/document.xml: {}", e))?;
+            writer.start_file("word/document.xml", options)
+                .map_err(|e| format!("Failed to write DOCX: {}", e))?;
+            writer.write_all(filled_xml.as_bytes())
+                .map_err(|e| format!("Failed to write DOCX: {}", e))?;
+        } else {
+            writer.raw_copy_file(entry)
+                .map_err(|e| format!("Failed to write DOCX: {}", e))?;
+        }
+    }
+
+    let docx_bytes = writer.finish()
+        .map_err(|e| format!("Failed to write DOCX: {}", e))?
+        .into_inner();
+    write_file_atomically(&validated_output, &docx_bytes)?;
+
+    if !missing.is_empty() {
+        warn!("Template placeholders without values: {}", missing.join(", "));
+    }
+    info!("Filled DOCX template {} -> {}", template_path, output_path);
+
+    Ok(FillResult {
+        output_path: validated_output.to_string_lossy().to_string(),
+        warnings: missing,
+    })
+}
+
+/// List the unique `{{VARIABLE}}` placeholders in a DOCX template's body, sorted
+#[tauri::command]
+pub(crate) fn list_docx_template_variables(template_path: String) -> Result<Vec<String>, String> {
+    let validated_template = validate_read_path(&template_path)?;
+    let xml = read_docx_document_xml(&validated_template)?;
+
+    let variables: std::collections::BTreeSet<String> = template_placeholder_regex()
+        .find_iter(&xml)
+        .map(|m| m.as_str().to_string())
+        .collect();
+
+    Ok(variables.into_iter().collect())
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct TemplateValidationResult {
+    /// Required variables found in the template
+    present: Vec<String>,
+    /// Required variables the template doesn't contain
+    missing: Vec<String>,
+    /// Placeholders in the template that weren't asked for
+    extra: Vec<String>,
+    is_valid_zip: bool,
+}
+
+/// Check a DOCX template contains the placeholders a caller needs before filling it.
+/// Variable names are given without braces (CLIENT_NAME for `{{CLIENT_NAME}}`).
+/// Missing variables are reported, not treated as an error.
+#[tauri::command]
+pub(crate) fn validate_docx_template(template_path: String, required_variables: Vec<String>) -> Result<TemplateValidationResult, String> {
+    let validated_template = validate_read_path(&template_path)?;
+
+    let is_valid_zip = fs::File::open(&validated_template)
+        .map_err(|e| format!("Failed to open DOCX: {}", e))
+        .map(|file| zip::ZipArchive::new(file).is_ok())?;
+    if !is_valid_zip {
+        return Ok(TemplateValidationResult {
+            present: Vec::new(),
+            missing: required_variables,
+            extra: Vec::new(),
+            is_valid_zip,
+        });
+    }
+
+    let found: std::collections::BTreeSet<String> = list_docx_template_variables(template_path)?
+        .into_iter()
+        .map(|placeholder| placeholder.trim_start_matches("{{").trim_end_matches("}}").to_string())
+        .collect();
+    let required: std::collections::BTreeSet<String> = required_variables
+        .iter()
+        .map(|name| name.trim().trim_start_matches("{{").trim_end_matches("}}").to_string())
+        .collect();
+
+    Ok(TemplateValidationResult {
+        present: required.intersection(&found).cloned().collect(),
+        missing: required.difference(&found).cloned().collect(),
+        extra: found.difference(&required).cloned().collect(),
+        is_valid_zip,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct DischargeSummaryData {
+    client_name: String,
+    presenting_problems: String,
+    treatment_summary: String,
+    progress_achieved: String,
+    recommendations: String,
+}
+
+/// Characters of each session note quoted in a discharge summary
+const DISCHARGE_EXCERPT_CHARS: usize = 300;
+
+/// Session length from a note's `duration_minutes:` front matter or its
+/// `**Duration:** N minutes` line (both written by create_session_note_template)
+fn session_note_duration_minutes(note: &str) -> Option<u32> {
+    let duration = regex::Regex::new(r"(?mi)^(?:duration_minutes:\s*|-?\s*\*\*Duration:\*\*\s*)(\d+)")
+        .expect("valid duration regex");
+    duration.captures(note)?[1].parse().ok()
+}
+
+/// The note's prose with front matter, headings, comments, detail bullets and
+/// placeholder prompts removed, cut to `max_chars`
+fn session_note_excerpt(note: &str, max_chars: usize) -> String {
+    let body = note.strip_prefix("---\n")
+        .and_then(|rest| rest.split_once("\n---\n").map(|(_, body)| body))
+        .unwrap_or(note);
+
+    let text = body.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .filter(|line| !line.starts_with('#') && !line.starts_with("<!--") && !line.starts_with("- **"))
+        .filter(|line| !(line.starts_with('_') && line.ends_with('_')))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if text.chars().count() <= max_chars {
+        return text;
+    }
+    let cut: String = text.chars().take(max_chars).collect();
+    format!("{}…", cut.trim_end())
+}
+
+/// Template variables for a discharge summary: the clinician's text plus statistics
+/// and excerpts from the notes in `notes` (paths sorted by name, i.e. by date)
+fn build_discharge_variables(data: &DischargeSummaryData, notes: &[String]) -> Result<HashMap<String, String>, String> {
+    let mut dates: Vec<chrono::NaiveDate> = Vec::new();
+    let mut total_minutes: u32 = 0;
+    let mut has_duration = false;
+    let mut excerpts: Vec<String> = Vec::new();
+
+    for note_path in notes {
+        let path = Path::new(note_path);
+        let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let note = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read session note {}: {}", file_name, e))?;
+
+        let date = session_note_date(&file_name);
+        dates.extend(date);
+        if let Some(minutes) = session_note_duration_minutes(&note) {
+            total_minutes += minutes;
+            has_duration = true;
+        }
+
+        let excerpt = session_note_excerpt(&note, DISCHARGE_EXCERPT_CHARS);
+        if !excerpt.is_empty() {
+            let label = date.map(|d| d.format("%Y-%m-%d").to_string()).unwrap_or(file_name);
+            excerpts.push(format!("{}: {}", label, excerpt));
+        }
+    }
+
+    let first = dates.iter().min().map(|d| d.format("%Y-%m-%d").to_string()).unwrap_or_default();
+    let last = dates.iter().max().map(|d| d.format("%Y-%m-%d").to_string()).unwrap_or_default();
+    let date_range = if first.is_empty() { "Not recorded".to_string() } else { format!("{} to {}", first, last) };
+    let total_duration = if has_duration {
+        format!("{} h {:02} min", total_minutes / 60, total_minutes % 60)
+    } else {
+        "Not recorded".to_string()
+    };
+
+    let variables = [
+        ("CLIENT_NAME", data.client_name.clone()),
+        ("PRESENTING_PROBLEMS", data.presenting_problems.clone()),
+        ("TREATMENT_SUMMARY", data.treatment_summary.clone()),
+        ("PROGRESS_ACHIEVED", data.progress_achieved.clone()),
+        ("RECOMMENDATIONS", data.recommendations.clone()),
+        ("SESSION_COUNT", notes.len().to_string()),
+        ("FIRST_SESSION_DATE", first),
+        ("LAST_SESSION_DATE", last),
+        ("DATE_RANGE", date_range),
+        ("TOTAL_DURATION", total_duration),
+        // Word ignores newlines inside a text run, so excerpts share one paragraph
+        ("SESSION_EXCERPTS", excerpts.join(" • ")),
+        ("DISCHARGE_DATE", chrono::Local::now().format("%Y-%m-%d").to_string()),
+    ];
+    Ok(variables.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+}
+
+/// Fill a discharge summary DOCX template from the client's session notes
+/// (`client_folder/Session_Notes/*.md`) and the clinician's closing summary.
+/// Besides the summary fields the template can use {{SESSION_COUNT}}, {{DATE_RANGE}},
+/// {{FIRST_SESSION_DATE}}, {{LAST_SESSION_DATE}}, {{TOTAL_DURATION}},
+/// {{SESSION_EXCERPTS}} and {{DISCHARGE_DATE}}.
+#[tauri::command]
+pub(crate) fn generate_discharge_summary(
+    client_folder: String,
+    summary_data: DischargeSummaryData,
+    template_path: String,
+    output_path: String,
+) -> Result<String, String> {
+    if summary_data.client_name.trim().is_empty() {
+        return Err("Client name is required".to_string());
+    }
+    if !output_path.to_lowercase().ends_with(".docx") {
+        return Err(format!("Output path must be a .docx file: {}", output_path));
+    }
+
+    let notes_dir = validate_read_path(&client_folder)?.join("Session_Notes");
+    if !notes_dir.is_dir() {
+        return Err(format!("Session_Notes folder not found in {}", client_folder));
+    }
+    let notes = list_files(
+        notes_dir.to_string_lossy().to_string(),
+        None,
+        false,
+        None,
+        Some("name".to_string()),
+        Some(true),
+        Some("md".to_string()),
+    )?;
+
+    let variables = build_discharge_variables(&summary_data, &notes)?;
+    let result = fill_docx_template(template_path, output_path, variables)?;
+
+    info!("Generated discharge summary for {} from {} session notes", summary_data.client_name, notes.len());
+    Ok(result.output_path)
+}
+
+// ============================================================================
+// QR CODES
+// ============================================================================
+
+const QR_MIN_SIZE_PIXELS: u32 = 100;
+/// Binary capacity of the largest (version 40) QR code
+const QR_MAX_DATA_BYTES: usize = 2953;
+
+/// Render `data` as a square PNG QR code (error correction M), centred on a white canvas
+fn render_qr_code_png(data: &str, size_pixels: u32) -> Result<Vec<u8>, String> {
+    use image::{ImageBuffer, Luma};
+
+    if data.is_empty() {
+        return Err("QR code data is required".to_string());
+    }
+    if data.len() > QR_MAX_DATA_BYTES {
+        return Err(format!("QR code data is too long: {} bytes (maximum {})", data.len(), QR_MAX_DATA_BYTES));
+    }
+    if size_pixels < QR_MIN_SIZE_PIXELS {
+        return Err(format!("QR code size must be at least {} pixels", QR_MIN_SIZE_PIXELS));
+    }
+
+    let code = qrcode::QrCode::with_error_correction_level(data.as_bytes(), qrcode::EcLevel::M)
+        .map_err(|e| format!("Failed to encode QR code: {}", e))?;
+    // Modules are whole pixels, so the code itself may come out a little smaller than requested
+    let rendered = code.render::<Luma<u8>>()
+        .max_dimensions(size_pixels, size_pixels)
+        .build();
+    if rendered.width() > size_pixels {
+        return Err(format!("QR code needs at least {} pixels for this much data", rendered.width()));
+    }
+
+    let mut canvas = ImageBuffer::from_pixel(size_pixels, size_pixels, Luma([255u8]));
+    let offset = ((size_pixels - rendered.width()) / 2) as i64;
+    image::imageops::overlay(&mut canvas, &rendered, offset, offset);
+
+    let mut png = Vec::new();
+    canvas.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+    Ok(png)
+}
+
+/// Save a QR code PNG (e.g. linking a printed intake form to the client's record)
+#[tauri::command]
+pub(crate) fn generate_qr_code(data: String, output_path: String, size_pixels: u32) -> Result<String, String> {
+    // Validate path is within allowed directories
+    let validated_output = validate_write_path(&output_path)?;
+    let is_png = validated_output
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("png"))
+        .unwrap_or(false);
+    if !is_png {
+        return Err(format!("Output path must be a .png file: {}", output_path));
+    }
+
+    let png = render_qr_code_png(&data, size_pixels)?;
+    write_file_atomically(&validated_output, &png)?;
+
+    info!("Saved {}px QR code ({} bytes of data) to {}", size_pixels, data.len(), output_path);
+    Ok(validated_output.to_string_lossy().to_string())
+}
+
+// ============================================================================
+// PRESCRIPTIONS
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+struct PrescriptionData {
+    client_name: String,
+    pet_name: String,
+    pet_species: String,
+    pet_weight: String,
+    medication_name: String,
+    brand_names: String,
+    formulation: String,
+    dose_concentration: String,
+    amount_to_dispense: String,
+    dose_rate: String,
+    frequency: String,
+    repeats: String,
+    special_instructions: String,
+    prescription_date: String,
+    schedule_class: String,
+}
+
+#[tauri::command]
+pub(crate) fn generate_prescription_docx(
+    template_content: String,
+    output_path: String,
+) -> Result<String, String> {
+    use std::process::Stdio;
+
+    // Validate output path is within allowed directories
+    validate_write_path(&output_path)?;
+
+    // Get templates folder path
+    let templates_path = templates_path_in(&resolve_pbs_admin_data_dir()?)?;
+
+    // Build letterhead file path (reference document for Pandoc)
+    let letterhead_file_path = templates_path.join("Prescription_Template.docx");
+
+    // Use the processed template content provided from frontend
+    let markdown_content = template_content;
+
+    // Build pandoc command with stdin input
+    let mut cmd = Command::new("pandoc");
+    cmd.stdin(Stdio::piped());
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+
+    // Read from stdin
+    cmd.arg("-");
+
+    // Use hard_line_breaks extension to preserve all newlines
+    cmd.arg("-f");
+    cmd.arg("markdown+hard_line_breaks");
+
+    // Add output file
+    cmd.arg("-o");
+    cmd.arg(&output_path);
+
+    // Add reference document (letterhead) if it exists
+    if letterhead_file_path.exists() {
+        info!("Using prescription letterhead file: {:?}", letterhead_file_path);
+        cmd.arg("--reference-doc");
+        cmd.arg(letterhead_file_path.to_string_lossy().to_string());
+    } else {
+        let error_msg = format!("Prescription template file not found: {:?}. Please create Prescription_Template.docx in Documents\\PBS_Admin\\Templates\\", letterhead_file_path);
+        warn!("{}", error_msg);
+        return Err(error_msg);
+    }
+
+    // Spawn process
+    let mut child = cmd.spawn()
+        .map_err(|e| format!("Failed to spawn pandoc: {}. Is pandoc installed?", e))?;
+
+    // Write markdown content to stdin
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(markdown_content.as_bytes())
+            .map_err(|e| format!("Failed to write to pandoc stdin: {}", e))?;
+    }
+
+    // Wait for process to complete
+    let output = child.wait_with_output()
+        .map_err(|e| format!("Failed to wait for pandoc: {}", e))?;
+
+    // Check if command succeeded
+    if output.status.success() {
+        Ok(output_path.clone())
+    } else {
+        let error_msg = String::from_utf8_lossy(&output.stderr);
+        Err(format!("Pandoc conversion failed: {}", error_msg))
+    }
+}
+
+// ============================================================================
+// CSV / EXCEL IMPORT / EXPORT
+// ============================================================================
+
+/// Serialize rows as RFC 4180 CSV with columns in `headers` order (missing keys are blank)
+fn build_csv(data: &[HashMap<String, String>], headers: &[String]) -> Result<Vec<u8>, String> {
+    let mut writer = csv::WriterBuilder::new()
+        .terminator(csv::Terminator::CRLF)
+        .from_writer(Vec::new());
+
+    writer.write_record(headers)
+        .map_err(|e| format!("Failed to write CSV header: {}", e))?;
+    for row in data {
+        let values = headers.iter().map(|h| row.get(h).map(String::as_str).unwrap_or(""));
+        writer.write_record(values)
+            .map_err(|e| format!("Failed to write CSV row: {}", e))?;
+    }
+
+    writer.into_inner()
+        .map_err(|e| format!("Failed to write CSV: {}", e))
+}
+
+/// Parse CSV into one map per row. Without a header row, columns are named `column_1`, `column_2`, ...
+fn parse_csv(content: &[u8], has_header_row: bool) -> Result<Vec<HashMap<String, String>>, String> {
+    // Spreadsheet exports often start with a UTF-8 BOM
+    let content = content.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(content);
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(has_header_row)
+        .flexible(true)
+        .from_reader(content);
+
+    let headers: Vec<String> = if has_header_row {
+        reader.headers()
+            .map_err(|e| format!("Failed to read CSV header: {}", e))?
+            .iter()
+            .map(|h| h.trim().to_string())
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut rows = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| format!("Failed to parse CSV row {}: {}", index + 1, e))?;
+        let row = record.iter()
+            .enumerate()
+            .map(|(column, value)| {
+                let key = headers.get(column)
+                    .cloned()
+                    .unwrap_or_else(|| format!("column_{}", column + 1));
+                (key, value.to_string())
+            })
+            .collect();
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Export rows (e.g. the client roster) to a CSV file. Returns the output path.
+#[tauri::command]
+pub(crate) fn export_csv(data: Vec<HashMap<String, String>>, output_path: String, headers: Vec<String>) -> Result<String, String> {
+    if headers.is_empty() {
+        return Err("At least one CSV column header is required".to_string());
+    }
+
+    // Validate path is within allowed directories
+    let validated_path = validate_write_path(&output_path)?;
+
+    let csv_bytes = build_csv(&data, &headers)?;
+    write_file_atomically(&validated_path, &csv_bytes)?;
+
+    info!("Exported {} CSV rows to {}", data.len(), output_path);
+    Ok(output_path)
+}
+
+/// Import a CSV file as a list of rows keyed by column header
+#[tauri::command]
+pub(crate) fn import_csv(file_path: String, has_header_row: bool) -> Result<Vec<HashMap<String, String>>, String> {
+    // Validate path is within allowed directories
+    let validated_path = validate_read_path(&file_path)?;
+
+    let content = fs::read(&validated_path)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    let rows = parse_csv(&content, has_header_row)?;
+
+    info!("Imported {} CSV rows from {}", rows.len(), file_path);
+    Ok(rows)
+}
+
+/// Convert a spreadsheet cell to JSON: numbers as f64, dates as ISO 8601 strings, empty as null
+fn excel_cell_to_json(cell: &calamine::Data) -> serde_json::Value {
+    use calamine::{Data, DataType};
+    use serde_json::Value;
+
+    match cell {
+        Data::Empty => Value::Null,
+        Data::Int(i) => serde_json::json!(*i as f64),
+        Data::Float(f) => serde_json::json!(f),
+        Data::String(s) => Value::String(s.clone()),
+        Data::Bool(b) => Value::Bool(*b),
+        Data::DateTimeIso(s) | Data::DurationIso(s) => Value::String(s.clone()),
+        Data::DateTime(excel_datetime) => {
+            if excel_datetime.is_duration() {
+                let seconds = cell.as_duration().map(|d| d.num_seconds()).unwrap_or(0);
+                Value::String(format!("PT{}S", seconds))
+            } else if let Some(datetime) = cell.as_datetime() {
+                // Date-only cells (midnight) stay plain dates
+                let format = if datetime.time() == chrono::NaiveTime::MIN { "%Y-%m-%d" } else { "%Y-%m-%dT%H:%M:%S" };
+                Value::String(datetime.format(format).to_string())
+            } else {
+                Value::Null
+            }
+        }
+        // Formula errors (#DIV/0! etc.) come through as their display text
+        Data::Error(e) => Value::String(e.to_string()),
+    }
+}
+
+/// Read one sheet of an .xlsx / .xls workbook (the first sheet if none is named) as rows of typed cells
+#[tauri::command]
+pub(crate) fn read_excel_file(file_path: String, sheet_name: Option<String>) -> Result<Vec<Vec<serde_json::Value>>, String> {
+    use calamine::Reader;
+
+    // Validate path is within allowed directories
+    let validated_path = validate_read_path(&file_path)?;
+
+    let mut workbook = calamine::open_workbook_auto(&validated_path)
+        .map_err(|e| format!("Failed to open spreadsheet: {}", e))?;
+    let sheet_name = match sheet_name.filter(|name| !name.trim().is_empty()) {
+        Some(name) => name,
+        None => workbook.sheet_names().first().cloned()
+            .ok_or_else(|| "Spreadsheet has no sheets".to_string())?,
+    };
+    let range = workbook.worksheet_range(&sheet_name)
+        .map_err(|e| format!("Failed to read sheet '{}': {}", sheet_name, e))?;
+
+    let rows: Vec<Vec<serde_json::Value>> = range.rows()
+        .map(|row| row.iter().map(excel_cell_to_json).collect())
+        .collect();
+
+    info!("Read {} rows from sheet '{}' of {}", rows.len(), sheet_name, file_path);
+    Ok(rows)
+}
+
+/// Build an .xlsx workbook with a bold header row and auto-fitted columns.
+/// Numbers and booleans keep their types; ISO 8601 date strings become Excel dates.
+fn build_excel_workbook(data: &[Vec<serde_json::Value>], headers: &[String], sheet_name: &str) -> Result<Vec<u8>, String> {
+    use rust_xlsxwriter::{ExcelDateTime, Format, Workbook};
+    use serde_json::Value;
+
+    let iso_date = regex::Regex::new(r"^\d{4}-\d{2}-\d{2}(T\d{2}:\d{2}(:\d{2}(\.\d+)?)?Z?)?$").expect("valid date regex");
+    let header_format = Format::new().set_bold();
+    let date_format = Format::new().set_num_format("yyyy-mm-dd");
+    let datetime_format = Format::new().set_num_format("yyyy-mm-dd hh:mm:ss");
+    let xlsx_err = |e: rust_xlsxwriter::XlsxError| format!("Failed to write spreadsheet: {}", e);
+
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name(sheet_name)
+        .map_err(|e| format!("Invalid sheet name '{}': {}", sheet_name, e))?;
+
+    for (col, header) in headers.iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, header, &header_format).map_err(xlsx_err)?;
+    }
+
+    for (row_index, row) in data.iter().enumerate() {
+        let row_num = row_index as u32 + 1;
+        for (col, value) in row.iter().enumerate() {
+            let col = col as u16;
+            match value {
+                Value::Null => {}
+                Value::Bool(b) => { worksheet.write_boolean(row_num, col, *b).map_err(xlsx_err)?; }
+                Value::Number(n) => {
+                    let number = n.as_f64().ok_or_else(|| format!("Invalid number: {}", n))?;
+                    worksheet.write_number(row_num, col, number).map_err(xlsx_err)?;
+                }
+                Value::String(s) if iso_date.is_match(s) => {
+                    let format = if s.len() > 10 { &datetime_format } else { &date_format };
+                    match ExcelDateTime::parse_from_str(s) {
+                        Ok(datetime) => { worksheet.write_datetime_with_format(row_num, col, datetime, format).map_err(xlsx_err)?; }
+                        // Looks like a date but isn't one (e.g. 2025-13-45) - keep the text
+                        Err(_) => { worksheet.write_string(row_num, col, s).map_err(xlsx_err)?; }
+                    }
+                }
+                Value::String(s) => { worksheet.write_string(row_num, col, s).map_err(xlsx_err)?; }
+                other => { worksheet.write_string(row_num, col, other.to_string()).map_err(xlsx_err)?; }
+            }
+        }
+    }
+
+    worksheet.autofit();
+    workbook.save_to_buffer().map_err(xlsx_err)
+}
+
+/// Write rows to a single-sheet .xlsx file (e.g. a billing report for non-technical staff)
+#[tauri::command]
+pub(crate) fn export_to_excel(data: Vec<Vec<serde_json::Value>>, headers: Vec<String>, output_path: String, sheet_name: String) -> Result<String, String> {
+    // Validate path is within allowed directories
+    let validated_path = validate_write_path(&output_path)?;
+    let is_xlsx = validated_path
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("xlsx"))
+        .unwrap_or(false);
+    if !is_xlsx {
+        return Err(format!("Output path must be a .xlsx file: {}", output_path));
+    }
+
+    let buffer = build_excel_workbook(&data, &headers, &sheet_name)?;
+    write_file_atomically(&validated_path, &buffer)?;
+
+    info!("Exported {} rows to {}", data.len(), output_path);
+    Ok(validated_path.to_string_lossy().to_string())
+}
+
+// ============================================================================
+// SESSION NOTE TEMPLATES
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SessionMetadata {
+    client_name: String,
+    client_id: String,
+    session_date: String,
+    session_type: String,
+    clinician_name: String,
+    duration_minutes: Option<u32>,
+}
+
+/// Placeholder prompt under each SOAP heading of a new session note
+const SOAP_SECTION_PROMPTS: [(&str, &str); 4] = [
+    ("Subjective", "_Owner's report: presenting concerns, history, changes since the last session._"),
+    ("Objective", "_Observed behaviour, body language and environment during the session._"),
+    ("Assessment", "_Clinical interpretation and progress against goals._"),
+    ("Plan", "_Training plan, management changes, homework and next appointment._"),
+];
+
+/// Markdown for a new session note: YAML front matter, a details block and SOAP headings
+fn build_session_note_template(metadata: &SessionMetadata) -> Result<String, String> {
+    let mut front_matter = serde_json::json!({
+        "title": format!("Session Note - {}", metadata.client_name),
+        "date": metadata.session_date,
+        "author": metadata.clinician_name,
+        "session_type": metadata.session_type,
+        "client_id": metadata.client_id,
+    });
+    if let Some(minutes) = metadata.duration_minutes {
+        front_matter["duration_minutes"] = serde_json::json!(minutes);
+    }
+
+    let mut markdown = build_yaml_front_matter(&front_matter)?;
+    markdown.push_str(&format!("# Session Note - {}\n\n", metadata.client_name));
+    markdown.push_str(&format!("- **Client:** {} ({})\n", metadata.client_name, metadata.client_id));
+    markdown.push_str(&format!("- **Date:** {}\n", metadata.session_date));
+    markdown.push_str(&format!("- **Session type:** {}\n", metadata.session_type));
+    if let Some(minutes) = metadata.duration_minutes {
+        markdown.push_str(&format!("- **Duration:** {} minutes\n", minutes));
+    }
+    markdown.push_str(&format!("- **Clinician:** {}\n", metadata.clinician_name));
+
+    for (heading, prompt) in SOAP_SECTION_PROMPTS {
+        markdown.push_str(&format!("\n## {}\n\n{}\n", heading, prompt));
+    }
+    Ok(markdown)
+}
+
+/// Write a new markdown session note pre-filled with the session details.
+/// Refuses to overwrite an existing note.
+#[tauri::command]
+pub(crate) fn create_session_note_template(output_path: String, metadata: SessionMetadata) -> Result<String, String> {
+    if !output_path.to_lowercase().ends_with(".md") {
+        return Err("Output file must be a .md file".to_string());
+    }
+    if metadata.client_name.trim().is_empty() || metadata.session_date.trim().is_empty() {
+        return Err("Client name and session date are required".to_string());
+    }
+
+    // Validate path is within allowed directories
+    let validated_path = validate_write_path(&output_path)?;
+    if validated_path.exists() {
+        return Err(format!("Session note already exists: {}", output_path));
+    }
+
+    let markdown = build_session_note_template(&metadata)?;
+    let result = write_file_atomically(&validated_path, markdown.as_bytes())
+        .map(|_| validated_path.to_string_lossy().to_string());
+    audited("create_session_note_template", &output_path, result)
+}
+
+/// Insert `block` at the end of the section headed `section` (case-insensitive, any
+/// heading level), before the next heading of the same or higher level. Appends a new
+/// `##` section when no heading matches. Headings inside code fences are ignored.
+fn insert_into_markdown_section(markdown: &str, section: &str, block: &str) -> String {
+    let heading = regex::Regex::new(r"^(#{1,6})\s+(.*?)\s*#*\s*$").expect("valid heading regex");
+    let lines: Vec<&str> = markdown.split_inclusive('\n').collect();
+
+    let mut in_fence = false;
+    let mut found: Option<usize> = None;
+    let mut end = lines.len();
+    for (index, line) in lines.iter().enumerate() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        let Some(captures) = heading.captures(line.trim_end()).filter(|_| !in_fence) else { continue };
+        let level = captures[1].len();
+        match found {
+            None if captures[2].eq_ignore_ascii_case(section) => found = Some(level),
+            Some(found_level) if level <= found_level => {
+                end = index;
+                break;
+            },
+            _ => {},
+        }
+    }
+
+    if found.is_none() {
+        let existing = markdown.trim_end();
+        let separator = if existing.is_empty() { "" } else { "\n\n" };
+        return format!("{}{}## {}\n\n{}\n", existing, separator, section, block);
+    }
+
+    let before = lines[..end].concat();
+    let after = lines[end..].concat();
+    let mut result = format!("{}\n\n{}\n", before.trim_end(), block);
+    if !after.is_empty() {
+        result.push('\n');
+        result.push_str(&after);
+    }
+    result
+}
+
+/// Append a transcript below the `section` heading of an existing session note
+/// (a new section is added if the heading is missing). `duration_seconds` is the
+/// transcription's duration and is noted in a comment above the text.
+#[tauri::command]
+pub(crate) fn import_transcription_to_session_note(
+    note_path: String,
+    transcript: String,
+    section: String,
+    duration_seconds: Option<f64>,
+) -> Result<String, String> {
+    let section = section.trim();
+    if section.is_empty() {
+        return Err("Section name cannot be empty".to_string());
+    }
+    if transcript.trim().is_empty() {
+        return Err("Transcript cannot be empty".to_string());
+    }
+
+    // Validate path is within allowed directories
+    let validated_path = validate_write_path(&note_path)?;
+    let note = fs::read_to_string(&validated_path)
+        .map_err(|e| format!("Failed to read session note: {}", e))?;
+
+    let imported_at = chrono::Local::now().format("%Y-%m-%d %H:%M");
+    let comment = match duration_seconds {
+        Some(seconds) => format!("<!-- Transcript imported {} (duration {}) -->", imported_at, format_duration_clock(seconds)),
+        None => format!("<!-- Transcript imported {} -->", imported_at),
+    };
+    let block = format!("{}\n{}", comment, transcript.trim());
+
+    let updated = insert_into_markdown_section(&note, section, &block);
+    let result = write_file_atomically(&validated_path, updated.as_bytes())
+        .map(|_| validated_path.to_string_lossy().to_string());
+    audited("import_transcription_to_session_note", &note_path, result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::merge_pdf_files;
+    use crate::system::read_last_lines;
+    use crate::transcription::export_transcription_as_docx_with;
+    use crate::test_support::{
+        audit_entries_for, mock_tool, path_string, sample_pdf, sample_transcription_metadata,
+        scratch_dir, strings,
+    };
+
+    #[test]
+    fn pandoc_args_append_allowed_flags() {
+        let dir = scratch_dir();
+        let output = path_string(&dir.path().join("note.html"));
+        let extra = strings(&["--toc", "--toc-depth=2", "--standalone", "--wrap", "none"]);
+
+        let args = build_pandoc_args("note.md", &output, None, Some(&extra)).unwrap();
+
+        assert_eq!(args[..5], strings(&["note.md", "-f", "markdown+hard_line_breaks", "-o", &output])[..]);
+        assert_eq!(args[5..], extra[..]);
+    }
+
+    #[test]
+    fn pandoc_args_reject_anything_not_allowlisted() {
+        let templates = scratch_dir();
+        let filter = templates.path().join("filter.lua");
+        fs::write(&filter, "-- no-op").unwrap();
+        let filter = path_string(&filter);
+
+        let rejected = [
+            vec!["--lua-filter".to_string(), filter.clone()],
+            vec![format!("--lua={}", filter)],
+            vec![format!("-L{}", filter)],
+            vec![format!("-F{}", filter)],
+            strings(&["--filt=/bin/sh"]),
+            strings(&["-d", "defaults.yaml"]),
+            strings(&["--defaults=defaults.yaml"]),
+            strings(&["-H", "/etc/passwd"]),
+            strings(&["-B", "/etc/passwd"]),
+            strings(&["-A", "/etc/passwd"]),
+            strings(&["--include-in-header=/etc/passwd"]),
+            strings(&["--extract-media=/tmp"]),
+            strings(&["--log=/tmp/pandoc.log"]),
+            strings(&["--template=/etc/passwd"]),
+            strings(&["--output=/tmp/elsewhere.html"]),
+            strings(&["--out=/tmp/elsewhere.html"]),
+            strings(&["-o", "/tmp/elsewhere.html"]),
+            strings(&["--toc=true"]),
+            strings(&["-sN"]),
+            strings(&["/etc/passwd"]),
+        ];
+        for args in rejected {
+            assert!(
+                validate_pandoc_extra_args_in(&args, templates.path()).is_err(),
+                "should reject {:?}", args
+            );
+        }
+    }
+
+    #[test]
+    fn pandoc_args_validate_choice_values() {
+        let templates = scratch_dir();
+        assert!(validate_pandoc_extra_args_in(&strings(&["--toc-depth=3"]), templates.path()).is_ok());
+        assert!(validate_pandoc_extra_args_in(&strings(&["--toc-depth", "3"]), templates.path()).is_ok());
+        assert!(validate_pandoc_extra_args_in(&strings(&["--toc-depth=9"]), templates.path()).is_err());
+        assert!(validate_pandoc_extra_args_in(&strings(&["--toc-depth"]), templates.path()).unwrap_err().contains("requires a value"));
+        assert!(validate_pandoc_extra_args_in(&strings(&["--wrap=none;rm"]), templates.path()).is_err());
+    }
+
+    #[test]
+    fn pandoc_file_args_must_live_in_templates() {
+        let templates = scratch_dir();
+        let css = templates.path().join("style.css");
+        fs::write(&css, "body {}").unwrap();
+        let outside = scratch_dir();
+        let stray = outside.path().join("style.css");
+        fs::write(&stray, "body {}").unwrap();
+
+        let ok = [format!("--css={}", path_string(&css)), "--epub-cover-image".to_string(), path_string(&css)];
+        assert!(validate_pandoc_extra_args_in(&ok, templates.path()).is_ok());
+
+        let escapes = [
+            format!("--css={}", path_string(&stray)),
+            format!("--css={}", path_string(&templates.path().join("..").join(outside.path().file_name().unwrap()).join("style.css"))),
+            format!("--css={}", path_string(&templates.path().join("missing.css"))),
+            format!("--css={}", path_string(templates.path())),
+        ];
+        for arg in escapes {
+            assert!(validate_pandoc_extra_args_in(std::slice::from_ref(&arg), templates.path()).is_err(), "should reject {}", arg);
+        }
+    }
+
+    #[test]
+    fn pandoc_version_parsing() {
+        assert_eq!(parse_pandoc_version("pandoc 3.1.13\nFeatures: +server +lua").as_deref(), Some("3.1.13"));
+        assert_eq!(parse_pandoc_version("pandoc.exe 3.1.9\r\n").as_deref(), Some("3.1.9"));
+        assert_eq!(parse_pandoc_version("pandoc\n"), None);
+        assert_eq!(parse_pandoc_version("not pandoc 1.0"), None);
+        assert_eq!(parse_pandoc_version(""), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_pandoc_installation_reports_version_and_formats() {
+        let dir = scratch_dir();
+        let pandoc = mock_tool(dir.path(), "pandoc", r#"
+case "$1" in
+  --version) printf 'pandoc 3.1.13\nFeatures: +server +lua\n' ;;
+  --list-output-formats) printf 'docx\nhtml\n\nepub\n' ;;
+esac"#);
+
+        let info = check_pandoc_installation_with(pandoc.as_os_str()).unwrap();
+
+        assert_eq!(info.version, "3.1.13");
+        assert_eq!(info.output_formats, strings(&["docx", "html", "epub"]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_pandoc_installation_errors() {
+        let dir = scratch_dir();
+        let missing = dir.path().join("no-pandoc-here");
+        let err = check_pandoc_installation_with(missing.as_os_str()).unwrap_err();
+        assert!(err.contains("https://pandoc.org/installing.html"), "{}", err);
+
+        let failing = mock_tool(dir.path(), "failing", "exit 1");
+        assert!(check_pandoc_installation_with(failing.as_os_str()).unwrap_err().contains("returned an error"));
+
+        let garbled = mock_tool(dir.path(), "garbled", "echo 'something else'");
+        assert!(check_pandoc_installation_with(garbled.as_os_str()).unwrap_err().contains("Could not parse pandoc version"));
+    }
+
+    #[test]
+    fn yaml_front_matter_quotes_values() {
+        let yaml = build_yaml_front_matter(&serde_json::json!({
+            "title": "Session \"Note\": follow-up",
+            "author": "Dr Smith",
+            "keywords": ["anxiety", "reactivity"],
+        })).unwrap();
+
+        assert!(yaml.starts_with("---\n"));
+        assert!(yaml.ends_with("---\n\n"));
+        assert!(yaml.contains("title: \"Session \\\"Note\\\": follow-up\"\n"));
+        assert!(yaml.contains("author: \"Dr Smith\"\n"));
+        assert!(yaml.contains("keywords: [\"anxiety\",\"reactivity\"]\n"));
+    }
+
+    #[test]
+    fn yaml_front_matter_rejects_malformed_metadata() {
+        assert!(build_yaml_front_matter(&serde_json::json!(["title"])).unwrap_err().contains("JSON object"));
+        assert!(build_yaml_front_matter(&serde_json::json!({"title": 42})).unwrap_err().contains("must be a string"));
+        assert!(build_yaml_front_matter(&serde_json::json!({"date": null})).is_err());
+        assert!(build_yaml_front_matter(&serde_json::json!({"bad key": "x"})).unwrap_err().contains("Invalid metadata key"));
+        assert!(build_yaml_front_matter(&serde_json::json!({"x\n---\nevil": "x"})).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pandoc_from_stdin_prepends_front_matter() {
+        let dir = scratch_dir();
+        // Fake pandoc: `- -f <format> -o <output>`; copies stdin to the output file and
+        // warns like pandoc does when the document has no title
+        let pandoc = mock_tool(dir.path(), "pandoc", r#"
+cat > "$5"
+grep -q '^title:' "$5" || echo '[WARNING] This document format requires a nonempty <title> element.' >&2"#);
+        let output = dir.path().join("note.html");
+
+        let result = run_pandoc_from_stdin_with(
+            pandoc.as_os_str(),
+            "# Consultation\n\nBody text".to_string(),
+            path_string(&output),
+            None,
+            Some(serde_json::json!({"title": "Session Note"})),
+        ).unwrap();
+
+        assert_eq!(result, path_string(&output));
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            "---\ntitle: \"Session Note\"\n---\n\n# Consultation\n\nBody text"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pandoc_from_stdin_passes_markdown_through_without_metadata() {
+        let dir = scratch_dir();
+        let pandoc = mock_tool(dir.path(), "pandoc", r#"cat > "$5""#);
+        let output = dir.path().join("note.html");
+
+        run_pandoc_from_stdin_with(pandoc.as_os_str(), "plain".to_string(), path_string(&output), None, None).unwrap();
+
+        assert_eq!(fs::read_to_string(&output).unwrap(), "plain");
+    }
+
+    #[test]
+    fn pandoc_commands_log_json_lines() {
+        use tracing_subscriber::prelude::*;
+
+        let dir = scratch_dir();
+        let log = dir.path().join("pbs_admin_test.log");
+        let file = fs::File::create(&log).unwrap();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_subscriber::fmt::layer().json().with_ansi(false).with_writer(Mutex::new(file)));
+        let template = dir.path().join("letterhead.docx");
+        fs::write(&template, "PK").unwrap();
+        let output = path_string(&dir.path().join("letter.docx"));
+
+        tracing::subscriber::with_default(subscriber, || {
+            build_pandoc_args("letter.md", &output, Some(&path_string(&template)), None).unwrap();
+        });
+
+        let lines = read_last_lines(&log, 10).unwrap();
+        let entry: serde_json::Value = serde_json::from_str(lines.last().unwrap()).unwrap();
+        assert_eq!(entry["level"], "INFO");
+        assert!(entry["fields"]["message"].as_str().unwrap().contains("Using reference document template"));
+    }
+
+    /// Write a minimal DOCX (content types, relationships and a document body) to `path`
+    fn minimal_docx(path: &Path, body: &str) {
+        let file = fs::File::create(path).unwrap();
+        let mut zip = zip::ZipWriter::new(file);
+        let options = zip::write::SimpleFileOptions::default();
+        let parts = [
+            ("[Content_Types].xml", r#"<?xml version="1.0" encoding="UTF-8"?><Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/></Types>"#.to_string()),
+            ("_rels/.rels", r#"<?xml version="1.0" encoding="UTF-8"?><Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/></Relationships>"#.to_string()),
+            ("word/document.xml", format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?><w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body>{}</w:body></w:document>"#,
+                body
+            )),
+        ];
+        for (name, content) in parts {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    /// Paragraph XML with one text run
+    fn docx_paragraph(text: &str) -> String {
+        format!("<w:p><w:r><w:t>{}</w:t></w:r></w:p>", text)
+    }
+
+    #[test]
+    fn fill_docx_template_replaces_and_escapes_placeholders() {
+        let dir = scratch_dir();
+        let template = dir.path().join("letter_template.docx");
+        let body = [
+            docx_paragraph("Dear {{CLIENT_NAME}},"),
+            docx_paragraph("Date: {{DATE}} ({{DATE}})"),
+            docx_paragraph("Vet: {{VET_NAME}}"),
+        ].concat();
+        minimal_docx(&template, &body);
+        let output = dir.path().join("letter.docx");
+        let variables = HashMap::from([
+            ("CLIENT_NAME".to_string(), "Jane & <Tom> O'Brien".to_string()),
+            ("DATE".to_string(), "15/01/2025".to_string()),
+            ("UNUSED".to_string(), "x".to_string()),
+        ]);
+
+        let result = fill_docx_template(path_string(&template), path_string(&output), variables).unwrap();
+
+        assert_eq!(result.warnings, ["{{VET_NAME}}"]);
+        let xml = read_docx_document_xml(&output).unwrap();
+        assert!(xml.contains("<w:t>Dear Jane &amp; &lt;Tom&gt; O&apos;Brien,</w:t>"), "{}", xml);
+        assert!(xml.contains("<w:t>Date: 15/01/2025 (15/01/2025)</w:t>"));
+        assert!(xml.contains("<w:t>Vet: {{VET_NAME}}</w:t>"));
+
+        // The other parts are carried over unchanged
+        let mut archive = zip::ZipArchive::new(fs::File::open(&output).unwrap()).unwrap();
+        let names: Vec<String> = (0..archive.len()).map(|i| archive.by_index(i).unwrap().name().to_string()).collect();
+        assert_eq!(names, ["[Content_Types].xml", "_rels/.rels", "word/document.xml"]);
+    }
+
+    #[test]
+    fn fill_docx_template_rejects_non_docx_input() {
+        let dir = scratch_dir();
+        let not_zip = dir.path().join("template.docx");
+        fs::write(&not_zip, "plain text").unwrap();
+        let output = path_string(&dir.path().join("out.docx"));
+
+        let err = fill_docx_template(path_string(&not_zip), output.clone(), HashMap::new()).err().unwrap();
+        assert!(err.contains("Not a valid DOCX"), "{}", err);
+
+        let no_body = dir.path().join("empty.docx");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&no_body).unwrap());
+        zip.start_file("word/styles.xml", zip::write::SimpleFileOptions::default()).unwrap();
+        zip.finish().unwrap();
+        let err = fill_docx_template(path_string(&no_body), output, HashMap::new()).err().unwrap();
+        assert!(err.contains("word/document.xml not found"), "{}", err);
+    }
+
+    #[test]
+    fn docx_template_listing_and_filling_agree_on_placeholders() {
+        let dir = scratch_dir();
+        let template = dir.path().join("mixed_template.docx");
+        let body = [
+            docx_paragraph("{{CLIENT_NAME_2}} / {{PET_NAME}}"),
+            docx_paragraph("{{PET_NAME}} again, {{not a placeholder}}"),
+        ].concat();
+        minimal_docx(&template, &body);
+
+        let listed = list_docx_template_variables(path_string(&template)).unwrap();
+        assert_eq!(listed, ["{{CLIENT_NAME_2}}", "{{PET_NAME}}"]);
+
+        let validation = validate_docx_template(
+            path_string(&template),
+            strings(&["CLIENT_NAME_2", "{{DATE}}"]),
+        ).unwrap();
+        assert!(validation.is_valid_zip);
+        assert_eq!(validation.present, ["CLIENT_NAME_2"]);
+        assert_eq!(validation.missing, ["DATE"]);
+        assert_eq!(validation.extra, ["PET_NAME"]);
+
+        let output = dir.path().join("mixed.docx");
+        let variables = HashMap::from([("CLIENT_NAME_2".to_string(), "Jane".to_string())]);
+        let result = fill_docx_template(path_string(&template), path_string(&output), variables).unwrap();
+        assert_eq!(result.warnings, ["{{PET_NAME}}"]);
+        let xml = read_docx_document_xml(&output).unwrap();
+        assert!(xml.contains("Jane / {{PET_NAME}}"));
+        assert!(xml.contains("{{not a placeholder}}"));
+    }
+
+    #[test]
+    fn lowercase_braces_are_not_placeholders() {
+        let dir = scratch_dir();
+        let template = dir.path().join("lowercase_template.docx");
+        minimal_docx(&template, &[
+            docx_paragraph("Dear {{CLIENT_NAME}},"),
+            docx_paragraph("Literal {{foo}} and {{Client_Name}} stay as typed."),
+        ].concat());
+
+        assert_eq!(list_docx_template_variables(path_string(&template)).unwrap(), ["{{CLIENT_NAME}}"]);
+
+        let output = dir.path().join("letter.docx");
+        let variables = HashMap::from([
+            ("CLIENT_NAME".to_string(), "Jane".to_string()),
+            ("foo".to_string(), "replaced".to_string()),
+            ("Client_Name".to_string(), "replaced".to_string()),
+        ]);
+        let result = fill_docx_template(path_string(&template), path_string(&output), variables).unwrap();
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+        let xml = read_docx_document_xml(&output).unwrap();
+        assert!(xml.contains("<w:t>Dear Jane,</w:t>"), "{}", xml);
+        assert!(xml.contains("<w:t>Literal {{foo}} and {{Client_Name}} stay as typed.</w:t>"), "{}", xml);
+    }
+
+    /// Fake pandoc: saves stdin beside itself and copies a prepared DOCX to the `-o` path
+    #[cfg(unix)]
+    fn mock_pandoc_docx(dir: &Path) -> PathBuf {
+        minimal_docx(&dir.join("rendered.docx"), &docx_paragraph("rendered"));
+        mock_tool(dir, "pandoc", r#"
+cat > "$(dirname "$0")/stdin.md"
+cp "$(dirname "$0")/rendered.docx" "$5""#)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn export_transcription_as_docx_writes_a_docx() {
+        let dir = scratch_dir();
+        let pandoc = mock_pandoc_docx(dir.path());
+        let output = dir.path().join("Transcript.docx");
+
+        let written = export_transcription_as_docx_with(
+            pandoc.as_os_str(),
+            "Owner: He barks at the door.".to_string(),
+            sample_transcription_metadata(),
+            path_string(&output),
+            None,
+        ).unwrap();
+
+        assert_eq!(PathBuf::from(written), output);
+        read_docx_document_xml(&output).expect("output contains word/document.xml");
+        let stdin = fs::read_to_string(dir.path().join("stdin.md")).unwrap();
+        assert!(stdin.starts_with("---\n"), "{}", stdin);
+        assert!(stdin.contains("title: \"Session Transcript - Jane Doe\""), "{}", stdin);
+        assert!(stdin.contains("## Transcript\n\nOwner: He barks at the door."), "{}", stdin);
+    }
+
+    #[test]
+    fn markdown_to_html_renders_headings_and_tables() {
+        let html = convert_markdown_to_html("# Session Notes\n\n| A | B |\n|---|---|\n| 1 | 2 |\n\n~~old~~".to_string(), false).unwrap();
+        assert!(html.contains("<h1>Session Notes</h1>"), "{}", html);
+        assert!(html.contains("<table>") && html.contains("<td>1</td>"), "{}", html);
+        assert!(html.contains("<del>old</del>"), "{}", html);
+        assert!(!html.contains("<html"), "returns a fragment, not a document");
+    }
+
+    #[test]
+    fn markdown_to_html_sanitize_strips_scripts() {
+        let markdown = "## Plan\n\n<script>alert('x')</script>\n\n<iframe src=\"https://evil\"></iframe>\n\n[link](javascript:alert(1))".to_string();
+
+        let raw = convert_markdown_to_html(markdown.clone(), false).unwrap();
+        assert!(raw.contains("<script>"));
+
+        let clean = convert_markdown_to_html(markdown, true).unwrap();
+        assert!(clean.contains("<h2>Plan</h2>"), "{}", clean);
+        assert!(!clean.contains("<script") && !clean.contains("alert('x')"), "{}", clean);
+        assert!(!clean.contains("<iframe"), "{}", clean);
+        assert!(!clean.contains("javascript:"), "{}", clean);
+    }
+
+    #[test]
+    fn html_to_markdown_converts_block_elements() {
+        let html = r#"<h1 class="title" style="color:red">Legacy Note</h1>
+<p>Seen <strong>today</strong>.</p>
+<ul><li>Walks twice daily</li><li>Crate at night</li></ul>
+<ol><li>First step</li><li>Second step</li></ol>
+<blockquote>Owner quote</blockquote>
+<h3>Plan</h3>
+<script>track()</script><style>p { color: blue }</style>"#;
+
+        let markdown = convert_html_to_markdown(html.to_string()).unwrap();
+        assert!(markdown.starts_with("# Legacy Note"), "{}", markdown);
+        assert!(markdown.contains("### Plan"), "{}", markdown);
+        assert!(markdown.contains("Seen **today**."), "{}", markdown);
+        assert!(markdown.contains("Walks twice daily") && markdown.contains("Crate at night"), "{}", markdown);
+        let numbered = |item: &str| markdown.lines().find(|line| line.contains(item)).map(|line| line.split_whitespace().next().unwrap().to_string());
+        assert_eq!(numbered("First step").as_deref(), Some("1."), "{}", markdown);
+        assert_eq!(numbered("Second step").as_deref(), Some("2."), "{}", markdown);
+        assert!(markdown.contains("> Owner quote"), "{}", markdown);
+        for dropped in ["class", "style", "color", "track()"] {
+            assert!(!markdown.contains(dropped), "{} in {}", dropped, markdown);
+        }
+    }
+
+    #[test]
+    fn html_to_markdown_tolerates_malformed_html() {
+        let markdown = convert_html_to_markdown("<p>Unclosed <em>emphasis<div><h2>Heading".to_string()).unwrap();
+        assert!(markdown.contains("Unclosed"), "{}", markdown);
+        assert!(markdown.lines().any(|line| line.starts_with("## ") && line.contains("Heading")), "{}", markdown);
+
+        let table = convert_html_to_markdown("<table><tr><th>Pet</th></tr><tr><td>Max</td></tr></table>".to_string()).unwrap();
+        assert!(table.contains("Pet") && table.contains("Max"), "{}", table);
+    }
+
+    #[test]
+    fn pdf_and_template_commands_are_audited() {
+        let dir = scratch_dir();
+        let pdf = dir.path().join("audited_in.pdf");
+        sample_pdf("Audit", 1).save(&pdf).unwrap();
+        let merged = dir.path().join("audited_merged.pdf");
+        merge_pdf_files(vec![path_string(&pdf)], path_string(&merged)).unwrap();
+
+        let template = dir.path().join("audited_template.docx");
+        minimal_docx(&template, &docx_paragraph("{{X}}"));
+        let filled = dir.path().join("audited_filled.docx");
+        fill_docx_template(path_string(&template), path_string(&filled), HashMap::new()).unwrap();
+
+        assert_eq!(audit_entries_for(&path_string(&merged))[0].command, "merge_pdf_files");
+        let fill = &audit_entries_for(&path_string(&filled))[0];
+        assert_eq!(fill.command, "fill_docx_template");
+        assert_eq!(fill.path, format!("{} -> {}", path_string(&template), path_string(&filled)));
+    }
+
+    fn row(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn csv_round_trips_commas_quotes_and_newlines() {
+        let dir = scratch_dir();
+        let file = dir.path().join("roster.csv");
+        let rows = vec![
+            row(&[("id", "1"), ("name", "Doe, Jane"), ("notes", "Line one\nLine two")]),
+            row(&[("id", "2"), ("name", "Tom \"TJ\" O'Brien"), ("notes", "café")]),
+            row(&[("id", "3"), ("name", "No notes")]),
+        ];
+        let headers = strings(&["id", "name", "notes"]);
+
+        export_csv(rows.clone(), path_string(&file), headers).unwrap();
+        let raw = fs::read_to_string(&file).unwrap();
+        assert!(raw.starts_with("id,name,notes\r\n1,\"Doe, Jane\",\"Line one\nLine two\"\r\n"), "{:?}", raw);
+        assert!(raw.contains("\"Tom \"\"TJ\"\" O'Brien\""), "{:?}", raw);
+
+        let mut expected = rows;
+        expected[2].insert("notes".to_string(), String::new());
+        assert_eq!(import_csv(path_string(&file), true).unwrap(), expected);
+    }
+
+    #[test]
+    fn csv_import_handles_bom_missing_headers_and_ragged_rows() {
+        let dir = scratch_dir();
+        let file = dir.path().join("excel_export.csv");
+        fs::write(&file, b"\xEF\xBB\xBF Name ,Pet\r\nJane,Max\r\nTom,Luna,extra\r\n").unwrap();
+
+        let rows = import_csv(path_string(&file), true).unwrap();
+        assert_eq!(rows[0], row(&[("Name", "Jane"), ("Pet", "Max")]));
+        assert_eq!(rows[1]["column_3"], "extra");
+
+        let rows = import_csv(path_string(&file), false).unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0]["column_1"], " Name ");
+
+        assert!(export_csv(Vec::new(), path_string(&file), Vec::new()).unwrap_err().contains("At least one CSV column header"));
+    }
+
+    /// Fake pandoc for batches: copies the input to the `-o` path, fails for inputs named bad*,
+    /// and logs how many conversions were running at once
+    #[cfg(unix)]
+    fn mock_batch_pandoc(dir: &Path) -> PathBuf {
+        mock_tool(dir, "pandoc", r#"
+here=$(dirname "$0")
+touch "$here/running.$$"
+ls "$here" | grep -c '^running\.' >> "$here/concurrency.log"
+sleep 0.2
+rm "$here/running.$$"
+case "$(basename "$1")" in bad*) echo "pandoc: parse error" >&2; exit 64;; esac
+cp "$1" "$5""#)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn pandoc_batch_reports_each_file_and_respects_concurrency() {
+        let dir = scratch_dir();
+        let bin = tempfile::tempdir().unwrap();
+        let pandoc = mock_batch_pandoc(bin.path());
+        let inputs: Vec<BatchPandocInput> = ["jan", "bad_feb", "mar", "apr"].iter().map(|name| {
+            let input = dir.path().join(format!("{}.md", name));
+            fs::write(&input, format!("# {}", name)).unwrap();
+            BatchPandocInput {
+                input_path: path_string(&input),
+                output_path: path_string(&dir.path().join(format!("{}.docx", name))),
+                template_path: None,
+            }
+        }).collect();
+
+        let results = run_pandoc_batch_with(pandoc.as_os_str(), inputs, 2).await;
+
+        let outcomes: Vec<(String, bool)> = results.iter()
+            .map(|r| (PathBuf::from(&r.input_path).file_name().unwrap().to_string_lossy().to_string(), r.success))
+            .collect();
+        assert_eq!(outcomes, [("jan.md", true), ("bad_feb.md", false), ("mar.md", true), ("apr.md", true)]
+            .map(|(n, ok)| (n.to_string(), ok)));
+        assert!(results[1].error.as_deref().unwrap().contains("pandoc: parse error"));
+        assert_eq!(fs::read_to_string(dir.path().join("mar.docx")).unwrap(), "# mar");
+
+        let peaks: Vec<u32> = fs::read_to_string(bin.path().join("concurrency.log")).unwrap()
+            .lines().map(|l| l.trim().parse().unwrap()).collect();
+        assert_eq!(peaks.len(), 4);
+        assert!(peaks.iter().all(|&p| p <= 2), "{:?}", peaks);
+    }
+
+    #[tokio::test]
+    async fn pandoc_batch_item_rejects_bad_extensions_without_running() {
+        let dir = scratch_dir();
+        let missing_tool = dir.path().join("no_pandoc");
+        let results = run_pandoc_batch_with(missing_tool.as_os_str(), vec![
+            BatchPandocInput {
+                input_path: path_string(&dir.path().join("notes.exe")),
+                output_path: path_string(&dir.path().join("notes.docx")),
+                template_path: None,
+            },
+            BatchPandocInput {
+                input_path: path_string(&dir.path().join("notes.md")),
+                output_path: path_string(&dir.path().join("notes.docx")),
+                template_path: None,
+            },
+        ], 0).await;
+
+        assert_eq!(results[0].error.as_deref(), Some("Input file must be a .md, .markdown, or .txt file"));
+        assert!(results[1].error.as_deref().unwrap().contains("Is pandoc installed?"));
+        assert!(run_pandoc_batch(Vec::new(), 4).await.unwrap().is_empty());
+    }
+
+    fn sample_invoice(units: u32, rate_cents: u64) -> InvoiceData {
+        InvoiceData {
+            client_name: "Jane Smith".to_string(),
+            client_id: "C-042".to_string(),
+            clinician_name: "Dr Glenn".to_string(),
+            session_date: "2025-03-14".to_string(),
+            service_code: "90837".to_string(),
+            units,
+            rate_cents,
+            insurance_provider: None,
+        }
+    }
+
+    #[test]
+    fn invoice_total_is_exact_in_cents() {
+        let invoice = sample_invoice(6, 3250);
+        assert_eq!(invoice_total_cents(&invoice).unwrap(), 19500);
+        assert_eq!(format_dollars(19500), "$195.00");
+        assert_eq!(format_dollars(5), "$0.05");
+        assert_eq!(format_dollars(123_456_789), "$1,234,567.89");
+
+        assert_eq!(invoice_total_cents(&sample_invoice(2, u64::MAX)).unwrap_err(), "Invoice total is too large");
+    }
+
+    #[test]
+    fn invoice_markdown_lists_service_and_total() {
+        let mut invoice = sample_invoice(6, 3250);
+        invoice.service_code = "90837|x".to_string();
+        invoice.insurance_provider = Some("PetCover".to_string());
+
+        let markdown = build_invoice_markdown(&invoice).unwrap();
+
+        assert!(markdown.contains("**Client:** Jane Smith (ID: C-042)"));
+        assert!(markdown.contains("**Insurance provider:** PetCover"));
+        assert!(markdown.contains("| 90837\\|x | 6 | $32.50 | $195.00 |"), "{}", markdown);
+        assert!(markdown.contains("**Total due: $195.00**"));
+
+        invoice.insurance_provider = Some(" ".to_string());
+        assert!(!build_invoice_markdown(&invoice).unwrap().contains("Insurance provider"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn generate_invoice_pipes_markdown_to_pandoc() {
+        let dir = scratch_dir();
+        let pandoc = mock_pandoc_docx(dir.path());
+        let output = dir.path().join("Invoice.docx");
+
+        generate_invoice_with(pandoc.as_os_str(), sample_invoice(6, 3250), String::new(), path_string(&output)).unwrap();
+
+        assert!(output.exists());
+        let stdin = fs::read_to_string(dir.path().join("stdin.md")).unwrap();
+        assert!(stdin.contains("Invoice - Jane Smith"), "{}", stdin);
+        assert!(stdin.contains("**Total due: $195.00**"));
+    }
+
+    #[test]
+    fn generate_invoice_requires_docx_output() {
+        let dir = scratch_dir();
+        let output = path_string(&dir.path().join("Invoice.pdf"));
+        let err = generate_invoice_with(dir.path().join("no_pandoc").as_os_str(), sample_invoice(1, 100), String::new(), output).unwrap_err();
+        assert!(err.starts_with("Output path must be a .docx file"), "{}", err);
+    }
+
+    #[test]
+    fn format_currency_uses_accounting_negatives() {
+        assert_eq!(format_currency(19500, "USD".into()).unwrap(), "$195.00");
+        assert_eq!(format_currency(-19500, "usd".into()).unwrap(), "($195.00)");
+        assert_eq!(format_currency(123_456_789, "AUD".into()).unwrap(), "A$1,234,567.89");
+        assert_eq!(format_currency(-5, "GBP".into()).unwrap(), "(£0.05)");
+        assert_eq!(format_currency(0, "EUR".into()).unwrap(), "€0.00");
+        assert_eq!(format_currency(1500, "JPY".into()).unwrap(), "¥1,500");
+        assert_eq!(format_currency(100, "XYZ".into()).unwrap_err(), "Unsupported currency code: XYZ");
+    }
+
+    #[test]
+    fn format_duration_supports_each_format() {
+        assert_eq!(format_duration(8100.0, "HH:MM:SS".into()).unwrap(), "02:15:00");
+        assert_eq!(format_duration(45.4, "HH:MM:SS".into()).unwrap(), "00:00:45");
+        assert_eq!(format_duration(150.0, "minutes".into()).unwrap(), "2.5 min");
+        assert_eq!(format_duration(30.0, "minutes".into()).unwrap(), "0.5 min");
+
+        assert_eq!(format_duration(8100.0, "human".into()).unwrap(), "2h 15m");
+        assert_eq!(format_duration(125.0, "human".into()).unwrap(), "2m 5s");
+        assert_eq!(format_duration(120.0, "human".into()).unwrap(), "2m");
+        assert_eq!(format_duration(42.0, "human".into()).unwrap(), "42s");
+        assert_eq!(format_duration(0.0, "human".into()).unwrap(), "0s");
+    }
+
+    #[test]
+    fn format_duration_rejects_bad_input() {
+        assert_eq!(format_duration(-1.0, "human".into()).unwrap_err(), "Invalid duration: -1");
+        assert!(format_duration(f64::NAN, "human".into()).is_err());
+        assert!(format_duration(10.0, "hours".into()).unwrap_err().starts_with("Unsupported duration format: hours"));
+    }
+
+    #[test]
+    fn late_evening_eastern_date_stays_on_the_same_day() {
+        let timestamp = parse_date("2025-01-15 23:00".into(), "YYYY-MM-DD HH:mm".into(), "America/New_York".into()).unwrap();
+        // 23:00 EST = 04:00 UTC the next day
+        assert_eq!(timestamp, 1_737_000_000);
+
+        assert_eq!(format_date(timestamp, "YYYY-MM-DD".into(), "America/New_York".into()).unwrap(), "2025-01-15");
+        assert_eq!(format_date(timestamp, "YYYY-MM-DD".into(), "UTC".into()).unwrap(), "2025-01-16");
+    }
+
+    #[test]
+    fn date_patterns_round_trip() {
+        let tz = "Australia/Brisbane".to_string();
+        let timestamp = parse_date("2025-01-15".into(), "YYYY-MM-DD".into(), tz.clone()).unwrap();
+        assert_eq!(parse_date("01/15/2025".into(), "MM/DD/YYYY".into(), tz.clone()).unwrap(), timestamp);
+        assert_eq!(parse_date("January 15, 2025".into(), "MMMM D, YYYY".into(), tz.clone()).unwrap(), timestamp);
+        assert_eq!(parse_date("2025-01-15".into(), "%Y-%m-%d".into(), tz.clone()).unwrap(), timestamp);
+
+        assert_eq!(format_date(timestamp, "MMMM D, YYYY".into(), tz.clone()).unwrap(), "January 15, 2025");
+        assert_eq!(format_date(timestamp, "MM/DD/YYYY".into(), tz.clone()).unwrap(), "01/15/2025");
+        assert_eq!(format_date(timestamp, "ddd D MMM".into(), tz).unwrap(), "Wed 15 Jan");
+    }
+
+    #[test]
+    fn date_commands_reject_bad_timezones_and_formats() {
+        assert_eq!(parse_date("2025-01-15".into(), "YYYY-MM-DD".into(), "Mars/Olympus".into()).unwrap_err(), "Invalid timezone: Mars/Olympus");
+        assert_eq!(format_date(0, "YYYY".into(), "EST5".into()).unwrap_err(), "Invalid timezone: EST5");
+        assert!(format_date(0, "YYYY-QQ".into(), "UTC".into()).unwrap_err().starts_with("Unsupported date format token 'Q'"));
+        assert_eq!(format_date(0, "%Y-%Q".into(), "UTC".into()).unwrap_err(), "Invalid date format: %Y-%Q");
+        assert_eq!(format_date(0, " ".into(), "UTC".into()).unwrap_err(), "Date format is required");
+        assert!(parse_date("15/01/2025".into(), "MM/DD/YYYY".into(), "UTC".into()).unwrap_err().starts_with("Failed to parse date"));
+        assert_eq!(parse_date("1969-12-31".into(), "YYYY-MM-DD".into(), "UTC".into()).unwrap_err(), "Date is before 1970: 1969-12-31");
+    }
+
+    #[test]
+    fn daylight_saving_gap_is_rejected() {
+        // Clocks jump from 02:00 to 03:00 on 9 March 2025 in New York
+        let err = parse_date("2025-03-09 02:30".into(), "YYYY-MM-DD HH:mm".into(), "America/New_York".into()).unwrap_err();
+        assert!(err.contains("daylight saving gap"), "{}", err);
+    }
+
+    #[test]
+    fn ninety_eight_three_seven_at_53_minutes_is_one_unit() {
+        assert_eq!(calculate_billing_units(53, "90837".into()).unwrap(), BillingCalculation {
+            units: 1,
+            base_code: "90837".to_string(),
+            add_on_code: None,
+            remaining_minutes: 8,
+        });
+    }
+
+    #[test]
+    fn psychotherapy_codes_bill_the_tier_the_session_meets() {
+        let base = |minutes, code: &str| calculate_billing_units(minutes, code.to_string()).unwrap().base_code;
+        assert_eq!(base(16, "90832"), "90832");
+        assert_eq!(base(37, "90834"), "90832");
+        assert_eq!(base(38, "90834"), "90834");
+        assert_eq!(base(45, "90837"), "90834");
+        assert_eq!(base(60, "90832"), "90837");
+        // Add-ons stay within the add-on family
+        assert_eq!(base(20, "90838"), "90833");
+        assert_eq!(base(40, "90833"), "90836");
+
+        assert_eq!(
+            calculate_billing_units(15, "90832".into()).unwrap_err(),
+            "15 minutes is too short to bill 90832 (minimum 16 minutes)"
+        );
+    }
+
+    #[test]
+    fn crisis_code_adds_ninety_eight_four_zero_units() {
+        let crisis = |minutes| calculate_billing_units(minutes, "90839".into()).unwrap();
+        assert_eq!((crisis(60).units, crisis(60).add_on_code), (1, None));
+        assert_eq!((crisis(74).units, crisis(74).add_on_code), (1, None));
+        assert_eq!((crisis(75).units, crisis(75).add_on_code.as_deref()), (2, Some("90840")));
+        assert_eq!(crisis(105).units, 3);
+        assert!(calculate_billing_units(29, "90839".into()).unwrap_err().ends_with("use 90832 instead"));
+    }
+
+    #[test]
+    fn unknown_cpt_codes_get_a_hint() {
+        let err = calculate_billing_units(50, "99213".into()).unwrap_err();
+        assert!(err.starts_with("Unsupported CPT code: 99213. Supported codes:"), "{}", err);
+        assert!(calculate_billing_units(50, "90840".into()).unwrap_err().contains("add-on code"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn html_preview_via_pandoc_is_a_sanitized_document() {
+        let dir = scratch_dir();
+        let pandoc = mock_tool(dir.path(), "pandoc", r#"
+printf '%s\n' "$@" > "$(dirname "$0")/pandoc_args.txt"
+body=$(cat)
+printf '<!DOCTYPE html>\n<html>\n<head>\n<style>body { color: #222; }</style>\n</head>\n<body>\n<p>%s</p><script>alert(1)</script>\n</body>\n</html>\n' "$body""#);
+
+        let preview = run_pandoc_to_html_preview_with(pandoc.as_os_str(), "Max is doing well", false).unwrap();
+
+        assert!(!preview.used_fallback);
+        assert!(preview.html.starts_with("<!DOCTYPE html>"), "{}", preview.html);
+        assert!(preview.html.contains("<style>body { color: #222; }</style>"));
+        assert!(preview.html.contains("<p>Max is doing well</p>"));
+        assert!(!preview.html.contains("<script>"));
+
+        let args = fs::read_to_string(dir.path().join("pandoc_args.txt")).unwrap();
+        assert_eq!(args.lines().collect::<Vec<_>>(), vec![
+            "-", "-f", "markdown+hard_line_breaks", "-t", "html5", "--standalone", "--metadata", "pagetitle=Preview",
+        ]);
+    }
+
+    #[test]
+    fn html_preview_falls_back_to_cmark_fragment() {
+        let dir = scratch_dir();
+        let missing = dir.path().join("no_pandoc");
+
+        let preview = run_pandoc_to_html_preview_with(missing.as_os_str(), "# Plan\n\n<script>x()</script>\n\nWalk **daily**", false).unwrap();
+
+        assert!(preview.used_fallback);
+        assert!(!preview.html.contains("<!DOCTYPE"));
+        assert!(preview.html.contains("<h1>Plan</h1>"), "{}", preview.html);
+        assert!(preview.html.contains("<strong>daily</strong>"));
+        assert!(!preview.html.contains("<script>"));
+    }
+
+    #[test]
+    fn document_body_sanitizing_keeps_the_head() {
+        let document = "<html><HEAD><style>p{}</style></HEAD><Body class=\"x\"><p onclick=\"evil()\">Hi</p></Body></html>";
+        assert_eq!(
+            sanitize_html_document_body(document),
+            "<html><HEAD><style>p{}</style></HEAD><Body class=\"x\"><p>Hi</p></Body></html>"
+        );
+        // Without a body everything is sanitized
+        assert_eq!(sanitize_html_document_body("<p>a</p><script>b</script>"), "<p>a</p>");
+    }
+
+    /// Insurer-style payment workbook: a header row, typed cells, a gap, and a second sheet
+    fn payment_workbook(path: &Path) {
+        use rust_xlsxwriter::{ExcelDateTime, Format, Workbook};
+
+        let mut workbook = Workbook::new();
+        let date_format = Format::new().set_num_format("dd/mm/yyyy");
+        let sheet = workbook.add_worksheet().set_name("Payments").unwrap();
+        sheet.write_string(0, 0, "Client").unwrap();
+        sheet.write_string(0, 1, "Paid").unwrap();
+        sheet.write_string(0, 2, "Date").unwrap();
+        sheet.write_string(0, 3, "Approved").unwrap();
+        sheet.write_string(1, 0, "Jane Smith").unwrap();
+        sheet.write_number(1, 1, 195.5).unwrap();
+        sheet.write_datetime_with_format(1, 2, ExcelDateTime::from_ymd(2025, 3, 14).unwrap(), &date_format).unwrap();
+        sheet.write_boolean(1, 3, true).unwrap();
+        sheet.write_string(2, 0, "O'Brien").unwrap();
+        sheet.write_number(2, 1, 80).unwrap();
+        sheet.write_boolean(2, 3, false).unwrap();
+        workbook.add_worksheet().set_name("Notes").unwrap().write_string(0, 0, "Batch 7").unwrap();
+        workbook.save(path).unwrap();
+    }
+
+    #[test]
+    fn excel_cells_keep_their_types() {
+        let dir = scratch_dir();
+        let file = dir.path().join("payments.xlsx");
+        payment_workbook(&file);
+
+        let rows = read_excel_file(path_string(&file), None).unwrap();
+
+        assert_eq!(rows, vec![
+            vec![serde_json::json!("Client"), serde_json::json!("Paid"), serde_json::json!("Date"), serde_json::json!("Approved")],
+            vec![serde_json::json!("Jane Smith"), serde_json::json!(195.5), serde_json::json!("2025-03-14"), serde_json::json!(true)],
+            vec![serde_json::json!("O'Brien"), serde_json::json!(80.0), serde_json::Value::Null, serde_json::json!(false)],
+        ]);
+        assert!(rows[2][1].is_f64());
+    }
+
+    #[test]
+    fn excel_reads_a_named_sheet() {
+        let dir = scratch_dir();
+        let file = dir.path().join("payments.xlsx");
+        payment_workbook(&file);
+
+        assert_eq!(read_excel_file(path_string(&file), Some("Notes".into())).unwrap(), vec![vec![serde_json::json!("Batch 7")]]);
+        assert!(read_excel_file(path_string(&file), Some("Missing".into())).unwrap_err().starts_with("Failed to read sheet 'Missing'"));
+    }
+
+    #[test]
+    fn excel_rejects_non_spreadsheets() {
+        let dir = scratch_dir();
+        let file = dir.path().join("payments.xlsx");
+        fs::write(&file, "not a workbook").unwrap();
+        assert!(read_excel_file(path_string(&file), None).unwrap_err().starts_with("Failed to open spreadsheet"));
+    }
+
+    #[test]
+    fn excel_datetime_cells_convert_to_iso_strings() {
+        use calamine::{Data, ExcelDateTime, ExcelDateTimeType};
+
+        let afternoon = Data::DateTime(ExcelDateTime::new(45730.5, ExcelDateTimeType::DateTime, false));
+        assert_eq!(excel_cell_to_json(&afternoon), serde_json::json!("2025-03-14T12:00:00"));
+        let duration = Data::DateTime(ExcelDateTime::new(0.25, ExcelDateTimeType::TimeDelta, false));
+        assert_eq!(excel_cell_to_json(&duration), serde_json::json!("PT21600S"));
+        assert_eq!(excel_cell_to_json(&Data::Int(3)), serde_json::json!(3.0));
+    }
+
+    #[test]
+    fn excel_export_round_trips_through_calamine() {
+        let dir = scratch_dir();
+        let output = dir.path().join("billing.xlsx");
+        let data = vec![
+            vec![serde_json::json!("Jane Smith"), serde_json::json!(195.5), serde_json::json!("2025-03-14"), serde_json::json!(true)],
+            vec![serde_json::json!("O'Brien"), serde_json::json!(80), serde_json::Value::Null, serde_json::json!(false)],
+            vec![serde_json::json!("Luna"), serde_json::json!(0.25), serde_json::json!("2025-13-45"), serde_json::json!("n/a")],
+        ];
+
+        export_to_excel(data, strings(&["Client", "Paid", "Date", "Approved"]), path_string(&output), "March".into()).unwrap();
+
+        let rows = read_excel_file(path_string(&output), Some("March".into())).unwrap();
+        assert_eq!(rows, vec![
+            vec![serde_json::json!("Client"), serde_json::json!("Paid"), serde_json::json!("Date"), serde_json::json!("Approved")],
+            vec![serde_json::json!("Jane Smith"), serde_json::json!(195.5), serde_json::json!("2025-03-14"), serde_json::json!(true)],
+            vec![serde_json::json!("O'Brien"), serde_json::json!(80.0), serde_json::Value::Null, serde_json::json!(false)],
+            // Date-shaped text that isn't a real date stays text
+            vec![serde_json::json!("Luna"), serde_json::json!(0.25), serde_json::json!("2025-13-45"), serde_json::json!("n/a")],
+        ]);
+    }
+
+    #[test]
+    fn excel_export_writes_datetimes_and_bold_headers() {
+        use calamine::{Data, Reader};
+
+        let dir = scratch_dir();
+        let output = dir.path().join("sessions.xlsx");
+        export_to_excel(
+            vec![vec![serde_json::json!("2025-03-14T09:30:00")]],
+            strings(&["Start"]),
+            path_string(&output),
+            "Sessions".into(),
+        ).unwrap();
+
+        let mut workbook = calamine::open_workbook_auto(&output).unwrap();
+        let range = workbook.worksheet_range("Sessions").unwrap();
+        assert!(matches!(range.get((1, 0)), Some(Data::DateTime(_))), "{:?}", range.get((1, 0)));
+        assert_eq!(excel_cell_to_json(range.get((1, 0)).unwrap()), serde_json::json!("2025-03-14T09:30:00"));
+
+        // Bold header format lives in the styles part
+        let mut archive = zip::ZipArchive::new(fs::File::open(&output).unwrap()).unwrap();
+        let mut styles = String::new();
+        std::io::Read::read_to_string(&mut archive.by_name("xl/styles.xml").unwrap(), &mut styles).unwrap();
+        assert!(styles.contains("<b/>"), "{}", styles);
+    }
+
+    #[test]
+    fn excel_export_validates_output_and_sheet_name() {
+        let dir = scratch_dir();
+        assert!(export_to_excel(Vec::new(), Vec::new(), path_string(&dir.path().join("report.csv")), "Sheet1".into())
+            .unwrap_err().starts_with("Output path must be a .xlsx file"));
+        assert!(export_to_excel(Vec::new(), Vec::new(), path_string(&dir.path().join("report.xlsx")), "Bad/Name".into())
+            .unwrap_err().starts_with("Invalid sheet name 'Bad/Name'"));
+        assert!(!dir.path().join("report.xlsx").exists());
+    }
+
+    #[test]
+    fn template_validation_reports_missing_variables() {
+        let dir = scratch_dir();
+        let template = dir.path().join("letter_template.docx");
+        let body = [
+            docx_paragraph("Dear {{CLIENT_NAME}},"),
+            docx_paragraph("Re: {{PET_NAME}} on {{DATE}}"),
+            docx_paragraph("{{CLINIC_PHONE}}"),
+        ].concat();
+        minimal_docx(&template, &body);
+
+        let result = validate_docx_template(
+            path_string(&template),
+            strings(&["CLIENT_NAME", "{{DATE}}", "DIAGNOSIS", " VET_NAME "]),
+        ).unwrap();
+
+        assert!(result.is_valid_zip);
+        assert_eq!(result.present, strings(&["CLIENT_NAME", "DATE"]));
+        assert_eq!(result.missing, strings(&["DIAGNOSIS", "VET_NAME"]));
+        assert_eq!(result.extra, strings(&["CLINIC_PHONE", "PET_NAME"]));
+    }
+
+    #[test]
+    fn template_validation_accepts_a_complete_template() {
+        let dir = scratch_dir();
+        let template = dir.path().join("letter_template.docx");
+        minimal_docx(&template, &docx_paragraph("{{CLIENT_NAME}} / {{DATE}}"));
+
+        let result = validate_docx_template(path_string(&template), strings(&["DATE", "CLIENT_NAME"])).unwrap();
+        assert!(result.missing.is_empty() && result.extra.is_empty());
+    }
+
+    #[test]
+    fn template_validation_flags_non_zip_files() {
+        let dir = scratch_dir();
+        let template = dir.path().join("letter_template.docx");
+        fs::write(&template, "plain text renamed to .docx").unwrap();
+
+        let result = validate_docx_template(path_string(&template), strings(&["CLIENT_NAME"])).unwrap();
+
+        assert!(!result.is_valid_zip);
+        assert_eq!(result.missing, strings(&["CLIENT_NAME"]));
+        assert!(result.present.is_empty());
+    }
+
+    /// Decode the QR code in a PNG file with rxing
+    fn decode_qr_png(path: &Path) -> String {
+        let image = image::open(path).unwrap().to_luma8();
+        let (width, height) = image.dimensions();
+        rxing::helpers::detect_in_luma(image.into_raw(), width, height, Some(rxing::BarcodeFormat::QR_CODE))
+            .unwrap()
+            .getText()
+            .to_string()
+    }
+
+    #[test]
+    fn qr_code_decodes_to_the_input() {
+        let dir = scratch_dir();
+        let output = dir.path().join("checkin.png");
+        let data = "pbs-admin://client/C-042?form=intake&pet=Max";
+
+        let written = generate_qr_code(data.to_string(), path_string(&output), 240).unwrap();
+
+        assert_eq!(PathBuf::from(written), output.canonicalize().unwrap());
+        assert_eq!(image::image_dimensions(&output).unwrap(), (240, 240));
+        assert_eq!(decode_qr_png(&output), data);
+    }
+
+    #[test]
+    fn qr_code_handles_longer_unicode_data() {
+        let dir = scratch_dir();
+        let output = dir.path().join("record.png");
+        let data = "Café Ünïcode owner notes – ".repeat(12);
+
+        generate_qr_code(data.clone(), path_string(&output), 600).unwrap();
+        assert_eq!(decode_qr_png(&output), data);
+    }
+
+    #[test]
+    fn qr_code_enforces_limits() {
+        let dir = scratch_dir();
+        let output = path_string(&dir.path().join("code.png"));
+
+        assert_eq!(generate_qr_code("x".into(), output.clone(), 99).unwrap_err(), "QR code size must be at least 100 pixels");
+        assert_eq!(
+            generate_qr_code("x".repeat(2954), output.clone(), 1000).unwrap_err(),
+            "QR code data is too long: 2954 bytes (maximum 2953)"
+        );
+        // More modules than pixels
+        assert!(generate_qr_code("x".repeat(1500), output.clone(), 100).unwrap_err().starts_with("QR code needs at least"));
+        assert!(generate_qr_code("x".into(), path_string(&dir.path().join("code.jpg")), 200)
+            .unwrap_err().starts_with("Output path must be a .png file"));
+        assert!(!dir.path().join("code.png").exists());
+    }
+
+    /// A pandoc stand-in that logs each invocation to `spawns.log` before listing formats
+    #[cfg(unix)]
+    fn mock_counting_pandoc(dir: &Path) -> PathBuf {
+        mock_tool(
+            dir,
+            "pandoc",
+            &format!(
+                "echo \"$1\" >> '{}'\ncase \"$1\" in\n  --list-input-formats) printf 'markdown\\ndocx\\n' ;;\n  --list-output-formats) printf 'docx\\npdf\\nhtml\\n' ;;\n  *) exit 1 ;;\nesac",
+                dir.join("spawns.log").display()
+            ),
+        )
+    }
+
+    #[cfg(unix)]
+    fn spawn_count(dir: &Path) -> usize {
+        fs::read_to_string(dir.join("spawns.log")).map(|log| log.lines().count()).unwrap_or(0)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pandoc_formats_are_cached_after_first_lookup() {
+        let bin = tempfile::tempdir().unwrap();
+        let pandoc = mock_counting_pandoc(bin.path());
+        let cache = PandocFormatsCache::default();
+
+        let first = cached_pandoc_formats_with(&cache, pandoc.as_os_str(), false).unwrap();
+        assert_eq!(first, PandocFormats { input: strings(&["markdown", "docx"]), output: strings(&["docx", "pdf", "html"]) });
+        assert_eq!(spawn_count(bin.path()), 2);
+
+        let second = cached_pandoc_formats_with(&cache, pandoc.as_os_str(), false).unwrap();
+        assert_eq!(second, first);
+        assert_eq!(spawn_count(bin.path()), 2, "cached lookup must not spawn pandoc");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refreshing_pandoc_formats_respawns_and_replaces_cache() {
+        let bin = tempfile::tempdir().unwrap();
+        let pandoc = mock_counting_pandoc(bin.path());
+        let cache = PandocFormatsCache::default();
+        cached_pandoc_formats_with(&cache, pandoc.as_os_str(), false).unwrap();
+
+        // Simulate a pandoc upgrade that adds an output format
+        let upgraded = mock_tool(
+            bin.path(),
+            "pandoc",
+            &format!(
+                "echo \"$1\" >> '{}'\ncase \"$1\" in\n  --list-input-formats) printf 'markdown\\n' ;;\n  *) printf 'docx\\ntypst\\n' ;;\nesac",
+                bin.path().join("spawns.log").display()
+            ),
+        );
+        let refreshed = cached_pandoc_formats_with(&cache, upgraded.as_os_str(), true).unwrap();
+        assert_eq!(refreshed.output, strings(&["docx", "typst"]));
+        assert_eq!(spawn_count(bin.path()), 4);
+
+        assert_eq!(cached_pandoc_formats_with(&cache, upgraded.as_os_str(), false).unwrap(), refreshed);
+        assert_eq!(spawn_count(bin.path()), 4);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_pandoc_lookup_leaves_cache_empty() {
+        let bin = tempfile::tempdir().unwrap();
+        let broken = mock_tool(bin.path(), "pandoc", "echo 'unknown option' >&2; exit 2");
+        let cache = PandocFormatsCache::default();
+
+        let err = cached_pandoc_formats_with(&cache, broken.as_os_str(), false).unwrap_err();
+        assert_eq!(err, "pandoc --list-input-formats failed: unknown option");
+        assert!(cache.0.lock().unwrap().is_none());
+
+        let pandoc = mock_counting_pandoc(bin.path());
+        assert_eq!(cached_pandoc_formats_with(&cache, pandoc.as_os_str(), false).unwrap().input, strings(&["markdown", "docx"]));
+    }
+
+    #[test]
+    fn missing_pandoc_reports_install_hint() {
+        let cache = PandocFormatsCache::default();
+        let err = cached_pandoc_formats_with(&cache, std::ffi::OsStr::new("/nonexistent/pandoc"), false).unwrap_err();
+        assert!(err.starts_with("Pandoc is not installed or not in PATH"), "{}", err);
+    }
+
+    fn sample_session_metadata() -> SessionMetadata {
+        SessionMetadata {
+            client_name: "Jane \"JJ\" Smith".to_string(),
+            client_id: "C-1042".to_string(),
+            session_date: "2024-03-05".to_string(),
+            session_type: "Follow-up".to_string(),
+            clinician_name: "Dr. Lee".to_string(),
+            duration_minutes: Some(75),
+        }
+    }
+
+    /// Front matter (as key -> JSON value) and H2 headings of a markdown document
+    fn markdown_outline(markdown: &str) -> (serde_json::Map<String, serde_json::Value>, Vec<String>) {
+        use pulldown_cmark::{Event, HeadingLevel, MetadataBlockKind, Options, Parser, Tag, TagEnd};
+
+        let mut front_matter = serde_json::Map::new();
+        let mut headings = Vec::new();
+        let (mut in_metadata, mut in_h2) = (false, false);
+        for event in Parser::new_ext(markdown, Options::ENABLE_YAML_STYLE_METADATA_BLOCKS) {
+            match event {
+                Event::Start(Tag::MetadataBlock(MetadataBlockKind::YamlStyle)) => in_metadata = true,
+                Event::End(TagEnd::MetadataBlock(_)) => in_metadata = false,
+                Event::Start(Tag::Heading { level: HeadingLevel::H2, .. }) => {
+                    in_h2 = true;
+                    headings.push(String::new());
+                },
+                Event::End(TagEnd::Heading(_)) => in_h2 = false,
+                Event::Text(text) if in_metadata => {
+                    for line in text.lines() {
+                        let (key, value) = line.split_once(": ").unwrap();
+                        front_matter.insert(key.to_string(), serde_json::from_str(value).unwrap());
+                    }
+                },
+                Event::Text(text) if in_h2 => headings.last_mut().unwrap().push_str(&text),
+                _ => {},
+            }
+        }
+        (front_matter, headings)
+    }
+
+    #[test]
+    fn session_note_template_parses_with_front_matter_and_soap_headings() {
+        let dir = scratch_dir();
+        let output = dir.path().join("2024-03-05_session.md");
+
+        let written = create_session_note_template(path_string(&output), sample_session_metadata()).unwrap();
+        assert_eq!(written, path_string(&output.canonicalize().unwrap()));
+
+        let markdown = fs::read_to_string(&output).unwrap();
+        let (front_matter, headings) = markdown_outline(&markdown);
+        assert_eq!(front_matter["title"], "Session Note - Jane \"JJ\" Smith");
+        assert_eq!(front_matter["date"], "2024-03-05");
+        assert_eq!(front_matter["author"], "Dr. Lee");
+        assert_eq!(front_matter["session_type"], "Follow-up");
+        assert_eq!(front_matter["duration_minutes"], 75);
+        assert_eq!(headings, strings(&["Subjective", "Objective", "Assessment", "Plan"]));
+        assert!(markdown.contains("- **Duration:** 75 minutes\n"));
+        for (_, prompt) in SOAP_SECTION_PROMPTS {
+            assert!(markdown.contains(prompt), "missing prompt {}", prompt);
+        }
+    }
+
+    #[test]
+    fn session_note_template_omits_unknown_duration() {
+        let metadata = SessionMetadata { duration_minutes: None, ..sample_session_metadata() };
+        let markdown = build_session_note_template(&metadata).unwrap();
+        let (front_matter, _) = markdown_outline(&markdown);
+        assert!(!front_matter.contains_key("duration_minutes"));
+        assert!(!markdown.contains("Duration"));
+    }
+
+    #[test]
+    fn session_note_template_never_overwrites() {
+        let dir = scratch_dir();
+        let output = dir.path().join("note.md");
+        fs::write(&output, "clinician's edits").unwrap();
+
+        let err = create_session_note_template(path_string(&output), sample_session_metadata()).unwrap_err();
+        assert!(err.starts_with("Session note already exists"), "{}", err);
+        assert_eq!(fs::read_to_string(&output).unwrap(), "clinician's edits");
+
+        let err = create_session_note_template(path_string(&dir.path().join("note.docx")), sample_session_metadata()).unwrap_err();
+        assert_eq!(err, "Output file must be a .md file");
+        let blank = SessionMetadata { client_name: " ".to_string(), ..sample_session_metadata() };
+        let err = create_session_note_template(path_string(&dir.path().join("blank.md")), blank).unwrap_err();
+        assert_eq!(err, "Client name and session date are required");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn transcript_is_inserted_at_the_end_of_its_section() {
+        let note = "# Note\n\n## Subjective\n\nOwner reports barking.\n\n## Objective\n\nCalm on arrival.\n";
+        assert_eq!(
+            insert_into_markdown_section(note, "subjective", "TRANSCRIPT"),
+            "# Note\n\n## Subjective\n\nOwner reports barking.\n\nTRANSCRIPT\n\n## Objective\n\nCalm on arrival.\n"
+        );
+        assert_eq!(
+            insert_into_markdown_section(note, "Objective", "TRANSCRIPT"),
+            "# Note\n\n## Subjective\n\nOwner reports barking.\n\n## Objective\n\nCalm on arrival.\n\nTRANSCRIPT\n"
+        );
+    }
+
+    #[test]
+    fn transcript_insertion_keeps_subsections_and_skips_code_fences() {
+        let note = "## Plan\n\n### Homework\n\nMat work.\n\n```\n## Assessment\n```\n\n## Assessment\n\nImproving.\n";
+        let updated = insert_into_markdown_section(note, "Plan", "TRANSCRIPT");
+        assert_eq!(
+            updated,
+            "## Plan\n\n### Homework\n\nMat work.\n\n```\n## Assessment\n```\n\nTRANSCRIPT\n\n## Assessment\n\nImproving.\n"
+        );
+        // The fenced heading is not a match, so the real section receives the text
+        let updated = insert_into_markdown_section(note, "Assessment", "TRANSCRIPT");
+        assert!(updated.ends_with("## Assessment\n\nImproving.\n\nTRANSCRIPT\n"), "{}", updated);
+    }
+
+    #[test]
+    fn transcript_for_missing_section_is_appended_as_new_section() {
+        assert_eq!(
+            insert_into_markdown_section("# Note\n\nIntro.\n\n\n", "Transcript", "TRANSCRIPT"),
+            "# Note\n\nIntro.\n\n## Transcript\n\nTRANSCRIPT\n"
+        );
+        assert_eq!(insert_into_markdown_section("", "Transcript", "TRANSCRIPT"), "## Transcript\n\nTRANSCRIPT\n");
+    }
+
+    #[test]
+    fn import_transcription_updates_note_with_duration_comment() {
+        let dir = scratch_dir();
+        let note_path = dir.path().join("2024-03-05_session.md");
+        create_session_note_template(path_string(&note_path), sample_session_metadata()).unwrap();
+
+        import_transcription_to_session_note(
+            path_string(&note_path), "  Owner: he barks at the door.\n".to_string(), "Subjective".to_string(), Some(3725.0),
+        ).unwrap();
+
+        let note = fs::read_to_string(&note_path).unwrap();
+        let subjective = note.find("## Subjective").unwrap();
+        let comment = note.find("<!-- Transcript imported ").unwrap();
+        let transcript = note.find("Owner: he barks at the door.\n").unwrap();
+        let objective = note.find("## Objective").unwrap();
+        assert!(subjective < comment && comment < transcript && transcript < objective, "{}", note);
+        assert!(note[comment..transcript].contains("(duration 1:02:05) -->\n"), "{}", note);
+        let (_, headings) = markdown_outline(&note);
+        assert_eq!(headings, strings(&["Subjective", "Objective", "Assessment", "Plan"]));
+    }
+
+    #[test]
+    fn import_transcription_validates_inputs() {
+        let dir = scratch_dir();
+        let note_path = dir.path().join("note.md");
+        fs::write(&note_path, "# Note\n").unwrap();
+        let import = |transcript: &str, section: &str| {
+            import_transcription_to_session_note(path_string(&note_path), transcript.to_string(), section.to_string(), None)
+        };
+
+        assert_eq!(import("text", " ").unwrap_err(), "Section name cannot be empty");
+        assert_eq!(import("\n ", "Subjective").unwrap_err(), "Transcript cannot be empty");
+        assert_eq!(fs::read_to_string(&note_path).unwrap(), "# Note\n");
+
+        import("text", "Transcript").unwrap();
+        let note = fs::read_to_string(&note_path).unwrap();
+        assert!(note.starts_with("# Note\n\n## Transcript\n\n<!-- Transcript imported "), "{}", note);
+        assert!(note.ends_with(" -->\ntext\n") && !note.contains("duration"), "{}", note);
+
+        let missing = import_transcription_to_session_note(
+            path_string(&dir.path().join("missing.md")), "text".to_string(), "Plan".to_string(), None,
+        ).unwrap_err();
+        assert!(missing.starts_with("Failed to read session note"), "{}", missing);
+    }
+
+    fn sample_treatment_plan() -> TreatmentPlan {
+        TreatmentPlan {
+            client_name: "Jane Smith & Rex".to_string(),
+            diagnosis: strings(&["Separation-related distress", " Noise sensitivity "]),
+            goals: vec![
+                TreatmentGoal {
+                    description: "Settle alone for 30 minutes".to_string(),
+                    target_date: "2024-06-01".to_string(),
+                    interventions: strings(&["Graduated departures", "Food puzzle on leaving"]),
+                    measurable_outcomes: strings(&["No vocalising on camera for 30 minutes"]),
+                },
+                TreatmentGoal {
+                    description: "Tolerate fireworks recordings".to_string(),
+                    target_date: " ".to_string(),
+                    interventions: strings(&["Counter-conditioning at low volume"]),
+                    measurable_outcomes: vec![],
+                },
+            ],
+            start_date: "2024-03-01".to_string(),
+            review_date: "2024-04-01".to_string(),
+        }
+    }
+
+    #[test]
+    fn treatment_plan_goals_are_numbered_with_sub_bullets() {
+        assert_eq!(build_treatment_plan_markdown(&sample_treatment_plan()), "\
+**Client:** Jane Smith & Rex
+**Start date:** 2024-03-01
+**Review date:** 2024-04-01
+
+## Diagnosis
+
+- Separation-related distress
+- Noise sensitivity
+
+## Goals
+
+1. **Settle alone for 30 minutes** (target: 2024-06-01)
+    - *Intervention:* Graduated departures
+    - *Intervention:* Food puzzle on leaving
+    - *Measurable outcome:* No vocalising on camera for 30 minutes
+2. **Tolerate fireworks recordings**
+    - *Intervention:* Counter-conditioning at low volume
+");
+
+        let plan = TreatmentPlan { diagnosis: vec![], ..sample_treatment_plan() };
+        assert!(build_treatment_plan_markdown(&plan).contains("## Diagnosis\n\n_None recorded._\n"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn generate_treatment_plan_docx_pipes_plan_to_pandoc() {
+        let dir = scratch_dir();
+        let pandoc = mock_pandoc_docx(dir.path());
+        let output = dir.path().join("Treatment_Plan.docx");
+
+        let written = generate_treatment_plan_docx_with(pandoc.as_os_str(), &sample_treatment_plan(), "", &path_string(&output)).unwrap();
+
+        assert_eq!(PathBuf::from(written), output.canonicalize().unwrap());
+        read_docx_document_xml(&output).expect("output contains word/document.xml");
+        let stdin = fs::read_to_string(dir.path().join("stdin.md")).unwrap();
+        assert!(stdin.starts_with("---\n"), "{}", stdin);
+        assert!(stdin.contains("title: \"Treatment Plan - Jane Smith & Rex\"\n"), "{}", stdin);
+        assert!(stdin.contains("1. **Settle alone for 30 minutes**"), "{}", stdin);
+    }
+
+    #[test]
+    fn generate_treatment_plan_docx_validates_the_plan() {
+        let dir = scratch_dir();
+        let output = path_string(&dir.path().join("plan.docx"));
+        let no_pandoc = dir.path().join("no_pandoc");
+        let generate = |plan: &TreatmentPlan, output: &str| {
+            generate_treatment_plan_docx_with(no_pandoc.as_os_str(), plan, "", output).unwrap_err()
+        };
+
+        let unnamed = TreatmentPlan { client_name: " ".to_string(), ..sample_treatment_plan() };
+        assert_eq!(generate(&unnamed, &output), "Client name is required");
+        let no_goals = TreatmentPlan { goals: vec![], ..sample_treatment_plan() };
+        assert_eq!(generate(&no_goals, &output), "A treatment plan needs at least one goal");
+        let err = generate(&sample_treatment_plan(), &output.replace(".docx", ".pdf"));
+        assert!(err.starts_with("Output path must be a .docx file"), "{}", err);
+    }
+
+    #[test]
+    fn generate_treatment_plan_docx_with_real_pandoc_when_installed() {
+        if which::which("pandoc").is_err() {
+            return;
+        }
+        let dir = scratch_dir();
+        let output = dir.path().join("Treatment_Plan.docx");
+        generate_treatment_plan_docx(sample_treatment_plan(), String::new(), path_string(&output)).unwrap();
+        let xml = read_docx_document_xml(&output).unwrap();
+        assert!(xml.contains("Jane Smith &amp; Rex"), "{}", xml);
+        assert!(xml.contains("Graduated departures"));
+    }
+
+    fn sample_discharge_data() -> DischargeSummaryData {
+        DischargeSummaryData {
+            client_name: "Jane Smith".to_string(),
+            presenting_problems: "Separation distress".to_string(),
+            treatment_summary: "Graduated departures over 8 weeks".to_string(),
+            progress_achieved: "Settles alone for 45 minutes".to_string(),
+            recommendations: "Maintain routine".to_string(),
+        }
+    }
+
+    /// A client folder with three dated session notes (one from the template) and a stray PDF
+    fn client_with_discharge_notes() -> (tempfile::TempDir, PathBuf) {
+        let dir = scratch_dir();
+        let notes_dir = dir.path().join("Session_Notes");
+        fs::create_dir_all(&notes_dir).unwrap();
+        create_session_note_template(path_string(&notes_dir.join("2024-03-01_intake.md")), sample_session_metadata()).unwrap();
+        fs::write(notes_dir.join("2024-03-15_followup.md"), "---\nduration_minutes: 50\n---\n\n## Subjective\n\nOwner reports calmer departures.\n").unwrap();
+        fs::write(notes_dir.join("2024-04-02_review.md"), "# Review\n\nGoals met; discharge planned.\n").unwrap();
+        fs::write(notes_dir.join("2024-04-02_scan.pdf"), "%PDF-1.4").unwrap();
+        (dir, notes_dir)
+    }
+
+    #[test]
+    fn discharge_summary_docx_contains_session_statistics() {
+        let (dir, _) = client_with_discharge_notes();
+        let template = dir.path().join("discharge_template.docx");
+        minimal_docx(&template, &[
+            docx_paragraph("Discharge summary for {{CLIENT_NAME}}"),
+            docx_paragraph("Sessions attended: {{SESSION_COUNT}}"),
+            docx_paragraph("Treatment period: {{DATE_RANGE}} ({{TOTAL_DURATION}})"),
+            docx_paragraph("Notes: {{SESSION_EXCERPTS}}"),
+            docx_paragraph("Progress: {{PROGRESS_ACHIEVED}}"),
+        ].concat());
+        let output = dir.path().join("Discharge_Summary.docx");
+
+        generate_discharge_summary(path_string(dir.path()), sample_discharge_data(), path_string(&template), path_string(&output)).unwrap();
+
+        let xml = read_docx_document_xml(&output).unwrap();
+        assert!(xml.contains("<w:t>Discharge summary for Jane Smith</w:t>"), "{}", xml);
+        assert!(xml.contains("<w:t>Sessions attended: 3</w:t>"), "{}", xml);
+        assert!(xml.contains("<w:t>Treatment period: 2024-03-01 to 2024-04-02 (2 h 05 min)</w:t>"), "{}", xml);
+        assert!(xml.contains("<w:t>Notes: 2024-03-15: Owner reports calmer departures. • 2024-04-02: Goals met; discharge planned.</w:t>"), "{}", xml);
+        assert!(xml.contains("<w:t>Progress: Settles alone for 45 minutes</w:t>"));
+    }
+
+    #[test]
+    fn session_note_duration_and_excerpt_parsing() {
+        assert_eq!(session_note_duration_minutes("---\nduration_minutes: 75\n---\n"), Some(75));
+        assert_eq!(session_note_duration_minutes("- **Duration:** 40 minutes\n"), Some(40));
+        assert_eq!(session_note_duration_minutes("Took about 40 minutes"), None);
+
+        let template_note = build_session_note_template(&sample_session_metadata()).unwrap();
+        assert_eq!(session_note_excerpt(&template_note, 300), "");
+        let long = format!("## Plan\n\n{}\n<!-- imported -->\n", "word ".repeat(100));
+        let excerpt = session_note_excerpt(&long, 12);
+        assert_eq!(excerpt, "word word wo…");
+    }
+
+    #[test]
+    fn discharge_variables_without_dates_or_durations() {
+        let dir = scratch_dir();
+        let note = dir.path().join("misc.md");
+        fs::write(&note, "Phone call only.").unwrap();
+
+        let variables = build_discharge_variables(&sample_discharge_data(), &[path_string(&note)]).unwrap();
+        assert_eq!(variables["SESSION_COUNT"], "1");
+        assert_eq!(variables["DATE_RANGE"], "Not recorded");
+        assert_eq!(variables["TOTAL_DURATION"], "Not recorded");
+        assert_eq!(variables["SESSION_EXCERPTS"], "misc.md: Phone call only.");
+        assert_eq!(variables["CLIENT_NAME"], "Jane Smith");
+    }
+
+    #[test]
+    fn discharge_summary_validates_inputs() {
+        let dir = scratch_dir();
+        let template = path_string(&dir.path().join("template.docx"));
+        let output = path_string(&dir.path().join("summary.docx"));
+        let folder = path_string(dir.path());
+
+        let unnamed = DischargeSummaryData { client_name: " ".to_string(), ..sample_discharge_data() };
+        assert_eq!(generate_discharge_summary(folder.clone(), unnamed, template.clone(), output.clone()).unwrap_err(), "Client name is required");
+        let err = generate_discharge_summary(folder.clone(), sample_discharge_data(), template.clone(), output.replace(".docx", ".pdf")).unwrap_err();
+        assert!(err.starts_with("Output path must be a .docx file"), "{}", err);
+        let err = generate_discharge_summary(folder, sample_discharge_data(), template, output.clone()).unwrap_err();
+        assert!(err.starts_with("Session_Notes folder not found"), "{}", err);
+        assert!(!Path::new(&output).exists());
+    }
+}
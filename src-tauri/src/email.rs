@@ -0,0 +1,469 @@
+// PBS Admin - Email commands
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{check_rate_limit, validate_read_path};
+use crate::audio::audio_mime_from_extension;
+use crate::documents::convert_html_to_markdown;
+
+// ============================================================================
+// EMAIL SENDING VIA RESEND API
+// ============================================================================
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EmailAttachment {
+    filename: String,
+    content: String, // Base64 encoded content
+}
+
+#[derive(Debug, Serialize)]
+struct ResendEmailRequest {
+    from: String,
+    to: Vec<String>,
+    subject: String,
+    html: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attachments: Option<Vec<ResendAttachment>>,
+}
+
+#[derive(Debug, Serialize)]
+struct ResendAttachment {
+    filename: String,
+    content: String, // Base64 encoded
+}
+
+#[derive(Debug, Deserialize)]
+struct ResendEmailResponse {
+    id: String,
+}
+
+/// Send an email via Resend API with optional attachments
+#[tauri::command]
+pub(crate) async fn send_email(
+    api_key: Option<String>,
+    from: String,
+    to: String,
+    subject: String,
+    html_body: String,
+    attachment_paths: Option<Vec<String>>,
+) -> Result<serde_json::Value, String> {
+    // Rate limit: 1 email per 5 seconds
+    check_rate_limit("email", Duration::from_secs(5))?;
+
+    // Use provided API key or fall back to environment variable
+    let api_key = if let Some(key) = api_key {
+        if key.is_empty() {
+            return Err("Resend API key cannot be empty".to_string());
+        }
+        key
+    } else {
+        std::env::var("RESEND_API_KEY")
+            .or_else(|_| std::env::var("VITE_RESEND_API_KEY"))
+            .map_err(|_| "Resend API key not configured. Please add your API key in Settings > API Keys.".to_string())?
+    };
+
+    info!("Sending email to: {}", to);
+    info!("Subject: {}", subject);
+
+    // Process attachments if provided
+    let attachments: Option<Vec<ResendAttachment>> = if let Some(paths) = attachment_paths {
+        let mut att_list = Vec::new();
+        for path in paths {
+            let file_path = std::path::Path::new(&path);
+            if !file_path.exists() {
+                return Err(format!("Attachment file not found: {}", path));
+            }
+
+            // Read file and encode as base64
+            let file_data = fs::read(&path)
+                .map_err(|e| format!("Failed to read attachment {}: {}", path, e))?;
+
+            let base64_content = base64_encode(&file_data);
+
+            let filename = file_path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("attachment")
+                .to_string();
+
+            info!("Attaching file: {} ({} bytes)", filename, file_data.len());
+
+            att_list.push(ResendAttachment {
+                filename,
+                content: base64_content,
+            });
+        }
+        Some(att_list)
+    } else {
+        None
+    };
+
+    // Build request body
+    let email_request = ResendEmailRequest {
+        from,
+        to: vec![to.clone()],
+        subject: subject.clone(),
+        html: html_body,
+        attachments,
+    };
+
+    // Send request to Resend API
+    let client = reqwest::Client::new();
+    let response = client
+        .post("https://api.resend.com/emails")
+        .header("Authorization", format!("Bearer {}", api_key))
+        .header("Content-Type", "application/json")
+        .json(&email_request)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send email request: {}", e))?;
+
+    // Check response status
+    let status = response.status();
+    let response_text = response.text().await
+        .unwrap_or_else(|_| "Unknown error".to_string());
+
+    if !status.is_success() {
+        return Err(format!("Resend API error ({}): {}", status, response_text));
+    }
+
+    // Parse response
+    let response_json: serde_json::Value = serde_json::from_str(&response_text)
+        .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+    let email_id = response_json["id"]
+        .as_str()
+        .unwrap_or("unknown")
+        .to_string();
+
+    info!("Email sent successfully! ID: {}", email_id);
+
+    Ok(serde_json::json!({
+        "success": true,
+        "id": email_id,
+        "to": to,
+        "subject": subject
+    }))
+}
+
+/// Simple base64 encoding function
+pub(crate) fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut result = String::new();
+    let mut i = 0;
+
+    while i < data.len() {
+        let b0 = data[i] as usize;
+        let b1 = if i + 1 < data.len() { data[i + 1] as usize } else { 0 };
+        let b2 = if i + 2 < data.len() { data[i + 2] as usize } else { 0 };
+
+        result.push(ALPHABET[b0 >> 2] as char);
+        result.push(ALPHABET[((b0 & 0x03) << 4) | (b1 >> 4)] as char);
+
+        if i + 1 < data.len() {
+            result.push(ALPHABET[((b1 & 0x0f) << 2) | (b2 >> 6)] as char);
+        } else {
+            result.push('=');
+        }
+
+        if i + 2 < data.len() {
+            result.push(ALPHABET[b2 & 0x3f] as char);
+        } else {
+            result.push('=');
+        }
+
+        i += 3;
+    }
+
+    result
+}
+/// Simple base64 decoding function (inverse of base64_encode)
+pub(crate) fn base64_decode(encoded: &str) -> Result<Vec<u8>, String> {
+    fn sextet(c: u8) -> Result<u32, String> {
+        match c {
+            b'A'..=b'Z' => Ok((c - b'A') as u32),
+            b'a'..=b'z' => Ok((c - b'a' + 26) as u32),
+            b'0'..=b'9' => Ok((c - b'0' + 52) as u32),
+            b'+' => Ok(62),
+            b'/' => Ok(63),
+            _ => Err(format!("Invalid base64 character: {}", c as char)),
+        }
+    }
+
+    let bytes: Vec<u8> = encoded.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if !bytes.len().is_multiple_of(4) {
+        return Err("Invalid base64 length".to_string());
+    }
+
+    let mut result = Vec::with_capacity(bytes.len() / 4 * 3);
+    for chunk in bytes.chunks(4) {
+        let padding = chunk.iter().rev().take_while(|&&b| b == b'=').count();
+        if padding > 2 {
+            return Err("Invalid base64 padding".to_string());
+        }
+
+        let mut n: u32 = 0;
+        for &c in &chunk[..4 - padding] {
+            n = (n << 6) | sextet(c)?;
+        }
+        n <<= 6 * padding as u32;
+
+        result.push((n >> 16) as u8);
+        if padding < 2 {
+            result.push((n >> 8) as u8);
+        }
+        if padding < 1 {
+            result.push(n as u8);
+        }
+    }
+
+    Ok(result)
+}
+
+
+// ============================================================================
+// EMAIL SENDING VIA SMTP
+// ============================================================================
+
+const SMTP_TIMEOUT_SECS: u64 = 30;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SmtpConfig {
+    host: String,
+    port: u16,
+    /// Also used as the sender address
+    username: String,
+    password: String,
+    /// Implicit TLS (usually port 465); false uses STARTTLS (usually port 587)
+    use_tls: bool,
+}
+
+fn attachment_content_type(file_name: &str) -> lettre::message::header::ContentType {
+    let mime = match Path::new(file_name).extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+        Some("pdf") => "application/pdf",
+        Some("docx") => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        Some("xlsx") => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        Some("csv") => "text/csv",
+        Some("txt") | Some("md") => "text/plain",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        _ => audio_mime_from_extension(file_name).unwrap_or("application/octet-stream"),
+    };
+    lettre::message::header::ContentType::parse(mime)
+        .unwrap_or(lettre::message::header::ContentType::TEXT_PLAIN)
+}
+
+/// Read attachment files as (file name, contents); every path must be inside PBS_Admin's folders
+fn read_email_attachments(paths: &[String]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut attachment_data = Vec::with_capacity(paths.len());
+    for path in paths {
+        // Validate attachment is within allowed directories
+        let validated_path = validate_read_path(path)?;
+        let data = fs::read(&validated_path)
+            .map_err(|e| format!("Failed to read attachment {}: {}", path, e))?;
+        let file_name = validated_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "attachment".to_string());
+        info!("Attaching file: {} ({} bytes)", file_name, data.len());
+        attachment_data.push((file_name, data));
+    }
+    Ok(attachment_data)
+}
+
+/// Multipart message: HTML body with a plain-text alternative, plus base64-encoded attachments
+fn build_smtp_message(
+    from: &str,
+    to: &[String],
+    subject: &str,
+    html_body: &str,
+    attachments: &[(String, Vec<u8>)],
+) -> Result<lettre::Message, String> {
+    use lettre::message::header::ContentTransferEncoding;
+    use lettre::message::{Attachment, Body, MultiPart};
+
+    if to.is_empty() {
+        return Err("At least one recipient is required".to_string());
+    }
+
+    let mut builder = lettre::Message::builder()
+        .from(from.parse().map_err(|e| format!("Invalid sender address {}: {}", from, e))?)
+        .subject(subject);
+    for recipient in to {
+        builder = builder.to(recipient.parse().map_err(|e| format!("Invalid recipient address {}: {}", recipient, e))?);
+    }
+
+    let plain_text = convert_html_to_markdown(html_body.to_string()).unwrap_or_else(|_| html_body.to_string());
+    let mut body = MultiPart::mixed().multipart(MultiPart::alternative_plain_html(plain_text, html_body.to_string()));
+    for (file_name, data) in attachments {
+        // lettre would send ASCII-only files as 7bit; always use base64 so files arrive byte-for-byte
+        let encoded = Body::new_with_encoding(data.clone(), ContentTransferEncoding::Base64)
+            .map_err(|_| format!("Failed to encode attachment {}", file_name))?;
+        body = body.singlepart(Attachment::new(file_name.clone()).body(encoded, attachment_content_type(file_name)));
+    }
+
+    builder.multipart(body)
+        .map_err(|e| format!("Failed to build email: {}", e))
+}
+
+/// Send through any lettre transport (the SMTP relay in the app, a stub transport in tests)
+async fn deliver_smtp_message<T>(transport: &T, message: lettre::Message) -> Result<(), String>
+where
+    T: lettre::AsyncTransport + Sync,
+    T::Error: std::fmt::Display,
+{
+    transport.send(message).await
+        .map(|_| ())
+        .map_err(|e| format!("Failed to send email: {}", e))
+}
+
+/// Send an email with attachments through the clinic's own SMTP server
+#[tauri::command]
+pub(crate) async fn send_email_smtp(
+    config: SmtpConfig,
+    to: Vec<String>,
+    subject: String,
+    body: String,
+    attachments: Vec<String>,
+) -> Result<(), String> {
+    use lettre::transport::smtp::authentication::Credentials;
+    use lettre::{AsyncSmtpTransport, Tokio1Executor};
+
+    // Rate limit: 1 email per 5 seconds
+    check_rate_limit("email", Duration::from_secs(5))?;
+
+    let attachment_data = read_email_attachments(&attachments)?;
+    let message = build_smtp_message(&config.username, &to, &subject, &body, &attachment_data)?;
+
+    let builder = if config.use_tls {
+        AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)
+    } else {
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+    }
+    .map_err(|e| format!("Failed to configure SMTP connection: {}", e))?;
+    let transport = builder
+        .port(config.port)
+        .credentials(Credentials::new(config.username.clone(), config.password.clone()))
+        .timeout(Some(Duration::from_secs(SMTP_TIMEOUT_SECS)))
+        .build();
+
+    info!("Sending email via {}:{} to {} recipients", config.host, config.port, to.len());
+    deliver_smtp_message(&transport, message).await?;
+    info!("Email sent: {}", subject);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::{export_database_json_value, json_to_sql_value};
+    use crate::test_support::{path_string, sample_database, scratch_dir, strings};
+
+    #[test]
+    fn database_export_serializes_every_table() {
+        let dir = scratch_dir();
+        let conn = sample_database(&dir.path().join("pbs_admin.db"));
+
+        let export = export_database_json_value(&conn).unwrap();
+
+        assert_eq!(export, serde_json::json!({
+            "Client": [
+                {"id": 1, "name": "Jane Doe", "balance": 195.5, "notes": null},
+                {"id": 2, "name": "O'Brien", "balance": 0.0, "notes": "Prefers email"},
+            ],
+            "Pet": [
+                {"id": 10, "clientId": 1, "name": "Max", "photo": {"base64": base64_encode(&[0x00, 0xFF, 0x10])}},
+                {"id": 11, "clientId": 2, "name": "Luna", "photo": null},
+            ],
+        }));
+    }
+
+    #[test]
+    fn exported_json_values_convert_back_to_sql() {
+        use rusqlite::types::Value;
+
+        assert_eq!(json_to_sql_value(&serde_json::json!(null)).unwrap(), Value::Null);
+        assert_eq!(json_to_sql_value(&serde_json::json!(true)).unwrap(), Value::Integer(1));
+        assert_eq!(json_to_sql_value(&serde_json::json!(42)).unwrap(), Value::Integer(42));
+        assert_eq!(json_to_sql_value(&serde_json::json!(1.25)).unwrap(), Value::Real(1.25));
+        assert_eq!(json_to_sql_value(&serde_json::json!("x")).unwrap(), Value::Text("x".into()));
+        assert_eq!(
+            json_to_sql_value(&serde_json::json!({"base64": base64_encode(b"blob")})).unwrap(),
+            Value::Blob(b"blob".to_vec())
+        );
+        assert!(json_to_sql_value(&serde_json::json!([1, 2])).is_err());
+    }
+
+    #[tokio::test]
+    async fn smtp_message_is_delivered_as_multipart_mime() {
+        use lettre::transport::stub::AsyncStubTransport;
+
+        let dir = scratch_dir();
+        let invoice = dir.path().join("invoice.pdf");
+        fs::write(&invoice, b"%PDF-1.4 invoice").unwrap();
+        let attachments = read_email_attachments(&[path_string(&invoice)]).unwrap();
+        let message = build_smtp_message(
+            "clinic@example.com",
+            &strings(&["jane@example.com", "Vet Office <vet@example.com>"]),
+            "Session notes for Max",
+            "<p>Hi Jane,</p><p>Notes <b>attached</b>.</p>",
+            &attachments,
+        ).unwrap();
+
+        let transport = AsyncStubTransport::new_ok();
+        deliver_smtp_message(&transport, message).await.unwrap();
+
+        let sent = transport.messages().await;
+        assert_eq!(sent.len(), 1);
+        let (envelope, raw) = &sent[0];
+        assert_eq!(envelope.from().unwrap().to_string(), "clinic@example.com");
+        let recipients: Vec<String> = envelope.to().iter().map(|a| a.to_string()).collect();
+        assert_eq!(recipients, strings(&["jane@example.com", "vet@example.com"]));
+
+        assert!(raw.contains("Subject: Session notes for Max"), "{}", raw);
+        assert!(raw.contains("Content-Type: multipart/mixed"));
+        assert!(raw.contains("Content-Type: multipart/alternative"));
+        assert!(raw.contains("Content-Type: text/plain; charset=utf-8"));
+        assert!(raw.contains("Content-Type: text/html; charset=utf-8"));
+        assert!(raw.contains("Notes **attached**."), "{}", raw);
+        assert!(raw.contains("Content-Disposition: attachment; filename=\"invoice.pdf\""));
+        assert!(raw.contains("Content-Type: application/pdf"));
+        assert!(raw.contains("Content-Transfer-Encoding: base64\r\n\r\nJVBERi0xLjQgaW52b2ljZQ=="), "{}", raw);
+    }
+
+    #[tokio::test]
+    async fn smtp_transport_errors_are_reported() {
+        use lettre::transport::stub::AsyncStubTransport;
+
+        let message = build_smtp_message("clinic@example.com", &strings(&["jane@example.com"]), "Hi", "<p>Hi</p>", &[]).unwrap();
+        let err = deliver_smtp_message(&AsyncStubTransport::new_error(), message).await.unwrap_err();
+        assert!(err.starts_with("Failed to send email: "), "{}", err);
+    }
+
+    #[test]
+    fn smtp_message_validates_addresses() {
+        assert_eq!(build_smtp_message("clinic@example.com", &[], "Hi", "", &[]).unwrap_err(), "At least one recipient is required");
+        assert!(build_smtp_message("not an address", &strings(&["jane@example.com"]), "Hi", "", &[])
+            .unwrap_err().starts_with("Invalid sender address not an address"));
+        assert!(build_smtp_message("clinic@example.com", &strings(&["jane@"]), "Hi", "", &[])
+            .unwrap_err().starts_with("Invalid recipient address jane@"));
+    }
+
+    #[test]
+    fn email_attachments_must_be_inside_pbs_admin() {
+        let outside = tempfile::tempdir().unwrap();
+        let file = outside.path().join("passwd");
+        fs::write(&file, "secret").unwrap();
+        assert!(read_email_attachments(&[path_string(&file)]).unwrap_err().starts_with("Access denied"));
+
+        let mime = |m: &str| lettre::message::header::ContentType::parse(m).unwrap();
+        assert_eq!(attachment_content_type("notes.DOCX"), mime("application/vnd.openxmlformats-officedocument.wordprocessingml.document"));
+        assert_eq!(attachment_content_type("session.m4a"), mime("audio/mp4"));
+        assert_eq!(attachment_content_type("archive.7z"), mime("application/octet-stream"));
+    }
+}
@@ -0,0 +1,4908 @@
+// PBS Admin - File and folder commands
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::collections::HashMap;
+use serde::Serialize;
+use tauri::Emitter;
+use sha2::{Sha256, Digest};
+use tracing::{info, warn};
+
+use crate::{
+    FileLockRegistry, assert_safe_path, atomic_temp_path, data_dir_display, is_within_pbs_root,
+    pbs_admin_roots, resolve_canonical_path, validate_read_path, validate_write_path,
+    write_file_atomically,
+};
+use crate::backup::create_parent_dirs_within_pbs;
+use crate::settings::{record_file_access, resolve_pbs_admin_data_dir};
+use crate::system::audited;
+
+// ============================================================================
+// FILE AND FOLDER OPERATIONS
+// ============================================================================
+
+/// Resolve `path` and check it stays inside `allowed_root` (for debugging path issues).
+/// Returns the canonical path.
+#[tauri::command]
+pub(crate) fn validate_path_safety(path: String, allowed_root: String) -> Result<String, String> {
+    if allowed_root.trim().is_empty() {
+        return Err("Allowed root is required".to_string());
+    }
+    let path_obj = Path::new(&path);
+    assert_safe_path(path_obj, Path::new(&allowed_root))?;
+    Ok(resolve_canonical_path(path_obj)?.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub(crate) fn create_folder(path: String) -> Result<String, String> {
+    // Validate path is within allowed directories
+    // For folder creation, we need to check the parent path
+    let folder_path = Path::new(&path);
+
+    // Check if folder already exists
+    if folder_path.exists() {
+        return Err(format!("Folder already exists: {}", path));
+    }
+
+    // Validate parent directory is within PBS_Admin
+    if let Some(parent) = folder_path.parent() {
+        let canonical_parent = parent.canonicalize()
+            .map_err(|_| format!("Parent directory does not exist: {}", parent.display()))?;
+
+        if !is_within_pbs_root(&canonical_parent) {
+            return Err(format!(
+                "Access denied: cannot create folder outside {}: {}",
+                data_dir_display(),
+                path
+            ));
+        }
+    }
+
+    // Create the folder
+    match fs::create_dir_all(folder_path) {
+        Ok(_) => Ok(path.clone()),
+        Err(e) => Err(format!("Failed to create folder: {}", e)),
+    }
+}
+
+/// Standard subfolders created for every new client
+const CLIENT_SUBFOLDERS: &[&str] = &[
+    "Session_Notes",
+    "Assessments",
+    "Consent_Forms",
+    "Correspondence",
+    "Billing",
+    "Audio_Recordings",
+];
+
+/// Create `<base_path>/<client_id>_<client_name>/` with the standard client subfolders.
+/// Folders that already exist are left alone. Returns the client folder path.
+#[tauri::command]
+pub(crate) fn create_client_folder_structure(base_path: String, client_id: String, client_name: String) -> Result<String, String> {
+    // Validate base folder exists and is within allowed directories
+    let validated_base = validate_read_path(&base_path)?;
+    if !validated_base.is_dir() {
+        return Err(format!("Path is not a directory: {}", base_path));
+    }
+
+    let folder_name = format!(
+        "{}_{}",
+        sanitize_filename_str(&client_id, "_"),
+        sanitize_filename_str(&client_name, "_")
+    );
+    if folder_name == "_" || folder_name.contains("..") {
+        return Err(format!("Invalid client folder name: {}", folder_name));
+    }
+
+    let client_root = validated_base.join(folder_name);
+    for subfolder in CLIENT_SUBFOLDERS {
+        fs::create_dir_all(client_root.join(subfolder))
+            .map_err(|e| format!("Failed to create folder {}: {}", subfolder, e))?;
+    }
+
+    info!("Client folder structure ready: {}", client_root.display());
+
+    Ok(client_root.to_string_lossy().to_string())
+}
+
+/// Highest numeric suffix tried by generate_unique_filename
+const MAX_UNIQUE_FILENAME_SUFFIX: u32 = 9999;
+
+/// Find the first free name among `<base>.<ext>`, `<base>_1.<ext>` ... `<base>_9999.<ext>`.
+/// Nothing is created; a dangling symlink counts as taken.
+fn find_unique_filename(dir: &Path, base_name: &str, extension: &str) -> Result<PathBuf, String> {
+    let extension = extension.trim_start_matches('.');
+    let file_name = |suffix: u32| {
+        let stem = if suffix == 0 { base_name.to_string() } else { format!("{}_{}", base_name, suffix) };
+        if extension.is_empty() { stem } else { format!("{}.{}", stem, extension) }
+    };
+
+    for suffix in 0..=MAX_UNIQUE_FILENAME_SUFFIX {
+        let candidate = dir.join(file_name(suffix));
+        match fs::symlink_metadata(&candidate) {
+            Ok(_) => continue,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(candidate),
+            Err(e) => return Err(format!("Failed to check {}: {}", candidate.display(), e)),
+        }
+    }
+
+    Err(format!(
+        "No free file name for {} in {} (tried up to _{})",
+        file_name(0),
+        dir.display(),
+        MAX_UNIQUE_FILENAME_SUFFIX
+    ))
+}
+
+/// Return a path in `dir` that doesn't exist yet, adding `_1`, `_2`, ... to the base name as needed.
+/// The file is not created, so the path can be handed to move_file or copy_file.
+#[tauri::command]
+pub(crate) fn generate_unique_filename(dir: String, base_name: String, extension: String) -> Result<String, String> {
+    // Validate folder exists and is within allowed directories
+    let validated_dir = validate_read_path(&dir)?;
+    if !validated_dir.is_dir() {
+        return Err(format!("Path is not a directory: {}", dir));
+    }
+
+    // Names must not smuggle in path separators or reserved characters
+    let extension = extension.trim_start_matches('.');
+    if base_name.is_empty() || sanitize_filename_str(&base_name, "_") != base_name {
+        return Err(format!("Invalid base name: {}", base_name));
+    }
+    if sanitize_filename_str(extension, "_") != extension {
+        return Err(format!("Invalid extension: {}", extension));
+    }
+
+    let path = find_unique_filename(&validated_dir, &base_name, extension)?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+pub(crate) fn client_records_path_in(data_dir: &Path) -> Result<PathBuf, String> {
+    let client_records_path = data_dir.join("Client_Records");
+
+    // Ensure the base directory exists
+    if !client_records_path.exists() {
+        fs::create_dir_all(&client_records_path)
+            .map_err(|e| format!("Failed to create base directory: {}", e))?;
+    }
+
+    Ok(client_records_path)
+}
+
+#[tauri::command]
+pub(crate) fn get_default_client_records_path() -> Result<String, String> {
+    let client_records_path = client_records_path_in(&resolve_pbs_admin_data_dir()?)?;
+    Ok(client_records_path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub(crate) fn read_text_file(file_path: String) -> Result<String, String> {
+    // Validate path is within allowed directories
+    let validated_path = validate_read_path(&file_path)?;
+
+    // Read file content
+    let content = fs::read_to_string(&validated_path)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+
+    // Recent files are a convenience; never fail the read over them
+    if let Err(e) = record_file_access(validated_path.to_string_lossy().to_string()) {
+        warn!("Failed to record file access: {}", e);
+    }
+    Ok(content)
+}
+
+#[tauri::command]
+pub(crate) fn write_text_file(locks: tauri::State<'_, FileLockRegistry>, file_path: String, content: String) -> Result<String, String> {
+    let result = write_text_file_locked(&locks, file_path.clone(), content);
+    audited("write_text_file", &file_path, result)
+}
+
+fn write_text_file_locked(locks: &FileLockRegistry, file_path: String, content: String) -> Result<String, String> {
+    // Validate path is within allowed directories
+    let validated_path = validate_write_path(&file_path)?;
+    let _lock = locks.try_lock(&validated_path)?;
+
+    // Ensure parent directory exists
+    if let Some(parent) = validated_path.parent() {
+        if !parent.exists() {
+            return Err(format!("Parent directory does not exist: {}", parent.display()));
+        }
+    }
+
+    // Write content to file (temp file + rename so a crash can't truncate it)
+    write_file_atomically(&validated_path, content.as_bytes())?;
+    Ok(file_path.clone())
+}
+
+#[derive(Serialize)]
+pub(crate) struct FileChunk {
+    content: String,
+    next_offset: u64,
+    is_eof: bool,
+}
+
+/// Length of a trailing multi-byte UTF-8 sequence that was cut off at the end of `bytes` (0 if none)
+fn incomplete_utf8_suffix_len(bytes: &[u8]) -> usize {
+    // A UTF-8 sequence is at most 4 bytes, so only the last 3 can be an unfinished one
+    for back in 1..=bytes.len().min(3) {
+        let byte = bytes[bytes.len() - back];
+        if byte & 0xC0 == 0x80 {
+            continue; // continuation byte, keep looking for the lead byte
+        }
+        let expected = match byte {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 1,
+        };
+        return if expected > back { back } else { 0 };
+    }
+    0
+}
+
+/// Read up to `chunk_size` bytes of a text file starting at `offset_bytes`.
+/// A character split by the chunk end is left for the next chunk, so
+/// concatenating every chunk's content reproduces the file.
+#[tauri::command]
+pub(crate) fn read_large_file_chunked(file_path: String, offset_bytes: u64, chunk_size: u64) -> Result<FileChunk, String> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    if chunk_size == 0 {
+        return Err("Chunk size must be greater than zero".to_string());
+    }
+
+    // Validate path is within allowed directories
+    let validated_path = validate_read_path(&file_path)?;
+
+    let mut file = fs::File::open(&validated_path)
+        .map_err(|e| format!("Failed to open file: {}", e))?;
+    let file_len = file.metadata()
+        .map_err(|e| format!("Failed to read file metadata: {}", e))?
+        .len();
+
+    file.seek(SeekFrom::Start(offset_bytes))
+        .map_err(|e| format!("Failed to seek in file: {}", e))?;
+    let mut buffer = Vec::new();
+    file.take(chunk_size).read_to_end(&mut buffer)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+
+    let end_of_chunk = offset_bytes.saturating_add(buffer.len() as u64);
+    if end_of_chunk < file_len {
+        let cut = incomplete_utf8_suffix_len(&buffer);
+        // Only back off if something is left; a chunk smaller than one character is decoded lossily
+        if cut < buffer.len() {
+            buffer.truncate(buffer.len() - cut);
+        }
+    }
+
+    let next_offset = offset_bytes.saturating_add(buffer.len() as u64);
+    Ok(FileChunk {
+        content: String::from_utf8_lossy(&buffer).to_string(),
+        next_offset,
+        is_eof: next_offset >= file_len,
+    })
+}
+
+/// Async variant of read_text_file - uses tokio::fs so the worker thread isn't blocked
+#[tauri::command]
+pub(crate) async fn read_text_file_async(file_path: String) -> Result<String, String> {
+    // Validate path is within allowed directories
+    let validated_path = validate_read_path(&file_path)?;
+
+    // Read file content
+    let content = tokio::fs::read_to_string(&validated_path).await
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+
+    // Recent files are a convenience; never fail the read over them
+    if let Err(e) = record_file_access(validated_path.to_string_lossy().to_string()) {
+        warn!("Failed to record file access: {}", e);
+    }
+    Ok(content)
+}
+
+/// Async variant of write_text_file - the atomic write runs on the blocking pool
+/// so the worker thread isn't blocked
+#[tauri::command]
+pub(crate) async fn write_text_file_async(
+    locks: tauri::State<'_, FileLockRegistry>,
+    file_path: String,
+    content: String,
+) -> Result<String, String> {
+    let result = write_text_file_async_locked(&locks, file_path.clone(), content).await;
+    audited("write_text_file_async", &file_path, result)
+}
+
+async fn write_text_file_async_locked(locks: &FileLockRegistry, file_path: String, content: String) -> Result<String, String> {
+    // Validate path is within allowed directories
+    let validated_path = validate_write_path(&file_path)?;
+    let _lock = locks.try_lock(&validated_path)?;
+
+    // Ensure parent directory exists
+    if let Some(parent) = validated_path.parent() {
+        let parent_is_dir = tokio::fs::metadata(parent).await
+            .map(|m| m.is_dir())
+            .unwrap_or(false);
+        if !parent_is_dir {
+            return Err(format!("Parent directory does not exist: {}", parent.display()));
+        }
+    }
+
+    // Write content to file (temp file + rename so a crash can't truncate it)
+    tokio::task::spawn_blocking(move || write_file_atomically(&validated_path, content.as_bytes()))
+        .await
+        .map_err(|e| format!("Write task failed: {}", e))??;
+    Ok(file_path)
+}
+
+#[tauri::command]
+pub(crate) fn write_binary_file(locks: tauri::State<'_, FileLockRegistry>, file_path: String, data: Vec<u8>) -> Result<String, String> {
+    let result = write_binary_file_locked(&locks, file_path.clone(), data);
+    audited("write_binary_file", &file_path, result)
+}
+
+fn write_binary_file_locked(locks: &FileLockRegistry, file_path: String, data: Vec<u8>) -> Result<String, String> {
+    // Validate path is within allowed directories
+    let validated_path = validate_write_path(&file_path)?;
+    let _lock = locks.try_lock(&validated_path)?;
+
+    // Ensure parent directory exists
+    if let Some(parent) = validated_path.parent() {
+        if !parent.exists() {
+            return Err(format!("Parent directory does not exist: {}", parent.display()));
+        }
+    }
+
+    // Write binary data to file (temp file + rename so a crash can't truncate it)
+    write_file_atomically(&validated_path, &data)?;
+    Ok(file_path.clone())
+}
+
+/// Validate a JSON value against a JSON Schema (given as a string), collecting every violation
+fn validate_json_against_schema(value: &serde_json::Value, schema: &str) -> Result<(), String> {
+    let schema: serde_json::Value = serde_json::from_str(schema)
+        .map_err(|e| format!("Invalid JSON schema: {}", e))?;
+    let validator = jsonschema::validator_for(&schema)
+        .map_err(|e| format!("Invalid JSON schema: {}", e))?;
+
+    let errors: Vec<String> = validator.iter_errors(value)
+        .map(|e| {
+            let location = e.instance_path.to_string();
+            if location.is_empty() { e.to_string() } else { format!("{}: {}", location, e) }
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("JSON does not match schema: {}", errors.join("; ")))
+    }
+}
+
+/// Read and parse a JSON file, optionally validating it against a JSON Schema
+#[tauri::command]
+pub(crate) fn parse_json_file(path: String, schema: Option<String>) -> Result<serde_json::Value, String> {
+    // Validate path is within allowed directories
+    let validated_path = validate_read_path(&path)?;
+
+    let content = fs::read_to_string(&validated_path)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    let value: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+    if let Some(schema) = schema.as_deref() {
+        validate_json_against_schema(&value, schema)?;
+    }
+    Ok(value)
+}
+
+/// Serialize a JSON value to a file (temp file + rename)
+#[tauri::command]
+pub(crate) fn write_json_file(
+    locks: tauri::State<'_, FileLockRegistry>,
+    path: String,
+    value: serde_json::Value,
+    pretty: bool,
+) -> Result<String, String> {
+    let result = write_json_file_locked(&locks, path.clone(), &value, pretty);
+    audited("write_json_file", &path, result)
+}
+
+fn write_json_file_locked(locks: &FileLockRegistry, path: String, value: &serde_json::Value, pretty: bool) -> Result<String, String> {
+    // Validate path is within allowed directories
+    let validated_path = validate_write_path(&path)?;
+    let _lock = locks.try_lock(&validated_path)?;
+
+    // Ensure parent directory exists
+    if let Some(parent) = validated_path.parent() {
+        if !parent.exists() {
+            return Err(format!("Parent directory does not exist: {}", parent.display()));
+        }
+    }
+
+    let json = if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    }
+    .map_err(|e| format!("Failed to serialize JSON: {}", e))?;
+
+    write_file_atomically(&validated_path, json.as_bytes())?;
+    Ok(path)
+}
+
+/// Delete a single file within the PBS_Admin folder structure
+#[tauri::command]
+pub(crate) fn delete_file(path: String) -> Result<(), String> {
+    audited("delete_file", &path, delete_file_unaudited(&path))
+}
+
+fn delete_file_unaudited(path: &str) -> Result<(), String> {
+    // Validate path exists and is within allowed directories
+    let validated_path = validate_read_path(path)?;
+
+    if validated_path.is_dir() {
+        return Err(format!("Path is a directory, use delete_folder instead: {}", path));
+    }
+
+    fs::remove_file(&validated_path)
+        .map_err(|e| format!("Failed to delete file: {}", e))
+}
+
+/// Delete a folder within the PBS_Admin folder structure.
+/// Non-recursive deletes only succeed on empty folders.
+#[tauri::command]
+pub(crate) fn delete_folder(path: String, recursive: bool) -> Result<(), String> {
+    audited("delete_folder", &path, delete_folder_unaudited(&path, recursive))
+}
+
+fn delete_folder_unaudited(path: &str, recursive: bool) -> Result<(), String> {
+    // Validate path exists and is within allowed directories
+    let validated_path = validate_read_path(path)?;
+
+    if !validated_path.is_dir() {
+        return Err(format!("Path is not a directory: {}", path));
+    }
+
+    // Never allow a PBS_Admin root (or a folder containing one) to be removed
+    for root in pbs_admin_roots()? {
+        let canonical_root = root.canonicalize().unwrap_or(root);
+        if canonical_root.starts_with(&validated_path) {
+            return Err("Cannot delete the PBS_Admin root folder".to_string());
+        }
+    }
+
+    let result = if recursive {
+        fs::remove_dir_all(&validated_path)
+    } else {
+        fs::remove_dir(&validated_path)
+    };
+
+    result.map_err(|e| format!("Failed to delete folder: {}", e))
+}
+
+/// Move a file within the PBS_Admin folder structure.
+/// Tries a fast rename first and falls back to copy-then-delete across devices.
+#[tauri::command]
+pub(crate) fn move_file(src: String, dst: String) -> Result<String, String> {
+    audited("move_file", &format!("{} -> {}", src, dst), move_file_unaudited(&src, &dst))
+}
+
+fn move_file_unaudited(src: &str, dst: &str) -> Result<String, String> {
+    // Validate both ends are within allowed directories
+    let validated_src = validate_read_path(src)?;
+    let validated_dst = validate_write_path(dst)?;
+
+    if validated_src.is_dir() {
+        return Err(format!("Source is a directory, only files can be moved: {}", src));
+    }
+    if validated_dst.exists() {
+        return Err(format!("Destination already exists: {}", dst));
+    }
+
+    move_file_internal(&validated_src, &validated_dst)?;
+    Ok(validated_dst.to_string_lossy().to_string())
+}
+
+/// Copy a file within the PBS_Admin folder structure
+#[tauri::command]
+pub(crate) fn copy_file(src: String, dst: String, overwrite: bool) -> Result<String, String> {
+    audited("copy_file", &format!("{} -> {}", src, dst), copy_file_unaudited(&src, &dst, overwrite))
+}
+
+fn copy_file_unaudited(src: &str, dst: &str, overwrite: bool) -> Result<String, String> {
+    // Validate both ends are within allowed directories
+    let validated_src = validate_read_path(src)?;
+    let validated_dst = validate_write_path(dst)?;
+
+    if validated_src.is_dir() {
+        return Err(format!("Source is a directory, use copy_directory instead: {}", src));
+    }
+    if !overwrite && validated_dst.exists() {
+        return Err(format!("Destination already exists: {}", dst));
+    }
+
+    fs::copy(&validated_src, &validated_dst)
+        .map_err(|e| format!("Failed to copy file: {}", e))?;
+    Ok(validated_dst.to_string_lossy().to_string())
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct RenamePreview {
+    old_path: String,
+    new_path: String,
+    /// Another file already has, or would also get, the new name
+    would_collide: bool,
+}
+
+/// New names for the files directly in `dir` whose names match `pattern` (files whose name wouldn't change are left out)
+fn preview_batch_rename(dir: &Path, pattern: &regex::Regex, replacement: &str) -> Result<Vec<RenamePreview>, String> {
+    let mut renames = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| format!("Failed to read directory: {}", e))? {
+        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+        if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+            continue;
+        }
+        let old_name = entry.file_name().to_string_lossy().to_string();
+        if !pattern.is_match(&old_name) {
+            continue;
+        }
+
+        let new_name = pattern.replace(&old_name, replacement).to_string();
+        if new_name == old_name {
+            continue;
+        }
+        // The result must stay a plain file name in the same folder
+        if new_name.is_empty() || sanitize_filename_str(&new_name, "_") != new_name {
+            return Err(format!("Invalid new name for {}: {}", old_name, new_name));
+        }
+        renames.push((entry.path(), dir.join(new_name)));
+    }
+    renames.sort();
+
+    let mut target_counts: HashMap<&Path, usize> = HashMap::new();
+    for (_, new_path) in &renames {
+        *target_counts.entry(new_path.as_path()).or_default() += 1;
+    }
+
+    Ok(renames.iter().map(|(old_path, new_path)| {
+        // A case-only rename on a case-insensitive filesystem "exists" as the same file
+        let taken = new_path.exists()
+            && new_path.canonicalize().ok() != old_path.canonicalize().ok();
+        RenamePreview {
+            old_path: old_path.to_string_lossy().to_string(),
+            new_path: new_path.to_string_lossy().to_string(),
+            would_collide: taken || target_counts[new_path.as_path()] > 1,
+        }
+    }).collect())
+}
+
+/// Rename files in a folder whose names match a regex; `replacement` may use `$1`-style groups
+/// (`${1}` when followed by a letter, digit or underscore).
+/// With `dry_run` nothing is renamed. Nothing is renamed either if any new name would collide.
+#[tauri::command]
+pub(crate) fn batch_rename_files(directory: String, pattern: String, replacement: String, dry_run: bool) -> Result<Vec<RenamePreview>, String> {
+    // Validate directory is within allowed paths
+    let validated_dir = validate_read_path(&directory)?;
+
+    if !validated_dir.is_dir() {
+        return Err(format!("Path is not a directory: {}", directory));
+    }
+
+    let pattern = regex::Regex::new(&pattern)
+        .map_err(|e| format!("Invalid pattern: {}", e))?;
+    let previews = preview_batch_rename(&validated_dir, &pattern, &replacement)?;
+    if dry_run {
+        return Ok(previews);
+    }
+
+    let collisions: Vec<&str> = previews.iter()
+        .filter(|preview| preview.would_collide)
+        .map(|preview| preview.old_path.as_str())
+        .collect();
+    if !collisions.is_empty() {
+        return Err(format!("Rename would overwrite existing names for: {}", collisions.join(", ")));
+    }
+
+    for preview in &previews {
+        let result = fs::rename(&preview.old_path, &preview.new_path)
+            .map_err(|e| format!("Failed to rename {}: {}", preview.old_path, e));
+        audited("batch_rename_files", &format!("{} -> {}", preview.old_path, preview.new_path), result)?;
+    }
+    info!("Renamed {} files in {}", previews.len(), directory);
+
+    Ok(previews)
+}
+
+/// Recursively copy a directory tree (including empty subfolders).
+/// Returns the number of files copied. With `overwrite` false, nothing is copied
+/// if any destination file already exists.
+#[tauri::command]
+pub(crate) fn copy_directory(src: String, dst: String, overwrite: bool) -> Result<u32, String> {
+    audited("copy_directory", &format!("{} -> {}", src, dst), copy_directory_unaudited(&src, &dst, overwrite))
+}
+
+fn copy_directory_unaudited(src: &str, dst: &str, overwrite: bool) -> Result<u32, String> {
+    // Validate both ends are within allowed directories
+    let validated_src = validate_read_path(src)?;
+    let validated_dst = validate_write_path(dst)?;
+
+    if !validated_src.is_dir() {
+        return Err(format!("Source is not a directory: {}", src));
+    }
+    if validated_dst.starts_with(&validated_src) {
+        return Err("Cannot copy a directory into itself".to_string());
+    }
+
+    // Plan the copy first (relative paths) so conflicts are reported before anything is written
+    let mut dirs_to_create: Vec<PathBuf> = Vec::new();
+    let mut files_to_copy: Vec<PathBuf> = Vec::new();
+    let mut stack = vec![PathBuf::new()];
+
+    while let Some(relative_dir) = stack.pop() {
+        let entries = fs::read_dir(validated_src.join(&relative_dir))
+            .map_err(|e| format!("Failed to read directory: {}", e))?;
+
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
+            let file_type = entry.file_type()
+                .map_err(|e| format!("Failed to read entry type: {}", e))?;
+            let relative = relative_dir.join(entry.file_name());
+
+            // Symlinks are skipped rather than followed
+            if file_type.is_dir() {
+                dirs_to_create.push(relative.clone());
+                stack.push(relative);
+            } else if file_type.is_file() {
+                files_to_copy.push(relative);
+            }
+        }
+    }
+
+    if !overwrite {
+        let conflicts: Vec<String> = files_to_copy
+            .iter()
+            .map(|relative| validated_dst.join(relative))
+            .filter(|target| target.exists())
+            .map(|target| target.to_string_lossy().to_string())
+            .collect();
+        if !conflicts.is_empty() {
+            return Err(format!("Destination files already exist: {}", conflicts.join(", ")));
+        }
+    }
+
+    fs::create_dir_all(&validated_dst)
+        .map_err(|e| format!("Failed to create destination directory: {}", e))?;
+    for relative in &dirs_to_create {
+        fs::create_dir_all(validated_dst.join(relative))
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+
+    let mut copied: u32 = 0;
+    for relative in &files_to_copy {
+        fs::copy(validated_src.join(relative), validated_dst.join(relative))
+            .map_err(|e| format!("Failed to copy {}: {}", relative.display(), e))?;
+        copied += 1;
+    }
+
+    info!("Copied {} files from {} to {}", copied, src, dst);
+
+    Ok(copied)
+}
+
+/// Rename a file in place (same directory, new file name)
+#[tauri::command]
+pub(crate) fn rename_file(path: String, new_name: String) -> Result<String, String> {
+    // The new name must be a bare file name - no separators or traversal
+    if new_name.is_empty()
+        || new_name == "."
+        || new_name == ".."
+        || new_name.contains('/')
+        || new_name.contains('\\')
+    {
+        return Err(format!("Invalid file name: {}", new_name));
+    }
+
+    let validated_src = validate_read_path(&path)?;
+    let parent = validated_src.parent()
+        .ok_or_else(|| format!("Invalid path: {}", path))?;
+    let new_path = parent.join(&new_name);
+
+    move_file(
+        validated_src.to_string_lossy().to_string(),
+        new_path.to_string_lossy().to_string(),
+    )
+}
+
+/// Rename, or copy + delete when the rename fails (e.g. source and destination on different devices)
+pub(crate) fn move_file_internal(src: &Path, dst: &Path) -> Result<(), String> {
+    if fs::rename(src, dst).is_ok() {
+        return Ok(());
+    }
+
+    fs::copy(src, dst)
+        .map_err(|e| format!("Failed to copy file: {}", e))?;
+    if let Err(e) = fs::remove_file(src) {
+        // Don't leave two copies behind if the original can't be removed
+        let _ = fs::remove_file(dst);
+        return Err(format!("Failed to remove original file after copy: {}", e));
+    }
+    Ok(())
+}
+
+/// Exponential backoff for download retries: 500 ms, doubling each attempt, capped at 8 s
+fn download_retry_delay(attempt: u32) -> Duration {
+    let millis = 500u64.saturating_mul(1u64 << attempt.min(16));
+    Duration::from_millis(millis.min(8_000))
+}
+
+/// Upper bound on `max_retries` so a bad argument can't stall a download for minutes
+const MAX_DOWNLOAD_RETRIES: u8 = 10;
+
+/// GET a URL, retrying network errors and 429/503 responses with exponential backoff.
+/// Retry-After (in seconds) is honored when the server sends it.
+async fn get_with_retry(client: &reqwest::Client, url: &str, max_retries: u8) -> Result<reqwest::Response, String> {
+    let max_retries = max_retries.min(MAX_DOWNLOAD_RETRIES);
+    let mut attempt: u32 = 0;
+
+    loop {
+        let retry_after = match client.get(url).send().await {
+            Ok(response) => {
+                let status = response.status();
+                if status.is_success() {
+                    return Ok(response);
+                }
+                let retryable = status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                    || status == reqwest::StatusCode::SERVICE_UNAVAILABLE;
+                if !retryable || attempt >= max_retries as u32 {
+                    return Err(format!("HTTP error: {}", status));
+                }
+                response.headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.trim().parse::<u64>().ok())
+                    .map(|secs| Duration::from_secs(secs.min(60)))
+            },
+            Err(e) => {
+                if attempt >= max_retries as u32 {
+                    return Err(format!("Failed to download file: {}", e));
+                }
+                None
+            },
+        };
+
+        let delay = retry_after.unwrap_or_else(|| download_retry_delay(attempt));
+        attempt += 1;
+        warn!(
+            "Download attempt failed, retrying ({}/{}) in {} ms: {}",
+            attempt, max_retries, delay.as_millis(), url
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[tauri::command]
+pub(crate) async fn download_file(
+    url: String,
+    file_path: String,
+    max_retries: Option<u8>,
+    expected_sha256: Option<String>,
+) -> Result<String, String> {
+    // Validate path is within allowed directories
+    let validated_path = validate_write_path(&file_path)?;
+
+    // Ensure parent directory exists
+    if let Some(parent) = validated_path.parent() {
+        if !parent.exists() {
+            return Err(format!("Parent directory does not exist: {}", parent.display()));
+        }
+    }
+
+    // Download file from URL (transient failures are retried)
+    let client = reqwest::Client::new();
+    let response = get_with_retry(&client, &url, max_retries.unwrap_or(3)).await?;
+
+    // Get response body as bytes
+    let bytes = response.bytes().await
+        .map_err(|e| format!("Failed to read response body: {}", e))?;
+
+    // Verify the downloaded bytes against the expected digest before anything touches the disk
+    if let Some(expected) = expected_sha256 {
+        let mut hasher = FileHasher::new("sha256")?;
+        for chunk in bytes.chunks(64 * 1024) {
+            hasher.update(chunk);
+        }
+        let actual = hasher.finalize_hex();
+
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            return Err(format!(
+                "Checksum mismatch for downloaded file. Expected SHA-256: {}, actual: {}. The file was not saved.",
+                expected.trim(), actual
+            ));
+        }
+    }
+
+    // Write to file (temp file + rename, so a failed write never leaves a truncated download)
+    tokio::task::spawn_blocking(move || write_file_atomically(&validated_path, &bytes))
+        .await
+        .map_err(|e| format!("Write task failed: {}", e))??;
+
+    Ok(file_path)
+}
+
+#[derive(Clone, Serialize)]
+struct DownloadProgress {
+    bytes_downloaded: u64,
+    total_bytes: Option<u64>,
+}
+
+/// Download a file while emitting `download-progress` events to the calling window only
+#[tauri::command]
+pub(crate) async fn download_file_with_progress(url: String, file_path: String, window: tauri::Window) -> Result<String, String> {
+    // Window::emit broadcasts to every window; target the caller so concurrent downloads stay separate
+    download_with_progress(&url, &file_path, |progress| {
+        let _ = window.emit_to(window.label(), "download-progress", progress);
+    }).await
+}
+
+/// Stream `url` to `file_path` (temp file, then rename), reporting progress about every 64 KB
+/// and once more at the end
+async fn download_with_progress(
+    url: &str,
+    file_path: &str,
+    mut on_progress: impl FnMut(DownloadProgress),
+) -> Result<String, String> {
+    use tokio::io::AsyncWriteExt;
+
+    const PROGRESS_INTERVAL_BYTES: u64 = 64 * 1024;
+
+    // Validate path is within allowed directories
+    let validated_path = validate_write_path(file_path)?;
+
+    // Ensure parent directory exists
+    if let Some(parent) = validated_path.parent() {
+        if !parent.exists() {
+            return Err(format!("Parent directory does not exist: {}", parent.display()));
+        }
+    }
+
+    let mut response = reqwest::get(url)
+        .await
+        .map_err(|e| format!("Failed to download file: {}", e))?;
+
+    // Check response status
+    if !response.status().is_success() {
+        return Err(format!("HTTP error: {}", response.status()));
+    }
+
+    // Only known when the server sends Content-Length
+    let total_bytes = response.content_length();
+
+    // Stream into a sibling temp file, then rename once complete so no partial file is left behind
+    let temp_path = atomic_temp_path(&validated_path)?;
+    let mut file = tokio::fs::File::create(&temp_path)
+        .await
+        .map_err(|e| format!("Failed to create file: {}", e))?;
+
+    let mut bytes_downloaded: u64 = 0;
+    let mut last_reported: u64 = 0;
+    let stream_result: Result<(), String> = async {
+        while let Some(chunk) = response.chunk()
+            .await
+            .map_err(|e| format!("Failed to read response body: {}", e))?
+        {
+            file.write_all(&chunk)
+                .await
+                .map_err(|e| format!("Failed to write file: {}", e))?;
+            bytes_downloaded += chunk.len() as u64;
+
+            // Report roughly every 64 KB rather than on every network chunk
+            if bytes_downloaded - last_reported >= PROGRESS_INTERVAL_BYTES {
+                last_reported = bytes_downloaded;
+                on_progress(DownloadProgress { bytes_downloaded, total_bytes });
+            }
+        }
+        file.flush()
+            .await
+            .map_err(|e| format!("Failed to write file: {}", e))?;
+        file.sync_all()
+            .await
+            .map_err(|e| format!("Failed to sync file to disk: {}", e))?;
+        Ok(())
+    }.await;
+
+    drop(file);
+
+    if let Err(e) = stream_result {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(e);
+    }
+
+    // Final event so the UI always sees 100%
+    if bytes_downloaded != last_reported || bytes_downloaded == 0 {
+        on_progress(DownloadProgress { bytes_downloaded, total_bytes });
+    }
+
+    if let Err(e) = tokio::fs::rename(&temp_path, &validated_path).await {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(format!("Failed to replace file: {}", e));
+    }
+
+    Ok(file_path.to_string())
+}
+
+/// Download a file, resuming from any partial file already at `file_path`.
+/// Falls back to a full download (with a `download-warning` event) when the server
+/// doesn't advertise range support.
+#[tauri::command]
+pub(crate) async fn download_file_resumable(url: String, file_path: String, app: tauri::AppHandle) -> Result<String, String> {
+    download_resumable(&url, &file_path, |message| {
+        let _ = app.emit("download-warning", message);
+    }).await
+}
+
+/// Start offset from a `Content-Range: bytes <start>-<end>/<total>` header
+fn content_range_start(headers: &reqwest::header::HeaderMap) -> Option<u64> {
+    headers.get(reqwest::header::CONTENT_RANGE)?
+        .to_str().ok()?
+        .trim()
+        .strip_prefix("bytes ")?
+        .split('-')
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Resume `url` into `file_path`, reporting fallbacks through `on_warning`.
+/// A 206 is appended only when its Content-Range starts where the partial file ends;
+/// anything else restarts the download into a `.part` file that replaces `file_path`
+/// once complete, so the existing partial file survives a failed restart.
+async fn download_resumable(
+    url: &str,
+    file_path: &str,
+    mut on_warning: impl FnMut(String),
+) -> Result<String, String> {
+    use tokio::io::AsyncWriteExt;
+
+    // Validate path is within allowed directories
+    let validated_path = validate_write_path(file_path)?;
+
+    // Ensure parent directory exists
+    if let Some(parent) = validated_path.parent() {
+        if !parent.exists() {
+            return Err(format!("Parent directory does not exist: {}", parent.display()));
+        }
+    }
+
+    let client = reqwest::Client::new();
+
+    // Length of any partial download already on disk
+    let mut resume_from = tokio::fs::metadata(&validated_path)
+        .await
+        .map(|m| m.len())
+        .unwrap_or(0);
+    let mut remote_length: Option<u64> = None;
+
+    if resume_from > 0 {
+        // Only send a Range request if the server says it supports one
+        let head = client.head(url)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to download file: {}", e))?;
+        let supports_ranges = head.headers()
+            .get(reqwest::header::ACCEPT_RANGES)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.contains("bytes"))
+            .unwrap_or(false);
+        // Read the header directly - a HEAD response has no body to size
+        remote_length = head.headers()
+            .get(reqwest::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse().ok());
+
+        if !supports_ranges {
+            let message = format!(
+                "Server does not support resuming downloads; restarting {} from the beginning",
+                url
+            );
+            warn!("{}", message);
+            on_warning(message);
+            resume_from = 0;
+        }
+    }
+
+    let mut request = client.get(url);
+    if resume_from > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", resume_from));
+    }
+
+    let mut response = request.send()
+        .await
+        .map_err(|e| format!("Failed to download file: {}", e))?;
+
+    let mut status = response.status();
+    if resume_from > 0 {
+        let restart_reason = if status == reqwest::StatusCode::PARTIAL_CONTENT {
+            match content_range_start(response.headers()) {
+                Some(start) if start == resume_from => None,
+                start => Some(format!(
+                    "Server resumed at byte {} instead of {}",
+                    start.map(|s| s.to_string()).unwrap_or_else(|| "?".to_string()),
+                    resume_from
+                )),
+            }
+        } else if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            if remote_length == Some(resume_from) {
+                // Nothing left to fetch - the partial file is already complete
+                return Ok(file_path.to_string());
+            }
+            Some(format!(
+                "Partial file is {} bytes but the server reports {}",
+                resume_from,
+                remote_length.map(|l| l.to_string()).unwrap_or_else(|| "an unknown length".to_string())
+            ))
+        } else {
+            None
+        };
+
+        if let Some(reason) = restart_reason {
+            let message = format!("{}; restarting {} from the beginning", reason, url);
+            warn!("{}", message);
+            on_warning(message);
+            response = client.get(url)
+                .send()
+                .await
+                .map_err(|e| format!("Failed to download file: {}", e))?;
+            status = response.status();
+        }
+    }
+
+    let (mut file, write_path) = if status == reqwest::StatusCode::PARTIAL_CONTENT {
+        // Server honored the range - append to what we already have
+        let file = tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(&validated_path)
+            .await
+            .map_err(|e| format!("Failed to open file for resume: {}", e))?;
+        (file, None)
+    } else if status.is_success() {
+        // Full response - start fresh in a sibling .part file
+        let file_name = validated_path.file_name()
+            .ok_or_else(|| format!("Invalid path: no file name: {}", validated_path.display()))?;
+        let part_path = validated_path.with_file_name(format!("{}.part", file_name.to_string_lossy()));
+        let file = tokio::fs::File::create(&part_path)
+            .await
+            .map_err(|e| format!("Failed to create file: {}", e))?;
+        (file, Some(part_path))
+    } else {
+        return Err(format!("HTTP error: {}", status));
+    };
+
+    let stream_result: Result<(), String> = async {
+        while let Some(chunk) = response.chunk()
+            .await
+            .map_err(|e| format!("Failed to read response body: {}", e))?
+        {
+            file.write_all(&chunk)
+                .await
+                .map_err(|e| format!("Failed to write file: {}", e))?;
+        }
+        file.flush()
+            .await
+            .map_err(|e| format!("Failed to write file: {}", e))?;
+        file.sync_all()
+            .await
+            .map_err(|e| format!("Failed to sync file to disk: {}", e))?;
+        Ok(())
+    }.await;
+
+    drop(file);
+
+    // An interrupted append stays in place so the next call can resume it;
+    // an interrupted fresh download is discarded and the old file is left alone
+    if let Some(part_path) = write_path {
+        if let Err(e) = stream_result {
+            let _ = tokio::fs::remove_file(&part_path).await;
+            return Err(e);
+        }
+        if let Err(e) = tokio::fs::rename(&part_path, &validated_path).await {
+            let _ = tokio::fs::remove_file(&part_path).await;
+            return Err(format!("Failed to replace file: {}", e));
+        }
+    } else {
+        stream_result?;
+    }
+
+    Ok(file_path.to_string())
+}
+
+/// Download update installer to temp directory with hash verification, then run it
+#[tauri::command]
+pub(crate) async fn download_and_run_update(url: String, filename: String, expected_size: Option<u64>) -> Result<String, String> {
+    // Validate URL is from the official GitHub repository releases
+    // Only allow the specific releases download pattern
+    if !url.starts_with("https://github.com/PBSGlenn/PBS_Admin/releases/download/") {
+        return Err("Updates can only be downloaded from official PBS Admin GitHub releases.".to_string());
+    }
+
+    // Validate filename has no path traversal and is an executable
+    if filename.contains("..") || filename.contains('/') || filename.contains('\\') {
+        return Err("Invalid installer filename.".to_string());
+    }
+    if !filename.to_lowercase().ends_with(".exe") {
+        return Err("Installer must be an .exe file.".to_string());
+    }
+
+    // Get temp directory
+    let temp_dir = std::env::temp_dir().join("PBS_Admin_Updates");
+
+    // Create temp directory if needed
+    if !temp_dir.exists() {
+        fs::create_dir_all(&temp_dir)
+            .map_err(|e| format!("Failed to create temp directory: {}", e))?;
+    }
+
+    let file_path = temp_dir.join(&filename);
+
+    // Download file from URL using async reqwest with redirect policy
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::limited(5))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+    let response = client.get(&url)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to download installer: {}", e))?;
+
+    // Verify we ended up at a GitHub domain after redirects
+    let final_url = response.url().to_string();
+    let allowed_hosts = [
+        "github.com",
+        "objects.githubusercontent.com",
+        "github-releases.githubusercontent.com",
+        "release-assets.githubusercontent.com",
+    ];
+    let final_host = response.url().host_str().unwrap_or("");
+    if !allowed_hosts.iter().any(|h| final_host == *h || final_host.ends_with(&format!(".{}", h))) {
+        return Err(format!(
+            "Download redirected to untrusted host: {}. Aborting for security.",
+            final_host
+        ));
+    }
+
+    // Check response status
+    if !response.status().is_success() {
+        return Err(format!("HTTP error: {}", response.status()));
+    }
+
+    // Get response body as bytes
+    let bytes = response.bytes()
+        .await
+        .map_err(|e| format!("Failed to read response body: {}", e))?;
+
+    // Verify file size if expected size was provided (from GitHub release API)
+    if let Some(expected) = expected_size {
+        if bytes.len() as u64 != expected {
+            return Err(format!(
+                "Downloaded file size ({} bytes) does not match expected size ({} bytes). Download may be corrupted or tampered with.",
+                bytes.len(), expected
+            ));
+        }
+    }
+
+    // Basic sanity check: installer should be a reasonable size (> 1MB, < 500MB)
+    if bytes.len() < 1_000_000 {
+        return Err("Downloaded file is too small to be a valid installer. Aborting.".to_string());
+    }
+    if bytes.len() > 500_000_000 {
+        return Err("Downloaded file is unexpectedly large. Aborting.".to_string());
+    }
+
+    // Verify it looks like a PE executable (MZ header)
+    if bytes.len() < 2 || bytes[0] != 0x4D || bytes[1] != 0x5A {
+        return Err("Downloaded file is not a valid Windows executable. Aborting.".to_string());
+    }
+
+    // Write to file
+    fs::write(&file_path, &bytes)
+        .map_err(|e| format!("Failed to write installer file: {}", e))?;
+
+    // Run the installer
+    Command::new(&file_path)
+        .spawn()
+        .map_err(|e| format!("Failed to launch installer: {}", e))?;
+
+    Ok(file_path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub(crate) fn list_files(
+    directory: String,
+    pattern: Option<String>,
+    recursive: bool,
+    max_depth: Option<u32>,
+    sort_by: Option<String>,
+    ascending: Option<bool>,
+    extension_filter: Option<String>,
+) -> Result<Vec<String>, String> {
+    if let Some(ref sort_by) = sort_by {
+        if !matches!(sort_by.as_str(), "name" | "size" | "modified") {
+            return Err(format!("Unsupported sort field: {}. Use name, size, or modified.", sort_by));
+        }
+    }
+
+    // Validate directory is within allowed paths
+    let validated_dir = validate_read_path(&directory)?;
+
+    if !validated_dir.is_dir() {
+        return Err(format!("Path is not a directory: {}", directory));
+    }
+
+    // Compile the glob pattern up front so an invalid pattern is reported, not silently empty
+    let glob_pattern = match pattern {
+        Some(ref pattern_str) => Some(
+            glob::Pattern::new(pattern_str)
+                .map_err(|e| format!("Invalid file pattern '{}': {}", pattern_str, e))?,
+        ),
+        None => None,
+    };
+
+    // Extension filter matches only the final extension, case-insensitively ("docx" or ".docx")
+    let extension_filter = extension_filter.map(|ext| ext.trim_start_matches('.').to_lowercase());
+
+    let mut files = Vec::new();
+
+    // Walk with a manual stack of (directory, depth). Depth 0 is the requested directory;
+    // max_depth caps how many subdirectory levels are descended into when recursive.
+    let mut stack: Vec<(PathBuf, u32)> = vec![(validated_dir, 0)];
+
+    while let Some((dir, depth)) = stack.pop() {
+        // Read directory entries
+        let entries = fs::read_dir(&dir)
+            .map_err(|e| format!("Failed to read directory: {}", e))?;
+
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
+            let path = entry.path();
+
+            // Descend into real subdirectories only - symlinks are not followed to avoid loops
+            let is_real_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            if is_real_dir {
+                let within_depth = max_depth.map(|max| depth < max).unwrap_or(true);
+                if recursive && within_depth {
+                    stack.push((path, depth + 1));
+                }
+                continue;
+            }
+
+            // Only include files (not directories)
+            if path.is_file() {
+                if let Some(file_name) = path.file_name() {
+                    let file_name_str = file_name.to_string_lossy().to_string();
+
+                    // Apply glob filter if provided (file name only, not the full path)
+                    let matches_pattern = glob_pattern
+                        .as_ref()
+                        .map(|glob_pattern| glob_pattern.matches(&file_name_str))
+                        .unwrap_or(true);
+
+                    let matches_extension = extension_filter
+                        .as_ref()
+                        .map(|wanted| {
+                            path.extension()
+                                .map(|ext| ext.to_string_lossy().to_lowercase() == *wanted)
+                                .unwrap_or(false)
+                        })
+                        .unwrap_or(true);
+
+                    if matches_pattern && matches_extension {
+                        files.push(path.to_string_lossy().to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(sort_by) = sort_by {
+        sort_file_list(&mut files, &sort_by, ascending.unwrap_or(true));
+    }
+
+    Ok(files)
+}
+
+/// Sort a file listing by "name", "size" or "modified".
+/// Metadata is read once per file; files whose metadata can't be read go last.
+fn sort_file_list(files: &mut Vec<String>, sort_by: &str, ascending: bool) {
+    if sort_by == "name" {
+        files.sort_by(|a, b| {
+            let name_a = Path::new(a).file_name().map(|n| n.to_string_lossy().to_lowercase());
+            let name_b = Path::new(b).file_name().map(|n| n.to_string_lossy().to_lowercase());
+            let order = name_a.cmp(&name_b).then_with(|| a.cmp(b));
+            if ascending { order } else { order.reverse() }
+        });
+        return;
+    }
+
+    let mut keyed: Vec<(String, Option<u128>)> = files
+        .drain(..)
+        .map(|path| {
+            let key = fs::metadata(&path).ok().and_then(|metadata| {
+                if sort_by == "size" {
+                    Some(metadata.len() as u128)
+                } else {
+                    metadata.modified().ok()
+                        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                        .map(|d| d.as_nanos())
+                }
+            });
+            if key.is_none() {
+                warn!("Could not read metadata for {} - listing it last", path);
+            }
+            (path, key)
+        })
+        .collect();
+
+    keyed.sort_by(|(path_a, key_a), (path_b, key_b)| match (key_a, key_b) {
+        (Some(a), Some(b)) => {
+            let order = a.cmp(b).then_with(|| path_a.cmp(path_b));
+            if ascending { order } else { order.reverse() }
+        }
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => path_a.cmp(path_b),
+    });
+
+    files.extend(keyed.into_iter().map(|(path, _)| path));
+}
+
+#[derive(Serialize)]
+pub(crate) struct FileMetadata {
+    size_bytes: u64,
+    created_at: u64,
+    modified_at: u64,
+    is_dir: bool,
+    is_symlink: bool,
+}
+
+/// Convert a SystemTime to Unix seconds (0 if it predates the epoch)
+pub(crate) fn system_time_to_unix_secs(time: std::time::SystemTime) -> u64 {
+    time.duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Get size, timestamps, and type of a file without reading its contents
+#[tauri::command]
+pub(crate) fn get_file_metadata(path: String) -> Result<FileMetadata, String> {
+    // Validate path exists and is within allowed directories
+    let validated_path = validate_read_path(&path)?;
+
+    // symlink_metadata doesn't follow links, so it can tell us whether the path itself is one
+    let is_symlink = fs::symlink_metadata(Path::new(&path))
+        .map(|m| m.file_type().is_symlink())
+        .unwrap_or(false);
+
+    let metadata = fs::metadata(&validated_path)
+        .map_err(|e| format!("Failed to read file metadata: {}", e))?;
+
+    let modified = metadata.modified()
+        .map_err(|e| format!("Failed to read modification time: {}", e))?;
+    // Creation time isn't available on every platform/filesystem (e.g. some Linux setups)
+    let created = metadata.created().unwrap_or(modified);
+
+    Ok(FileMetadata {
+        size_bytes: metadata.len(),
+        created_at: system_time_to_unix_secs(created),
+        modified_at: system_time_to_unix_secs(modified),
+        is_dir: metadata.is_dir(),
+        is_symlink,
+    })
+}
+
+#[derive(Serialize)]
+pub(crate) struct ListFileEntry {
+    path: String,
+    size_bytes: u64,
+    modified_at: u64,
+    is_dir: bool,
+    /// Set when the entry's metadata couldn't be read (other fields are then defaults)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// List a directory's entries (files and subfolders) with their metadata in one call
+#[tauri::command]
+pub(crate) fn list_files_with_metadata(directory: String, pattern: Option<String>) -> Result<Vec<ListFileEntry>, String> {
+    // Validate directory is within allowed paths
+    let validated_dir = validate_read_path(&directory)?;
+
+    if !validated_dir.is_dir() {
+        return Err(format!("Path is not a directory: {}", directory));
+    }
+
+    let glob_pattern = match pattern {
+        Some(ref pattern_str) => Some(
+            glob::Pattern::new(pattern_str)
+                .map_err(|e| format!("Invalid file pattern '{}': {}", pattern_str, e))?,
+        ),
+        None => None,
+    };
+
+    let entries = fs::read_dir(&validated_dir)
+        .map_err(|e| format!("Failed to read directory: {}", e))?;
+
+    let mut results = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
+
+        if let Some(ref glob_pattern) = glob_pattern {
+            if !glob_pattern.matches(&entry.file_name().to_string_lossy()) {
+                continue;
+            }
+        }
+
+        let path = entry.path().to_string_lossy().to_string();
+
+        // DirEntry::metadata reuses data from the directory read on most platforms
+        let listed = match entry.metadata() {
+            Ok(metadata) => ListFileEntry {
+                path,
+                size_bytes: metadata.len(),
+                modified_at: metadata.modified().map(system_time_to_unix_secs).unwrap_or(0),
+                is_dir: metadata.is_dir(),
+                error: None,
+            },
+            Err(e) => ListFileEntry {
+                path,
+                size_bytes: 0,
+                modified_at: 0,
+                is_dir: false,
+                error: Some(format!("Failed to read metadata: {}", e)),
+            },
+        };
+        results.push(listed);
+    }
+
+    Ok(results)
+}
+
+#[derive(Serialize)]
+pub(crate) struct DirSizeResult {
+    total_bytes: u64,
+    /// Files or folders that couldn't be read (they're left out of the total)
+    errors: Vec<String>,
+}
+
+/// Totals from walking a directory tree (symlinks are not followed)
+#[derive(Default)]
+struct DirectoryTotals {
+    total_bytes: u64,
+    file_count: u32,
+    /// Subdirectories, not counting the root itself
+    dir_count: u32,
+    errors: Vec<String>,
+}
+
+fn walk_directory_totals(root: PathBuf) -> DirectoryTotals {
+    let mut totals = DirectoryTotals::default();
+    let mut stack = vec![root];
+
+    while let Some(dir) = stack.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                totals.errors.push(format!("{}: {}", dir.display(), e));
+                continue;
+            }
+        };
+
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    totals.errors.push(format!("{}: {}", dir.display(), e));
+                    continue;
+                }
+            };
+
+            let file_type = match entry.file_type() {
+                Ok(file_type) => file_type,
+                Err(e) => {
+                    totals.errors.push(format!("{}: {}", entry.path().display(), e));
+                    continue;
+                }
+            };
+
+            if file_type.is_symlink() {
+                continue;
+            }
+            if file_type.is_dir() {
+                totals.dir_count += 1;
+                stack.push(entry.path());
+                continue;
+            }
+
+            match entry.metadata() {
+                Ok(metadata) => {
+                    totals.total_bytes += metadata.len();
+                    totals.file_count += 1;
+                }
+                Err(e) => totals.errors.push(format!("{}: {}", entry.path().display(), e)),
+            }
+        }
+    }
+
+    totals
+}
+
+/// Sum the sizes of all files under a directory (symlinks are not followed)
+#[tauri::command]
+pub(crate) fn calculate_directory_size(path: String) -> Result<DirSizeResult, String> {
+    // Validate directory is within allowed paths
+    let validated_dir = validate_read_path(&path)?;
+
+    if !validated_dir.is_dir() {
+        return Err(format!("Path is not a directory: {}", path));
+    }
+
+    let totals = walk_directory_totals(validated_dir);
+    Ok(DirSizeResult { total_bytes: totals.total_bytes, errors: totals.errors })
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct DiskUsage {
+    used_bytes: u64,
+    /// Space available to the user on the volume holding `path`
+    free_bytes: u64,
+    file_count: u32,
+    dir_count: u32,
+}
+
+/// Space used by files under a PBS_Admin folder plus the free space left on its drive
+#[tauri::command]
+pub(crate) fn get_disk_usage_for_path(path: String) -> Result<DiskUsage, String> {
+    // Validate directory is within allowed paths
+    let validated_dir = validate_read_path(&path)?;
+
+    if !validated_dir.is_dir() {
+        return Err(format!("Path is not a directory: {}", path));
+    }
+
+    let free_bytes = fs2::available_space(&validated_dir)
+        .map_err(|e| format!("Failed to get free space: {}", e))?;
+    let totals = walk_directory_totals(validated_dir);
+    if !totals.errors.is_empty() {
+        warn!("Disk usage for {} skipped {} unreadable entries", path, totals.errors.len());
+    }
+
+    Ok(DiskUsage {
+        used_bytes: totals.total_bytes,
+        free_bytes,
+        file_count: totals.file_count,
+        dir_count: totals.dir_count,
+    })
+}
+
+/// One node of read_directory_tree's output. Directories get a sorted `children` array
+/// (folders first) unless `max_depth` was reached, in which case `children` is null
+/// and `truncated` is true. Symlinks are skipped rather than followed.
+fn build_directory_tree_node(path: &Path, depth: u32, max_depth: Option<u32>) -> serde_json::Value {
+    use serde_json::{json, Value};
+
+    let name = path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string_lossy().to_string());
+    let is_dir = path.is_dir();
+    let mut node = json!({
+        "name": name,
+        "path": path.to_string_lossy(),
+        "is_dir": is_dir,
+        "is_hidden": name.starts_with('.'),
+    });
+    if !is_dir {
+        return node;
+    }
+
+    if max_depth.is_some_and(|max| depth >= max) {
+        node["children"] = Value::Null;
+        node["truncated"] = Value::Bool(true);
+        return node;
+    }
+
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(e) => {
+            node["children"] = Value::Null;
+            node["error"] = Value::String(format!("Failed to read directory: {}", e));
+            return node;
+        }
+    };
+
+    let mut child_paths: Vec<(bool, String, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().map(|t| !t.is_symlink()).unwrap_or(false))
+        .map(|entry| {
+            let child = entry.path();
+            (!child.is_dir(), entry.file_name().to_string_lossy().to_lowercase(), child)
+        })
+        .collect();
+    child_paths.sort();
+
+    let children: Vec<Value> = child_paths.iter()
+        .map(|(_, _, child)| build_directory_tree_node(child, depth + 1, max_depth))
+        .collect();
+    node["children"] = Value::Array(children);
+    node
+}
+
+/// Nested JSON tree of a folder (e.g. a client record archive): `{ name, path, is_dir, is_hidden, children }`
+#[tauri::command]
+pub(crate) fn read_directory_tree(root: String, max_depth: Option<u32>) -> Result<serde_json::Value, String> {
+    // Validate directory is within allowed paths
+    let validated_root = validate_read_path(&root)?;
+
+    if !validated_root.is_dir() {
+        return Err(format!("Path is not a directory: {}", root));
+    }
+
+    Ok(build_directory_tree_node(&validated_root, 0, max_depth))
+}
+
+#[derive(Serialize)]
+pub(crate) struct SearchMatch {
+    file_path: String,
+    line_number: u32,
+    line_content: String,
+}
+
+/// Search text files (.txt, .md, .json) under a directory for a plain-text or regex query
+#[tauri::command]
+pub(crate) fn search_in_files(
+    directory: String,
+    query: String,
+    use_regex: bool,
+    case_sensitive: bool,
+) -> Result<Vec<SearchMatch>, String> {
+    if query.is_empty() {
+        return Err("Search query cannot be empty".to_string());
+    }
+
+    // Validate directory is within allowed paths
+    let validated_dir = validate_read_path(&directory)?;
+
+    if !validated_dir.is_dir() {
+        return Err(format!("Path is not a directory: {}", directory));
+    }
+
+    // Plain-text queries are escaped so both modes share the same (Unicode case-folding) matcher
+    let pattern = if use_regex { query.clone() } else { regex::escape(&query) };
+    let matcher = regex::RegexBuilder::new(&pattern)
+        .case_insensitive(!case_sensitive)
+        .build()
+        .map_err(|e| format!("Invalid regular expression: {}", e))?;
+
+    const SEARCHABLE_EXTENSIONS: &[&str] = &["txt", "md", "json"];
+
+    let mut matches = Vec::new();
+    let mut stack = vec![validated_dir];
+
+    while let Some(dir) = stack.pop() {
+        let entries = fs::read_dir(&dir)
+            .map_err(|e| format!("Failed to read directory: {}", e))?;
+
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
+            let path = entry.path();
+            let file_type = match entry.file_type() {
+                Ok(file_type) => file_type,
+                Err(_) => continue,
+            };
+
+            if file_type.is_dir() {
+                stack.push(path);
+                continue;
+            }
+            if !file_type.is_file() {
+                continue;
+            }
+
+            let searchable = path.extension()
+                .map(|ext| SEARCHABLE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+                .unwrap_or(false);
+            if !searchable {
+                continue;
+            }
+
+            let content = match fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(e) => {
+                    warn!("Skipping {} during search: {}", path.display(), e);
+                    continue;
+                }
+            };
+
+            for (index, line) in content.lines().enumerate() {
+                if matcher.is_match(line) {
+                    matches.push(SearchMatch {
+                        file_path: path.to_string_lossy().to_string(),
+                        line_number: index as u32 + 1,
+                        line_content: line.to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    info!("Search for '{}' found {} matches in {}", query, matches.len(), directory);
+
+    Ok(matches)
+}
+
+/// Default adult silent reading speed, in words per minute
+const DEFAULT_READING_WPM: u32 = 238;
+
+#[derive(Debug, Serialize, PartialEq)]
+pub(crate) struct WordCount {
+    words: u32,
+    sentences: u32,
+    paragraphs: u32,
+    /// All characters, including spaces and line breaks
+    characters: u32,
+}
+
+fn count_words_in_text(text: &str) -> WordCount {
+    let has_word = |s: &str| s.chars().any(char::is_alphanumeric);
+
+    let words = text.split_whitespace().filter(|token| has_word(token)).count();
+    let sentences = text.split(['.', '!', '?']).filter(|part| has_word(part)).count();
+
+    // Paragraphs are separated by one or more blank lines
+    let mut paragraphs = 0;
+    let mut in_paragraph = false;
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            in_paragraph = false;
+        } else if !in_paragraph {
+            paragraphs += 1;
+            in_paragraph = true;
+        }
+    }
+
+    WordCount {
+        words: words as u32,
+        sentences: sentences as u32,
+        paragraphs,
+        characters: text.chars().count() as u32,
+    }
+}
+
+/// Count words, sentences, paragraphs and characters in a UTF-8 text file
+#[tauri::command]
+pub(crate) fn count_words_in_file(path: String) -> Result<WordCount, String> {
+    // Validate path is within allowed directories
+    let validated_path = validate_read_path(&path)?;
+
+    let content = fs::read_to_string(&validated_path)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    Ok(count_words_in_text(&content))
+}
+
+/// Estimated reading time in minutes (`wpm` defaults to 238)
+#[tauri::command]
+pub(crate) fn estimate_reading_time(word_count: u32, wpm: Option<u32>) -> f64 {
+    let wpm = wpm.filter(|&w| w > 0).unwrap_or(DEFAULT_READING_WPM);
+    word_count as f64 / wpm as f64
+}
+
+/// Incremental digest over one of the supported algorithms
+enum FileHasher {
+    Sha256(Sha256),
+    Md5(md5::Md5),
+}
+
+impl FileHasher {
+    fn new(algorithm: &str) -> Result<Self, String> {
+        match algorithm.to_lowercase().as_str() {
+            "sha256" => Ok(FileHasher::Sha256(Sha256::new())),
+            "md5" => Ok(FileHasher::Md5(md5::Md5::new())),
+            _ => Err(format!("Unsupported algorithm: {}", algorithm)),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            FileHasher::Sha256(h) => h.update(data),
+            FileHasher::Md5(h) => h.update(data),
+        }
+    }
+
+    fn finalize_hex(self) -> String {
+        match self {
+            FileHasher::Sha256(h) => format!("{:x}", h.finalize()),
+            FileHasher::Md5(h) => format!("{:x}", h.finalize()),
+        }
+    }
+}
+
+/// Hash a file in 64 KB chunks so large audio files never need to be held in memory
+fn hash_file_streaming(path: &Path, algorithm: &str) -> Result<String, String> {
+    use std::io::Read;
+
+    let mut hasher = FileHasher::new(algorithm)?;
+    let mut file = fs::File::open(path)
+        .map_err(|e| format!("Failed to open file: {}", e))?;
+    let mut buffer = vec![0u8; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer)
+            .map_err(|e| format!("Failed to read file: {}", e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher.finalize_hex())
+}
+
+/// Compute the hex digest of a file ("sha256", or "md5" for legacy compatibility)
+#[tauri::command]
+pub(crate) fn compute_file_hash(path: String, algorithm: String) -> Result<String, String> {
+    // Validate path exists and is within allowed directories
+    let validated_path = validate_read_path(&path)?;
+
+    if validated_path.is_dir() {
+        return Err(format!("Path is a directory: {}", path));
+    }
+
+    hash_file_streaming(&validated_path, &algorithm)
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct DuplicateGroup {
+    hash: String,
+    files: Vec<String>,
+    size_bytes: u64,
+}
+
+/// Group identical files under `root`. Files are first bucketed by size so only
+/// size collisions are hashed; empty files and symlinks are ignored.
+fn find_duplicate_files_in(root: &Path) -> Result<Vec<DuplicateGroup>, String> {
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    let mut stack = vec![root.to_path_buf()];
+
+    while let Some(dir) = stack.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("Skipping unreadable directory {}: {}", dir.display(), e);
+                continue;
+            }
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let Ok(file_type) = entry.file_type() else { continue };
+            if file_type.is_symlink() {
+                continue;
+            }
+            if file_type.is_dir() {
+                stack.push(entry.path());
+                continue;
+            }
+            match entry.metadata() {
+                Ok(metadata) if metadata.len() > 0 => {
+                    by_size.entry(metadata.len()).or_default().push(entry.path());
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Skipping {}: {}", entry.path().display(), e),
+            }
+        }
+    }
+
+    let mut groups = Vec::new();
+    for (size_bytes, paths) in by_size.into_iter().filter(|(_, paths)| paths.len() > 1) {
+        let mut by_hash: HashMap<String, Vec<String>> = HashMap::new();
+        for path in paths {
+            match hash_file_streaming(&path, "sha256") {
+                Ok(hash) => by_hash.entry(hash).or_default().push(path.to_string_lossy().to_string()),
+                Err(e) => warn!("Skipping {}: {}", path.display(), e),
+            }
+        }
+        for (hash, mut files) in by_hash.into_iter().filter(|(_, files)| files.len() > 1) {
+            files.sort();
+            groups.push(DuplicateGroup { hash, files, size_bytes });
+        }
+    }
+
+    // Biggest wasted space first
+    groups.sort_by(|a, b| {
+        let wasted = |g: &DuplicateGroup| g.size_bytes * (g.files.len() as u64 - 1);
+        wasted(b).cmp(&wasted(a)).then_with(|| a.files.cmp(&b.files))
+    });
+    Ok(groups)
+}
+
+/// Find files with identical content (SHA-256) anywhere under a directory
+#[tauri::command]
+pub(crate) fn find_duplicate_files(directory: String) -> Result<Vec<DuplicateGroup>, String> {
+    // Validate directory is within allowed paths
+    let validated_dir = validate_read_path(&directory)?;
+
+    if !validated_dir.is_dir() {
+        return Err(format!("Path is not a directory: {}", directory));
+    }
+
+    let groups = find_duplicate_files_in(&validated_dir)?;
+    info!("Found {} duplicate groups in {}", groups.len(), directory);
+    Ok(groups)
+}
+
+/// Lines of unchanged context kept around each change in calculate_file_diff
+const DIFF_CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Serialize, PartialEq)]
+struct DiffLine {
+    kind: &'static str,
+    content: String,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct DiffHunk {
+    old_start: u32,
+    old_count: u32,
+    new_start: u32,
+    new_count: u32,
+    lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct FileDiff {
+    hunks: Vec<DiffHunk>,
+}
+
+/// Read a file for diffing; a NUL byte in the file marks it as binary
+fn read_diffable_text(path: &Path) -> Result<String, String> {
+    let bytes = fs::read(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    if bytes.contains(&0) {
+        return Err("Binary file differs".to_string());
+    }
+    String::from_utf8(bytes)
+        .map_err(|_| format!("File is not valid UTF-8 text: {}", path.display()))
+}
+
+/// Line-level unified diff hunks (1-based line numbers, as in `diff -u`)
+fn diff_text_lines(old: &str, new: &str) -> Vec<DiffHunk> {
+    let diff = similar::TextDiff::from_lines(old, new);
+
+    diff.grouped_ops(DIFF_CONTEXT_LINES)
+        .iter()
+        .filter_map(|group| {
+            let first = group.first()?;
+            let last = group.last()?;
+            let old_range = first.old_range().start..last.old_range().end;
+            let new_range = first.new_range().start..last.new_range().end;
+
+            let lines = group.iter()
+                .flat_map(|op| diff.iter_changes(op))
+                .map(|change| DiffLine {
+                    kind: match change.tag() {
+                        similar::ChangeTag::Equal => "context",
+                        similar::ChangeTag::Insert => "insert",
+                        similar::ChangeTag::Delete => "delete",
+                    },
+                    content: change.value().trim_end_matches(['\n', '\r']).to_string(),
+                })
+                .collect();
+
+            Some(DiffHunk {
+                old_start: old_range.start as u32 + 1,
+                old_count: old_range.len() as u32,
+                new_start: new_range.start as u32 + 1,
+                new_count: new_range.len() as u32,
+                lines,
+            })
+        })
+        .collect()
+}
+
+/// Compare two text files line by line, e.g. an updated session note against the original
+#[tauri::command]
+pub(crate) fn calculate_file_diff(old_path: String, new_path: String) -> Result<FileDiff, String> {
+    // Validate paths are within allowed directories
+    let old_file = validate_read_path(&old_path)?;
+    let new_file = validate_read_path(&new_path)?;
+
+    let old_text = read_diffable_text(&old_file)?;
+    let new_text = read_diffable_text(&new_file)?;
+
+    Ok(FileDiff { hunks: diff_text_lines(&old_text, &new_text) })
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct EncodingResult {
+    detected_encoding: String,
+    confidence: f64,
+    is_utf8: bool,
+}
+
+/// Guess the text encoding of `bytes`. Valid UTF-8 (including plain ASCII) is reported
+/// with confidence 1.0; otherwise chardetng's guess scores 0.8 when it is confident
+/// and 0.5 when the text is too short or ambiguous to be sure.
+fn detect_text_encoding(bytes: &[u8]) -> EncodingResult {
+    let without_bom = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    if std::str::from_utf8(without_bom).is_ok() {
+        return EncodingResult {
+            detected_encoding: encoding_rs::UTF_8.name().to_string(),
+            confidence: 1.0,
+            is_utf8: true,
+        };
+    }
+
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(bytes, true);
+    let (encoding, confident) = detector.guess_assess(None, false);
+    EncodingResult {
+        detected_encoding: encoding.name().to_string(),
+        confidence: if confident { 0.8 } else { 0.5 },
+        is_utf8: false,
+    }
+}
+
+/// Detect a file's character encoding (e.g. Windows-1252 exports from legacy systems)
+#[tauri::command]
+pub(crate) fn check_file_encoding(path: String) -> Result<EncodingResult, String> {
+    // Validate path is within allowed directories
+    let validated_path = validate_read_path(&path)?;
+    let bytes = fs::read(&validated_path)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    Ok(detect_text_encoding(&bytes))
+}
+
+/// Decode `bytes` from the encoding named `from_encoding` (any WHATWG label, so
+/// "latin1" and "iso-8859-1" decode as Windows-1252). A leading BOM is removed.
+fn decode_to_utf8(bytes: &[u8], from_encoding: &str) -> Result<String, String> {
+    let encoding = encoding_rs::Encoding::for_label(from_encoding.trim().as_bytes())
+        .ok_or_else(|| format!("Unknown encoding: {}", from_encoding))?;
+    let (text, had_errors) = encoding.decode_with_bom_removal(bytes);
+    if had_errors {
+        return Err(format!("File is not valid {} text", encoding.name()));
+    }
+    Ok(text.into_owned())
+}
+
+/// Re-encode a text file as UTF-8. `output_path` may be the input file itself.
+#[tauri::command]
+pub(crate) fn convert_file_to_utf8(path: String, from_encoding: String, output_path: String) -> Result<(), String> {
+    // Validate paths are within allowed directories
+    let validated_input = validate_read_path(&path)?;
+    let validated_output = validate_write_path(&output_path)?;
+
+    let bytes = fs::read(&validated_input)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    let text = decode_to_utf8(&bytes, &from_encoding)?;
+
+    let result = write_file_atomically(&validated_output, text.as_bytes());
+    audited("convert_file_to_utf8", &output_path, result)?;
+    info!("Converted {} from {} to UTF-8: {}", path, from_encoding, output_path);
+    Ok(())
+}
+
+/// Open a file in the OS default application (e.g. a generated PDF or DOCX)
+#[tauri::command]
+pub(crate) fn open_file(path: String) -> Result<(), String> {
+    // Only files inside PBS_Admin's folders may be handed to the OS
+    let validated_path = validate_read_path(&path)?;
+
+    tauri_plugin_opener::open_path(&validated_path, None::<&str>)
+        .map_err(|e| format!("Failed to open file: {}", e))?;
+    info!("Opened {} in default application", path);
+    Ok(())
+}
+
+/// Open a web or mailto link in the OS default handler
+#[tauri::command]
+pub(crate) fn open_url(url: String) -> Result<(), String> {
+    let scheme = url.split_once(':').map(|(scheme, _)| scheme.to_ascii_lowercase());
+    if !matches!(scheme.as_deref(), Some("http" | "https" | "mailto")) {
+        return Err(format!("Unsupported URL: {}", url));
+    }
+
+    tauri_plugin_opener::open_url(&url, None::<&str>)
+        .map_err(|e| format!("Failed to open URL: {}", e))
+}
+
+// ============================================================================
+// DIRECTORY WATCHING
+// ============================================================================
+
+/// Active directory watchers, keyed by (window label, validated directory path), so two
+/// windows can watch the same folder independently. Dropping a watcher stops it.
+#[derive(Default)]
+pub(crate) struct DirectoryWatchers(Mutex<HashMap<(String, String), notify::RecommendedWatcher>>);
+
+/// Payload of the `fs-change` event. `watch_id` is the path watch_directory returned.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+struct FsChangeEvent {
+    watch_id: String,
+    kind: &'static str,
+    path: String,
+}
+
+/// Map a notify event to one `FsChangeEvent` per affected path (access events are ignored)
+fn fs_change_events(event: &notify::Event, watch_id: &str) -> Vec<FsChangeEvent> {
+    use notify::EventKind;
+
+    let kind = match event.kind {
+        EventKind::Create(_) => "create",
+        EventKind::Modify(_) => "modify",
+        EventKind::Remove(_) => "delete",
+        _ => return Vec::new(),
+    };
+    event.paths.iter()
+        .map(|p| FsChangeEvent { watch_id: watch_id.to_string(), kind, path: p.to_string_lossy().to_string() })
+        .collect()
+}
+
+/// Start a recursive watcher on `directory`, calling `on_change` for each change.
+/// Events carry the directory path as their watch id.
+fn start_directory_watcher<F>(directory: &Path, on_change: F) -> Result<notify::RecommendedWatcher, String>
+where
+    F: Fn(FsChangeEvent) + Send + 'static,
+{
+    use notify::Watcher;
+
+    let watch_id = directory.to_string_lossy().to_string();
+    let mut watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+        match result {
+            Ok(event) => fs_change_events(&event, &watch_id).into_iter().for_each(&on_change),
+            Err(e) => warn!("Directory watcher error: {}", e),
+        }
+    }).map_err(|e| format!("Failed to create directory watcher: {}", e))?;
+
+    watcher.watch(directory, notify::RecursiveMode::Recursive)
+        .map_err(|e| format!("Failed to watch directory: {}", e))?;
+    Ok(watcher)
+}
+
+/// Watch a directory (recursively) and emit `fs-change` events to the calling window only.
+/// Watching a directory this window already watches replaces the previous watcher.
+/// Returns the watched path, which events carry as `watch_id`.
+#[tauri::command]
+pub(crate) fn watch_directory(directory: String, window: tauri::Window, watchers: tauri::State<'_, DirectoryWatchers>) -> Result<String, String> {
+    // Validate directory exists and is within allowed directories
+    let validated_dir = validate_read_path(&directory)?;
+    if !validated_dir.is_dir() {
+        return Err(format!("Path is not a directory: {}", directory));
+    }
+
+    // Window::emit broadcasts to every window, so address the registering window by label
+    let label = window.label().to_string();
+    let target = label.clone();
+    let watcher = start_directory_watcher(&validated_dir, move |change| {
+        let _ = window.emit_to(target.as_str(), "fs-change", change);
+    })?;
+
+    let key = validated_dir.to_string_lossy().to_string();
+    let mut map = watchers.0.lock().map_err(|_| "Directory watcher lock error".to_string())?;
+    map.insert((label.clone(), key.clone()), watcher);
+    info!("Watching directory for window {}: {}", label, key);
+    Ok(key)
+}
+
+/// Stop the calling window's watcher on a directory previously passed to `watch_directory`
+#[tauri::command]
+pub(crate) fn stop_watching(directory: String, window: tauri::Window, watchers: tauri::State<'_, DirectoryWatchers>) -> Result<(), String> {
+    stop_watching_for(&watchers, window.label(), &directory)
+}
+
+fn stop_watching_for(watchers: &DirectoryWatchers, label: &str, directory: &str) -> Result<(), String> {
+    // The directory may already be gone, so fall back to the path as given
+    let key = validate_read_path(directory)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| directory.to_string());
+
+    let mut map = watchers.0.lock().map_err(|_| "Directory watcher lock error".to_string())?;
+    match map.remove(&(label.to_string(), key.clone())) {
+        Some(_) => {
+            info!("Stopped watching directory for window {}: {}", label, key);
+            Ok(())
+        }
+        None => Err(format!("Directory is not being watched: {}", directory)),
+    }
+}
+
+// ============================================================================
+// ZIP ARCHIVES
+// ============================================================================
+
+/// Archive a directory (recursively) into a ZIP file. Returns the archive size in bytes.
+/// `compression_level` defaults to 6 and is clamped to 0-9.
+#[tauri::command]
+pub(crate) fn zip_directory(source_dir: String, output_zip: String, compression_level: Option<u32>) -> Result<u64, String> {
+    audited(
+        "zip_directory",
+        &format!("{} -> {}", source_dir, output_zip),
+        zip_directory_unaudited(&source_dir, &output_zip, compression_level),
+    )
+}
+
+fn zip_directory_unaudited(source_dir: &str, output_zip: &str, compression_level: Option<u32>) -> Result<u64, String> {
+    // Validate both ends are within allowed directories
+    let validated_source = validate_read_path(source_dir)?;
+    let validated_output = validate_write_path(output_zip)?;
+
+    if !validated_source.is_dir() {
+        return Err(format!("Source is not a directory: {}", source_dir));
+    }
+    if validated_output.starts_with(&validated_source) {
+        return Err("Output archive cannot be inside the directory being archived".to_string());
+    }
+
+    let level = compression_level.unwrap_or(6).min(9) as i64;
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .compression_level(Some(level));
+
+    // Build the archive beside the target and rename it into place, so a failure
+    // part-way through never leaves a truncated ZIP at output_zip
+    let temp_path = atomic_temp_path(&validated_output)?;
+    let file = fs::File::create(&temp_path)
+        .map_err(|e| format!("Failed to create archive: {}", e))?;
+    let mut writer = zip::ZipWriter::new(file);
+
+    let write_result = (|| -> Result<(), String> {
+        // Entry names use forward slashes relative to the source directory
+        let mut stack = vec![(validated_source.clone(), String::new())];
+        while let Some((dir, prefix)) = stack.pop() {
+            let entries = fs::read_dir(&dir)
+                .map_err(|e| format!("Failed to read directory: {}", e))?;
+
+            for entry in entries {
+                let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
+                let file_type = entry.file_type()
+                    .map_err(|e| format!("Failed to read entry type: {}", e))?;
+                let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+
+                // Symlinks are skipped rather than followed
+                if file_type.is_dir() {
+                    writer.add_directory(format!("{}/", name), options)
+                        .map_err(|e| format!("Failed to add folder {}: {}", name, e))?;
+                    stack.push((entry.path(), format!("{}/", name)));
+                } else if file_type.is_file() {
+                    writer.start_file(name.as_str(), options)
+                        .map_err(|e| format!("Failed to add file {}: {}", name, e))?;
+                    let mut source = fs::File::open(entry.path())
+                        .map_err(|e| format!("Failed to open {}: {}", name, e))?;
+                    std::io::copy(&mut source, &mut writer)
+                        .map_err(|e| format!("Failed to add file {}: {}", name, e))?;
+                }
+            }
+        }
+
+        let file = writer.finish()
+            .map_err(|e| format!("Failed to finish archive: {}", e))?;
+        file.sync_all()
+            .map_err(|e| format!("Failed to sync archive to disk: {}", e))
+    })();
+
+    if let Err(e) = write_result {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+    if let Err(e) = fs::rename(&temp_path, &validated_output) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(format!("Failed to replace archive: {}", e));
+    }
+
+    let size = fs::metadata(&validated_output)
+        .map(|m| m.len())
+        .map_err(|e| format!("Failed to read archive size: {}", e))?;
+
+    info!("Archived {} to {} ({} bytes)", source_dir, output_zip, size);
+
+    Ok(size)
+}
+
+/// Extract a ZIP archive into `output_dir`. Returns the number of files extracted.
+/// Entries that would land outside `output_dir` (Zip Slip) abort the extraction before anything is written.
+#[tauri::command]
+pub(crate) fn unzip_archive(zip_path: String, output_dir: String, overwrite: bool) -> Result<u32, String> {
+    audited(
+        "unzip_archive",
+        &format!("{} -> {}", zip_path, output_dir),
+        unzip_archive_unaudited(&zip_path, &output_dir, overwrite),
+    )
+}
+
+fn unzip_archive_unaudited(zip_path: &str, output_dir: &str, overwrite: bool) -> Result<u32, String> {
+    let validated_zip = validate_read_path(zip_path)?;
+
+    let output_root = Path::new(output_dir);
+    if !output_root.exists() {
+        // Treat output_dir as the parent of a placeholder entry so it gets created (within PBS_Admin only)
+        create_parent_dirs_within_pbs(&output_root.join("_"))?;
+    }
+    let validated_output = validate_write_path(output_dir)?;
+
+    let file = fs::File::open(&validated_zip)
+        .map_err(|e| format!("Failed to open archive: {}", e))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .map_err(|e| format!("Not a valid ZIP archive: {}", e))?;
+
+    // Resolve every entry up front: reject unsafe paths and (optionally) existing files
+    let mut targets: Vec<(usize, PathBuf, bool)> = Vec::new();
+    let mut conflicts: Vec<String> = Vec::new();
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index)
+            .map_err(|e| format!("Failed to read archive entry: {}", e))?;
+        let relative = entry.enclosed_name()
+            .ok_or_else(|| format!("Unsafe path in archive (outside output folder): {}", entry.name()))?;
+        let target = validated_output.join(relative);
+
+        if !entry.is_dir() && !overwrite && target.exists() {
+            conflicts.push(target.to_string_lossy().to_string());
+        }
+        targets.push((index, target, entry.is_dir()));
+    }
+    if !conflicts.is_empty() {
+        return Err(format!("Destination files already exist: {}", conflicts.join(", ")));
+    }
+
+    let mut extracted: u32 = 0;
+    for (index, target, is_dir) in targets {
+        if is_dir {
+            fs::create_dir_all(&target)
+                .map_err(|e| format!("Failed to create folder: {}", e))?;
+            continue;
+        }
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create folder: {}", e))?;
+        }
+
+        let mut entry = archive.by_index(index)
+            .map_err(|e| format!("Failed to read archive entry: {}", e))?;
+        let mut out = fs::File::create(&target)
+            .map_err(|e| format!("Failed to create {}: {}", target.display(), e))?;
+        std::io::copy(&mut entry, &mut out)
+            .map_err(|e| format!("Failed to extract {}: {}", target.display(), e))?;
+        extracted += 1;
+    }
+
+    info!("Extracted {} files from {} to {}", extracted, zip_path, output_dir);
+
+    Ok(extracted)
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ArchiveResult {
+    archived_count: u32,
+    total_size_saved_bytes: u64,
+    archive_paths: Vec<String>,
+}
+
+/// A session note waiting to be archived
+struct ArchiveCandidate {
+    path: PathBuf,
+    name: String,
+    size: u64,
+    modified: chrono::DateTime<chrono::Local>,
+}
+
+/// Session notes in `notes_dir` last modified before `cutoff`, grouped by (year, month)
+fn old_session_files_by_month(
+    notes_dir: &Path,
+    cutoff: std::time::SystemTime,
+) -> Result<std::collections::BTreeMap<(i32, u32), Vec<ArchiveCandidate>>, String> {
+    use chrono::Datelike;
+
+    let mut months: std::collections::BTreeMap<(i32, u32), Vec<ArchiveCandidate>> = std::collections::BTreeMap::new();
+    for entry in fs::read_dir(notes_dir).map_err(|e| format!("Failed to read directory: {}", e))? {
+        let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
+        let metadata = entry.metadata()
+            .map_err(|e| format!("Failed to read metadata: {}", e))?;
+        // Symlinks and subfolders stay where they are
+        if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+            continue;
+        }
+        let modified = metadata.modified()
+            .map_err(|e| format!("Failed to read modification time: {}", e))?;
+        if modified >= cutoff {
+            continue;
+        }
+
+        let modified: chrono::DateTime<chrono::Local> = modified.into();
+        months.entry((modified.year(), modified.month())).or_default().push(ArchiveCandidate {
+            path: entry.path(),
+            name: entry.file_name().to_string_lossy().to_string(),
+            size: metadata.len(),
+            modified,
+        });
+    }
+    Ok(months)
+}
+
+/// Add `files` to the monthly archive at `archive_path` (appending if it already exists).
+/// The archive is built beside the target and only replaces it once it re-opens with
+/// every expected entry. Returns the files that were archived; files whose name is
+/// already in the archive are left in place.
+fn append_to_month_archive<'a>(archive_path: &Path, files: &'a [ArchiveCandidate]) -> Result<Vec<&'a ArchiveCandidate>, String> {
+    use chrono::{Datelike, Timelike};
+
+    let temp_path = archive_path.with_extension("zip.tmp");
+    let (mut writer, existing_names) = if archive_path.exists() {
+        let file = fs::File::open(archive_path)
+            .map_err(|e| format!("Failed to open archive: {}", e))?;
+        let names: std::collections::HashSet<String> = zip::ZipArchive::new(file)
+            .map_err(|e| format!("Not a valid ZIP archive {}: {}", archive_path.display(), e))?
+            .file_names()
+            .map(|name| name.to_string())
+            .collect();
+
+        fs::copy(archive_path, &temp_path)
+            .map_err(|e| format!("Failed to copy archive: {}", e))?;
+        let file = fs::OpenOptions::new().read(true).write(true).open(&temp_path)
+            .map_err(|e| format!("Failed to open archive: {}", e))?;
+        let writer = zip::ZipWriter::new_append(file)
+            .map_err(|e| format!("Failed to open archive for appending: {}", e))?;
+        (writer, names)
+    } else {
+        let file = fs::File::create(&temp_path)
+            .map_err(|e| format!("Failed to create archive: {}", e))?;
+        (zip::ZipWriter::new(file), std::collections::HashSet::new())
+    };
+
+    let mut added = Vec::new();
+    for candidate in files {
+        if existing_names.contains(&candidate.name) {
+            warn!("{} is already in {}; leaving it in place", candidate.name, archive_path.display());
+            continue;
+        }
+        let modified = &candidate.modified;
+        let mut options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        if let Ok(timestamp) = zip::DateTime::from_date_and_time(
+            modified.year().clamp(1980, 2107) as u16,
+            modified.month() as u8,
+            modified.day() as u8,
+            modified.hour() as u8,
+            modified.minute() as u8,
+            modified.second().min(59) as u8,
+        ) {
+            options = options.last_modified_time(timestamp);
+        }
+
+        writer.start_file(candidate.name.as_str(), options)
+            .map_err(|e| format!("Failed to add file {}: {}", candidate.name, e))?;
+        let mut source = fs::File::open(&candidate.path)
+            .map_err(|e| format!("Failed to open {}: {}", candidate.name, e))?;
+        std::io::copy(&mut source, &mut writer)
+            .map_err(|e| format!("Failed to add file {}: {}", candidate.name, e))?;
+        added.push(candidate);
+    }
+    writer.finish()
+        .map_err(|e| format!("Failed to finish archive: {}", e))?;
+
+    // Re-open and check every entry made it before the originals can be deleted
+    let verify = || -> Result<(), String> {
+        let file = fs::File::open(&temp_path)
+            .map_err(|e| format!("Failed to open archive: {}", e))?;
+        let mut archive = zip::ZipArchive::new(file)
+            .map_err(|e| format!("Archive failed verification: {}", e))?;
+        if archive.len() != existing_names.len() + added.len() {
+            return Err(format!(
+                "Archive failed verification: expected {} entries, found {}",
+                existing_names.len() + added.len(),
+                archive.len()
+            ));
+        }
+        for candidate in &added {
+            let entry = archive.by_name(&candidate.name)
+                .map_err(|_| format!("Archive failed verification: {} is missing", candidate.name))?;
+            if entry.size() != candidate.size {
+                return Err(format!("Archive failed verification: {} has the wrong size", candidate.name));
+            }
+        }
+        Ok(())
+    };
+    if let Err(e) = verify() {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    fs::rename(&temp_path, archive_path)
+        .map_err(|e| format!("Failed to save archive: {}", e))?;
+    Ok(added)
+}
+
+/// Move session notes not modified in `older_than_days` days from
+/// `client_folder/Session_Notes` into `archive_dir/<YYYY>/<MM>.zip` (by modification
+/// month). Originals are deleted only after their archive verifies.
+/// `total_size_saved_bytes` is the original size minus the space the archives grew by.
+#[tauri::command]
+pub(crate) fn archive_old_sessions(client_folder: String, older_than_days: u32, archive_dir: String) -> Result<ArchiveResult, String> {
+    // Validate paths are within allowed directories
+    let validated_folder = validate_write_path(&client_folder)?;
+    let notes_dir = validated_folder.join("Session_Notes");
+    if !notes_dir.is_dir() {
+        return Err(format!("Session_Notes folder not found in {}", client_folder));
+    }
+
+    let archive_root = Path::new(&archive_dir);
+    if !archive_root.exists() {
+        // Treat archive_dir as the parent of a placeholder entry so it gets created (within PBS_Admin only)
+        create_parent_dirs_within_pbs(&archive_root.join("_"))?;
+    }
+    let validated_archive = validate_write_path(&archive_dir)?;
+    if validated_archive.starts_with(&notes_dir) {
+        return Err("Archive folder cannot be inside Session_Notes".to_string());
+    }
+
+    let cutoff = std::time::SystemTime::now()
+        .checked_sub(Duration::from_secs(older_than_days as u64 * 86_400))
+        .ok_or_else(|| "older_than_days is too large".to_string())?;
+    let months = old_session_files_by_month(&notes_dir, cutoff)?;
+
+    let mut archived_count: u32 = 0;
+    let mut original_bytes: u64 = 0;
+    let mut archive_growth: u64 = 0;
+    let mut archive_paths = Vec::new();
+    for ((year, month), files) in &months {
+        let year_dir = validated_archive.join(year.to_string());
+        fs::create_dir_all(&year_dir)
+            .map_err(|e| format!("Failed to create folder: {}", e))?;
+        let archive_path = year_dir.join(format!("{:02}.zip", month));
+        let size_before = fs::metadata(&archive_path).map(|m| m.len()).unwrap_or(0);
+
+        let added = append_to_month_archive(&archive_path, files)?;
+        if added.is_empty() {
+            continue;
+        }
+        let size_after = fs::metadata(&archive_path).map(|m| m.len()).unwrap_or(size_before);
+        archive_growth += size_after.saturating_sub(size_before);
+
+        for candidate in added {
+            let result = fs::remove_file(&candidate.path)
+                .map_err(|e| format!("Failed to delete {} after archiving: {}", candidate.path.display(), e));
+            audited("archive_old_sessions", &candidate.path.to_string_lossy(), result)?;
+            archived_count += 1;
+            original_bytes += candidate.size;
+        }
+        archive_paths.push(archive_path.to_string_lossy().to_string());
+    }
+
+    info!("Archived {} session files from {} into {} archives", archived_count, client_folder, archive_paths.len());
+    Ok(ArchiveResult {
+        archived_count,
+        total_size_saved_bytes: original_bytes.saturating_sub(archive_growth),
+        archive_paths,
+    })
+}
+
+// ============================================================================
+// FILE ENCRYPTION
+// ============================================================================
+
+const ENCRYPTION_SALT_LEN: usize = 16;
+const ENCRYPTION_NONCE_LEN: usize = 12;
+const ENCRYPTION_TAG_LEN: usize = 16;
+
+/// Argon2id costs: memory (KiB), iterations, parallelism. The file layout doesn't record
+/// them, so they are pinned here; changing them makes existing files undecryptable.
+const ENCRYPTION_KDF_PARAMS: KdfParams = KdfParams { m_cost: 19 * 1024, t_cost: 2, p_cost: 1 };
+
+/// Argon2id cost parameters
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct KdfParams {
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+/// Derive a 256-bit key from a password with Argon2id
+fn derive_file_key(password: &str, salt: &[u8], params: KdfParams) -> Result<[u8; 32], String> {
+    let argon2_params = argon2::Params::new(params.m_cost, params.t_cost, params.p_cost, Some(32))
+        .map_err(|e| format!("Invalid key derivation parameters: {}", e))?;
+    let mut key = [0u8; 32];
+    argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, argon2_params)
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Failed to derive key: {}", e))?;
+    Ok(key)
+}
+
+/// Encrypt to `salt || nonce || ciphertext || tag`
+fn encrypt_bytes(plaintext: &[u8], password: &str) -> Result<Vec<u8>, String> {
+    encrypt_bytes_with(plaintext, password, ENCRYPTION_KDF_PARAMS)
+}
+
+fn encrypt_bytes_with(plaintext: &[u8], password: &str, params: KdfParams) -> Result<Vec<u8>, String> {
+    use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+    use aes_gcm::aead::rand_core::RngCore;
+
+    let mut salt = [0u8; ENCRYPTION_SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_file_key(password, &salt, params)?;
+
+    let cipher = aes_gcm::Aes256Gcm::new_from_slice(&key)
+        .map_err(|e| format!("Failed to initialise cipher: {}", e))?;
+    let nonce = aes_gcm::Aes256Gcm::generate_nonce(&mut OsRng);
+    // aes-gcm appends the 16-byte tag to the ciphertext
+    let ciphertext = cipher.encrypt(&nonce, plaintext)
+        .map_err(|_| "Encryption failed".to_string())?;
+
+    let mut output = Vec::with_capacity(salt.len() + nonce.len() + ciphertext.len());
+    output.extend_from_slice(&salt);
+    output.extend_from_slice(&nonce);
+    output.extend_from_slice(&ciphertext);
+    Ok(output)
+}
+
+/// Reverse of `encrypt_bytes`. A wrong password or tampered data fails the GCM tag check.
+fn decrypt_bytes(encrypted: &[u8], password: &str) -> Result<Vec<u8>, String> {
+    decrypt_bytes_with(encrypted, password, ENCRYPTION_KDF_PARAMS)
+}
+
+fn decrypt_bytes_with(encrypted: &[u8], password: &str, params: KdfParams) -> Result<Vec<u8>, String> {
+    use aes_gcm::aead::{Aead, KeyInit};
+
+    if encrypted.len() < ENCRYPTION_SALT_LEN + ENCRYPTION_NONCE_LEN + ENCRYPTION_TAG_LEN {
+        return Err("Encrypted file is too short or corrupted".to_string());
+    }
+    let (salt, rest) = encrypted.split_at(ENCRYPTION_SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(ENCRYPTION_NONCE_LEN);
+
+    let key = derive_file_key(password, salt, params)?;
+    let cipher = aes_gcm::Aes256Gcm::new_from_slice(&key)
+        .map_err(|e| format!("Failed to initialise cipher: {}", e))?;
+    cipher.decrypt(aes_gcm::Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Authentication failed".to_string())
+}
+
+/// Encrypt a file with a password (Argon2id key derivation + AES-256-GCM)
+#[tauri::command]
+pub(crate) fn encrypt_file(plaintext_path: String, encrypted_path: String, password: String) -> Result<(), String> {
+    audited(
+        "encrypt_file",
+        &format!("{} -> {}", plaintext_path, encrypted_path),
+        encrypt_file_unaudited(&plaintext_path, &encrypted_path, &password),
+    )
+}
+
+fn encrypt_file_unaudited(plaintext_path: &str, encrypted_path: &str, password: &str) -> Result<(), String> {
+    if password.is_empty() {
+        return Err("Password is required".to_string());
+    }
+
+    // Validate paths are within allowed directories
+    let validated_input = validate_read_path(plaintext_path)?;
+    let validated_output = validate_write_path(encrypted_path)?;
+
+    let plaintext = fs::read(&validated_input)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    let encrypted = encrypt_bytes(&plaintext, password)?;
+    write_file_atomically(&validated_output, &encrypted)?;
+
+    info!("Encrypted {} -> {}", plaintext_path, encrypted_path);
+    Ok(())
+}
+
+/// Decrypt a file written by `encrypt_file`. Nothing is written if the password is wrong.
+#[tauri::command]
+pub(crate) fn decrypt_file(encrypted_path: String, output_path: String, password: String) -> Result<(), String> {
+    audited(
+        "decrypt_file",
+        &format!("{} -> {}", encrypted_path, output_path),
+        decrypt_file_unaudited(&encrypted_path, &output_path, &password),
+    )
+}
+
+fn decrypt_file_unaudited(encrypted_path: &str, output_path: &str, password: &str) -> Result<(), String> {
+    // Validate paths are within allowed directories
+    let validated_input = validate_read_path(encrypted_path)?;
+    let validated_output = validate_write_path(output_path)?;
+
+    let encrypted = fs::read(&validated_input)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    let plaintext = decrypt_bytes(&encrypted, password)?;
+    write_file_atomically(&validated_output, &plaintext)?;
+
+    info!("Decrypted {} -> {}", encrypted_path, output_path);
+    Ok(())
+}
+
+// ============================================================================
+// FILENAME SANITIZING
+// ============================================================================
+
+/// Device names Windows reserves regardless of extension (e.g. "CON.txt")
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Longest file name (in bytes) accepted by common filesystems
+const MAX_FILENAME_BYTES: usize = 255;
+
+/// Make `name` safe to use as a file or folder name on Windows, macOS and Linux:
+/// reserved/control characters become `replacement`, leading/trailing dots and spaces
+/// are trimmed, Windows device names are prefixed, and the result is capped at 255 bytes.
+pub fn sanitize_filename_str(name: &str, replacement: &str) -> String {
+    const RESERVED_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+    let replaced: String = name
+        .chars()
+        .map(|c| {
+            if RESERVED_CHARS.contains(&c) || c.is_control() {
+                replacement.to_string()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect();
+
+    let mut sanitized = replaced.trim_matches(['.', ' ']).to_string();
+
+    let stem = sanitized.split('.').next().unwrap_or("").trim_end().to_uppercase();
+    if WINDOWS_RESERVED_NAMES.contains(&stem.as_str()) {
+        sanitized = format!("{}{}", replacement, sanitized);
+    }
+
+    if sanitized.len() > MAX_FILENAME_BYTES {
+        let mut end = MAX_FILENAME_BYTES;
+        while !sanitized.is_char_boundary(end) {
+            end -= 1;
+        }
+        sanitized.truncate(end);
+        sanitized = sanitized.trim_end_matches(['.', ' ']).to_string();
+    }
+
+    sanitized
+}
+
+/// Make a file name safe for all supported platforms (`replacement` defaults to "_")
+#[tauri::command]
+pub(crate) fn sanitize_filename(name: String, replacement: Option<String>) -> Result<String, String> {
+    let replacement = replacement.unwrap_or_else(|| "_".to_string());
+    if replacement != sanitize_filename_str(&replacement, "") {
+        return Err(format!("Replacement contains characters not allowed in file names: {}", replacement));
+    }
+
+    let sanitized = sanitize_filename_str(&name, &replacement);
+    if sanitized.is_empty() {
+        return Err(format!("File name is empty after sanitizing: {}", name));
+    }
+    Ok(sanitized)
+}
+
+// ============================================================================
+// SFTP TRANSFER
+// ============================================================================
+
+const SFTP_CONNECT_TIMEOUT_SECS: u64 = 15;
+const SFTP_SESSION_TIMEOUT_MS: u32 = 60_000;
+
+#[derive(Serialize)]
+pub(crate) struct SftpEntry {
+    name: String,
+    size_bytes: u64,
+    is_dir: bool,
+    modified_at: u64,
+}
+
+/// Known hosts file used to verify SFTP servers (OpenSSH format)
+fn get_known_hosts_path() -> Result<PathBuf, String> {
+    Ok(resolve_pbs_admin_data_dir()?.join("known_hosts"))
+}
+
+/// Split "host" or "host:port" (port defaults to 22)
+fn split_sftp_host(remote_host: &str) -> Result<(String, u16), String> {
+    let remote_host = remote_host.trim();
+    match remote_host.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && !host.contains(':') => {
+            let port = port.parse::<u16>()
+                .map_err(|_| format!("Invalid port in remote host: {}", remote_host))?;
+            Ok((host.to_string(), port))
+        }
+        _ if remote_host.is_empty() => Err("Remote host is required".to_string()),
+        _ => Ok((remote_host.to_string(), 22)),
+    }
+}
+
+/// Check the server's host key against a known_hosts file; unknown or changed keys are rejected
+fn verify_sftp_host_key(session: &ssh2::Session, host: &str, port: u16, known_hosts_path: &Path) -> Result<(), String> {
+    let mut known_hosts = session.known_hosts()
+        .map_err(|e| format!("Failed to initialise known hosts: {}", e))?;
+    if known_hosts_path.exists() {
+        known_hosts.read_file(known_hosts_path, ssh2::KnownHostFileKind::OpenSSH)
+            .map_err(|e| format!("Failed to read known hosts file: {}", e))?;
+    }
+
+    let (key, _) = session.host_key()
+        .ok_or_else(|| "Server did not provide a host key".to_string())?;
+    match known_hosts.check_port(host, port, key) {
+        ssh2::CheckResult::Match => Ok(()),
+        ssh2::CheckResult::NotFound => Err(format!(
+            "Host key for {} is not in {}. Add it before connecting.",
+            host,
+            known_hosts_path.display()
+        )),
+        ssh2::CheckResult::Mismatch => Err(format!(
+            "Host key for {} does not match {} - the server may have been replaced or intercepted",
+            host,
+            known_hosts_path.display()
+        )),
+        ssh2::CheckResult::Failure => Err(format!("Failed to check host key for {}", host)),
+    }
+}
+
+/// Connect, verify the host key, authenticate with a private key and open an SFTP channel
+fn open_sftp(remote_host: &str, username: &str, private_key_path: &str, known_hosts_path: &Path) -> Result<ssh2::Sftp, String> {
+    use std::net::{TcpStream, ToSocketAddrs};
+
+    let (host, port) = split_sftp_host(remote_host)?;
+    let key_path = Path::new(private_key_path);
+    if !key_path.is_file() {
+        return Err(format!("Private key not found: {}", private_key_path));
+    }
+
+    let addr = (host.as_str(), port).to_socket_addrs()
+        .map_err(|e| format!("Failed to resolve {}: {}", host, e))?
+        .next()
+        .ok_or_else(|| format!("Failed to resolve {}", host))?;
+    let tcp = TcpStream::connect_timeout(&addr, Duration::from_secs(SFTP_CONNECT_TIMEOUT_SECS))
+        .map_err(|e| format!("Failed to connect to {}:{}: {}", host, port, e))?;
+
+    let mut session = ssh2::Session::new()
+        .map_err(|e| format!("Failed to create SSH session: {}", e))?;
+    session.set_tcp_stream(tcp);
+    session.set_timeout(SFTP_SESSION_TIMEOUT_MS);
+    session.handshake()
+        .map_err(|e| format!("SSH handshake failed: {}", e))?;
+
+    verify_sftp_host_key(&session, &host, port, known_hosts_path)?;
+
+    session.userauth_pubkey_file(username, None, key_path, None)
+        .map_err(|e| format!("SSH authentication failed: {}", e))?;
+    if !session.authenticated() {
+        return Err("SSH authentication failed".to_string());
+    }
+
+    session.sftp()
+        .map_err(|e| format!("Failed to start SFTP: {}", e))
+}
+
+fn sftp_upload_blocking(local_path: &Path, remote_host: &str, remote_path: &str, username: &str, private_key_path: &str) -> Result<(), String> {
+    let mut local_file = fs::File::open(local_path)
+        .map_err(|e| format!("Failed to open local file: {}", e))?;
+
+    let sftp = open_sftp(remote_host, username, private_key_path, &get_known_hosts_path()?)?;
+    let mut remote_file = sftp.create(Path::new(remote_path))
+        .map_err(|e| format!("Failed to create remote file: {}", e))?;
+    std::io::copy(&mut local_file, &mut remote_file)
+        .map_err(|e| format!("Failed to upload file: {}", e))?;
+    Ok(())
+}
+
+fn sftp_download_blocking(remote_host: &str, remote_path: &str, local_path: &Path, username: &str, private_key_path: &str) -> Result<(), String> {
+    let sftp = open_sftp(remote_host, username, private_key_path, &get_known_hosts_path()?)?;
+    let mut remote_file = sftp.open(Path::new(remote_path))
+        .map_err(|e| format!("Failed to open remote file: {}", e))?;
+
+    let mut data = Vec::new();
+    std::io::Read::read_to_end(&mut remote_file, &mut data)
+        .map_err(|e| format!("Failed to download file: {}", e))?;
+    write_file_atomically(local_path, &data)
+}
+
+fn sftp_list_directory_blocking(remote_host: &str, remote_path: &str, username: &str, private_key_path: &str) -> Result<Vec<SftpEntry>, String> {
+    let sftp = open_sftp(remote_host, username, private_key_path, &get_known_hosts_path()?)?;
+    let entries = sftp.readdir(Path::new(remote_path))
+        .map_err(|e| format!("Failed to list remote directory: {}", e))?;
+
+    let mut listing: Vec<SftpEntry> = entries.into_iter()
+        .filter_map(|(path, stat)| {
+            let name = path.file_name()?.to_string_lossy().to_string();
+            Some(SftpEntry {
+                name,
+                size_bytes: stat.size.unwrap_or(0),
+                is_dir: stat.is_dir(),
+                modified_at: stat.mtime.unwrap_or(0),
+            })
+        })
+        .collect();
+    listing.sort_by_key(|e| e.name.to_lowercase());
+    Ok(listing)
+}
+
+/// Upload a local file to an SFTP server (host key must be in known_hosts in the data directory)
+#[tauri::command]
+pub(crate) async fn sftp_upload(
+    local_path: String,
+    remote_host: String,
+    remote_path: String,
+    username: String,
+    private_key_path: String,
+) -> Result<(), String> {
+    // Validate path exists and is within allowed directories
+    let validated_local = validate_read_path(&local_path)?;
+
+    info!("SFTP upload: {} -> {}:{}", local_path, remote_host, remote_path);
+    tokio::task::spawn_blocking(move || {
+        sftp_upload_blocking(&validated_local, &remote_host, &remote_path, &username, &private_key_path)
+    })
+    .await
+    .map_err(|e| format!("SFTP upload task failed: {}", e))?
+}
+
+/// Download a file from an SFTP server. Returns the local path.
+#[tauri::command]
+pub(crate) async fn sftp_download(
+    remote_host: String,
+    remote_path: String,
+    local_path: String,
+    username: String,
+    private_key_path: String,
+) -> Result<String, String> {
+    // Validate path is within allowed directories
+    let validated_local = validate_write_path(&local_path)?;
+
+    info!("SFTP download: {}:{} -> {}", remote_host, remote_path, local_path);
+    tokio::task::spawn_blocking(move || {
+        sftp_download_blocking(&remote_host, &remote_path, &validated_local, &username, &private_key_path)
+    })
+    .await
+    .map_err(|e| format!("SFTP download task failed: {}", e))??;
+    Ok(local_path)
+}
+
+/// List a directory on an SFTP server, sorted by name
+#[tauri::command]
+pub(crate) async fn sftp_list_directory(
+    remote_host: String,
+    remote_path: String,
+    username: String,
+    private_key_path: String,
+) -> Result<Vec<SftpEntry>, String> {
+    tokio::task::spawn_blocking(move || {
+        sftp_list_directory_blocking(&remote_host, &remote_path, &username, &private_key_path)
+    })
+    .await
+    .map_err(|e| format!("SFTP list task failed: {}", e))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::time::Instant;
+    use tauri::Manager;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use crate::{FileLockRegistry, validate_read_path, validate_write_path, write_file_atomically};
+    use crate::test_support::{
+        audit_entries_for, http_response, path_string, scratch_dir, serve_http, strings,
+    };
+
+    #[tokio::test]
+    async fn async_text_file_round_trip() {
+        let dir = scratch_dir();
+        let file = dir.path().join("note.md");
+        let locks = FileLockRegistry::default();
+
+        write_text_file_async_locked(&locks, path_string(&file), "first".into()).await.unwrap();
+        write_text_file_async_locked(&locks, path_string(&file), "second".into()).await.unwrap();
+        assert_eq!(read_text_file_async(path_string(&file)).await.unwrap(), "second");
+        // The atomic write leaves no temp files behind
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let missing_parent = dir.path().join("missing").join("note.md");
+        assert!(write_text_file_async_locked(&locks, path_string(&missing_parent), "x".into()).await.is_err());
+        assert!(read_text_file_async("/etc/hostname".into()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn async_write_respects_file_lock() {
+        let dir = scratch_dir();
+        let file = dir.path().join("note.md");
+        fs::write(&file, "original").unwrap();
+        let locks = FileLockRegistry::default();
+
+        let _held = locks.try_lock(&validate_write_path(&path_string(&file)).unwrap()).unwrap();
+        let err = write_text_file_async_locked(&locks, path_string(&file), "new".into()).await.unwrap_err();
+        assert_eq!(err, "File is locked by another operation");
+        assert_eq!(fs::read_to_string(&file).unwrap(), "original");
+    }
+
+    fn assert_send<T: Send>(_: T) {}
+
+    #[test]
+    fn async_file_commands_are_send() {
+        // Tauri runs async commands on a multi-threaded runtime
+        let locks = FileLockRegistry::default();
+        assert_send(read_text_file_async(String::new()));
+        assert_send(write_text_file_async_locked(&locks, String::new(), String::new()));
+    }
+
+    /// A named pipe: reading it blocks until something writes to it
+    #[cfg(unix)]
+    fn fifo(path: &Path) {
+        let status = Command::new("mkfifo").arg(path).status().unwrap();
+        assert!(status.success(), "mkfifo failed");
+    }
+
+    #[cfg(unix)]
+    // One worker thread: if a read blocked its worker, the other couldn't make progress
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn concurrent_async_reads_overlap() {
+        let dir = scratch_dir();
+        let (first_pipe, second_pipe) = (dir.path().join("first.md"), dir.path().join("second.md"));
+        fifo(&first_pipe);
+        fifo(&second_pipe);
+
+        // Both reads block in their I/O until their pipe is fed
+        let first = tokio::spawn(read_text_file_async(path_string(&first_pipe)));
+        let second = tokio::spawn(read_text_file_async(path_string(&second_pipe)));
+
+        // Feed only the second pipe. If reads ran one after another, the second would wait
+        // behind the first (which is still blocked) and this would time out.
+        let feed = |path: PathBuf, text: &'static str| tokio::task::spawn_blocking(move || fs::write(path, text).unwrap());
+        tokio::time::timeout(Duration::from_secs(5), feed(second_pipe, "second note")).await.unwrap().unwrap();
+        let second = tokio::time::timeout(Duration::from_secs(5), second).await
+            .expect("second read finished while the first was still blocked");
+        assert_eq!(second.unwrap().unwrap(), "second note");
+        assert!(!first.is_finished());
+
+        feed(first_pipe, "first note").await.unwrap();
+        assert_eq!(first.await.unwrap().unwrap(), "first note");
+    }
+
+    #[test]
+    fn write_binary_file_is_atomic() {
+        let dir = scratch_dir();
+        let file = dir.path().join("scan.bin");
+        let locks = FileLockRegistry::default();
+
+        write_binary_file_locked(&locks, path_string(&file), vec![1, 2, 3]).unwrap();
+        write_binary_file_locked(&locks, path_string(&file), vec![4, 5]).unwrap();
+        assert_eq!(fs::read(&file).unwrap(), vec![4, 5]);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn delete_file_and_folder() {
+        let dir = scratch_dir();
+        let folder = dir.path().join("client");
+        fs::create_dir(&folder).unwrap();
+        fs::write(folder.join("note.md"), "x").unwrap();
+
+        assert!(delete_file(path_string(&folder)).unwrap_err().contains("use delete_folder"));
+        // Non-recursive delete only removes empty folders
+        assert!(delete_folder(path_string(&folder), false).is_err());
+        delete_file(path_string(&folder.join("note.md"))).unwrap();
+        assert!(delete_file(path_string(&folder.join("note.md"))).is_err());
+        fs::write(folder.join("other.md"), "x").unwrap();
+        delete_folder(path_string(&folder), true).unwrap();
+        assert!(!folder.exists());
+    }
+
+    #[test]
+    fn delete_folder_refuses_pbs_roots() {
+        let temp_root = std::env::temp_dir().join("PBS_Admin");
+        fs::create_dir_all(&temp_root).unwrap();
+        assert_eq!(
+            delete_folder(path_string(&temp_root), true).unwrap_err(),
+            "Cannot delete the PBS_Admin root folder"
+        );
+        let data_dir = resolve_pbs_admin_data_dir().unwrap();
+        if data_dir.is_dir() {
+            assert!(delete_folder(path_string(&data_dir), true).is_err());
+        }
+        assert!(temp_root.exists());
+    }
+
+    #[test]
+    fn move_and_rename_files() {
+        let dir = scratch_dir();
+        let src = dir.path().join("a.md");
+        fs::write(&src, "note").unwrap();
+
+        let moved = move_file(path_string(&src), path_string(&dir.path().join("b.md"))).unwrap();
+        assert!(moved.ends_with("b.md"));
+        assert!(!src.exists());
+        assert_eq!(fs::read_to_string(&moved).unwrap(), "note");
+
+        let renamed = rename_file(moved.clone(), "c.md".into()).unwrap();
+        assert_eq!(Path::new(&renamed).parent(), Path::new(&moved).parent());
+        assert!(rename_file(renamed.clone(), "../escape.md".into()).is_err());
+    }
+
+    #[test]
+    fn move_between_directories_falls_back_to_copy() {
+        // Separate temp dirs stand in for separate devices
+        let first = scratch_dir();
+        let second = scratch_dir();
+        let src = first.path().join("a.md");
+        let dst = second.path().join("a.md");
+        fs::write(&src, "note").unwrap();
+
+        move_file_internal(&src, &dst).unwrap();
+        assert!(!src.exists());
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "note");
+    }
+
+    #[test]
+    fn move_rejects_collisions_and_directories() {
+        let dir = scratch_dir();
+        let a = dir.path().join("a.md");
+        let b = dir.path().join("b.md");
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+
+        assert!(move_file(path_string(&a), path_string(&b)).unwrap_err().contains("already exists"));
+        assert!(rename_file(path_string(&a), "b.md".into()).is_err());
+        assert_eq!(fs::read_to_string(&b).unwrap(), "b");
+
+        let folder = dir.path().join("folder");
+        fs::create_dir(&folder).unwrap();
+        assert!(move_file(path_string(&folder), path_string(&dir.path().join("moved"))).unwrap_err().contains("directory"));
+    }
+
+    /// root/top.txt, root/a/mid.txt, root/a/b/deep.txt
+    fn three_level_tree() -> tempfile::TempDir {
+        let dir = scratch_dir();
+        fs::create_dir_all(dir.path().join("a").join("b")).unwrap();
+        fs::write(dir.path().join("top.txt"), "1").unwrap();
+        fs::write(dir.path().join("a").join("mid.txt"), "22").unwrap();
+        fs::write(dir.path().join("a").join("b").join("deep.txt"), "333").unwrap();
+        dir
+    }
+
+    fn file_names(paths: &[String]) -> Vec<String> {
+        let mut names: Vec<String> = paths.iter()
+            .map(|p| Path::new(p).file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn list_files_recursion_and_depth() {
+        let dir = three_level_tree();
+        let root = path_string(dir.path());
+        let list = |recursive, max_depth| list_files(root.clone(), None, recursive, max_depth, None, None, None).unwrap();
+
+        assert_eq!(file_names(&list(false, None)), vec!["top.txt"]);
+        assert_eq!(file_names(&list(true, None)), vec!["deep.txt", "mid.txt", "top.txt"]);
+        assert_eq!(file_names(&list(true, Some(1))), vec!["mid.txt", "top.txt"]);
+        assert_eq!(file_names(&list(true, Some(0))), vec!["top.txt"]);
+        assert!(list(true, None).iter().all(|p| Path::new(p).is_absolute()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn list_files_does_not_follow_symlinks() {
+        let dir = three_level_tree();
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("a").join("loop")).unwrap();
+        let files = list_files(path_string(dir.path()), None, true, None, None, None, None).unwrap();
+        assert_eq!(file_names(&files), vec!["deep.txt", "mid.txt", "top.txt"]);
+    }
+
+    #[test]
+    fn list_files_glob_patterns() {
+        let dir = scratch_dir();
+        for name in ["data.json", "notes.json", "report_01.docx", "report_123.docx", "report_ab.docx", "readme.md"] {
+            fs::write(dir.path().join(name), "x").unwrap();
+        }
+        let root = path_string(dir.path());
+        let list = |pattern: &str| list_files(root.clone(), Some(pattern.to_string()), false, None, None, None, None);
+
+        assert_eq!(file_names(&list("*.json").unwrap()), vec!["data.json", "notes.json"]);
+        assert_eq!(file_names(&list("report_??.docx").unwrap()), vec!["report_01.docx", "report_ab.docx"]);
+        assert!(list("[unclosed").unwrap_err().contains("Invalid file pattern"));
+        assert_eq!(list_files(root, None, false, None, None, None, None).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn file_metadata_timestamps() {
+        let dir = scratch_dir();
+        let file = dir.path().join("note.md");
+        let before = system_time_to_unix_secs(std::time::SystemTime::now());
+        fs::write(&file, "hello").unwrap();
+        let after = system_time_to_unix_secs(std::time::SystemTime::now());
+
+        let metadata = get_file_metadata(path_string(&file)).unwrap();
+        assert_eq!(metadata.size_bytes, 5);
+        assert!(metadata.modified_at >= before && metadata.modified_at <= after);
+        assert!(metadata.created_at > 0);
+        assert!(!metadata.is_dir && !metadata.is_symlink);
+        assert!(get_file_metadata(path_string(dir.path())).unwrap().is_dir);
+    }
+
+    #[test]
+    fn file_hash_matches_known_digests() {
+        let dir = scratch_dir();
+        let file = dir.path().join("abc.txt");
+        fs::write(&file, "abc").unwrap();
+        let file = path_string(&file);
+
+        assert_eq!(
+            compute_file_hash(file.clone(), "sha256".into()).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(compute_file_hash(file.clone(), "md5".into()).unwrap(), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(compute_file_hash(file, "crc32".into()).unwrap_err(), "Unsupported algorithm: crc32");
+    }
+
+    /// 300 KB of non-repeating-ish content for download tests
+    fn download_body() -> Vec<u8> {
+        (0..300_000u32).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[tokio::test]
+    async fn download_with_progress_reports_chunks() {
+        let body = download_body();
+        let served = body.clone();
+        let url = serve_http(move |request| http_response(request, "200 OK", &[], &served));
+        let dir = scratch_dir();
+        let file = dir.path().join("audio.bin");
+
+        let mut events = Vec::new();
+        download_with_progress(&format!("{}/file", url), &path_string(&file), |p| events.push(p)).await.unwrap();
+
+        assert_eq!(fs::read(&file).unwrap(), body);
+        assert!(events.len() >= 2, "expected several progress events, got {}", events.len());
+        assert!(events.windows(2).all(|w| w[0].bytes_downloaded < w[1].bytes_downloaded));
+        let last = events.last().unwrap();
+        assert_eq!(last.bytes_downloaded, body.len() as u64);
+        assert_eq!(last.total_bytes, Some(body.len() as u64));
+        // Only the final file remains - the temp file was renamed into place
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_progress_download_leaves_no_file() {
+        let url = serve_http(|request| http_response(request, "404 Not Found", &[], b"missing"));
+        let dir = scratch_dir();
+        let file = dir.path().join("audio.bin");
+
+        let err = download_with_progress(&url, &path_string(&file), |_| {}).await.unwrap_err();
+        assert!(err.contains("404"));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn download_retry_delay_doubles_and_caps() {
+        assert_eq!(download_retry_delay(0), Duration::from_millis(500));
+        assert_eq!(download_retry_delay(1), Duration::from_millis(1000));
+        assert_eq!(download_retry_delay(3), Duration::from_millis(4000));
+        assert_eq!(download_retry_delay(4), Duration::from_millis(8000));
+        assert_eq!(download_retry_delay(40), Duration::from_millis(8000));
+    }
+
+    #[tokio::test]
+    async fn download_retries_503_then_succeeds() {
+        let body = download_body();
+        let served = body.clone();
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let url = serve_http(move |request| {
+            if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                // Retry-After: 0 keeps the test fast while exercising the header path
+                http_response(request, "503 Service Unavailable", &[("Retry-After", "0".to_string())], b"busy")
+            } else {
+                http_response(request, "200 OK", &[], &served)
+            }
+        });
+        let dir = scratch_dir();
+        let file = dir.path().join("template.docx");
+
+        let written = download_file(url, path_string(&file), Some(3), None).await.unwrap();
+
+        assert_eq!(written, path_string(&file));
+        assert_eq!(fs::read(&file).unwrap(), body);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn download_gives_up_after_max_retries() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let url = serve_http(move |request| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            http_response(request, "429 Too Many Requests", &[("Retry-After", "0".to_string())], b"slow down")
+        });
+        let dir = scratch_dir();
+
+        let err = download_file(url, path_string(&dir.path().join("x.bin")), Some(2), None).await.unwrap_err();
+
+        assert!(err.contains("429"), "{}", err);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn download_fails_fast_on_client_errors() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let url = serve_http(move |request| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            http_response(request, "404 Not Found", &[], b"missing")
+        });
+        let dir = scratch_dir();
+        let file = dir.path().join("x.bin");
+
+        let started = Instant::now();
+        let err = download_file(url, path_string(&file), None, None).await.unwrap_err();
+
+        assert!(err.contains("404"), "{}", err);
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert!(started.elapsed() < Duration::from_millis(400));
+        assert!(!file.exists());
+    }
+
+    #[test]
+    fn download_file_is_send() {
+        assert_send(download_file(String::new(), String::new(), None, None));
+    }
+
+    fn sha256_hex(data: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(data))
+    }
+
+    #[tokio::test]
+    async fn download_checksum_match_keeps_file() {
+        let body = download_body();
+        let served = body.clone();
+        let url = serve_http(move |request| http_response(request, "200 OK", &[], &served));
+        let dir = scratch_dir();
+        let file = dir.path().join("pandoc.zip");
+
+        // Digest comparison is case-insensitive
+        let expected = sha256_hex(&body).to_uppercase();
+        download_file(url, path_string(&file), None, Some(expected)).await.unwrap();
+
+        assert_eq!(fs::read(&file).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn download_checksum_mismatch_discards_file() {
+        let body = download_body();
+        let served = body.clone();
+        let url = serve_http(move |request| http_response(request, "200 OK", &[], &served));
+        let dir = scratch_dir();
+        let file = dir.path().join("pandoc.zip");
+        let expected = sha256_hex(b"something else");
+
+        let err = download_file(url, path_string(&file), None, Some(expected.clone())).await.unwrap_err();
+
+        assert!(err.contains(&expected), "{}", err);
+        assert!(err.contains(&sha256_hex(&body)), "{}", err);
+        assert!(!file.exists());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn download_without_checksum_skips_verification() {
+        let url = serve_http(|request| http_response(request, "200 OK", &[], b"any bytes"));
+        let dir = scratch_dir();
+        let file = dir.path().join("notes.txt");
+
+        download_file(url, path_string(&file), None, None).await.unwrap();
+
+        assert_eq!(fs::read(&file).unwrap(), b"any bytes");
+    }
+
+    /// `Range: bytes=<n>-` start offset from a raw request head
+    fn requested_range_start(request: &str) -> Option<usize> {
+        request.lines()
+            .find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                // hyper sends header names in lowercase
+                name.eq_ignore_ascii_case("range").then(|| value.trim().strip_prefix("bytes="))?
+            })
+            .and_then(|range| range.trim_end_matches('-').parse().ok())
+    }
+
+    /// Serves `body` with byte-range support; `range_offset` shifts the start of every
+    /// 206 to simulate a server that resumes at the wrong place
+    fn serve_ranged(body: Vec<u8>, range_offset: usize) -> String {
+        serve_http(move |request| {
+            let accept = ("Accept-Ranges", "bytes".to_string());
+            match requested_range_start(request) {
+                Some(start) if start >= body.len() => http_response(
+                    request,
+                    "416 Range Not Satisfiable",
+                    &[accept, ("Content-Range", format!("bytes */{}", body.len()))],
+                    b"",
+                ),
+                Some(start) => {
+                    let start = (start + range_offset).min(body.len() - 1);
+                    http_response(
+                        request,
+                        "206 Partial Content",
+                        &[accept, ("Content-Range", format!("bytes {}-{}/{}", start, body.len() - 1, body.len()))],
+                        &body[start..],
+                    )
+                }
+                None => http_response(request, "200 OK", &[accept], &body),
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn resumable_download_appends_missing_range() {
+        let body = download_body();
+        let url = serve_ranged(body.clone(), 0);
+        let dir = scratch_dir();
+        let file = dir.path().join("recording.mp3");
+        fs::write(&file, &body[..100_000]).unwrap();
+
+        let mut warnings = Vec::new();
+        download_resumable(&url, &path_string(&file), |w| warnings.push(w)).await.unwrap();
+
+        assert_eq!(fs::read(&file).unwrap(), body);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+    }
+
+    #[tokio::test]
+    async fn resumable_download_restarts_on_misaligned_range() {
+        let body = download_body();
+        let url = serve_ranged(body.clone(), 10);
+        let dir = scratch_dir();
+        let file = dir.path().join("recording.mp3");
+        fs::write(&file, &body[..100_000]).unwrap();
+
+        let mut warnings = Vec::new();
+        download_resumable(&url, &path_string(&file), |w| warnings.push(w)).await.unwrap();
+
+        assert_eq!(fs::read(&file).unwrap(), body);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("instead of 100000"), "{}", warnings[0]);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn resumable_download_416_checks_remote_length() {
+        let body = download_body();
+        let url = serve_ranged(body.clone(), 0);
+        let dir = scratch_dir();
+
+        // Already complete - nothing is fetched or rewritten
+        let complete = dir.path().join("complete.mp3");
+        fs::write(&complete, &body).unwrap();
+        let mut warnings = Vec::new();
+        download_resumable(&url, &path_string(&complete), |w| warnings.push(w)).await.unwrap();
+        assert_eq!(fs::read(&complete).unwrap(), body);
+        assert!(warnings.is_empty());
+
+        // Longer than the remote file - a different file, so start again
+        let stale = dir.path().join("stale.mp3");
+        let mut longer = body.clone();
+        longer.extend_from_slice(b"trailing junk");
+        fs::write(&stale, &longer).unwrap();
+        download_resumable(&url, &path_string(&stale), |w| warnings.push(w)).await.unwrap();
+        assert_eq!(fs::read(&stale).unwrap(), body);
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn resumable_download_without_range_support_warns_and_restarts() {
+        let body = download_body();
+        let served = body.clone();
+        let url = serve_http(move |request| http_response(request, "200 OK", &[], &served));
+        let dir = scratch_dir();
+        let file = dir.path().join("recording.mp3");
+        fs::write(&file, b"partial junk").unwrap();
+
+        let mut warnings = Vec::new();
+        download_resumable(&url, &path_string(&file), |w| warnings.push(w)).await.unwrap();
+
+        assert_eq!(fs::read(&file).unwrap(), body);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("does not support resuming"));
+        // The .part file was renamed into place
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_fresh_download_keeps_partial_file() {
+        let url = serve_http(|request| {
+            if request.starts_with("HEAD ") {
+                http_response(request, "200 OK", &[], b"")
+            } else {
+                http_response(request, "500 Internal Server Error", &[], b"oops")
+            }
+        });
+        let dir = scratch_dir();
+        let file = dir.path().join("recording.mp3");
+        fs::write(&file, b"partial").unwrap();
+
+        let err = download_resumable(&url, &path_string(&file), |_| {}).await.unwrap_err();
+
+        assert!(err.contains("500"), "{}", err);
+        assert_eq!(fs::read(&file).unwrap(), b"partial");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn content_range_start_parsing() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        assert_eq!(content_range_start(&headers), None);
+        headers.insert(reqwest::header::CONTENT_RANGE, "bytes 100-199/200".parse().unwrap());
+        assert_eq!(content_range_start(&headers), Some(100));
+        headers.insert(reqwest::header::CONTENT_RANGE, "bytes */200".parse().unwrap());
+        assert_eq!(content_range_start(&headers), None);
+    }
+
+    /// File names in the order they were returned
+    fn names_in_order(paths: &[String]) -> Vec<String> {
+        paths.iter()
+            .map(|p| Path::new(p).file_name().unwrap().to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn list_files_sorting() {
+        let dir = scratch_dir();
+        let now = std::time::SystemTime::now();
+        // (name, size, age in seconds): name, size and age orders all differ
+        for (name, size, age) in [("b.txt", 30, 10), ("c.txt", 10, 300), ("a.txt", 20, 100)] {
+            let file = fs::File::create(dir.path().join(name)).unwrap();
+            file.set_len(size).unwrap();
+            file.set_modified(now - Duration::from_secs(age)).unwrap();
+        }
+        let root = path_string(dir.path());
+        let sorted = |sort_by: &str, ascending: Option<bool>| {
+            names_in_order(&list_files(root.clone(), None, false, None, Some(sort_by.to_string()), ascending, None).unwrap())
+        };
+
+        assert_eq!(sorted("name", Some(false)), ["c.txt", "b.txt", "a.txt"]);
+        assert_eq!(sorted("name", None), ["a.txt", "b.txt", "c.txt"]);
+        assert_eq!(sorted("size", Some(true)), ["c.txt", "a.txt", "b.txt"]);
+        assert_eq!(sorted("modified", Some(true)), ["c.txt", "a.txt", "b.txt"]);
+        assert_eq!(sorted("modified", Some(false)), ["b.txt", "a.txt", "c.txt"]);
+        assert!(list_files(root, None, false, None, Some("colour".to_string()), None, None).is_err());
+    }
+
+    #[test]
+    fn list_files_extension_filter() {
+        let dir = scratch_dir();
+        for name in ["letter.docx", "REPORT.DOCX", "notes.Docx", "letter.docx.bak", "data.json", "docx"] {
+            fs::write(dir.path().join(name), "x").unwrap();
+        }
+        let root = path_string(dir.path());
+        let list = |pattern: Option<&str>, extension: &str| {
+            file_names(&list_files(root.clone(), pattern.map(String::from), false, None, None, None, Some(extension.to_string())).unwrap())
+        };
+
+        assert_eq!(list(None, "docx"), ["REPORT.DOCX", "letter.docx", "notes.Docx"]);
+        // A leading dot is accepted, and the filter combines with the pattern (AND)
+        assert_eq!(list(None, ".json"), ["data.json"]);
+        assert_eq!(list(Some("letter*"), "docx"), ["letter.docx"]);
+        assert_eq!(list(None, "bak"), ["letter.docx.bak"]);
+    }
+
+    #[test]
+    fn list_files_with_metadata_reports_sizes() {
+        let dir = scratch_dir();
+        fs::write(dir.path().join("note.md"), "hello world").unwrap();
+        fs::write(dir.path().join("empty.txt"), "").unwrap();
+        fs::create_dir(dir.path().join("attachments")).unwrap();
+        let root = path_string(dir.path());
+
+        let mut entries = list_files_with_metadata(root.clone(), None).unwrap();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let summary: Vec<(String, u64, bool)> = entries.iter()
+            .map(|e| (names_in_order(std::slice::from_ref(&e.path)).remove(0), e.size_bytes, e.is_dir))
+            .collect();
+        assert_eq!(summary[1..], [("empty.txt".to_string(), 0, false), ("note.md".to_string(), 11, false)]);
+        assert_eq!(summary[0].0, "attachments");
+        assert!(summary[0].2);
+        assert!(entries.iter().all(|e| e.error.is_none() && e.modified_at > 0));
+
+        let notes = list_files_with_metadata(root, Some("*.md".to_string())).unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].size_bytes, 11);
+    }
+
+    #[test]
+    fn directory_size_sums_nested_files() {
+        let dir = scratch_dir();
+        fs::create_dir_all(dir.path().join("2024/consults")).unwrap();
+        fs::write(dir.path().join("intake.md"), vec![b'x'; 100]).unwrap();
+        fs::write(dir.path().join("2024/invoice.pdf"), vec![b'x'; 2048]).unwrap();
+        fs::write(dir.path().join("2024/consults/recording.m4a"), vec![b'x'; 5000]).unwrap();
+        fs::create_dir(dir.path().join("empty")).unwrap();
+
+        let result = calculate_directory_size(path_string(dir.path())).unwrap();
+
+        assert_eq!(result.total_bytes, 7148);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn directory_size_skips_symlinks() {
+        let dir = scratch_dir();
+        let client = dir.path().join("client");
+        fs::create_dir(&client).unwrap();
+        fs::write(client.join("note.md"), vec![b'x'; 10]).unwrap();
+        let other = dir.path().join("other");
+        fs::create_dir(&other).unwrap();
+        fs::write(other.join("big.bin"), vec![b'x'; 4096]).unwrap();
+        std::os::unix::fs::symlink(&other, client.join("linked_dir")).unwrap();
+        std::os::unix::fs::symlink(other.join("big.bin"), client.join("linked_file")).unwrap();
+
+        assert_eq!(calculate_directory_size(path_string(&client)).unwrap().total_bytes, 10);
+        assert!(calculate_directory_size(path_string(&client.join("note.md"))).err().unwrap().contains("not a directory"));
+    }
+
+    #[test]
+    fn copy_file_respects_overwrite() {
+        let dir = scratch_dir();
+        let src = dir.path().join("template.docx");
+        let dst = dir.path().join("copy.docx");
+        fs::write(&src, "template").unwrap();
+        fs::write(&dst, "existing").unwrap();
+
+        let err = copy_file(path_string(&src), path_string(&dst), false).unwrap_err();
+        assert!(err.contains("already exists"), "{}", err);
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "existing");
+
+        copy_file(path_string(&src), path_string(&dst), true).unwrap();
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "template");
+        assert!(copy_file(path_string(dir.path()), path_string(&dir.path().join("x")), true).unwrap_err().contains("copy_directory"));
+    }
+
+    #[test]
+    fn copy_directory_copies_tree_including_empty_dirs() {
+        let dir = scratch_dir();
+        let src = dir.path().join("Client A");
+        fs::create_dir_all(src.join("notes/2024")).unwrap();
+        fs::create_dir_all(src.join("empty")).unwrap();
+        fs::write(src.join("intake.md"), "intake").unwrap();
+        fs::write(src.join("notes/2024/consult.md"), "consult").unwrap();
+        let dst = dir.path().join("Client A copy");
+
+        assert_eq!(copy_directory(path_string(&src), path_string(&dst), false).unwrap(), 2);
+
+        assert_eq!(fs::read_to_string(dst.join("notes/2024/consult.md")).unwrap(), "consult");
+        assert_eq!(fs::read_to_string(dst.join("intake.md")).unwrap(), "intake");
+        assert!(dst.join("empty").is_dir());
+        let err = copy_directory(path_string(&src), path_string(&src.join("nested")), false).unwrap_err();
+        assert!(err.contains("into itself"), "{}", err);
+    }
+
+    #[test]
+    fn copy_directory_reports_all_conflicts_before_copying() {
+        let dir = scratch_dir();
+        let src = dir.path().join("src");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        for name in ["a.md", "b.md", "sub/c.md"] {
+            fs::write(src.join(name), "new").unwrap();
+        }
+        let dst = dir.path().join("dst");
+        fs::create_dir_all(dst.join("sub")).unwrap();
+        fs::write(dst.join("a.md"), "old").unwrap();
+        fs::write(dst.join("sub/c.md"), "old").unwrap();
+
+        let err = copy_directory(path_string(&src), path_string(&dst), false).unwrap_err();
+
+        assert!(err.contains(&path_string(&dst.join("a.md"))), "{}", err);
+        assert!(err.contains(&path_string(&dst.join("sub/c.md"))), "{}", err);
+        // Nothing was copied - not even the non-conflicting file
+        assert!(!dst.join("b.md").exists());
+        assert_eq!(fs::read_to_string(dst.join("a.md")).unwrap(), "old");
+
+        assert_eq!(copy_directory(path_string(&src), path_string(&dst), true).unwrap(), 3);
+        assert_eq!(fs::read_to_string(dst.join("sub/c.md")).unwrap(), "new");
+    }
+
+    fn search_summary(matches: &[SearchMatch]) -> Vec<(String, u32)> {
+        let mut summary: Vec<(String, u32)> = matches.iter()
+            .map(|m| (names_in_order(std::slice::from_ref(&m.file_path)).remove(0), m.line_number))
+            .collect();
+        summary.sort();
+        summary
+    }
+
+    #[test]
+    fn search_in_files_reports_line_numbers() {
+        let dir = scratch_dir();
+        fs::create_dir(dir.path().join("2024")).unwrap();
+        fs::write(dir.path().join("intake.md"), "Client: Jane Doe\nDog: Max\nNotes: max pulls on lead\n").unwrap();
+        fs::write(dir.path().join("2024/consult.txt"), "Follow-up\n\nMax is calmer\n").unwrap();
+        fs::write(dir.path().join("2024/data.json"), "{\"dog\": \"MAX\"}").unwrap();
+        fs::write(dir.path().join("letter.docx"), "Max").unwrap();
+        fs::write(dir.path().join("binary.txt"), [0xFFu8, 0xFE, b'M', b'a', b'x']).unwrap();
+        let root = path_string(dir.path());
+
+        let sensitive = search_in_files(root.clone(), "Max".to_string(), false, true).unwrap();
+        assert_eq!(search_summary(&sensitive), [("consult.txt".to_string(), 3), ("intake.md".to_string(), 2)]);
+        let intake = sensitive.iter().find(|m| m.file_path.ends_with("intake.md")).unwrap();
+        assert_eq!(intake.line_content, "Dog: Max");
+
+        let insensitive = search_in_files(root.clone(), "max".to_string(), false, false).unwrap();
+        assert_eq!(insensitive.len(), 4);
+
+        let regex = search_in_files(root.clone(), r"^(Dog|Client):".to_string(), true, true).unwrap();
+        assert_eq!(search_summary(&regex), [("intake.md".to_string(), 1), ("intake.md".to_string(), 2)]);
+    }
+
+    #[test]
+    fn search_in_files_escapes_plain_queries_and_rejects_bad_regex() {
+        let dir = scratch_dir();
+        fs::write(dir.path().join("fees.md"), "Consult fee (1.5h)\nConsult fee 115h\n").unwrap();
+        let root = path_string(dir.path());
+
+        let plain = search_in_files(root.clone(), "(1.5h)".to_string(), false, true).unwrap();
+        assert_eq!(search_summary(&plain), [("fees.md".to_string(), 1)]);
+
+        let err = search_in_files(root.clone(), "([".to_string(), true, true).err().unwrap();
+        assert!(err.contains("Invalid regular expression"), "{}", err);
+        assert!(search_in_files(root, String::new(), false, true).err().unwrap().contains("cannot be empty"));
+    }
+
+    #[test]
+    fn create_client_folder_structure_creates_all_subfolders() {
+        let dir = scratch_dir();
+        let base = path_string(dir.path());
+
+        let root = create_client_folder_structure(base.clone(), "42".to_string(), "Jane/Doe: Smith?".to_string()).unwrap();
+        let root = PathBuf::from(root);
+        assert_eq!(root.file_name().unwrap(), "42_Jane_Doe_ Smith_");
+        for subfolder in CLIENT_SUBFOLDERS {
+            assert!(root.join(subfolder).is_dir(), "{} missing", subfolder);
+        }
+        assert_eq!(CLIENT_SUBFOLDERS.len(), 6);
+
+        // Running again keeps existing folders and their contents
+        fs::write(root.join("Billing").join("invoice.pdf"), b"%PDF").unwrap();
+        create_client_folder_structure(base, "42".to_string(), "Jane/Doe: Smith?".to_string()).unwrap();
+        assert!(root.join("Billing").join("invoice.pdf").exists());
+    }
+
+    #[test]
+    fn create_client_folder_structure_rejects_bad_bases_and_names() {
+        let dir = scratch_dir();
+        let file = dir.path().join("not_a_dir.txt");
+        fs::write(&file, "x").unwrap();
+        let err = create_client_folder_structure(path_string(&file), "1".to_string(), "A".to_string()).unwrap_err();
+        assert!(err.contains("not a directory"), "{}", err);
+
+        let outside = tempfile::tempdir().unwrap();
+        let err = create_client_folder_structure(path_string(outside.path()), "1".to_string(), "A".to_string()).unwrap_err();
+        assert!(err.contains("Access denied"), "{}", err);
+
+        let err = create_client_folder_structure(path_string(dir.path()), "..".to_string(), "..".to_string()).unwrap_err();
+        assert!(err.contains("Invalid client folder name"), "{}", err);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    /// Write a ZIP with the given (entry name, contents) pairs, names taken verbatim
+    fn raw_zip(path: &Path, entries: &[(&str, &str)]) {
+        let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, content) in entries {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn zip_directory_round_trips_through_unzip_archive() {
+        let dir = scratch_dir();
+        let source = dir.path().join("Client_42");
+        fs::create_dir_all(source.join("Session_Notes").join("2024")).unwrap();
+        fs::write(source.join("intake.txt"), "intake").unwrap();
+        fs::write(source.join("Session_Notes").join("2024").join("note.md"), "# Note").unwrap();
+
+        let archive = dir.path().join("client.zip");
+        fs::write(&archive, "stale archive").unwrap();
+        let size = zip_directory(path_string(&source), path_string(&archive), Some(42)).unwrap();
+        assert_eq!(size, fs::metadata(&archive).unwrap().len());
+        // Only the finished archive is left beside it - no temp files
+        let mut siblings: Vec<String> = fs::read_dir(dir.path()).unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        siblings.sort();
+        assert_eq!(siblings, ["Client_42", "client.zip"]);
+
+        let restored = dir.path().join("restored");
+        assert_eq!(unzip_archive(path_string(&archive), path_string(&restored), false).unwrap(), 2);
+        assert_eq!(fs::read_to_string(restored.join("intake.txt")).unwrap(), "intake");
+        assert_eq!(fs::read_to_string(restored.join("Session_Notes").join("2024").join("note.md")).unwrap(), "# Note");
+
+        // Existing files are only replaced when asked
+        let err = unzip_archive(path_string(&archive), path_string(&restored), false).unwrap_err();
+        assert!(err.contains("Destination files already exist"), "{}", err);
+        fs::write(restored.join("intake.txt"), "edited").unwrap();
+        unzip_archive(path_string(&archive), path_string(&restored), true).unwrap();
+        assert_eq!(fs::read_to_string(restored.join("intake.txt")).unwrap(), "intake");
+    }
+
+    #[test]
+    fn zip_directory_refuses_output_inside_source() {
+        let dir = scratch_dir();
+        let err = zip_directory(path_string(dir.path()), path_string(&dir.path().join("self.zip")), None).unwrap_err();
+        assert!(err.contains("cannot be inside"), "{}", err);
+        assert!(!dir.path().join("self.zip").exists());
+    }
+
+    #[test]
+    fn unzip_archive_refuses_zip_slip_entries() {
+        let dir = scratch_dir();
+        let archive = dir.path().join("evil.zip");
+        raw_zip(&archive, &[("readme.txt", "hello"), ("../../../etc/passwd", "root::0:0")]);
+        let output = dir.path().join("out");
+
+        let err = unzip_archive(path_string(&archive), path_string(&output), true).unwrap_err();
+        assert!(err.contains("Unsafe path in archive") && err.contains("../../../etc/passwd"), "{}", err);
+        // Nothing was extracted, not even the safe entry listed first
+        assert!(!output.join("readme.txt").exists());
+        assert!(!dir.path().join("etc").exists());
+    }
+
+    #[test]
+    fn sanitize_filename_replaces_reserved_and_control_characters() {
+        assert_eq!(sanitize_filename_str(r#"a/b\c:d*e?f"g<h>i|j"#, "_"), "a_b_c_d_e_f_g_h_i_j");
+        assert_eq!(sanitize_filename_str("tab\there\nnew", "-"), "tab-here-new");
+        assert_eq!(sanitize_filename_str("Café – notes.md", "_"), "Café – notes.md");
+        assert_eq!(sanitize_filename("a:b".to_string(), Some("".to_string())).unwrap(), "ab");
+    }
+
+    #[test]
+    fn sanitize_filename_handles_windows_reserved_names() {
+        for name in ["CON", "prn", "Nul.txt", "COM1.log", "lpt9", "aux "] {
+            let sanitized = sanitize_filename_str(name, "_");
+            assert!(sanitized.starts_with('_'), "{} -> {}", name, sanitized);
+        }
+        assert_eq!(sanitize_filename_str("CONSOLE.txt", "_"), "CONSOLE.txt");
+        assert_eq!(sanitize_filename_str("COM10", "_"), "COM10");
+    }
+
+    #[test]
+    fn sanitize_filename_trims_dots_and_spaces() {
+        assert_eq!(sanitize_filename_str("  ..report.docx.. ", "_"), "report.docx");
+        assert_eq!(sanitize_filename_str("...", "_"), "");
+        assert!(sanitize_filename("...".to_string(), None).unwrap_err().contains("empty after sanitizing"));
+    }
+
+    #[test]
+    fn sanitize_filename_truncates_at_a_character_boundary() {
+        let long = "é".repeat(200); // 400 bytes
+        let sanitized = sanitize_filename_str(&long, "_");
+        assert!(sanitized.len() <= 255);
+        assert_eq!(sanitized.len(), 254);
+        assert!(sanitized.chars().all(|c| c == 'é'));
+
+        // Truncation can expose a trailing dot, which is trimmed again
+        let dotted = format!("{}.{}", "a".repeat(254), "bbbb");
+        assert_eq!(sanitize_filename_str(&dotted, "_"), "a".repeat(254));
+    }
+
+    #[test]
+    fn sanitize_filename_rejects_unsafe_replacement() {
+        let err = sanitize_filename("a b".to_string(), Some("/".to_string())).unwrap_err();
+        assert!(err.contains("Replacement contains characters not allowed"), "{}", err);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn concurrent_writers_exactly_one_gets_the_lock() {
+        let dir = scratch_dir();
+        let file = path_string(&dir.path().join("client_42.json"));
+        let locks = Arc::new(FileLockRegistry::default());
+        let (locked_tx, locked_rx) = tokio::sync::oneshot::channel();
+        let (attempted_tx, attempted_rx) = tokio::sync::oneshot::channel::<()>();
+
+        // Writer A is mid-write (holding the lock) when writer B arrives
+        let first = tokio::spawn({
+            let (locks, file) = (locks.clone(), file.clone());
+            async move {
+                let path = validate_write_path(&file)?;
+                let _guard = locks.try_lock(&path)?;
+                locked_tx.send(()).unwrap();
+                attempted_rx.await.unwrap();
+                write_file_atomically(&path, br#"{"writer":"A"}"#)
+            }
+        });
+        let second = tokio::spawn({
+            let (locks, file) = (locks.clone(), file.clone());
+            async move {
+                locked_rx.await.unwrap();
+                let result = tokio::task::spawn_blocking(move || {
+                    let text = write_text_file_locked(&locks, file.clone(), r#"{"writer":"B"}"#.to_string());
+                    let binary = write_binary_file_locked(&locks, file, b"B".to_vec());
+                    (text, binary)
+                }).await.unwrap();
+                attempted_tx.send(()).unwrap();
+                result
+            }
+        });
+
+        let first = first.await.unwrap();
+        let (text, binary) = second.await.unwrap();
+        assert!(first.is_ok(), "{:?}", first);
+        assert_eq!(text.unwrap_err(), "File is locked by another operation");
+        assert_eq!(binary.unwrap_err(), "File is locked by another operation");
+        assert_eq!(fs::read_to_string(&file).unwrap(), r#"{"writer":"A"}"#);
+
+        // The guard released the path, so the next write goes through
+        write_text_file_locked(&locks, file.clone(), "{}".to_string()).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "{}");
+    }
+
+    #[test]
+    fn racing_writers_never_corrupt_the_file() {
+        let dir = scratch_dir();
+        let file = path_string(&dir.path().join("record.json"));
+        let locks = Arc::new(FileLockRegistry::default());
+        let start = Arc::new(std::sync::Barrier::new(8));
+        let payloads: Vec<String> = (0..8).map(|i| format!("{{\"writer\":{}}}{}", i, " ".repeat(200_000))).collect();
+
+        let handles: Vec<_> = payloads.iter().cloned().map(|payload| {
+            let (locks, file, start) = (locks.clone(), file.clone(), start.clone());
+            std::thread::spawn(move || {
+                start.wait();
+                write_text_file_locked(&locks, file, payload)
+            })
+        }).collect();
+
+        for handle in handles {
+            if let Err(e) = handle.join().unwrap() {
+                assert_eq!(e, "File is locked by another operation");
+            }
+        }
+        let content = fs::read_to_string(&file).unwrap();
+        assert!(payloads.contains(&content), "file holds a mix of writes");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert!(locks.0.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn watched_directory_reports_new_files_within_two_seconds() {
+        let dir = scratch_dir();
+        let watched = validate_read_path(&path_string(dir.path())).unwrap();
+        fs::create_dir(watched.join("Session_Notes")).unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let tx = Mutex::new(tx);
+        let watcher = start_directory_watcher(&watched, move |change| {
+            let _ = tx.lock().unwrap().send(change);
+        }).unwrap();
+
+        // Recursive: a file in a subfolder is seen too
+        let note = watched.join("Session_Notes").join("2025-01-15.md");
+        fs::write(&note, "# Session").unwrap();
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(2);
+        let mut created = false;
+        while let Some(remaining) = deadline.checked_duration_since(std::time::Instant::now()) {
+            match rx.recv_timeout(remaining) {
+                Ok(change) if change.kind == "create" && change.path == path_string(&note) => {
+                    assert_eq!(change.watch_id, path_string(&watched));
+                    created = true;
+                    break;
+                }
+                Ok(_) => continue,
+                Err(_) => break,
+            }
+        }
+        drop(watcher);
+        assert!(created, "no create event for {}", note.display());
+    }
+
+    #[test]
+    fn fs_change_events_map_kinds_and_skip_access() {
+        use notify::event::{AccessKind, CreateKind, ModifyKind, RemoveKind};
+        use notify::{Event, EventKind};
+
+        let event = |kind| Event::new(kind).add_path(PathBuf::from("/a.md")).add_path(PathBuf::from("/b.md"));
+        let kinds = |kind| fs_change_events(&event(kind), "/").into_iter().map(|c| c.kind).collect::<Vec<_>>();
+
+        assert_eq!(kinds(EventKind::Create(CreateKind::File)), ["create", "create"]);
+        assert_eq!(kinds(EventKind::Modify(ModifyKind::Any)), ["modify", "modify"]);
+        assert_eq!(kinds(EventKind::Remove(RemoveKind::File)), ["delete", "delete"]);
+        assert!(kinds(EventKind::Access(AccessKind::Any)).is_empty());
+        assert_eq!(fs_change_events(&event(EventKind::Create(CreateKind::Any)), "/")[1], FsChangeEvent {
+            watch_id: "/".to_string(),
+            kind: "create",
+            path: "/b.md".to_string(),
+        });
+    }
+
+    #[test]
+    fn directory_watchers_are_kept_per_window() {
+        let dir = scratch_dir();
+        let watched = validate_read_path(&path_string(dir.path())).unwrap();
+        let key = path_string(&watched);
+        let watchers = DirectoryWatchers::default();
+        for label in ["main", "client-42"] {
+            let watcher = start_directory_watcher(&watched, |_| {}).unwrap();
+            watchers.0.lock().unwrap().insert((label.to_string(), key.clone()), watcher);
+        }
+
+        stop_watching_for(&watchers, "client-42", &path_string(dir.path())).unwrap();
+        let remaining: Vec<_> = watchers.0.lock().unwrap().keys().cloned().collect();
+        assert_eq!(remaining, vec![("main".to_string(), key.clone())]);
+
+        let err = stop_watching_for(&watchers, "client-42", &key).unwrap_err();
+        assert!(err.starts_with("Directory is not being watched"), "{}", err);
+        stop_watching_for(&watchers, "main", &key).unwrap();
+    }
+
+    #[test]
+    fn start_directory_watcher_fails_for_missing_directory() {
+        let dir = scratch_dir();
+        let err = start_directory_watcher(&dir.path().join("gone"), |_| {}).err().unwrap();
+        assert!(err.contains("Failed to watch directory"), "{}", err);
+    }
+
+    #[test]
+    fn chunked_reads_concatenate_to_the_original() {
+        let dir = scratch_dir();
+        let file = dir.path().join("transcript.txt");
+        let original = "Line one\nLigne deux — café\n第三行\nLast line 🐕\n";
+        fs::write(&file, original).unwrap();
+        let path = path_string(&file);
+
+        let first = read_large_file_chunked(path.clone(), 0, 20).unwrap();
+        assert!(!first.is_eof);
+        let second = read_large_file_chunked(path.clone(), first.next_offset, 1024).unwrap();
+        assert!(second.is_eof);
+        assert_eq!(second.next_offset, original.len() as u64);
+        assert_eq!(format!("{}{}", first.content, second.content), original);
+
+        // Any chunk that can hold the widest character (4 bytes) reassembles exactly
+        for chunk_size in 4..=9 {
+            let (mut offset, mut text) = (0, String::new());
+            loop {
+                let chunk = read_large_file_chunked(path.clone(), offset, chunk_size).unwrap();
+                assert!(!chunk.content.contains('\u{FFFD}'), "chunk size {}", chunk_size);
+                text.push_str(&chunk.content);
+                offset = chunk.next_offset;
+                if chunk.is_eof {
+                    break;
+                }
+            }
+            assert_eq!(text, original, "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn chunked_read_edge_cases() {
+        let dir = scratch_dir();
+        let file = dir.path().join("log.txt");
+        fs::write(&file, "abc").unwrap();
+        let path = path_string(&file);
+
+        let past_end = read_large_file_chunked(path.clone(), 10, 5).unwrap();
+        assert_eq!(past_end.content, "");
+        assert!(past_end.is_eof);
+        assert_eq!(read_large_file_chunked(path, 0, 0).err().unwrap(), "Chunk size must be greater than zero");
+
+        assert_eq!(incomplete_utf8_suffix_len("ab".as_bytes()), 0);
+        assert_eq!(incomplete_utf8_suffix_len(&"é".as_bytes()[..1]), 1);
+        assert_eq!(incomplete_utf8_suffix_len(&"🐕".as_bytes()[..3]), 3);
+        assert_eq!(incomplete_utf8_suffix_len("🐕".as_bytes()), 0);
+    }
+
+    #[test]
+    fn sftp_host_parsing() {
+        assert_eq!(split_sftp_host("files.clinic.local").unwrap(), ("files.clinic.local".to_string(), 22));
+        assert_eq!(split_sftp_host(" 10.0.0.5:2222 ").unwrap(), ("10.0.0.5".to_string(), 2222));
+        // A bare IPv6 address is not mistaken for host:port
+        assert_eq!(split_sftp_host("::1").unwrap(), ("::1".to_string(), 22));
+        assert!(split_sftp_host("host:notaport").unwrap_err().contains("Invalid port"));
+        assert_eq!(split_sftp_host("  ").unwrap_err(), "Remote host is required");
+    }
+
+    #[test]
+    fn sftp_requires_an_existing_private_key() {
+        let dir = scratch_dir();
+        let err = open_sftp("127.0.0.1:1", "clinic", &path_string(&dir.path().join("id_ed25519")), &dir.path().join("known_hosts"))
+            .err().unwrap();
+        assert!(err.starts_with("Private key not found"), "{}", err);
+    }
+
+    #[test]
+    fn sftp_handshake_fails_against_a_non_ssh_server() {
+        let dir = scratch_dir();
+        let key = dir.path().join("id_ed25519");
+        fs::write(&key, "not really a key").unwrap();
+
+        // Speaks HTTP instead of SSH, then hangs up
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let _ = stream.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n");
+            }
+        });
+
+        let err = open_sftp(&format!("127.0.0.1:{}", port), "clinic", &path_string(&key), &dir.path().join("known_hosts"))
+            .err().unwrap();
+        assert!(err.starts_with("SSH handshake failed"), "{}", err);
+
+        // Nothing listening at all
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let err = open_sftp(&format!("127.0.0.1:{}", closed), "clinic", &path_string(&key), &dir.path().join("known_hosts"))
+            .err().unwrap();
+        assert!(err.starts_with("Failed to connect to 127.0.0.1"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn sftp_upload_validates_local_path_first() {
+        let outside = tempfile::tempdir().unwrap();
+        let file = outside.path().join("notes.md");
+        fs::write(&file, "x").unwrap();
+        let err = sftp_upload(path_string(&file), "127.0.0.1:1".into(), "/notes.md".into(), "clinic".into(), "key".into())
+            .await
+            .unwrap_err();
+        assert!(err.contains("Access denied"), "{}", err);
+    }
+
+    /// Cheap Argon2 costs so the format tests stay fast
+    const TEST_KDF_PARAMS: KdfParams = KdfParams { m_cost: 64, t_cost: 1, p_cost: 1 };
+
+    #[test]
+    fn encrypt_decrypt_file_round_trip_and_wrong_password() {
+        let dir = scratch_dir();
+        let plain = dir.path().join("notes.md");
+        fs::write(&plain, "Confidential: Max, reactive to dogs").unwrap();
+        let sealed = dir.path().join("notes.md.enc");
+        let restored = dir.path().join("restored.md");
+
+        encrypt_file(path_string(&plain), path_string(&sealed), "correct horse".to_string()).unwrap();
+        let blob = fs::read(&sealed).unwrap();
+        assert_eq!(blob.len(), ENCRYPTION_SALT_LEN + ENCRYPTION_NONCE_LEN + 35 + ENCRYPTION_TAG_LEN);
+        assert!(!blob.windows(12).any(|w| w == b"Confidential"));
+
+        let err = decrypt_file(path_string(&sealed), path_string(&restored), "wrong horse".to_string()).unwrap_err();
+        assert_eq!(err, "Authentication failed");
+        assert!(!restored.exists(), "nothing is written for a wrong password");
+
+        decrypt_file(path_string(&sealed), path_string(&restored), "correct horse".to_string()).unwrap();
+        assert_eq!(fs::read_to_string(&restored).unwrap(), "Confidential: Max, reactive to dogs");
+
+        assert_eq!(
+            encrypt_file(path_string(&plain), path_string(&sealed), String::new()).unwrap_err(),
+            "Password is required"
+        );
+    }
+
+    #[test]
+    fn encrypted_layout_is_salt_nonce_ciphertext_tag() {
+        use aes_gcm::aead::{Aead, KeyInit};
+
+        let blob = encrypt_bytes_with(b"session", "pw", TEST_KDF_PARAMS).unwrap();
+        assert_eq!(blob.len(), ENCRYPTION_SALT_LEN + ENCRYPTION_NONCE_LEN + 7 + ENCRYPTION_TAG_LEN);
+        assert_eq!(decrypt_bytes_with(&blob, "pw", TEST_KDF_PARAMS).unwrap(), b"session");
+
+        // Decrypt by hand from the documented layout
+        let (salt, rest) = blob.split_at(ENCRYPTION_SALT_LEN);
+        let (nonce, ciphertext_and_tag) = rest.split_at(ENCRYPTION_NONCE_LEN);
+        let key = derive_file_key("pw", salt, TEST_KDF_PARAMS).unwrap();
+        let cipher = aes_gcm::Aes256Gcm::new_from_slice(&key).unwrap();
+        assert_eq!(cipher.decrypt(aes_gcm::Nonce::from_slice(nonce), ciphertext_and_tag).unwrap(), b"session");
+
+        // Two encryptions of the same data use fresh salts and nonces
+        let again = encrypt_bytes_with(b"session", "pw", TEST_KDF_PARAMS).unwrap();
+        assert_ne!(blob[..ENCRYPTION_SALT_LEN + ENCRYPTION_NONCE_LEN], again[..ENCRYPTION_SALT_LEN + ENCRYPTION_NONCE_LEN]);
+    }
+
+    #[test]
+    fn decrypt_rejects_tampered_or_truncated_data() {
+        let blob = encrypt_bytes_with(b"session", "pw", TEST_KDF_PARAMS).unwrap();
+        for index in [0, ENCRYPTION_SALT_LEN, ENCRYPTION_SALT_LEN + ENCRYPTION_NONCE_LEN, blob.len() - 1] {
+            let mut tampered = blob.clone();
+            tampered[index] ^= 1;
+            assert_eq!(decrypt_bytes_with(&tampered, "pw", TEST_KDF_PARAMS).unwrap_err(), "Authentication failed", "byte {}", index);
+        }
+        assert_eq!(decrypt_bytes_with(&blob, "other", TEST_KDF_PARAMS).unwrap_err(), "Authentication failed");
+
+        let too_short = vec![0u8; ENCRYPTION_SALT_LEN + ENCRYPTION_NONCE_LEN + ENCRYPTION_TAG_LEN - 1];
+        assert_eq!(decrypt_bytes(&too_short, "pw").unwrap_err(), "Encrypted file is too short or corrupted");
+    }
+
+    #[test]
+    fn write_text_file_appends_an_audit_line() {
+        let app = tauri::test::mock_app();
+        app.manage(FileLockRegistry::default());
+        let dir = scratch_dir();
+        let file = path_string(&dir.path().join("audited_note.md"));
+
+        write_text_file(app.state::<FileLockRegistry>(), file.clone(), "# Note".to_string()).unwrap();
+        let missing_parent = path_string(&dir.path().join("missing").join("note.md"));
+        write_text_file(app.state::<FileLockRegistry>(), missing_parent.clone(), "x".to_string()).unwrap_err();
+
+        let entries = audit_entries_for(&file);
+        assert_eq!(entries.len(), 1, "{:?}", entries);
+        assert_eq!(entries[0].command, "write_text_file");
+        assert_eq!(entries[0].outcome, "success");
+        let failures = audit_entries_for(&missing_parent);
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].outcome.starts_with("error: ") && failures[0].outcome.contains("parent directory does not exist"), "{:?}", failures);
+    }
+
+    #[test]
+    fn archive_and_crypto_commands_are_audited() {
+        let dir = scratch_dir();
+        let folder = dir.path().join("audited_folder");
+        fs::create_dir(&folder).unwrap();
+        fs::write(folder.join("a.txt"), "a").unwrap();
+        let zip = dir.path().join("audited.zip");
+        let sealed = dir.path().join("audited.enc");
+
+        zip_directory(path_string(&folder), path_string(&zip), None).unwrap();
+        unzip_archive(path_string(&zip), path_string(&dir.path().join("audited_out")), false).unwrap();
+        copy_directory(path_string(&folder), path_string(&dir.path().join("audited_copy")), false).unwrap();
+        encrypt_file(path_string(&folder.join("a.txt")), path_string(&sealed), "pw".to_string()).unwrap();
+        decrypt_file(path_string(&sealed), path_string(&dir.path().join("audited.txt")), "nope".to_string()).unwrap_err();
+        delete_folder(path_string(&folder), true).unwrap();
+
+        let root = path_string(dir.path());
+        let commands: Vec<(String, String)> = audit_entries_for(&root).into_iter()
+            .rev()
+            .map(|entry| (entry.command, entry.outcome))
+            .collect();
+        let expected = [
+            ("zip_directory", "success"),
+            ("unzip_archive", "success"),
+            ("copy_directory", "success"),
+            ("encrypt_file", "success"),
+            ("decrypt_file", "error: Authentication failed"),
+            ("delete_folder", "success"),
+        ];
+        assert_eq!(commands, expected.map(|(c, o)| (c.to_string(), o.to_string())));
+    }
+
+    #[test]
+    fn count_words_in_file_matches_known_counts() {
+        let dir = scratch_dir();
+        let note = dir.path().join("progress_note.md");
+        // 27 words, 4 sentences, 3 paragraphs
+        let text = "Max was seen today. He is anxious around visitors!\n\nThe owner reports barking at 6 p.m. most evenings\n\n- Plan: desensitisation, counter-conditioning and a daily walk routine?";
+        fs::write(&note, text).unwrap();
+
+        let count = count_words_in_file(path_string(&note)).unwrap();
+        assert!((count.words as i64 - 27).abs() <= 1, "{:?}", count);
+        assert_eq!(count.paragraphs, 3);
+        assert_eq!(count.characters, text.chars().count() as u32);
+        // "p.m." splits into extra sentence fragments; allow for abbreviation noise
+        assert!((4..=6).contains(&count.sentences), "{:?}", count);
+
+        assert_eq!(count_words_in_text(""), WordCount { words: 0, sentences: 0, paragraphs: 0, characters: 0 });
+        assert_eq!(count_words_in_text("— - ...").words, 0);
+        assert_eq!(count_words_in_text("Café crème, s'il vous plaît.").words, 5);
+    }
+
+    #[test]
+    fn reading_time_defaults_to_238_wpm() {
+        assert!((estimate_reading_time(476, None) - 2.0).abs() < 1e-9);
+        assert!((estimate_reading_time(300, Some(150)) - 2.0).abs() < 1e-9);
+        // A zero rate falls back to the default instead of dividing by zero
+        assert!((estimate_reading_time(238, Some(0)) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn generate_unique_filename_returns_next_free_name() {
+        let dir = scratch_dir();
+        let dir_path = path_string(dir.path());
+        for name in ["report.docx", "report_1.docx", "report_2.docx", "report_4.docx"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+
+        let first = generate_unique_filename(dir_path.clone(), "report".into(), ".docx".into()).unwrap();
+        assert_eq!(PathBuf::from(&first).file_name().unwrap(), "report_3.docx");
+        // Nothing is created, so asking again gives the same name
+        assert!(!Path::new(&first).exists());
+        assert_eq!(generate_unique_filename(dir_path.clone(), "report".into(), "docx".into()).unwrap(), first);
+
+        let fresh = generate_unique_filename(dir_path.clone(), "letter".into(), "".into()).unwrap();
+        assert_eq!(PathBuf::from(&fresh).file_name().unwrap(), "letter");
+    }
+
+    #[test]
+    fn generate_unique_filename_validates_inputs_and_gives_up_after_9999() {
+        let dir = scratch_dir();
+        let dir_path = path_string(dir.path());
+        assert!(generate_unique_filename(dir_path.clone(), "../escape".into(), "md".into()).unwrap_err().contains("Invalid base name"));
+        assert!(generate_unique_filename(dir_path.clone(), "".into(), "md".into()).unwrap_err().contains("Invalid base name"));
+        assert!(generate_unique_filename(dir_path.clone(), "note".into(), "m/d".into()).unwrap_err().contains("Invalid extension"));
+
+        fs::write(dir.path().join("x"), "").unwrap();
+        for n in 1..=MAX_UNIQUE_FILENAME_SUFFIX {
+            fs::write(dir.path().join(format!("x_{}", n)), "").unwrap();
+        }
+        let err = find_unique_filename(dir.path(), "x", "").unwrap_err();
+        assert!(err.contains("tried up to _9999"), "{}", err);
+    }
+
+    #[test]
+    fn unique_filename_can_be_used_as_move_or_copy_destination() {
+        let dir = scratch_dir();
+        let dir_path = path_string(dir.path());
+        let draft = dir.path().join("draft.md");
+        fs::write(&draft, "notes").unwrap();
+        fs::write(dir.path().join("note.md"), "older").unwrap();
+
+        let moved_to = generate_unique_filename(dir_path.clone(), "note".into(), "md".into()).unwrap();
+        move_file(path_string(&draft), moved_to.clone()).unwrap();
+        assert_eq!(fs::read_to_string(&moved_to).unwrap(), "notes");
+
+        let copied_to = generate_unique_filename(dir_path, "note".into(), "md".into()).unwrap();
+        assert_eq!(PathBuf::from(&copied_to).file_name().unwrap(), "note_2.md");
+        copy_file(moved_to, copied_to.clone(), false).unwrap();
+        assert_eq!(fs::read_to_string(&copied_to).unwrap(), "notes");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unique_filename_treats_dangling_symlinks_as_taken() {
+        let dir = scratch_dir();
+        std::os::unix::fs::symlink(dir.path().join("gone.md"), dir.path().join("note.md")).unwrap();
+        let path = find_unique_filename(dir.path(), "note", "md").unwrap();
+        assert_eq!(path.file_name().unwrap(), "note_1.md");
+    }
+
+    const CLIENT_SCHEMA: &str = r#"{
+        "type": "object",
+        "required": ["client_id"],
+        "properties": {
+            "client_id": { "type": "integer" },
+            "name": { "type": "string" }
+        }
+    }"#;
+
+    #[test]
+    fn parse_json_file_validates_against_schema() {
+        let dir = scratch_dir();
+        let good = dir.path().join("client.json");
+        fs::write(&good, r#"{"client_id": 42, "name": "Jane"}"#).unwrap();
+        let value = parse_json_file(path_string(&good), Some(CLIENT_SCHEMA.to_string())).unwrap();
+        assert_eq!(value["client_id"], 42);
+
+        let missing = dir.path().join("no_id.json");
+        fs::write(&missing, r#"{"name": 7}"#).unwrap();
+        let err = parse_json_file(path_string(&missing), Some(CLIENT_SCHEMA.to_string())).unwrap_err();
+        assert!(err.starts_with("JSON does not match schema"), "{}", err);
+        assert!(err.contains("client_id"), "{}", err);
+        assert!(err.contains("/name"), "every violation is reported: {}", err);
+
+        // Without a schema any JSON is accepted
+        assert_eq!(parse_json_file(path_string(&missing), None).unwrap()["name"], 7);
+
+        fs::write(&missing, "{ not json").unwrap();
+        assert!(parse_json_file(path_string(&missing), None).unwrap_err().starts_with("Failed to parse JSON"));
+        assert!(parse_json_file(path_string(&good), Some("{".to_string())).unwrap_err().starts_with("Invalid JSON schema"));
+    }
+
+    #[test]
+    fn write_json_file_is_pretty_or_compact_and_atomic() {
+        let dir = scratch_dir();
+        let file = dir.path().join("client.json");
+        let locks = FileLockRegistry::default();
+        let value = serde_json::json!({"client_id": 42, "pets": ["Max"]});
+
+        write_json_file_locked(&locks, path_string(&file), &value, false).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), r#"{"client_id":42,"pets":["Max"]}"#);
+
+        write_json_file_locked(&locks, path_string(&file), &value, true).unwrap();
+        let pretty = fs::read_to_string(&file).unwrap();
+        assert!(pretty.contains("\n  \"client_id\": 42"), "{}", pretty);
+        assert_eq!(parse_json_file(path_string(&file), Some(CLIENT_SCHEMA.to_string())).unwrap(), value);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn safe_path_rejects_unc_style_paths() {
+        let dir = scratch_dir();
+        let root = path_string(dir.path());
+        for unc in [
+            r"\\server\share\PBS_Admin\x.txt",
+            r"\\?\UNC\localhost\c$\Windows\win.ini",
+            r"\\.\pipe\pbs",
+        ] {
+            assert!(validate_path_safety(unc.to_string(), root.clone()).is_err(), "{}", unc);
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn safe_path_rejects_unc_alias_of_local_root() {
+        let dir = scratch_dir();
+        let local = path_string(dir.path());
+        // \\localhost\C$\... names the same folder but canonicalizes to \\?\UNC\..., not \\?\C:\...
+        let unc = format!(r"\\localhost\{}$\{}\x.txt", &local[..1], &local[3..]);
+        assert!(validate_path_safety(unc, local).is_err());
+    }
+
+    #[test]
+    fn validate_path_safety_returns_the_canonical_path() {
+        let dir = scratch_dir();
+        fs::create_dir_all(dir.path().join("a")).unwrap();
+        let root = path_string(dir.path());
+
+        let resolved = validate_path_safety(path_string(&dir.path().join("a/../b.txt")), root.clone()).unwrap();
+        assert_eq!(PathBuf::from(resolved), dir.path().canonicalize().unwrap().join("b.txt"));
+        assert_eq!(validate_path_safety(path_string(dir.path()), " ".into()).unwrap_err(), "Allowed root is required");
+    }
+
+    #[test]
+    fn directory_tree_nests_two_levels() {
+        let dir = scratch_dir();
+        fs::create_dir_all(dir.path().join("Smith_Jane/Reports")).unwrap();
+        fs::write(dir.path().join("Smith_Jane/intake.pdf"), "x").unwrap();
+        fs::write(dir.path().join("Smith_Jane/Reports/report.docx"), "x").unwrap();
+        fs::write(dir.path().join(".index"), "x").unwrap();
+
+        let tree = read_directory_tree(path_string(dir.path()), None).unwrap();
+
+        assert_eq!(tree["is_dir"], true);
+        let top = tree["children"].as_array().unwrap();
+        // Folders first, then files
+        assert_eq!(top[0]["name"], "Smith_Jane");
+        assert_eq!(top[1]["name"], ".index");
+        assert_eq!(top[1]["is_hidden"], true);
+        assert!(top[1].get("children").is_none());
+
+        let client = top[0]["children"].as_array().unwrap();
+        assert_eq!(client[0]["name"], "Reports");
+        assert_eq!(client[0]["is_hidden"], false);
+        assert_eq!(client[1]["name"], "intake.pdf");
+        assert_eq!(client[1]["is_dir"], false);
+        let reports = client[0]["children"].as_array().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0]["name"], "report.docx");
+        assert_eq!(PathBuf::from(reports[0]["path"].as_str().unwrap()).file_name().unwrap(), "report.docx");
+    }
+
+    #[test]
+    fn directory_tree_truncates_at_max_depth() {
+        let dir = scratch_dir();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+
+        let tree = read_directory_tree(path_string(dir.path()), Some(1)).unwrap();
+
+        let a = &tree["children"][0];
+        assert_eq!(a["name"], "a");
+        assert_eq!(a["children"], serde_json::Value::Null);
+        assert_eq!(a["truncated"], true);
+        assert!(tree.get("truncated").is_none());
+
+        let root_only = read_directory_tree(path_string(dir.path()), Some(0)).unwrap();
+        assert_eq!(root_only["truncated"], true);
+    }
+
+    #[test]
+    fn directory_tree_requires_a_directory() {
+        let dir = scratch_dir();
+        let file = dir.path().join("notes.txt");
+        fs::write(&file, "x").unwrap();
+        assert!(read_directory_tree(path_string(&file), None).unwrap_err().starts_with("Path is not a directory"));
+    }
+
+    #[test]
+    fn duplicate_files_grouped_by_content() {
+        let dir = scratch_dir();
+        fs::create_dir_all(dir.path().join("Smith_Jane")).unwrap();
+        fs::write(dir.path().join("consent.pdf"), "signed consent").unwrap();
+        fs::write(dir.path().join("Smith_Jane/consent copy.pdf"), "signed consent").unwrap();
+        // Same size, different content
+        fs::write(dir.path().join("Smith_Jane/other.pdf"), "signed cOnsent").unwrap();
+
+        let groups = find_duplicate_files(path_string(dir.path())).unwrap();
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].hash, sha256_hex(b"signed consent"));
+        assert_eq!(groups[0].size_bytes, 14);
+        assert_eq!(file_names(&groups[0].files), strings(&["consent copy.pdf", "consent.pdf"]));
+    }
+
+    #[test]
+    fn duplicate_files_ignore_empty_files_and_sort_by_waste() {
+        let dir = scratch_dir();
+        for name in ["a.txt", "b.txt"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        for name in ["small1", "small2", "small3"] {
+            fs::write(dir.path().join(name), "tiny").unwrap();
+        }
+        for name in ["big1", "big2"] {
+            fs::write(dir.path().join(name), "a much larger template body").unwrap();
+        }
+
+        let groups = find_duplicate_files_in(dir.path()).unwrap();
+
+        // 27 wasted bytes beats 2 x 4
+        let sizes: Vec<u64> = groups.iter().map(|g| g.size_bytes).collect();
+        assert_eq!(sizes, vec![27, 4]);
+        assert_eq!(groups[1].files.len(), 3);
+    }
+
+    #[test]
+    fn open_file_rejects_paths_outside_pbs_admin() {
+        let outside = tempfile::tempdir().unwrap();
+        let report = outside.path().join("report.pdf");
+        fs::write(&report, "%PDF").unwrap();
+        assert!(open_file(path_string(&report)).unwrap_err().starts_with("Access denied"));
+
+        let dir = scratch_dir();
+        let missing = dir.path().join("missing.pdf");
+        assert!(open_file(path_string(&missing)).unwrap_err().starts_with("File does not exist"));
+        assert!(open_file(path_string(&dir.path().join("../../../etc/hostname"))).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn open_file_rejects_symlinks_leading_outside() {
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.path().join("secret.pdf"), "%PDF").unwrap();
+        let dir = scratch_dir();
+        let link = dir.path().join("shortcut.pdf");
+        std::os::unix::fs::symlink(outside.path().join("secret.pdf"), &link).unwrap();
+
+        assert!(open_file(path_string(&link)).unwrap_err().starts_with("Access denied"));
+    }
+
+    #[test]
+    fn open_url_only_allows_web_and_mail_links() {
+        for url in ["file:///etc/passwd", "javascript:alert(1)", "smb://server/share", "no-scheme"] {
+            assert_eq!(open_url(url.to_string()).unwrap_err(), format!("Unsupported URL: {}", url));
+        }
+    }
+
+    #[test]
+    fn disk_usage_totals_known_file_sizes() {
+        let dir = scratch_dir();
+        fs::create_dir_all(dir.path().join("Smith_Jane/Reports")).unwrap();
+        fs::create_dir_all(dir.path().join("Empty")).unwrap();
+        fs::write(dir.path().join("index.json"), vec![b'x'; 100]).unwrap();
+        fs::write(dir.path().join("Smith_Jane/intake.pdf"), vec![b'x'; 2_000]).unwrap();
+        fs::write(dir.path().join("Smith_Jane/Reports/report.docx"), vec![b'x'; 30_000]).unwrap();
+
+        let usage = get_disk_usage_for_path(path_string(dir.path())).unwrap();
+
+        assert_eq!(usage.used_bytes, 32_100);
+        assert_eq!(usage.file_count, 3);
+        assert_eq!(usage.dir_count, 3);
+        assert!(usage.free_bytes > 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn disk_usage_does_not_follow_symlinks() {
+        let dir = scratch_dir();
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.path().join("big.bin"), vec![0u8; 50_000]).unwrap();
+        fs::write(dir.path().join("note.txt"), "12345").unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("linked")).unwrap();
+
+        let usage = get_disk_usage_for_path(path_string(dir.path())).unwrap();
+        assert_eq!((usage.used_bytes, usage.file_count, usage.dir_count), (5, 1, 0));
+    }
+
+    #[test]
+    fn disk_usage_rejects_files_and_outside_paths() {
+        let dir = scratch_dir();
+        let file = dir.path().join("note.txt");
+        fs::write(&file, "x").unwrap();
+        assert!(get_disk_usage_for_path(path_string(&file)).unwrap_err().starts_with("Path is not a directory"));
+
+        let outside = tempfile::tempdir().unwrap();
+        assert!(get_disk_usage_for_path(path_string(outside.path())).unwrap_err().starts_with("Access denied"));
+    }
+
+    #[test]
+    fn batch_rename_dry_run_previews_new_names() {
+        let dir = scratch_dir();
+        for name in ["Report_2024-03-01.docx", "Report_2024-11-20.docx", "notes.txt"] {
+            fs::write(dir.path().join(name), name).unwrap();
+        }
+
+        let previews = batch_rename_files(
+            path_string(dir.path()),
+            r"^Report_(\d{4})-(\d{2})-(\d{2})".into(),
+            "${3}-${2}-${1}_Report".into(),
+            true,
+        ).unwrap();
+
+        let names: Vec<(String, String, bool)> = previews.iter().map(|p| (
+            PathBuf::from(&p.old_path).file_name().unwrap().to_string_lossy().to_string(),
+            PathBuf::from(&p.new_path).file_name().unwrap().to_string_lossy().to_string(),
+            p.would_collide,
+        )).collect();
+        assert_eq!(names, vec![
+            ("Report_2024-03-01.docx".to_string(), "01-03-2024_Report.docx".to_string(), false),
+            ("Report_2024-11-20.docx".to_string(), "20-11-2024_Report.docx".to_string(), false),
+        ]);
+        // Nothing changed on disk
+        assert!(dir.path().join("Report_2024-03-01.docx").exists());
+        assert!(!dir.path().join("01-03-2024_Report.docx").exists());
+    }
+
+    #[test]
+    fn batch_rename_renames_when_not_a_dry_run() {
+        let dir = scratch_dir();
+        fs::write(dir.path().join("scan1.pdf"), "one").unwrap();
+        fs::write(dir.path().join("scan2.pdf"), "two").unwrap();
+
+        let previews = batch_rename_files(path_string(dir.path()), r"^scan(\d)".into(), "Consent_$1".into(), false).unwrap();
+
+        assert_eq!(previews.len(), 2);
+        assert_eq!(fs::read_to_string(dir.path().join("Consent_2.pdf")).unwrap(), "two");
+        assert!(!dir.path().join("scan1.pdf").exists());
+    }
+
+    #[test]
+    fn batch_rename_refuses_collisions_before_renaming() {
+        let dir = scratch_dir();
+        for name in ["a_v1.txt", "a_v2.txt", "b_v1.txt", "b.txt"] {
+            fs::write(dir.path().join(name), name).unwrap();
+        }
+
+        let previews = batch_rename_files(path_string(dir.path()), r"_v\d".into(), "".into(), true).unwrap();
+        assert!(previews.iter().all(|p| p.would_collide), "{:?}", previews);
+
+        let err = batch_rename_files(path_string(dir.path()), r"_v\d".into(), "".into(), false).unwrap_err();
+        assert!(err.starts_with("Rename would overwrite existing names for:"), "{}", err);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 4);
+        assert_eq!(fs::read_to_string(dir.path().join("b.txt")).unwrap(), "b.txt");
+    }
+
+    #[test]
+    fn batch_rename_rejects_bad_patterns_and_names() {
+        let dir = scratch_dir();
+        fs::write(dir.path().join("note.txt"), "x").unwrap();
+
+        assert!(batch_rename_files(path_string(dir.path()), "(".into(), "x".into(), true).unwrap_err().starts_with("Invalid pattern"));
+        assert_eq!(
+            batch_rename_files(path_string(dir.path()), "^note".into(), "../note".into(), true).unwrap_err(),
+            "Invalid new name for note.txt: ../note.txt"
+        );
+    }
+
+    #[test]
+    fn file_diff_of_one_changed_line_is_a_single_hunk() {
+        let dir = scratch_dir();
+        let old = dir.path().join("note_v1.md");
+        let new = dir.path().join("note_v2.md");
+        let lines: Vec<String> = (1..=10).map(|n| format!("line {}", n)).collect();
+        fs::write(&old, lines.join("\n") + "\n").unwrap();
+        let mut changed = lines.clone();
+        changed[4] = "line 5 (revised)".to_string();
+        fs::write(&new, changed.join("\n") + "\n").unwrap();
+
+        let diff = calculate_file_diff(path_string(&old), path_string(&new)).unwrap();
+
+        let line = |kind, content: &str| DiffLine { kind, content: content.to_string() };
+        assert_eq!(diff.hunks, vec![DiffHunk {
+            old_start: 2,
+            old_count: 7,
+            new_start: 2,
+            new_count: 7,
+            lines: vec![
+                line("context", "line 2"),
+                line("context", "line 3"),
+                line("context", "line 4"),
+                line("delete", "line 5"),
+                line("insert", "line 5 (revised)"),
+                line("context", "line 6"),
+                line("context", "line 7"),
+                line("context", "line 8"),
+            ],
+        }]);
+    }
+
+    #[test]
+    fn distant_changes_produce_separate_hunks() {
+        let old: String = (1..=30).map(|n| format!("{}\n", n)).collect();
+        let new = old.replacen("2\n", "two\n", 1).replace("29\n", "").replace("30\n", "30\n31\n");
+
+        let hunks = diff_text_lines(&old, &new);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!((hunks[0].old_start, hunks[0].old_count, hunks[0].new_start, hunks[0].new_count), (1, 5, 1, 5));
+        assert_eq!((hunks[1].old_start, hunks[1].old_count, hunks[1].new_start, hunks[1].new_count), (26, 5, 26, 5));
+        let kinds: Vec<_> = hunks[1].lines.iter().map(|l| l.kind).collect();
+        assert_eq!(kinds, vec!["context", "context", "context", "delete", "context", "insert"]);
+    }
+
+    #[test]
+    fn identical_files_have_no_hunks() {
+        assert!(diff_text_lines("same\ntext\n", "same\ntext\n").is_empty());
+        let hunks = diff_text_lines("", "first line\n");
+        assert_eq!((hunks[0].old_start, hunks[0].old_count, hunks[0].new_count), (1, 0, 1));
+    }
+
+    #[test]
+    fn binary_or_non_utf8_files_are_not_diffed() {
+        let dir = scratch_dir();
+        let text = dir.path().join("note.md");
+        fs::write(&text, "notes\n").unwrap();
+        let binary = dir.path().join("scan.png");
+        fs::write(&binary, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        let latin1 = dir.path().join("legacy.txt");
+        fs::write(&latin1, b"caf\xe9\n").unwrap();
+
+        assert_eq!(calculate_file_diff(path_string(&text), path_string(&binary)).unwrap_err(), "Binary file differs");
+        assert_eq!(calculate_file_diff(path_string(&binary), path_string(&text)).unwrap_err(), "Binary file differs");
+        let err = calculate_file_diff(path_string(&text), path_string(&latin1)).unwrap_err();
+        assert!(err.starts_with("File is not valid UTF-8 text:"), "{}", err);
+    }
+
+    #[test]
+    fn file_diff_rejects_paths_outside_allowed_directories() {
+        let dir = scratch_dir();
+        let note = dir.path().join("note.md");
+        fs::write(&note, "notes\n").unwrap();
+        assert!(calculate_file_diff(path_string(&note), "/etc/passwd".to_string()).is_err());
+    }
+
+    /// Write `name` in `dir` with its modification time set to noon local time on `date`
+    fn note_modified_on(dir: &Path, name: &str, date: (i32, u32, u32), contents: &str) -> PathBuf {
+        use chrono::TimeZone;
+
+        let path = dir.join(name);
+        fs::write(&path, contents).unwrap();
+        let modified = chrono::Local.with_ymd_and_hms(date.0, date.1, date.2, 12, 0, 0).unwrap();
+        fs::File::options().write(true).open(&path).unwrap()
+            .set_modified(std::time::SystemTime::from(modified)).unwrap();
+        path
+    }
+
+    fn zip_entry_names(path: &Path) -> Vec<String> {
+        let archive = zip::ZipArchive::new(fs::File::open(path).unwrap()).unwrap();
+        let mut names: Vec<String> = archive.file_names().map(|n| n.to_string()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn archive_old_sessions_zips_by_modification_month() {
+        let dir = scratch_dir();
+        let notes_dir = dir.path().join("Session_Notes");
+        fs::create_dir_all(notes_dir.join("drafts")).unwrap();
+        let long_note = "Owner reports reactivity on walks. ".repeat(200);
+        note_modified_on(&notes_dir, "intake.md", (2023, 1, 15), &long_note);
+        note_modified_on(&notes_dir, "followup.md", (2023, 1, 28), &long_note);
+        note_modified_on(&notes_dir, "review.md", (2023, 2, 10), &long_note);
+        note_modified_on(&notes_dir, "report.md", (2022, 12, 31), "short");
+        fs::write(notes_dir.join("current.md"), "this week").unwrap();
+        let archive_dir = dir.path().join("Archive");
+
+        let result = archive_old_sessions(path_string(dir.path()), 90, path_string(&archive_dir)).unwrap();
+
+        let archives = archive_dir.canonicalize().unwrap();
+        assert_eq!(result.archived_count, 4);
+        assert_eq!(result.archive_paths, vec![
+            path_string(&archives.join("2022").join("12.zip")),
+            path_string(&archives.join("2023").join("01.zip")),
+            path_string(&archives.join("2023").join("02.zip")),
+        ]);
+        assert_eq!(zip_entry_names(&archives.join("2023").join("01.zip")), strings(&["followup.md", "intake.md"]));
+        assert_eq!(zip_entry_names(&archives.join("2023").join("02.zip")), strings(&["review.md"]));
+        assert_eq!(zip_entry_names(&archives.join("2022").join("12.zip")), strings(&["report.md"]));
+        assert!(result.total_size_saved_bytes > 0);
+
+        let mut remaining: Vec<String> = fs::read_dir(&notes_dir).unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        remaining.sort();
+        assert_eq!(remaining, strings(&["current.md", "drafts"]));
+
+        let mut archive = zip::ZipArchive::new(fs::File::open(archives.join("2023").join("01.zip")).unwrap()).unwrap();
+        let mut restored = String::new();
+        std::io::Read::read_to_string(&mut archive.by_name("intake.md").unwrap(), &mut restored).unwrap();
+        assert_eq!(restored, long_note);
+    }
+
+    #[test]
+    fn archive_old_sessions_appends_to_existing_month_archive() {
+        let dir = scratch_dir();
+        let notes_dir = dir.path().join("Session_Notes");
+        fs::create_dir_all(&notes_dir).unwrap();
+        let archive_dir = dir.path().join("Archive");
+        note_modified_on(&notes_dir, "intake.md", (2023, 1, 15), "first run");
+        archive_old_sessions(path_string(dir.path()), 30, path_string(&archive_dir)).unwrap();
+
+        note_modified_on(&notes_dir, "followup.md", (2023, 1, 28), "second run");
+        // Same name as an archived note: it stays put rather than overwriting the archived copy
+        note_modified_on(&notes_dir, "intake.md", (2023, 1, 16), "edited copy");
+        let result = archive_old_sessions(path_string(dir.path()), 30, path_string(&archive_dir)).unwrap();
+
+        assert_eq!(result.archived_count, 1);
+        let january = archive_dir.join("2023").join("01.zip");
+        assert_eq!(zip_entry_names(&january), strings(&["followup.md", "intake.md"]));
+        assert_eq!(fs::read_to_string(notes_dir.join("intake.md")).unwrap(), "edited copy");
+        assert!(!notes_dir.join("followup.md").exists());
+        assert!(!archive_dir.join("2023").join("01.zip.tmp").exists());
+    }
+
+    #[test]
+    fn archive_old_sessions_rejects_bad_locations() {
+        let dir = scratch_dir();
+        let err = archive_old_sessions(path_string(dir.path()), 30, path_string(&dir.path().join("Archive"))).unwrap_err();
+        assert!(err.starts_with("Session_Notes folder not found"), "{}", err);
+
+        let notes_dir = dir.path().join("Session_Notes");
+        fs::create_dir_all(notes_dir.join("old")).unwrap();
+        note_modified_on(&notes_dir, "intake.md", (2023, 1, 15), "notes");
+        let err = archive_old_sessions(path_string(dir.path()), 30, path_string(&notes_dir.join("old"))).unwrap_err();
+        assert_eq!(err, "Archive folder cannot be inside Session_Notes");
+        assert!(notes_dir.join("intake.md").exists());
+
+        // A corrupt month archive aborts before anything is deleted
+        let archive_dir = dir.path().join("Archive");
+        fs::create_dir_all(archive_dir.join("2023")).unwrap();
+        fs::write(archive_dir.join("2023").join("01.zip"), "not a zip").unwrap();
+        let err = archive_old_sessions(path_string(dir.path()), 30, path_string(&archive_dir)).unwrap_err();
+        assert!(err.starts_with("Not a valid ZIP archive"), "{}", err);
+        assert!(notes_dir.join("intake.md").exists());
+    }
+
+    /// Latin-1 bytes for `text` (every character must be below U+0100)
+    fn latin1_bytes(text: &str) -> Vec<u8> {
+        text.chars().map(|c| u8::try_from(u32::from(c)).expect("Latin-1 character")).collect()
+    }
+
+    const LATIN1_NOTE: &str = "Séance de suivi : le propriétaire signale que le chien réagit moins à la sonnette. \
+        Prochaine étape : exercices à la maison, récompenses données après le calme. Café offert, très apprécié.";
+
+    #[test]
+    fn latin1_file_is_detected_and_converted_to_utf8() {
+        let dir = scratch_dir();
+        let legacy = dir.path().join("legacy_note.txt");
+        fs::write(&legacy, latin1_bytes(LATIN1_NOTE)).unwrap();
+        assert!(fs::read_to_string(&legacy).is_err());
+
+        let detected = check_file_encoding(path_string(&legacy)).unwrap();
+        assert_eq!(detected.detected_encoding, "windows-1252");
+        assert!(!detected.is_utf8);
+        assert_eq!(detected.confidence, 0.8);
+
+        let converted = dir.path().join("legacy_note_utf8.txt");
+        convert_file_to_utf8(path_string(&legacy), "ISO-8859-1".to_string(), path_string(&converted)).unwrap();
+        assert_eq!(fs::read_to_string(&converted).unwrap(), LATIN1_NOTE);
+
+        let round_trip = check_file_encoding(path_string(&converted)).unwrap();
+        assert_eq!((round_trip.detected_encoding.as_str(), round_trip.confidence, round_trip.is_utf8), ("UTF-8", 1.0, true));
+    }
+
+    #[test]
+    fn utf8_detection_accepts_bom_and_plain_ascii() {
+        let with_bom = detect_text_encoding("\u{feff}Café".as_bytes());
+        assert!(with_bom.is_utf8 && with_bom.confidence == 1.0);
+        assert!(detect_text_encoding(b"plain ascii").is_utf8);
+        // A lone accented byte is too little text for a confident guess
+        let short = detect_text_encoding(b"caf\xe9");
+        assert!(!short.is_utf8 && short.confidence < 1.0);
+    }
+
+    #[test]
+    fn convert_file_to_utf8_in_place_and_rejects_bad_encodings() {
+        let dir = scratch_dir();
+        let note = dir.path().join("note.txt");
+        fs::write(&note, latin1_bytes("Ça va très bien")).unwrap();
+
+        convert_file_to_utf8(path_string(&note), "latin1".to_string(), path_string(&note)).unwrap();
+        assert_eq!(fs::read_to_string(&note).unwrap(), "Ça va très bien");
+
+        let legacy = dir.path().join("legacy.txt");
+        fs::write(&legacy, b"caf\xe9").unwrap();
+        let output = path_string(&dir.path().join("out.txt"));
+        assert_eq!(
+            convert_file_to_utf8(path_string(&legacy), "klingon".to_string(), output.clone()).unwrap_err(),
+            "Unknown encoding: klingon"
+        );
+        assert_eq!(
+            convert_file_to_utf8(path_string(&legacy), "utf-8".to_string(), output.clone()).unwrap_err(),
+            "File is not valid UTF-8 text"
+        );
+        assert!(!Path::new(&output).exists());
+    }
+}
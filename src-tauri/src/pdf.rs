@@ -0,0 +1,1277 @@
+// PBS Admin - PDF commands
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::collections::HashMap;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::{validate_read_path, validate_write_path, write_file_atomically};
+use crate::documents::build_pandoc_args;
+use crate::files::move_file_internal;
+use crate::system::audited;
+
+// ============================================================================
+// PDF TEXT EXTRACTION
+// ============================================================================
+
+#[tauri::command]
+pub(crate) fn pdf_to_text(pdf_path: String) -> Result<String, String> {
+    if !pdf_path.to_lowercase().ends_with(".pdf") {
+        return Err("Input file must be a .pdf file".to_string());
+    }
+    if !std::path::Path::new(&pdf_path).exists() {
+        return Err(format!("File not found: {}", pdf_path));
+    }
+
+    let bytes = fs::read(&pdf_path)
+        .map_err(|e| format!("Failed to read PDF: {}", e))?;
+
+    let text = pdf_extract::extract_text_from_mem(&bytes)
+        .map_err(|e| format!("Failed to extract text from PDF: {}", e))?;
+
+    if text.trim().is_empty() {
+        return Err("PDF contained no extractable text (it may be scanned/image-based)".to_string());
+    }
+
+    Ok(text)
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct PdfTextResult {
+    pages: Vec<String>,
+    total_characters: u32,
+}
+
+/// Text of one page from its content stream's text operators (Tj, TJ, ', ").
+/// Line moves (Td/TD/T*) become newlines and wide TJ gaps become spaces.
+fn extract_pdf_page_text(doc: &lopdf::Document, page_id: lopdf::ObjectId) -> Result<String, String> {
+    use lopdf::{Encoding, Object};
+
+    fn push_separator(text: &mut String, separator: char) {
+        if !text.is_empty() && !text.ends_with(char::is_whitespace) {
+            text.push(separator);
+        }
+    }
+
+    fn push_strings(text: &mut String, encoding: Option<&Encoding>, operands: &[Object]) {
+        for operand in operands {
+            match operand {
+                Object::String(bytes, _) => {
+                    let decoded = encoding
+                        .and_then(|encoding| lopdf::Document::decode_text(encoding, bytes).ok())
+                        .unwrap_or_else(|| String::from_utf8_lossy(bytes).to_string());
+                    text.push_str(&decoded);
+                }
+                Object::Array(items) => push_strings(text, encoding, items),
+                // TJ offsets are in thousandths of an em; a large negative one is a word gap
+                Object::Integer(offset) if *offset < -200 => push_separator(text, ' '),
+                Object::Real(offset) if *offset < -200.0 => push_separator(text, ' '),
+                _ => {}
+            }
+        }
+    }
+
+    // Skip fonts whose encoding lopdf can't read rather than failing the whole page
+    let encodings: HashMap<Vec<u8>, Encoding> = doc.get_page_fonts(page_id)
+        .map_err(|e| format!("Failed to read page fonts: {}", e))?
+        .into_iter()
+        .filter_map(|(name, font)| font.get_font_encoding(doc).ok().map(|encoding| (name, encoding)))
+        .collect();
+    let content = doc.get_and_decode_page_content(page_id)
+        .map_err(|e| format!("Failed to read page content: {}", e))?;
+
+    let mut text = String::new();
+    let mut encoding = None;
+    for operation in &content.operations {
+        match operation.operator.as_str() {
+            "Tf" => {
+                encoding = operation.operands.first()
+                    .and_then(|name| name.as_name().ok())
+                    .and_then(|name| encodings.get(name));
+            }
+            "Tj" | "TJ" => push_strings(&mut text, encoding, &operation.operands),
+            "'" | "\"" => {
+                push_separator(&mut text, '\n');
+                push_strings(&mut text, encoding, &operation.operands);
+            }
+            "Td" | "TD" => {
+                let dy = operation.operands.get(1).and_then(|dy| dy.as_float().ok()).unwrap_or(0.0);
+                push_separator(&mut text, if dy != 0.0 { '\n' } else { ' ' });
+            }
+            "T*" | "ET" => push_separator(&mut text, '\n'),
+            _ => {}
+        }
+    }
+
+    Ok(text.trim_end().to_string())
+}
+
+/// Extract the text layer of a PDF page by page. `page_range` is 1-based and inclusive;
+/// pages with no text (scanned images) come back as empty strings.
+#[tauri::command]
+pub(crate) fn extract_text_from_pdf(pdf_path: String, page_range: Option<(u32, u32)>) -> Result<PdfTextResult, String> {
+    // Validate path is within allowed directories
+    let validated_path = validate_read_path(&pdf_path)?;
+
+    let doc = lopdf::Document::load(&validated_path)
+        .map_err(|e| format!("Failed to open PDF: {}", e))?;
+    let page_ids = doc.get_pages();
+    let page_count = page_ids.len() as u32;
+
+    let (first, last) = page_range.unwrap_or((1, page_count));
+    if first == 0 || first > last || last > page_count {
+        return Err(format!("Invalid page range {}-{} (PDF has {} pages)", first, last, page_count));
+    }
+
+    let mut pages = Vec::with_capacity((last - first + 1) as usize);
+    for page_number in first..=last {
+        let page_id = page_ids[&page_number];
+        let text = extract_pdf_page_text(&doc, page_id)
+            .map_err(|e| format!("Page {}: {}", page_number, e))?;
+        if text.trim().is_empty() {
+            warn!("Page {} of {} has no text layer (it may be a scanned image)", page_number, pdf_path);
+        }
+        pages.push(text);
+    }
+
+    let total_characters = pages.iter().map(|page| page.chars().count() as u32).sum();
+    info!("Extracted {} characters from {} pages of {}", total_characters, pages.len(), pdf_path);
+    Ok(PdfTextResult { pages, total_characters })
+}
+
+// ============================================================================
+// DOCX TO PDF CONVERSION
+// ============================================================================
+
+/// Convert DOCX to PDF using whatever converter the platform has:
+/// Word COM automation on Windows, LibreOffice (or textutil + cupsfilter) on macOS,
+/// and LibreOffice on Linux.
+#[tauri::command]
+pub(crate) fn convert_docx_to_pdf(docx_path: String, pdf_path: String) -> Result<String, String> {
+    // Validate both paths are within allowed directories
+    validate_read_path(&docx_path)?;
+    validate_write_path(&pdf_path)?;
+
+    if cfg!(target_os = "windows") {
+        return convert_docx_to_pdf_word(&docx_path, &pdf_path);
+    }
+
+    // LibreOffice gives the most faithful rendering, so prefer it wherever it's installed
+    if let Some(soffice) = find_libreoffice() {
+        return convert_docx_to_pdf_libreoffice(&soffice, &docx_path, &pdf_path);
+    }
+
+    if cfg!(target_os = "macos") {
+        return convert_docx_to_pdf_textutil(&docx_path, &pdf_path);
+    }
+
+    Err(format!(
+        "No PDF converter found. Searched for LibreOffice in PATH (soffice, libreoffice) and at: {}. Please install LibreOffice.",
+        LIBREOFFICE_CANDIDATE_PATHS.join(", ")
+    ))
+}
+
+/// Common LibreOffice install locations checked when soffice isn't in PATH
+pub(crate) const LIBREOFFICE_CANDIDATE_PATHS: &[&str] = &[
+    "/usr/bin/soffice",
+    "/usr/bin/libreoffice",
+    "/usr/local/bin/soffice",
+    "/snap/bin/libreoffice",
+    "/opt/homebrew/bin/soffice",
+    "/Applications/LibreOffice.app/Contents/MacOS/soffice",
+];
+
+/// Locate a LibreOffice executable, checking PATH first and then common install locations
+fn find_libreoffice() -> Option<PathBuf> {
+    if let Some(paths) = std::env::var_os("PATH") {
+        for dir in std::env::split_paths(&paths) {
+            for name in ["soffice", "libreoffice"] {
+                let candidate = dir.join(name);
+                if candidate.is_file() {
+                    return Some(candidate);
+                }
+            }
+        }
+    }
+
+    LIBREOFFICE_CANDIDATE_PATHS
+        .iter()
+        .map(PathBuf::from)
+        .find(|p| p.is_file())
+}
+
+/// Arguments for a headless LibreOffice DOCX → PDF conversion into `out_dir`
+fn libreoffice_convert_args(docx_path: &Path, out_dir: &Path) -> Vec<std::ffi::OsString> {
+    vec![
+        "--headless".into(),
+        "--convert-to".into(),
+        "pdf".into(),
+        "--outdir".into(),
+        out_dir.as_os_str().to_os_string(),
+        docx_path.as_os_str().to_os_string(),
+    ]
+}
+
+/// Convert with LibreOffice into a private temp directory, then move the PDF to `pdf_path`.
+/// LibreOffice names its output after the input, so converting straight into the destination
+/// folder could overwrite an unrelated `<stem>.pdf` sitting next to `pdf_path`.
+fn convert_docx_to_pdf_libreoffice(soffice: &Path, docx_path: &str, pdf_path: &str) -> Result<String, String> {
+    let docx = validate_read_path(docx_path)?;
+    let pdf = validate_write_path(pdf_path)?;
+
+    let stem = docx.file_stem()
+        .ok_or_else(|| format!("Invalid DOCX path: {}", docx_path))?;
+    let out_dir = std::env::temp_dir().join("PBS_Admin").join(format!(
+        "docx_to_pdf_{}_{}",
+        std::process::id(),
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0)
+    ));
+    fs::create_dir_all(&out_dir)
+        .map_err(|e| format!("Failed to create temp directory: {}", e))?;
+
+    let result = (|| -> Result<(), String> {
+        let output = Command::new(soffice)
+            .args(libreoffice_convert_args(&docx, &out_dir))
+            .output()
+            .map_err(|e| format!("Failed to execute LibreOffice: {}", e))?;
+
+        if !output.status.success() {
+            let error_msg = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("PDF conversion failed: {}", error_msg));
+        }
+
+        let produced = out_dir.join(stem).with_extension("pdf");
+        if !produced.is_file() {
+            return Err(format!(
+                "PDF conversion failed: LibreOffice did not produce {}",
+                produced.display()
+            ));
+        }
+        move_file_internal(&produced, &pdf)
+            .map_err(|e| format!("Failed to move converted PDF into place: {}", e))
+    })();
+
+    let _ = fs::remove_dir_all(&out_dir);
+    result?;
+
+    Ok(pdf_path.to_string())
+}
+
+/// macOS fallback when LibreOffice isn't installed: textutil renders the DOCX to HTML,
+/// then cupsfilter prints the HTML to PDF
+fn convert_docx_to_pdf_textutil(docx_path: &str, pdf_path: &str) -> Result<String, String> {
+    let temp_dir = std::env::temp_dir().join("PBS_Admin");
+    fs::create_dir_all(&temp_dir)
+        .map_err(|e| format!("Failed to create temp directory: {}", e))?;
+    let html_path = temp_dir.join(format!(
+        "{}_docx_to_pdf.html",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0)
+    ));
+
+    let textutil = Command::new("textutil")
+        .args(["-convert", "html", "-output"])
+        .arg(&html_path)
+        .arg(docx_path)
+        .output()
+        .map_err(|_| "No PDF converter found. Searched for LibreOffice and textutil/cupsfilter. Please install LibreOffice.".to_string())?;
+
+    if !textutil.status.success() {
+        let _ = fs::remove_file(&html_path);
+        return Err(format!("textutil conversion failed: {}", String::from_utf8_lossy(&textutil.stderr)));
+    }
+
+    let cupsfilter = Command::new("cupsfilter")
+        .arg(&html_path)
+        .output();
+    let _ = fs::remove_file(&html_path);
+    let cupsfilter = cupsfilter
+        .map_err(|e| format!("Failed to execute cupsfilter: {}", e))?;
+
+    if !cupsfilter.status.success() {
+        return Err(format!("cupsfilter conversion failed: {}", String::from_utf8_lossy(&cupsfilter.stderr)));
+    }
+
+    // cupsfilter writes the PDF to stdout
+    fs::write(pdf_path, &cupsfilter.stdout)
+        .map_err(|e| format!("Failed to write PDF: {}", e))?;
+
+    Ok(pdf_path.to_string())
+}
+
+/// Windows: convert via Word COM automation in PowerShell
+fn convert_docx_to_pdf_word(docx_path: &str, pdf_path: &str) -> Result<String, String> {
+    // Build PowerShell script for Word COM automation
+    // IMPORTANT: The DOCX file MUST be closed in Word before conversion
+    // If the file is open, Word COM will hang trying to access it
+    // Use single-quoted strings in PowerShell to prevent variable expansion injection.
+    // Single quotes treat content literally — no $variable or backtick interpretation.
+    // Escape any embedded single quotes by doubling them ('').
+    let safe_docx = docx_path.replace("'", "''");
+    let safe_pdf = pdf_path.replace("'", "''");
+
+    let ps_script = format!(
+        r#"
+$ErrorActionPreference = "Stop"
+$word = $null
+try {{
+    $word = New-Object -ComObject Word.Application
+    $word.Visible = $false
+    $word.DisplayAlerts = 0
+
+    # Open document: FileName, ConfirmConversions, ReadOnly
+    # ReadOnly=$true helps avoid conflicts if file is somehow locked
+    $doc = $word.Documents.Open('{}', $false, $true)
+
+    # Save as PDF (17 = wdFormatPDF)
+    $doc.SaveAs('{}', 17)
+    $doc.Close($false)
+    Write-Output "Success"
+}} catch {{
+    Write-Error "PDF conversion failed: $($_.Exception.Message)"
+    exit 1
+}} finally {{
+    if ($word -ne $null) {{
+        try {{ $word.Quit() }} catch {{ }}
+        try {{ [System.Runtime.Interopservices.Marshal]::ReleaseComObject($word) | Out-Null }} catch {{ }}
+    }}
+    [System.GC]::Collect()
+    [System.GC]::WaitForPendingFinalizers()
+}}
+"#,
+        safe_docx,
+        safe_pdf
+    );
+
+    // Execute PowerShell script
+    let output = Command::new("powershell")
+        .args(&["-NoProfile", "-Command", &ps_script])
+        .output()
+        .map_err(|e| format!("Failed to execute PowerShell: {}", e))?;
+
+    // Check if command succeeded
+    if output.status.success() {
+        Ok(pdf_path.to_string())
+    } else {
+        let error_msg = String::from_utf8_lossy(&output.stderr);
+        // Provide helpful error message if file might be locked
+        if error_msg.contains("locked") || error_msg.contains("being used") || error_msg.contains("access") {
+            Err(format!("PDF conversion failed - the DOCX file may be open in Word. Please close it and try again. Error: {}", error_msg))
+        } else {
+            Err(format!("PDF conversion failed: {}", error_msg))
+        }
+    }
+}
+
+// ============================================================================
+// PDF MERGING
+// ============================================================================
+
+/// Check a file starts with the `%PDF-` magic bytes
+fn has_pdf_header(path: &Path) -> Result<bool, String> {
+    use std::io::Read;
+
+    let mut header = [0u8; 5];
+    let mut file = fs::File::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    match file.read_exact(&mut header) {
+        Ok(()) => Ok(&header == b"%PDF-"),
+        Err(_) => Ok(false), // Shorter than the header
+    }
+}
+
+/// Concatenate parsed PDF documents (in order) into a single document.
+/// Objects from each input are renumbered so their IDs never collide, then all pages
+/// are re-parented under one Pages tree in the original order.
+fn merge_pdf_documents(documents: Vec<lopdf::Document>) -> Result<lopdf::Document, String> {
+    use lopdf::{Document, Object, ObjectId};
+
+    let mut max_id = 1;
+    let mut pages: Vec<(ObjectId, Object)> = Vec::new();
+    let mut objects: std::collections::BTreeMap<ObjectId, Object> = std::collections::BTreeMap::new();
+
+    for mut doc in documents {
+        doc.renumber_objects_with(max_id);
+        max_id = doc.max_id + 1;
+
+        // get_pages is keyed by page number, so this preserves page order within each input
+        for (_, page_id) in doc.get_pages() {
+            let page = doc.get_object(page_id)
+                .map_err(|e| format!("Failed to read page object: {}", e))?
+                .to_owned();
+            pages.push((page_id, page));
+        }
+        objects.extend(doc.objects);
+    }
+
+    let mut merged = Document::with_version("1.5");
+    let mut catalog: Option<(ObjectId, Object)> = None;
+    let mut pages_root: Option<(ObjectId, Object)> = None;
+
+    for (object_id, object) in objects {
+        match object.type_name().unwrap_or("") {
+            // Keep the first Catalog; its Pages entry is rewritten below
+            "Catalog" => {
+                if catalog.is_none() {
+                    catalog = Some((object_id, object));
+                }
+            },
+            // Keep the first Pages root; Kids and Count are rebuilt below
+            "Pages" => {
+                if pages_root.is_none() {
+                    pages_root = Some((object_id, object));
+                }
+            },
+            // Pages are re-inserted with their new parent; outlines aren't carried over
+            "Page" | "Outlines" | "Outline" => {},
+            _ => {
+                merged.objects.insert(object_id, object);
+            },
+        }
+    }
+
+    let (catalog_id, catalog_object) = catalog
+        .ok_or_else(|| "Merged PDF has no document catalog".to_string())?;
+    let (pages_id, pages_object) = pages_root
+        .ok_or_else(|| "Merged PDF has no page tree".to_string())?;
+
+    for (page_id, page) in &pages {
+        let mut dictionary = page.as_dict()
+            .map_err(|e| format!("Invalid page object: {}", e))?
+            .clone();
+        dictionary.set("Parent", pages_id);
+        merged.objects.insert(*page_id, Object::Dictionary(dictionary));
+    }
+
+    let mut pages_dict = pages_object.as_dict()
+        .map_err(|e| format!("Invalid page tree: {}", e))?
+        .clone();
+    pages_dict.set("Count", pages.len() as u32);
+    pages_dict.set(
+        "Kids",
+        pages.iter().map(|(id, _)| Object::Reference(*id)).collect::<Vec<_>>(),
+    );
+    merged.objects.insert(pages_id, Object::Dictionary(pages_dict));
+
+    let mut catalog_dict = catalog_object.as_dict()
+        .map_err(|e| format!("Invalid document catalog: {}", e))?
+        .clone();
+    catalog_dict.set("Pages", pages_id);
+    catalog_dict.remove(b"Outlines");
+    merged.objects.insert(catalog_id, Object::Dictionary(catalog_dict));
+
+    merged.trailer.set("Root", catalog_id);
+    merged.max_id = merged.objects.keys().map(|(id, _)| *id).max().unwrap_or(0);
+    merged.renumber_objects();
+
+    Ok(merged)
+}
+
+/// Merge PDF files in the order given (e.g. cover sheet + session notes + attachments)
+#[tauri::command]
+pub(crate) fn merge_pdf_files(input_paths: Vec<String>, output_path: String) -> Result<String, String> {
+    audited("merge_pdf_files", &output_path, merge_pdf_files_unaudited(&input_paths, &output_path))
+}
+
+fn merge_pdf_files_unaudited(input_paths: &[String], output_path: &str) -> Result<String, String> {
+    if input_paths.is_empty() {
+        return Err("No PDF files provided to merge".to_string());
+    }
+
+    // Validate output path is within allowed directories
+    validate_write_path(output_path)?;
+
+    // Ensure output directory exists
+    if let Some(parent) = Path::new(output_path).parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            return Err(format!("Output directory does not exist: {}", parent.display()));
+        }
+    }
+
+    let mut documents = Vec::with_capacity(input_paths.len());
+    for input in input_paths {
+        // Every input must exist within allowed directories, not just the output
+        let input_path = validate_read_path(input)?;
+        if !has_pdf_header(&input_path)? {
+            return Err(format!("Not a valid PDF file (missing %PDF- header): {}", input));
+        }
+        let doc = lopdf::Document::load(&input_path)
+            .map_err(|e| format!("Failed to parse PDF {}: {}", input, e))?;
+        documents.push(doc);
+    }
+
+    let mut merged = merge_pdf_documents(documents)?;
+    merged.save(output_path)
+        .map_err(|e| format!("Failed to write merged PDF: {}", e))?;
+
+    info!("Merged {} PDFs into {}", input_paths.len(), output_path);
+
+    Ok(output_path.to_string())
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ExportResult {
+    output_path: String,
+    session_count: u32,
+    skipped_files: Vec<String>,
+}
+
+/// Session date from a note's `YYYY-MM-DD` filename prefix (markdown files only)
+pub(crate) fn session_note_date(file_name: &str) -> Option<chrono::NaiveDate> {
+    let lower = file_name.to_lowercase();
+    if !lower.ends_with(".md") {
+        return None;
+    }
+    let prefix = file_name.get(..10)?;
+    chrono::NaiveDate::parse_from_str(prefix, "%Y-%m-%d").ok()
+}
+
+/// Session notes in `notes_dir` dated within `start..=end`, oldest first
+fn session_notes_in_range(
+    notes_dir: &Path,
+    start: chrono::NaiveDate,
+    end: chrono::NaiveDate,
+) -> Result<Vec<PathBuf>, String> {
+    let mut notes: Vec<(chrono::NaiveDate, String, PathBuf)> = Vec::new();
+    for entry in fs::read_dir(notes_dir).map_err(|e| format!("Failed to read directory: {}", e))? {
+        let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let is_file = entry.file_type().map(|t| t.is_file()).unwrap_or(false);
+        if let Some(date) = session_note_date(&file_name).filter(|_| is_file) {
+            if date >= start && date <= end {
+                notes.push((date, file_name, entry.path()));
+            }
+        }
+    }
+    // Same-day notes keep filename order (e.g. 2024-03-01_a.md, 2024-03-01_b.md)
+    notes.sort();
+    Ok(notes.into_iter().map(|(_, _, path)| path).collect())
+}
+
+/// Convert one note to PDF with pandoc and check the result is a PDF
+fn convert_session_note_to_pdf(pandoc: &std::ffi::OsStr, note: &Path, pdf_path: &Path) -> Result<(), String> {
+    let args = build_pandoc_args(&note.to_string_lossy(), &pdf_path.to_string_lossy(), None, None)?;
+    let output = Command::new(pandoc)
+        .args(&args)
+        .output()
+        .map_err(|e| format!("Failed to execute pandoc: {}. Is pandoc installed?", e))?;
+    if !output.status.success() {
+        return Err(format!("Pandoc conversion failed: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+    if !pdf_path.exists() || !has_pdf_header(pdf_path)? {
+        return Err("Pandoc did not produce a PDF".to_string());
+    }
+    Ok(())
+}
+
+fn export_session_notes_to_pdf_with(
+    pandoc: &std::ffi::OsStr,
+    client_folder: &str,
+    start_date: &str,
+    end_date: &str,
+    output_path: &str,
+) -> Result<ExportResult, String> {
+    // Validate paths are within allowed directories
+    let validated_folder = validate_read_path(client_folder)?;
+    validate_write_path(output_path)?;
+    if !output_path.to_lowercase().ends_with(".pdf") {
+        return Err("Output file must be a .pdf file".to_string());
+    }
+
+    let parse = |value: &str| chrono::NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date (expected YYYY-MM-DD): {}", value));
+    let start = parse(start_date)?;
+    let end = parse(end_date)?;
+    if start > end {
+        return Err("Start date must be on or before the end date".to_string());
+    }
+
+    let notes_dir = validated_folder.join("Session_Notes");
+    if !notes_dir.is_dir() {
+        return Err(format!("Session_Notes folder not found in {}", client_folder));
+    }
+
+    let notes = session_notes_in_range(&notes_dir, start, end)?;
+    if notes.is_empty() {
+        return Err(format!("No session notes dated {} to {}", start_date, end_date));
+    }
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let work_dir = std::env::temp_dir()
+        .join("PBS_Admin")
+        .join(format!("session_export_{}", timestamp));
+    fs::create_dir_all(&work_dir)
+        .map_err(|e| format!("Failed to create temp directory: {}", e))?;
+
+    let mut converted = Vec::new();
+    let mut skipped_files = Vec::new();
+    for (index, note) in notes.iter().enumerate() {
+        let pdf_path = work_dir.join(format!("{:04}.pdf", index));
+        match convert_session_note_to_pdf(pandoc, note, &pdf_path) {
+            Ok(()) => converted.push(pdf_path.to_string_lossy().to_string()),
+            Err(e) => {
+                warn!("Skipping session note {}: {}", note.display(), e);
+                skipped_files.push(note.to_string_lossy().to_string());
+            },
+        }
+    }
+
+    let session_count = converted.len() as u32;
+    let merged = if converted.is_empty() {
+        Err("None of the session notes could be converted to PDF".to_string())
+    } else {
+        merge_pdf_files(converted, output_path.to_string())
+    };
+    if let Err(e) = fs::remove_dir_all(&work_dir) {
+        warn!("Failed to remove temp directory {}: {}", work_dir.display(), e);
+    }
+    let output_path = merged?;
+
+    info!("Exported {} session notes ({} skipped) to {}", session_count, skipped_files.len(), output_path);
+    Ok(ExportResult { output_path, session_count, skipped_files })
+}
+
+/// Combine a client's session notes from `start_date` to `end_date` (inclusive,
+/// YYYY-MM-DD) into one PDF. Notes are matched by their YYYY-MM-DD filename prefix;
+/// notes pandoc can't convert are listed in `skipped_files`.
+#[tauri::command]
+pub(crate) fn export_session_notes_to_pdf(
+    client_folder: String,
+    start_date: String,
+    end_date: String,
+    output_path: String,
+) -> Result<ExportResult, String> {
+    export_session_notes_to_pdf_with(
+        std::ffi::OsStr::new("pandoc"),
+        &client_folder,
+        &start_date,
+        &end_date,
+        &output_path,
+    )
+}
+
+/// Decode a PDF text string (UTF-16BE with BOM, otherwise treated as Latin-1)
+fn decode_pdf_text(bytes: &[u8]) -> String {
+    if let Some(utf16) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = utf16.chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        bytes.iter().map(|&b| b as char).collect()
+    }
+}
+
+/// Encode a PDF text string, using UTF-16BE only when the value isn't plain ASCII
+fn encode_pdf_text(text: &str) -> lopdf::Object {
+    let bytes = if text.is_ascii() {
+        text.as_bytes().to_vec()
+    } else {
+        let mut bytes = vec![0xFE, 0xFF];
+        bytes.extend(text.encode_utf16().flat_map(|unit| unit.to_be_bytes()));
+        bytes
+    };
+    lopdf::Object::String(bytes, lopdf::StringFormat::Literal)
+}
+
+/// Top-level field references from the document's /AcroForm /Fields array
+fn acroform_fields(doc: &lopdf::Document) -> Result<Vec<lopdf::ObjectId>, String> {
+    let catalog = doc.catalog()
+        .map_err(|e| format!("Failed to read PDF catalog: {}", e))?;
+    let acroform = match catalog.get(b"AcroForm") {
+        Ok(object) => doc.dereference(object)
+            .map_err(|e| format!("Failed to read AcroForm: {}", e))?.1,
+        Err(_) => return Err("PDF has no fillable form (missing /AcroForm)".to_string()),
+    };
+    let fields = acroform.as_dict()
+        .ok()
+        .and_then(|form| form.get(b"Fields").ok())
+        .map(|fields| doc.dereference(fields).map(|(_, object)| object))
+        .transpose()
+        .map_err(|e| format!("Failed to read AcroForm fields: {}", e))?;
+
+    Ok(fields
+        .and_then(|fields| fields.as_array().ok())
+        .map(|refs| refs.iter().filter_map(|r| r.as_reference().ok()).collect())
+        .unwrap_or_default())
+}
+
+/// Every named form field as (fully qualified name, object ID), walking /Kids.
+/// Kids without /T are widget annotations of their parent and aren't separate fields.
+fn collect_pdf_form_fields(doc: &lopdf::Document) -> Result<Vec<(String, lopdf::ObjectId)>, String> {
+    let mut fields = Vec::new();
+    let mut stack: Vec<(String, lopdf::ObjectId)> = acroform_fields(doc)?
+        .into_iter()
+        .rev()
+        .map(|id| (String::new(), id))
+        .collect();
+    let mut visited = std::collections::HashSet::new();
+
+    while let Some((parent_name, id)) = stack.pop() {
+        // Guard against malformed files whose Kids point back up the tree
+        if !visited.insert(id) {
+            continue;
+        }
+        let Ok(dict) = doc.get_dictionary(id) else { continue };
+
+        let name = match dict.get(b"T").and_then(|t| t.as_str()) {
+            Ok(partial) if parent_name.is_empty() => decode_pdf_text(partial),
+            Ok(partial) => format!("{}.{}", parent_name, decode_pdf_text(partial)),
+            Err(_) => continue,
+        };
+
+        if let Ok(kids) = dict.get(b"Kids").and_then(|k| k.as_array()) {
+            for kid in kids.iter().rev().filter_map(|k| k.as_reference().ok()) {
+                stack.push((name.clone(), kid));
+            }
+        }
+        fields.push((name, id));
+    }
+
+    Ok(fields)
+}
+
+/// Set /V on the named AcroForm fields. Returns the names in `values` that weren't found.
+fn fill_pdf_form_fields(doc: &mut lopdf::Document, values: &HashMap<String, String>) -> Result<Vec<String>, String> {
+    use lopdf::Object;
+
+    let fields = collect_pdf_form_fields(doc)?;
+    let mut filled = std::collections::HashSet::new();
+
+    for (name, id) in &fields {
+        let Some(value) = values.get(name) else { continue };
+        let dict = doc.get_object_mut(*id)
+            .and_then(|object| object.as_dict_mut())
+            .map_err(|e| format!("Failed to update field {}: {}", name, e))?;
+
+        let is_button = matches!(dict.get(b"FT").and_then(|ft| ft.as_name()), Ok(b"Btn"));
+        if is_button {
+            // Checkboxes/radios take a name (e.g. /Yes or /Off) and show it via /AS
+            dict.set("V", Object::Name(value.as_bytes().to_vec()));
+            dict.set("AS", Object::Name(value.as_bytes().to_vec()));
+        } else {
+            dict.set("V", encode_pdf_text(value));
+        }
+        filled.insert(name.as_str());
+    }
+
+    // Ask viewers to regenerate field appearances from the new values
+    let root_id = doc.trailer.get(b"Root")
+        .and_then(|root| root.as_reference())
+        .map_err(|e| format!("Failed to read PDF catalog: {}", e))?;
+    let acroform_ref = doc.get_dictionary(root_id)
+        .and_then(|catalog| catalog.get(b"AcroForm"))
+        .and_then(|form| form.as_reference())
+        .ok();
+    let acroform = match acroform_ref {
+        Some(form_id) => doc.get_object_mut(form_id).and_then(|form| form.as_dict_mut()),
+        None => doc.get_object_mut(root_id)
+            .and_then(|catalog| catalog.as_dict_mut())
+            .and_then(|catalog| catalog.get_mut(b"AcroForm"))
+            .and_then(|form| form.as_dict_mut()),
+    };
+    if let Ok(acroform) = acroform {
+        acroform.set("NeedAppearances", true);
+    }
+
+    let mut missing: Vec<String> = values.keys()
+        .filter(|name| !filled.contains(name.as_str()))
+        .cloned()
+        .collect();
+    missing.sort();
+    Ok(missing)
+}
+
+/// Pre-fill an intake form PDF's AcroForm fields by name. Field names not present
+/// in the PDF are logged as warnings rather than failing. Returns the output path.
+#[tauri::command]
+pub(crate) fn generate_client_intake_pdf(
+    template_pdf_path: String,
+    output_path: String,
+    fields: HashMap<String, String>,
+) -> Result<String, String> {
+    // Validate paths are within allowed directories
+    let validated_template = validate_read_path(&template_pdf_path)?;
+    let validated_output = validate_write_path(&output_path)?;
+
+    if !has_pdf_header(&validated_template)? {
+        return Err(format!("Not a valid PDF file (missing %PDF- header): {}", template_pdf_path));
+    }
+    let mut doc = lopdf::Document::load(&validated_template)
+        .map_err(|e| format!("Failed to parse PDF {}: {}", template_pdf_path, e))?;
+
+    let missing = fill_pdf_form_fields(&mut doc, &fields)?;
+    for name in &missing {
+        warn!("Intake form field not found in {}: {}", template_pdf_path, name);
+    }
+
+    let mut pdf_bytes = Vec::new();
+    doc.save_to(&mut pdf_bytes)
+        .map_err(|e| format!("Failed to write filled PDF: {}", e))?;
+    write_file_atomically(&validated_output, &pdf_bytes)?;
+
+    info!(
+        "Filled {} of {} intake form fields into {}",
+        fields.len() - missing.len(),
+        fields.len(),
+        output_path
+    );
+    Ok(output_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{mock_tool, path_string, sample_pdf, scratch_dir, strings};
+
+    #[test]
+    fn libreoffice_args_convert_into_out_dir() {
+        let args = libreoffice_convert_args(Path::new("/docs/report.docx"), Path::new("/tmp/out"));
+        assert_eq!(
+            args,
+            ["--headless", "--convert-to", "pdf", "--outdir", "/tmp/out", "/docs/report.docx"]
+                .map(std::ffi::OsString::from)
+        );
+    }
+
+    /// Fake soffice: `--headless --convert-to pdf --outdir <dir> <docx>`. Records the out dir
+    /// next to itself and writes `<dir>/<stem>.pdf` like LibreOffice does.
+    #[cfg(unix)]
+    fn mock_soffice(dir: &Path) -> PathBuf {
+        mock_tool(dir, "soffice", r#"
+echo "$5" > "$(dirname "$0")/outdir.txt"
+name=$(basename "$6" .docx)
+printf '%%PDF-1.4 from %s' "$name" > "$5/$name.pdf""#)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn libreoffice_conversion_uses_private_out_dir() {
+        let dir = scratch_dir();
+        let soffice = mock_soffice(dir.path());
+        let docx = dir.path().join("report.docx");
+        fs::write(&docx, b"PK fake docx").unwrap();
+        // An unrelated report.pdf next to the requested output must survive the conversion
+        let bystander = dir.path().join("report.pdf");
+        fs::write(&bystander, b"keep me").unwrap();
+        let pdf = dir.path().join("Report for vet.pdf");
+
+        let result = convert_docx_to_pdf_libreoffice(&soffice, &path_string(&docx), &path_string(&pdf)).unwrap();
+
+        assert_eq!(result, path_string(&pdf));
+        assert_eq!(fs::read(&pdf).unwrap(), b"%PDF-1.4 from report");
+        assert_eq!(fs::read(&bystander).unwrap(), b"keep me");
+        let out_dir = fs::read_to_string(dir.path().join("outdir.txt")).unwrap();
+        let out_dir = Path::new(out_dir.trim());
+        assert_ne!(out_dir, dir.path());
+        assert!(!out_dir.exists(), "temp out dir should be removed");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn libreoffice_conversion_failures_leave_no_output() {
+        let dir = scratch_dir();
+        let docx = dir.path().join("report.docx");
+        fs::write(&docx, b"PK fake docx").unwrap();
+        let pdf = dir.path().join("report out.pdf");
+
+        let failing = mock_tool(dir.path(), "soffice-fail", "echo 'source file could not be loaded' >&2; exit 1");
+        let err = convert_docx_to_pdf_libreoffice(&failing, &path_string(&docx), &path_string(&pdf)).unwrap_err();
+        assert!(err.contains("could not be loaded"), "{}", err);
+
+        let silent = mock_tool(dir.path(), "soffice-silent", "exit 0");
+        let err = convert_docx_to_pdf_libreoffice(&silent, &path_string(&docx), &path_string(&pdf)).unwrap_err();
+        assert!(err.contains("did not produce"), "{}", err);
+
+        assert!(!pdf.exists());
+    }
+
+    #[test]
+    fn docx_to_pdf_validates_paths() {
+        let dir = scratch_dir();
+        let docx = dir.path().join("report.docx");
+        fs::write(&docx, b"PK fake docx").unwrap();
+
+        assert!(convert_docx_to_pdf("/etc/report.docx".to_string(), path_string(&dir.path().join("a.pdf"))).is_err());
+        let err = convert_docx_to_pdf(path_string(&docx), "/etc/report.pdf".to_string()).unwrap_err();
+        assert!(err.contains("Access denied"), "{}", err);
+        let err = convert_docx_to_pdf_libreoffice(Path::new("soffice"), path_string(&docx).as_str(), "/etc/report.pdf").unwrap_err();
+        assert!(err.contains("Access denied"), "{}", err);
+    }
+
+    #[test]
+    fn merge_pdf_files_concatenates_in_order() {
+        let dir = scratch_dir();
+        let cover = dir.path().join("cover.pdf");
+        let notes = dir.path().join("notes.pdf");
+        let merged = dir.path().join("merged.pdf");
+        sample_pdf("Cover", 1).save(&cover).unwrap();
+        sample_pdf("Notes", 2).save(&notes).unwrap();
+
+        merge_pdf_files(vec![path_string(&cover), path_string(&notes)], path_string(&merged)).unwrap();
+
+        let doc = lopdf::Document::load(&merged).unwrap();
+        assert_eq!(doc.get_pages().len(), 3);
+        let text = doc.extract_text(&[1, 2, 3]).unwrap();
+        let positions: Vec<usize> = ["Cover 0", "Notes 0", "Notes 1"]
+            .iter()
+            .map(|label| text.find(label).unwrap_or_else(|| panic!("{} missing from {:?}", label, text)))
+            .collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]), "{:?}", positions);
+    }
+
+    #[test]
+    fn merge_pdf_files_names_the_bad_input() {
+        let dir = scratch_dir();
+        let good = dir.path().join("good.pdf");
+        sample_pdf("Good", 1).save(&good).unwrap();
+        let not_pdf = dir.path().join("scan.pdf");
+        fs::write(&not_pdf, "not a pdf").unwrap();
+        let truncated = dir.path().join("truncated.pdf");
+        fs::write(&truncated, "%PDF-1.5\nbroken").unwrap();
+        let output = path_string(&dir.path().join("merged.pdf"));
+
+        assert!(merge_pdf_files(vec![], output.clone()).unwrap_err().contains("No PDF files"));
+        let err = merge_pdf_files(vec![path_string(&good), path_string(&not_pdf)], output.clone()).unwrap_err();
+        assert!(err.contains("%PDF-") && err.contains("scan.pdf"), "{}", err);
+        let err = merge_pdf_files(vec![path_string(&good), path_string(&truncated)], output.clone()).unwrap_err();
+        assert!(err.contains("truncated.pdf"), "{}", err);
+        let err = merge_pdf_files(vec![path_string(&dir.path().join("missing.pdf"))], output).unwrap_err();
+        assert!(err.contains("does not exist"), "{}", err);
+    }
+
+    #[test]
+    fn merge_pdf_files_validates_every_input() {
+        let dir = scratch_dir();
+        let inside = dir.path().join("inside.pdf");
+        sample_pdf("Inside", 1).save(&inside).unwrap();
+        // A PDF outside every PBS_Admin root, reached directly and through a symlink
+        let outside = tempfile::tempdir().unwrap();
+        let secret = outside.path().join("secret.pdf");
+        sample_pdf("Secret", 1).save(&secret).unwrap();
+        let output = dir.path().join("merged.pdf");
+
+        let err = merge_pdf_files(vec![path_string(&inside), path_string(&secret)], path_string(&output)).unwrap_err();
+        assert!(err.contains("Access denied"), "{}", err);
+
+        #[cfg(unix)]
+        {
+            let link = dir.path().join("link.pdf");
+            std::os::unix::fs::symlink(&secret, &link).unwrap();
+            let err = merge_pdf_files(vec![path_string(&inside), path_string(&link)], path_string(&output)).unwrap_err();
+            assert!(err.contains("Access denied"), "{}", err);
+        }
+        assert!(!output.exists());
+    }
+
+    /// One-page PDF with an AcroForm: text fields client_name and address.street
+    /// (a field with a child) plus a consent checkbox. The form sits in the catalog directly.
+    fn intake_form_pdf(path: &Path) {
+        use lopdf::{dictionary, Object};
+
+        let mut doc = sample_pdf("Intake", 1);
+        let client_name = doc.add_object(dictionary! {"FT" => "Tx", "T" => Object::string_literal("client_name")});
+        let street = doc.add_object(dictionary! {"FT" => "Tx", "T" => Object::string_literal("street")});
+        let address = doc.add_object(dictionary! {"T" => Object::string_literal("address"), "Kids" => vec![street.into()]});
+        let consent = doc.add_object(dictionary! {"FT" => "Btn", "T" => Object::string_literal("consent"), "V" => "Off"});
+        let root = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        doc.get_dictionary_mut(root).unwrap().set("AcroForm", dictionary! {
+            "Fields" => vec![client_name.into(), address.into(), consent.into()],
+        });
+        doc.save(path).unwrap();
+    }
+
+    /// Field values (text decoded, names as-is) keyed by fully qualified name
+    fn pdf_form_values(path: &Path) -> HashMap<String, String> {
+        let doc = lopdf::Document::load(path).unwrap();
+        collect_pdf_form_fields(&doc).unwrap()
+            .into_iter()
+            .filter_map(|(name, id)| {
+                let value = doc.get_dictionary(id).ok()?.get(b"V").ok()?;
+                let text = match value {
+                    lopdf::Object::Name(name) => String::from_utf8_lossy(name).to_string(),
+                    other => decode_pdf_text(other.as_str().ok()?),
+                };
+                Some((name, text))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn intake_pdf_fields_are_filled_by_name() {
+        let dir = scratch_dir();
+        let template = dir.path().join("intake_template.pdf");
+        intake_form_pdf(&template);
+        let output = dir.path().join("intake_jane.pdf");
+        let fields = HashMap::from([
+            ("client_name".to_string(), "Jane Döe".to_string()),
+            ("address.street".to_string(), "12 Bark St".to_string()),
+            ("consent".to_string(), "Yes".to_string()),
+            ("not_in_form".to_string(), "ignored".to_string()),
+        ]);
+
+        let written = generate_client_intake_pdf(path_string(&template), path_string(&output), fields).unwrap();
+        assert_eq!(written, path_string(&output));
+
+        let values = pdf_form_values(&output);
+        assert_eq!(values["client_name"], "Jane Döe");
+        assert_eq!(values["address.street"], "12 Bark St");
+        assert_eq!(values["consent"], "Yes");
+        assert!(!values.contains_key("not_in_form"));
+        // The template itself is untouched
+        assert_eq!(pdf_form_values(&template)["consent"], "Off");
+    }
+
+    #[test]
+    fn intake_pdf_replaces_existing_output_atomically() {
+        let dir = scratch_dir();
+        let template = dir.path().join("intake_template.pdf");
+        intake_form_pdf(&template);
+        let output = dir.path().join("intake_jane.pdf");
+        fs::write(&output, "previous export").unwrap();
+        // A hard link still sees the old bytes only if the output was swapped in by rename
+        let old_link = dir.path().join("previous.pdf");
+        fs::hard_link(&output, &old_link).unwrap();
+
+        let fields = HashMap::from([("client_name".to_string(), "Jane".to_string())]);
+        generate_client_intake_pdf(path_string(&template), path_string(&output), fields).unwrap();
+
+        assert_eq!(pdf_form_values(&output)["client_name"], "Jane");
+        assert_eq!(fs::read_to_string(&old_link).unwrap(), "previous export");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn fill_pdf_form_fields_reports_unknown_names_and_sets_need_appearances() {
+        let dir = scratch_dir();
+        let template = dir.path().join("intake_template.pdf");
+        intake_form_pdf(&template);
+        let mut doc = lopdf::Document::load(&template).unwrap();
+
+        let missing = fill_pdf_form_fields(&mut doc, &HashMap::from([
+            ("zeta".to_string(), "1".to_string()),
+            ("client_name".to_string(), "Jane".to_string()),
+            ("alpha".to_string(), "2".to_string()),
+            ("street".to_string(), "partial names don't match".to_string()),
+        ])).unwrap();
+        assert_eq!(missing, ["alpha", "street", "zeta"]);
+
+        let acroform = doc.catalog().unwrap().get(b"AcroForm").unwrap().as_dict().unwrap();
+        assert!(acroform.get(b"NeedAppearances").unwrap().as_bool().unwrap());
+    }
+
+    #[test]
+    fn intake_pdf_requires_a_form() {
+        let dir = scratch_dir();
+        let plain = dir.path().join("plain.pdf");
+        sample_pdf("Plain", 1).save(&plain).unwrap();
+        let err = generate_client_intake_pdf(path_string(&plain), path_string(&dir.path().join("out.pdf")), HashMap::new()).unwrap_err();
+        assert!(err.contains("missing /AcroForm"), "{}", err);
+
+        let not_pdf = dir.path().join("fake.pdf");
+        fs::write(&not_pdf, "hello").unwrap();
+        let err = generate_client_intake_pdf(path_string(&not_pdf), path_string(&dir.path().join("out.pdf")), HashMap::new()).unwrap_err();
+        assert!(err.contains("missing %PDF- header"), "{}", err);
+    }
+
+    /// PDF with one page of mixed text operators and one page with no text (like a scan)
+    fn mixed_text_pdf(path: &Path) {
+        use lopdf::content::{Content, Operation};
+        use lopdf::{dictionary, Object, Stream};
+
+        let mut doc = lopdf::Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {"Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Helvetica", "Encoding" => "WinAnsiEncoding"});
+        let resources_id = doc.add_object(dictionary! {"Font" => dictionary! {"F1" => font_id}});
+        let pages = [
+            vec![
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                Operation::new("Td", vec![72.into(), 720.into()]),
+                Operation::new("Tj", vec![Object::string_literal("Referral letter")]),
+                Operation::new("Td", vec![0.into(), (-14).into()]),
+                Operation::new("TJ", vec![Object::Array(vec![
+                    Object::string_literal("Patient"),
+                    (-250).into(),
+                    Object::string_literal("Max"),
+                    (-30).into(),
+                    Object::string_literal("well"),
+                ])]),
+                Operation::new("T*", vec![]),
+                Operation::new("'", vec![Object::string_literal("Next line")]),
+                Operation::new("ET", vec![]),
+            ],
+            vec![Operation::new("q", vec![]), Operation::new("Q", vec![])],
+        ];
+        let mut kids: Vec<Object> = Vec::new();
+        for operations in pages {
+            let content_id = doc.add_object(Stream::new(dictionary! {}, Content { operations }.encode().unwrap()));
+            kids.push(doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => content_id,
+                "Resources" => resources_id,
+                "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+            }).into());
+        }
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {"Type" => "Pages", "Kids" => kids, "Count" => 2}));
+        let catalog_id = doc.add_object(dictionary! {"Type" => "Catalog", "Pages" => pages_id});
+        doc.trailer.set("Root", catalog_id);
+        doc.save(path).unwrap();
+    }
+
+    #[test]
+    fn pdf_text_follows_text_operators() {
+        let dir = scratch_dir();
+        let pdf = dir.path().join("referral.pdf");
+        mixed_text_pdf(&pdf);
+
+        let result = extract_text_from_pdf(path_string(&pdf), None).unwrap();
+
+        assert_eq!(result.pages, vec!["Referral letter\nPatient Maxwell\nNext line".to_string(), String::new()]);
+        assert_eq!(result.total_characters, result.pages[0].chars().count() as u32);
+    }
+
+    #[test]
+    fn pdf_text_honours_page_range() {
+        let dir = scratch_dir();
+        let pdf = dir.path().join("bundle.pdf");
+        sample_pdf("Page", 3).save(&pdf).unwrap();
+
+        assert_eq!(extract_text_from_pdf(path_string(&pdf), Some((2, 3))).unwrap().pages, strings(&["Page 1", "Page 2"]));
+        for range in [(0, 1), (2, 1), (1, 4)] {
+            assert_eq!(
+                extract_text_from_pdf(path_string(&pdf), Some(range)).err().unwrap(),
+                format!("Invalid page range {}-{} (PDF has 3 pages)", range.0, range.1)
+            );
+        }
+    }
+
+    #[test]
+    fn pdf_text_from_pandoc_output() {
+        // pandoc needs a LaTeX engine to write PDF
+        if which::which("pandoc").is_err() || which::which("pdflatex").is_err() {
+            return;
+        }
+        let dir = scratch_dir();
+        let markdown = dir.path().join("report.md");
+        let pdf = dir.path().join("report.pdf");
+        fs::write(&markdown, "# Behaviour Report\n\nMax shows separation anxiety.\n").unwrap();
+        let status = Command::new("pandoc").arg(&markdown).arg("-o").arg(&pdf).status().unwrap();
+        assert!(status.success());
+
+        let result = extract_text_from_pdf(path_string(&pdf), None).unwrap();
+        let text = result.pages.join("\n");
+        assert!(text.contains("Behaviour"), "{}", text);
+        assert!(text.contains("anxiety"), "{}", text);
+    }
+
+    /// A pandoc stand-in that "converts" `<note>.md` by copying `<note>.md.pdf` from its own folder.
+    /// Notes without a prepared PDF fail the way a LaTeX error would.
+    #[cfg(unix)]
+    fn mock_pdf_pandoc(dir: &Path) -> PathBuf {
+        mock_tool(dir, "pandoc", r#"
+src="$(dirname "$0")/$(basename "$1").pdf"
+[ -f "$src" ] || { echo "Error producing PDF." >&2; exit 43; }
+cp "$src" "$5""#)
+    }
+
+    #[cfg(unix)]
+    fn client_with_session_notes(notes: &[&str]) -> (tempfile::TempDir, PathBuf) {
+        let dir = scratch_dir();
+        let notes_dir = dir.path().join("Session_Notes");
+        fs::create_dir_all(&notes_dir).unwrap();
+        for name in notes {
+            fs::write(notes_dir.join(name), format!("# {}", name)).unwrap();
+        }
+        (dir, notes_dir)
+    }
+
+    #[test]
+    fn session_note_dates_come_from_the_filename_prefix() {
+        let date = |y, m, d| chrono::NaiveDate::from_ymd_opt(y, m, d);
+        assert_eq!(session_note_date("2024-03-05_session.md"), date(2024, 3, 5));
+        assert_eq!(session_note_date("2024-03-05.MD"), date(2024, 3, 5));
+        assert_eq!(session_note_date("2024-03-05_session.pdf"), None);
+        assert_eq!(session_note_date("session_2024-03-05.md"), None);
+        assert_eq!(session_note_date("2024-02-30_session.md"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn export_session_notes_merges_three_notes_chronologically() {
+        let bin = tempfile::tempdir().unwrap();
+        let pandoc = mock_pdf_pandoc(bin.path());
+        let notes = ["2024-03-20_followup.md", "2024-03-01_intake.md", "2024-03-05_session.md"];
+        let (dir, _) = client_with_session_notes(&[&notes[..], &["2024-04-02_later.md", "2024-03-10_scan.pdf"]].concat());
+        for name in notes.iter().chain(&["2024-04-02_later.md"]) {
+            sample_pdf(&name[..10], 1).save(bin.path().join(format!("{}.pdf", name))).unwrap();
+        }
+        let output = dir.path().join("March.pdf");
+
+        let result = export_session_notes_to_pdf_with(
+            pandoc.as_os_str(), &path_string(dir.path()), "2024-03-01", "2024-03-31", &path_string(&output),
+        ).unwrap();
+
+        assert_eq!((result.session_count, result.skipped_files.len()), (3, 0));
+        assert_eq!(result.output_path, path_string(&output));
+        let doc = lopdf::Document::load(&output).unwrap();
+        assert_eq!(doc.get_pages().len(), 3);
+        let text = doc.extract_text(&[1, 2, 3]).unwrap();
+        let positions: Vec<usize> = ["2024-03-01 0", "2024-03-05 0", "2024-03-20 0"]
+            .iter()
+            .map(|label| text.find(label).unwrap_or_else(|| panic!("{} missing from {:?}", label, text)))
+            .collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]), "{:?}", positions);
+        assert!(!text.contains("2024-04-02"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn export_session_notes_skips_notes_that_fail_to_convert() {
+        let bin = tempfile::tempdir().unwrap();
+        let pandoc = mock_pdf_pandoc(bin.path());
+        let (dir, notes_dir) = client_with_session_notes(&["2024-03-01_intake.md", "2024-03-05_broken.md", "2024-03-20_followup.md"]);
+        sample_pdf("Intake", 1).save(bin.path().join("2024-03-01_intake.md.pdf")).unwrap();
+        sample_pdf("Followup", 2).save(bin.path().join("2024-03-20_followup.md.pdf")).unwrap();
+        let output = dir.path().join("export.pdf");
+
+        let result = export_session_notes_to_pdf_with(
+            pandoc.as_os_str(), &path_string(dir.path()), "2024-03-01", "2024-03-31", &path_string(&output),
+        ).unwrap();
+
+        assert_eq!(result.session_count, 2);
+        assert_eq!(result.skipped_files, vec![path_string(&notes_dir.canonicalize().unwrap().join("2024-03-05_broken.md"))]);
+        assert_eq!(lopdf::Document::load(&output).unwrap().get_pages().len(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn export_session_notes_reports_bad_ranges_and_empty_results() {
+        let bin = tempfile::tempdir().unwrap();
+        let pandoc = mock_pdf_pandoc(bin.path());
+        let (dir, _) = client_with_session_notes(&["2024-03-01_intake.md"]);
+        let folder = path_string(dir.path());
+        let output = path_string(&dir.path().join("export.pdf"));
+        let export = |start: &str, end: &str, output: &str| {
+            export_session_notes_to_pdf_with(pandoc.as_os_str(), &folder, start, end, output).unwrap_err()
+        };
+
+        assert_eq!(export("2024-03-31", "2024-03-01", &output), "Start date must be on or before the end date");
+        assert_eq!(export("March", "2024-03-31", &output), "Invalid date (expected YYYY-MM-DD): March");
+        assert_eq!(export("2024-01-01", "2024-01-31", &output), "No session notes dated 2024-01-01 to 2024-01-31");
+        assert_eq!(export("2024-03-01", "2024-03-31", &output), "None of the session notes could be converted to PDF");
+        assert_eq!(export("2024-03-01", "2024-03-31", &output.replace(".pdf", ".docx")), "Output file must be a .pdf file");
+        assert!(!Path::new(&output).exists());
+    }
+}
@@ -0,0 +1,649 @@
+// PBS Admin - Settings, API keys and recent files
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{get_pbs_admin_base_path, validate_read_path, write_file_atomically};
+use crate::files::system_time_to_unix_secs;
+
+// ============================================================================
+// API KEY STORAGE (OS KEYCHAIN)
+// ============================================================================
+
+/// Keychain service name - API keys are stored as accounts under it
+/// (macOS Keychain, Windows Credential Manager, Secret Service on Linux)
+const KEYRING_SERVICE: &str = "com.petbehaviourservices.pbsadmin";
+
+fn keyring_entry(service: &str) -> Result<keyring::Entry, String> {
+    if service.trim().is_empty() {
+        return Err("Service name cannot be empty".to_string());
+    }
+    keyring::Entry::new(KEYRING_SERVICE, service)
+        .map_err(|e| format!("Failed to access OS keychain: {}", e))
+}
+
+/// Store an API key (e.g. service "openai") in the OS keychain
+#[tauri::command]
+pub(crate) fn set_api_key(service: String, key: String) -> Result<(), String> {
+    if key.is_empty() {
+        return Err("API key cannot be empty".to_string());
+    }
+    keyring_entry(&service)?
+        .set_password(&key)
+        .map_err(|e| format!("Failed to store API key: {}", e))?;
+    info!("Stored API key for {} in OS keychain", service);
+    Ok(())
+}
+
+/// Read an API key from the OS keychain
+#[tauri::command]
+pub(crate) fn get_api_key(service: String) -> Result<String, String> {
+    match keyring_entry(&service)?.get_password() {
+        Ok(key) => Ok(key),
+        Err(keyring::Error::NoEntry) => Err(format!("No API key stored for {}", service)),
+        Err(e) => Err(format!("Failed to read API key: {}", e)),
+    }
+}
+
+/// Resolve the OpenAI API key: explicit argument, then OS keychain,
+/// then the deprecated OPENAI_API_KEY environment variable
+pub(crate) fn resolve_openai_api_key(provided: Option<String>) -> Result<String, String> {
+    resolve_openai_api_key_from(
+        provided,
+        || get_api_key("openai".to_string()),
+        || std::env::var("OPENAI_API_KEY").ok(),
+    )
+}
+
+/// The resolution chain behind `resolve_openai_api_key`, with the keychain and
+/// environment lookups passed in
+fn resolve_openai_api_key_from(
+    provided: Option<String>,
+    keychain: impl FnOnce() -> Result<String, String>,
+    env: impl FnOnce() -> Option<String>,
+) -> Result<String, String> {
+    if let Some(key) = provided {
+        if key.is_empty() {
+            return Err("OpenAI API key cannot be empty".to_string());
+        }
+        return Ok(key);
+    }
+
+    match keychain() {
+        Ok(key) if !key.is_empty() => return Ok(key),
+        Ok(_) => {}
+        Err(e) => info!("OpenAI API key not found in keychain: {}", e),
+    }
+
+    let key = env()
+        .ok_or_else(|| "OpenAI API key not configured. Please add your API key in Settings > API Keys.".to_string())?;
+    warn!("Reading the OpenAI API key from OPENAI_API_KEY is deprecated - save it in Settings > API Keys to store it in the OS keychain");
+    Ok(key)
+}
+
+/// Check an OpenAI API key against `{base_url}/v1/models`.
+/// Ok(true) = valid, Ok(false) = rejected (401), Err = network or unexpected response.
+async fn check_openai_api_key(base_url: &str, api_key: &str) -> Result<bool, String> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(15))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+    let response = client
+        .get(format!("{}/v1/models", base_url))
+        .header("Authorization", format!("Bearer {}", api_key))
+        .send()
+        .await
+        .map_err(|e| {
+            if e.is_connect() || e.is_timeout() {
+                "Network unreachable".to_string()
+            } else {
+                format!("Failed to contact OpenAI: {}", e)
+            }
+        })?;
+
+    match response.status().as_u16() {
+        200 => Ok(true),
+        401 => Ok(false),
+        status => Err(format!("Unexpected response from OpenAI (HTTP {})", status)),
+    }
+}
+
+/// Validate an OpenAI API key with a cheap request (lists models, no usage cost).
+/// The key itself is never logged.
+#[tauri::command]
+pub(crate) async fn validate_openai_api_key(api_key: String) -> Result<bool, String> {
+    if api_key.trim().is_empty() {
+        return Ok(false);
+    }
+
+    let valid = check_openai_api_key("https://api.openai.com", &api_key).await?;
+    info!("OpenAI API key validation: {}", if valid { "valid" } else { "invalid" });
+    Ok(valid)
+}
+
+// ============================================================================
+// APP SETTINGS
+// ============================================================================
+
+/// User preferences persisted in Documents/PBS_Admin/settings.json.
+/// `#[serde(default)]` lets files written by older versions (missing newer fields) still load.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub(crate) struct AppSettings {
+    default_template_path: Option<String>,
+    /// Preferred pandoc output format (e.g. "docx", "pdf")
+    pandoc_output_format: Option<String>,
+    /// Default transcription language code (e.g. "en")
+    audio_language: Option<String>,
+    /// Transcription provider: "openai", "azure" or "local"
+    api_provider: Option<String>,
+    /// Relocated data directory (e.g. an external drive); Documents/PBS_Admin when unset
+    data_dir: Option<String>,
+}
+
+fn get_settings_file_path() -> Result<PathBuf, String> {
+    Ok(get_pbs_admin_base_path()?.join("settings.json"))
+}
+
+fn load_settings_from(path: &Path) -> Result<AppSettings, String> {
+    if !path.exists() {
+        return Ok(AppSettings::default());
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read settings: {}", e))?;
+    serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse settings: {}", e))
+}
+
+fn save_settings_to(path: &Path, settings: &AppSettings) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create settings directory: {}", e))?;
+    }
+    let json = serde_json::to_string_pretty(settings)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    write_file_atomically(path, json.as_bytes())
+}
+
+/// Save `settings` at `path`, keeping the stored data directory: it is only changed
+/// through set_pbs_admin_data_dir, which checks the folder first
+fn save_settings_keeping_data_dir(path: &Path, mut settings: AppSettings) -> Result<(), String> {
+    settings.data_dir = load_settings_from(path).unwrap_or_default().data_dir;
+    save_settings_to(path, &settings)
+}
+
+/// Save user preferences (atomic write)
+#[tauri::command]
+pub(crate) fn save_settings(settings: AppSettings) -> Result<(), String> {
+    save_settings_keeping_data_dir(&get_settings_file_path()?, settings)
+}
+
+/// Load user preferences, or defaults if none have been saved yet
+#[tauri::command]
+pub(crate) fn load_settings() -> Result<AppSettings, String> {
+    load_settings_from(&get_settings_file_path()?)
+}
+
+/// Folders whose presence marks a directory as a PBS_Admin data directory
+const DATA_DIR_MARKERS: [&str; 2] = ["Client_Records", "Templates"];
+
+/// Data directory override loaded from settings.json, cached because every path check
+/// needs it. `None` until first use; `Some(None)` means no (valid) override is configured.
+static DATA_DIR_OVERRIDE: std::sync::LazyLock<std::sync::RwLock<Option<Option<PathBuf>>>> =
+    std::sync::LazyLock::new(|| std::sync::RwLock::new(None));
+
+/// Check that canonical `dir` can serve as the data directory. It becomes a trusted root
+/// for every path guard, so filesystem roots and the home folder (or anything containing
+/// it) are refused. Unless `allow_empty`, the folder must already hold Client_Records or Templates.
+fn check_data_dir_candidate(dir: &Path, home: Option<&Path>, allow_empty: bool) -> Result<(), String> {
+    if dir.parent().is_none() {
+        return Err(format!("Cannot use a filesystem root as the data directory: {}", dir.display()));
+    }
+    if let Some(home) = home {
+        if home.starts_with(dir) {
+            return Err(format!("Cannot use the home folder or one of its parents as the data directory: {}", dir.display()));
+        }
+    }
+
+    if DATA_DIR_MARKERS.iter().any(|marker| dir.join(marker).is_dir()) {
+        return Ok(());
+    }
+    let is_empty = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read data directory: {}", e))?
+        .next()
+        .is_none();
+    if allow_empty && is_empty {
+        Ok(())
+    } else {
+        Err(format!(
+            "Data directory must be empty or an existing PBS_Admin folder (containing Client_Records or Templates): {}",
+            dir.display()
+        ))
+    }
+}
+
+fn canonical_home_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.canonicalize().unwrap_or(home))
+}
+
+/// Data directory configured in the settings at `settings_path`, if any.
+/// settings.json itself always stays in Documents/PBS_Admin so it can be found before the override is known.
+/// An override that no longer passes check_data_dir_candidate is ignored.
+fn configured_data_dir_from(settings_path: &Path) -> Result<Option<PathBuf>, String> {
+    let settings = load_settings_from(settings_path)?;
+    let Some(dir) = settings.data_dir.filter(|dir| !dir.trim().is_empty()) else {
+        return Ok(None);
+    };
+
+    let checked = Path::new(&dir).canonicalize()
+        .map_err(|e| format!("Failed to resolve data directory {}: {}", dir, e))
+        .and_then(|canonical| {
+            check_data_dir_candidate(&canonical, canonical_home_dir().as_deref(), false)?;
+            Ok(canonical)
+        });
+    match checked {
+        Ok(canonical) => Ok(Some(canonical)),
+        Err(e) => {
+            warn!("Ignoring configured data directory: {}", e);
+            Ok(None)
+        },
+    }
+}
+
+/// Resolve the PBS_Admin data directory: the configured override, or Documents/PBS_Admin
+pub(crate) fn resolve_pbs_admin_data_dir() -> Result<PathBuf, String> {
+    let cached = DATA_DIR_OVERRIDE.read().unwrap_or_else(|e| e.into_inner()).clone();
+    let data_dir = match cached {
+        Some(data_dir) => data_dir,
+        None => {
+            let loaded = configured_data_dir_from(&get_settings_file_path()?)?;
+            *DATA_DIR_OVERRIDE.write().unwrap_or_else(|e| e.into_inner()) = Some(loaded.clone());
+            loaded
+        },
+    };
+    match data_dir {
+        Some(dir) => Ok(dir),
+        None => get_pbs_admin_base_path(),
+    }
+}
+
+/// Store `path` as the data directory override in the settings at `settings_path`.
+/// An empty folder is set up with Client_Records and Templates so it stays recognisable.
+fn set_data_dir_in(settings_path: &Path, path: &str) -> Result<PathBuf, String> {
+    let dir = Path::new(path);
+    if !dir.is_dir() {
+        return Err(format!("Directory does not exist: {}", path));
+    }
+    let canonical_dir = dir.canonicalize()
+        .map_err(|e| format!("Failed to resolve data directory: {}", e))?;
+    check_data_dir_candidate(&canonical_dir, canonical_home_dir().as_deref(), true)?;
+
+    for marker in DATA_DIR_MARKERS {
+        fs::create_dir_all(canonical_dir.join(marker))
+            .map_err(|e| format!("Failed to create {} folder: {}", marker, e))?;
+    }
+
+    let mut settings = load_settings_from(settings_path)?;
+    settings.data_dir = Some(canonical_dir.to_string_lossy().to_string());
+    save_settings_to(settings_path, &settings)?;
+    Ok(canonical_dir)
+}
+
+/// Base directory for client records, templates etc.
+#[tauri::command]
+pub(crate) fn get_pbs_admin_data_dir() -> Result<String, String> {
+    Ok(resolve_pbs_admin_data_dir()?.to_string_lossy().to_string())
+}
+
+/// Move the data directory (e.g. to an external drive). Existing files are not copied.
+#[tauri::command]
+pub(crate) fn set_pbs_admin_data_dir(path: String) -> Result<(), String> {
+    let data_dir = set_data_dir_in(&get_settings_file_path()?, &path)?;
+    // Path guards pick up the new root only now that it has been checked
+    *DATA_DIR_OVERRIDE.write().unwrap_or_else(|e| e.into_inner()) = Some(Some(data_dir.clone()));
+    info!("PBS_Admin data directory set to {}", data_dir.display());
+    Ok(())
+}
+
+// ============================================================================
+// RECENT FILES
+// ============================================================================
+
+const MAX_RECENT_FILES: usize = 50;
+
+/// Serializes read-modify-write of recent_files.json between concurrent reads
+static RECENT_FILES_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub(crate) struct RecentFile {
+    path: String,
+    accessed_at: u64,
+}
+
+fn get_recent_files_path() -> Result<PathBuf, String> {
+    Ok(get_pbs_admin_base_path()?.join("recent_files.json"))
+}
+
+fn load_recent_files_from(path: &Path) -> Result<Vec<RecentFile>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read recent files: {}", e))?;
+    serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse recent files: {}", e))
+}
+
+/// Move `file_path` to the front of the list (most recent first), capped at 50 entries
+fn push_recent_file(recent: &mut Vec<RecentFile>, file_path: &str, accessed_at: u64) {
+    recent.retain(|entry| entry.path != file_path);
+    recent.insert(0, RecentFile { path: file_path.to_string(), accessed_at });
+    recent.truncate(MAX_RECENT_FILES);
+}
+
+fn record_file_access_in(store: &Path, file_path: &str, accessed_at: u64) -> Result<(), String> {
+    let _guard = RECENT_FILES_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    // A corrupt list shouldn't block file access - start a fresh one
+    let mut recent = load_recent_files_from(store).unwrap_or_else(|e| {
+        warn!("{}; starting a new recent files list", e);
+        Vec::new()
+    });
+    push_recent_file(&mut recent, file_path, accessed_at);
+
+    if let Some(parent) = store.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create recent files directory: {}", e))?;
+    }
+    let json = serde_json::to_string_pretty(&recent)
+        .map_err(|e| format!("Failed to serialize recent files: {}", e))?;
+    write_file_atomically(store, json.as_bytes())
+}
+
+/// Add a file to the recent files list (also called by read_text_file)
+#[tauri::command]
+pub(crate) fn record_file_access(path: String) -> Result<(), String> {
+    // Validate path exists and is within allowed directories
+    let validated_path = validate_read_path(&path)?;
+    let now = system_time_to_unix_secs(std::time::SystemTime::now());
+    record_file_access_in(&get_recent_files_path()?, &validated_path.to_string_lossy(), now)
+}
+
+/// Most recently accessed files, newest first
+#[tauri::command]
+pub(crate) fn get_recent_files(limit: u32) -> Result<Vec<RecentFile>, String> {
+    let mut recent = load_recent_files_from(&get_recent_files_path()?)?;
+    recent.truncate(limit as usize);
+    Ok(recent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::backups_path_in;
+    use crate::documents::templates_path_in;
+    use crate::files::client_records_path_in;
+    use crate::transcription::read_clinical_terms_in;
+    use crate::test_support::{http_response, path_string, scratch_dir, serve_http, strings};
+
+    #[test]
+    fn api_key_resolution_prefers_argument_then_keychain_then_env() {
+        let keychain_unused = || -> Result<String, String> { panic!("keychain should not be consulted") };
+        let env_unused = || -> Option<String> { panic!("environment should not be consulted") };
+
+        assert_eq!(resolve_openai_api_key_from(Some("sk-arg".into()), keychain_unused, env_unused).unwrap(), "sk-arg");
+        assert!(resolve_openai_api_key_from(Some(String::new()), keychain_unused, env_unused).unwrap_err().contains("cannot be empty"));
+
+        assert_eq!(resolve_openai_api_key_from(None, || Ok("sk-keychain".into()), env_unused).unwrap(), "sk-keychain");
+
+        // Missing, empty or unreadable keychain entries fall back to the environment
+        for keychain in [Err("No API key stored for openai".to_string()), Ok(String::new())] {
+            let key = resolve_openai_api_key_from(None, || keychain, || Some("sk-env".into())).unwrap();
+            assert_eq!(key, "sk-env");
+        }
+
+        let err = resolve_openai_api_key_from(None, || Err("locked".into()), || None).unwrap_err();
+        assert!(err.contains("Settings > API Keys"), "{}", err);
+    }
+
+    #[test]
+    fn api_key_commands_reject_empty_input() {
+        assert!(keyring_entry("  ").unwrap_err().contains("Service name cannot be empty"));
+        assert!(set_api_key("openai".into(), String::new()).unwrap_err().contains("API key cannot be empty"));
+        assert!(get_api_key(String::new()).is_err());
+    }
+
+    /// Mock OpenAI `/v1/models`: 200 for `sk-good`, 401 for anything else
+    fn serve_openai_models() -> String {
+        serve_http(|request| {
+            let authorized = request.lines().any(|line| {
+                line.to_ascii_lowercase().starts_with("authorization:") && line.ends_with("Bearer sk-good")
+            });
+            if !request.starts_with("GET /v1/models ") {
+                http_response(request, "404 Not Found", &[], b"")
+            } else if authorized {
+                http_response(request, "200 OK", &[], br#"{"object":"list","data":[]}"#)
+            } else {
+                http_response(request, "401 Unauthorized", &[], br#"{"error":{"code":"invalid_api_key"}}"#)
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn api_key_check_distinguishes_valid_and_invalid_keys() {
+        let url = serve_openai_models();
+        assert!(check_openai_api_key(&url, "sk-good").await.unwrap());
+        assert!(!check_openai_api_key(&url, "sk-revoked").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn api_key_check_reports_other_failures_as_errors() {
+        let url = serve_http(|request| http_response(request, "500 Internal Server Error", &[], b""));
+        assert!(check_openai_api_key(&url, "sk-good").await.unwrap_err().contains("HTTP 500"));
+
+        // Nothing listening on a port we just released
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        assert_eq!(check_openai_api_key(&closed, "sk-good").await.unwrap_err(), "Network unreachable");
+    }
+
+    #[tokio::test]
+    async fn blank_api_key_is_invalid_without_a_request() {
+        assert!(!validate_openai_api_key("   ".to_string()).await.unwrap());
+    }
+
+    #[test]
+    fn settings_round_trip_and_default_when_missing() {
+        let dir = scratch_dir();
+        let path = dir.path().join("nested").join("settings.json");
+        assert_eq!(load_settings_from(&path).unwrap(), AppSettings::default());
+
+        let settings = AppSettings {
+            default_template_path: Some("C:\\Templates\\letter.docx".to_string()),
+            pandoc_output_format: Some("docx".to_string()),
+            audio_language: Some("en".to_string()),
+            api_provider: Some("azure".to_string()),
+            data_dir: None,
+        };
+        save_settings_to(&path, &settings).unwrap();
+        assert_eq!(load_settings_from(&path).unwrap(), settings);
+        assert!(fs::read_dir(path.parent().unwrap()).unwrap().count() == 1, "no temp files left behind");
+    }
+
+    #[test]
+    fn settings_tolerate_older_and_newer_files() {
+        let dir = scratch_dir();
+        let path = dir.path().join("settings.json");
+
+        // Written by an older version - missing fields take their defaults
+        fs::write(&path, r#"{"audio_language":"fr"}"#).unwrap();
+        let settings = load_settings_from(&path).unwrap();
+        assert_eq!(settings.audio_language.as_deref(), Some("fr"));
+        assert_eq!(settings.api_provider, None);
+
+        // Written by a newer version - unknown fields are ignored
+        fs::write(&path, r#"{"api_provider":"local","theme":"dark"}"#).unwrap();
+        assert_eq!(load_settings_from(&path).unwrap().api_provider.as_deref(), Some("local"));
+
+        fs::write(&path, "{ not json").unwrap();
+        assert!(load_settings_from(&path).unwrap_err().contains("Failed to parse settings"));
+    }
+
+    #[test]
+    fn recent_files_are_deduplicated_most_recent_first() {
+        let dir = scratch_dir();
+        let store = dir.path().join("recent_files.json");
+
+        record_file_access_in(&store, "/clients/a.md", 100).unwrap();
+        record_file_access_in(&store, "/clients/b.md", 200).unwrap();
+        record_file_access_in(&store, "/clients/a.md", 300).unwrap();
+
+        assert_eq!(load_recent_files_from(&store).unwrap(), vec![
+            RecentFile { path: "/clients/a.md".to_string(), accessed_at: 300 },
+            RecentFile { path: "/clients/b.md".to_string(), accessed_at: 200 },
+        ]);
+    }
+
+    #[test]
+    fn recent_files_are_capped_and_survive_corruption() {
+        let mut recent = Vec::new();
+        for n in 0..(MAX_RECENT_FILES as u64 + 5) {
+            push_recent_file(&mut recent, &format!("/notes/{}.md", n), n);
+        }
+        assert_eq!(recent.len(), MAX_RECENT_FILES);
+        assert_eq!(recent[0].path, format!("/notes/{}.md", MAX_RECENT_FILES + 4));
+        assert_eq!(recent.last().unwrap().path, "/notes/5.md");
+
+        let dir = scratch_dir();
+        let store = dir.path().join("nested").join("recent_files.json");
+        assert!(load_recent_files_from(&store).unwrap().is_empty());
+        fs::create_dir_all(store.parent().unwrap()).unwrap();
+        fs::write(&store, "[{broken").unwrap();
+        assert!(load_recent_files_from(&store).unwrap_err().contains("Failed to parse recent files"));
+        record_file_access_in(&store, "/clients/c.md", 5).unwrap();
+        assert_eq!(load_recent_files_from(&store).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn data_dir_override_is_read_from_settings() {
+        let dir = scratch_dir();
+        let settings = dir.path().join("settings.json");
+        assert_eq!(configured_data_dir_from(&settings).unwrap(), None);
+
+        let external = dir.path().join("External");
+        assert!(set_data_dir_in(&settings, &path_string(&external)).unwrap_err().starts_with("Directory does not exist"));
+
+        fs::create_dir_all(&external).unwrap();
+        let stored = set_data_dir_in(&settings, &path_string(&external.join("../External"))).unwrap();
+        assert_eq!(stored, external.canonicalize().unwrap());
+        assert_eq!(configured_data_dir_from(&settings).unwrap(), Some(stored));
+        // The empty folder is marked so it is still accepted once files are added
+        assert!(external.join("Client_Records").is_dir() && external.join("Templates").is_dir());
+    }
+
+    #[test]
+    fn data_dir_candidates_exclude_roots_and_home() {
+        let dir = scratch_dir();
+        let home = dir.path().join("home").join("clinician");
+        fs::create_dir_all(&home).unwrap();
+
+        let err = check_data_dir_candidate(Path::new("/"), Some(&home), true).unwrap_err();
+        assert!(err.starts_with("Cannot use a filesystem root"), "{}", err);
+        for refused in [home.clone(), dir.path().join("home")] {
+            let err = check_data_dir_candidate(&refused, Some(&home), true).unwrap_err();
+            assert!(err.starts_with("Cannot use the home folder"), "{}", err);
+        }
+
+        let inside_home = home.join("PBS_Data");
+        fs::create_dir_all(&inside_home).unwrap();
+        assert!(check_data_dir_candidate(&inside_home, Some(&home), true).is_ok());
+    }
+
+    #[test]
+    fn data_dir_must_be_empty_or_marked() {
+        let dir = scratch_dir();
+        let settings = dir.path().join("settings.json");
+        let photos = dir.path().join("Photos");
+        fs::create_dir_all(&photos).unwrap();
+        fs::write(photos.join("holiday.jpg"), "jpeg").unwrap();
+
+        let err = set_data_dir_in(&settings, &path_string(&photos)).unwrap_err();
+        assert!(err.starts_with("Data directory must be empty or an existing PBS_Admin folder"), "{}", err);
+        assert!(!settings.exists());
+        assert!(!photos.join("Client_Records").exists());
+
+        let existing = dir.path().join("Old_PBS_Admin");
+        fs::create_dir_all(existing.join("Templates")).unwrap();
+        fs::write(existing.join("settings.json"), "{}").unwrap();
+        assert_eq!(set_data_dir_in(&settings, &path_string(&existing)).unwrap(), existing.canonicalize().unwrap());
+    }
+
+    #[test]
+    fn unchecked_data_dir_in_settings_is_ignored() {
+        let dir = scratch_dir();
+        let settings = dir.path().join("settings.json");
+        let photos = dir.path().join("Photos");
+        fs::create_dir_all(&photos).unwrap();
+        fs::write(photos.join("holiday.jpg"), "jpeg").unwrap();
+
+        for tampered in [path_string(Path::new("/")), path_string(&photos), path_string(&dir.path().join("missing"))] {
+            let edited = AppSettings { data_dir: Some(tampered.clone()), ..AppSettings::default() };
+            save_settings_to(&settings, &edited).unwrap();
+            assert_eq!(configured_data_dir_from(&settings).unwrap(), None, "{}", tampered);
+        }
+    }
+
+    #[test]
+    fn saving_settings_keeps_the_checked_data_dir() {
+        let dir = scratch_dir();
+        let settings = dir.path().join("settings.json");
+        let external = dir.path().join("External");
+        fs::create_dir_all(&external).unwrap();
+        let stored = set_data_dir_in(&settings, &path_string(&external)).unwrap();
+
+        let from_frontend = AppSettings {
+            audio_language: Some("en".to_string()),
+            data_dir: Some("/".to_string()),
+            ..AppSettings::default()
+        };
+        save_settings_keeping_data_dir(&settings, from_frontend).unwrap();
+
+        let saved = load_settings_from(&settings).unwrap();
+        assert_eq!(saved.audio_language.as_deref(), Some("en"));
+        assert_eq!(saved.data_dir, Some(path_string(&stored)));
+    }
+
+    #[test]
+    fn path_commands_build_on_the_data_dir() {
+        let dir = scratch_dir();
+        let settings = dir.path().join("settings.json");
+        let external = dir.path().join("External");
+        fs::create_dir_all(&external).unwrap();
+        set_data_dir_in(&settings, &path_string(&external)).unwrap();
+        let data_dir = configured_data_dir_from(&settings).unwrap().unwrap();
+
+        assert_eq!(client_records_path_in(&data_dir).unwrap(), data_dir.join("Client_Records"));
+        assert_eq!(templates_path_in(&data_dir).unwrap(), data_dir.join("Templates"));
+        assert_eq!(backups_path_in(&data_dir).unwrap(), data_dir.join("Backups"));
+        for folder in ["Client_Records", "Templates", "Backups"] {
+            assert!(external.join(folder).is_dir(), "{}", folder);
+        }
+
+        fs::write(data_dir.join("clinical_terms.txt"), "fluoxetine\n").unwrap();
+        assert_eq!(read_clinical_terms_in(&data_dir).unwrap(), strings(&["fluoxetine"]));
+    }
+
+    #[test]
+    fn blank_data_dir_setting_falls_back_to_documents() {
+        let dir = scratch_dir();
+        let settings = dir.path().join("settings.json");
+        fs::write(&settings, r#"{"data_dir": "  "}"#).unwrap();
+        assert_eq!(configured_data_dir_from(&settings).unwrap(), None);
+    }
+}
@@ -0,0 +1,605 @@
+// PBS Admin - Logging, audit trail and system diagnostics
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::io::Write;
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::backup::get_database_path_internal;
+use crate::files::system_time_to_unix_secs;
+use crate::pdf::LIBREOFFICE_CANDIDATE_PATHS;
+use crate::settings::resolve_pbs_admin_data_dir;
+
+// ============================================================================
+// LOGGING
+// ============================================================================
+
+/// Log files larger than this are rotated aside at startup
+const MAX_LOG_FILE_BYTES: u64 = 5 * 1024 * 1024;
+
+static LOG_FILE_PATH: std::sync::OnceLock<PathBuf> = std::sync::OnceLock::new();
+
+fn get_logs_dir() -> Result<PathBuf, String> {
+    dirs::document_dir()
+        .map(|docs| docs.join("PBS_Admin").join("Logs"))
+        .ok_or_else(|| "Could not find Documents folder".to_string())
+}
+
+/// Path of today's log file: Documents/PBS_Admin/Logs/pbs_admin_<date>.log
+fn todays_log_file_path() -> Result<PathBuf, String> {
+    let date = chrono::Local::now().format("%Y-%m-%d");
+    Ok(get_logs_dir()?.join(format!("pbs_admin_{}.log", date)))
+}
+
+/// Rename an oversized log to `<name>_1.log` (or the next free number)
+fn rotate_log_file(path: &Path) -> Result<(), String> {
+    let size = match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(_) => return Ok(()),
+    };
+    if size <= MAX_LOG_FILE_BYTES {
+        return Ok(());
+    }
+
+    let stem = path.file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "pbs_admin".to_string());
+
+    let mut index = 1;
+    loop {
+        let rotated = path.with_file_name(format!("{}_{}.log", stem, index));
+        if !rotated.exists() {
+            return fs::rename(path, &rotated)
+                .map_err(|e| format!("Failed to rotate log file: {}", e));
+        }
+        index += 1;
+    }
+}
+
+/// Set up tracing: readable output on the console plus JSON lines in today's log file
+pub(crate) fn init_logging() -> Result<PathBuf, String> {
+    use tracing_subscriber::prelude::*;
+    use tracing_subscriber::filter::LevelFilter;
+
+    let path = todays_log_file_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create logs directory: {}", e))?;
+    }
+    rotate_log_file(&path)?;
+
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Failed to open log file: {}", e))?;
+
+    tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_subscriber::fmt::layer().json().with_ansi(false).with_writer(Mutex::new(file)))
+        .try_init()
+        .map_err(|e| format!("Failed to initialize logging: {}", e))?;
+
+    let _ = LOG_FILE_PATH.set(path.clone());
+    Ok(path)
+}
+
+/// Read the last `count` lines of a file, reading backwards so large logs aren't loaded whole
+pub(crate) fn read_last_lines(path: &Path, count: usize) -> Result<Vec<String>, String> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    const CHUNK_SIZE: u64 = 8192;
+
+    let mut file = fs::File::open(path)
+        .map_err(|e| format!("Failed to open log file: {}", e))?;
+    let mut pos = file.metadata()
+        .map_err(|e| format!("Failed to read log file: {}", e))?
+        .len();
+
+    // Stop once we hold more than `count` newlines - the first (partial) line is then dropped
+    let mut buffer: Vec<u8> = Vec::new();
+    while pos > 0 && buffer.iter().filter(|&&b| b == b'\n').count() <= count {
+        let read_len = CHUNK_SIZE.min(pos);
+        pos -= read_len;
+
+        let mut chunk = vec![0u8; read_len as usize];
+        file.seek(SeekFrom::Start(pos))
+            .and_then(|_| file.read_exact(&mut chunk))
+            .map_err(|e| format!("Failed to read log file: {}", e))?;
+        chunk.extend_from_slice(&buffer);
+        buffer = chunk;
+    }
+
+    let text = String::from_utf8_lossy(&buffer);
+    let lines: Vec<&str> = text.lines().collect();
+    let start = lines.len().saturating_sub(count);
+    Ok(lines[start..].iter().map(|line| line.to_string()).collect())
+}
+
+/// Get the path of the log file currently being written
+#[tauri::command]
+pub(crate) fn get_log_file_path() -> Result<String, String> {
+    let path = match LOG_FILE_PATH.get() {
+        Some(path) => path.clone(),
+        None => todays_log_file_path()?,
+    };
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Read the last N lines of the current log file
+#[tauri::command]
+pub(crate) fn read_recent_logs(lines: u32) -> Result<Vec<String>, String> {
+    let path = PathBuf::from(get_log_file_path()?);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    read_last_lines(&path, lines as usize)
+}
+
+/// One line of Documents/PBS_Admin/Logs/audit.log (JSON Lines, append-only)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub(crate) struct AuditEntry {
+    pub(crate) timestamp: u64,
+    pub(crate) command: String,
+    pub(crate) path: String,
+    pub(crate) outcome: String,
+}
+
+/// Keeps concurrent audit lines from interleaving
+static AUDIT_LOG_LOCK: Mutex<()> = Mutex::new(());
+
+pub(crate) fn get_audit_log_path() -> Result<PathBuf, String> {
+    Ok(get_logs_dir()?.join("audit.log"))
+}
+
+fn append_audit_entry(path: &Path, entry: &AuditEntry) -> Result<(), String> {
+    let _guard = AUDIT_LOG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create logs directory: {}", e))?;
+    }
+    let mut line = serde_json::to_string(entry)
+        .map_err(|e| format!("Failed to serialize audit entry: {}", e))?;
+    line.push('\n');
+
+    // Append only - the audit log is never truncated or rewritten
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open audit log: {}", e))?;
+    file.write_all(line.as_bytes())
+        .map_err(|e| format!("Failed to write audit log: {}", e))
+}
+
+/// Record a file-mutating operation in the audit log. Failures are logged, never raised.
+fn log_audit_event(command: &str, path: &str, outcome: &str) {
+    let entry = AuditEntry {
+        timestamp: system_time_to_unix_secs(std::time::SystemTime::now()),
+        command: command.to_string(),
+        path: path.to_string(),
+        outcome: outcome.to_string(),
+    };
+    if let Err(e) = get_audit_log_path().and_then(|log| append_audit_entry(&log, &entry)) {
+        warn!("Failed to write audit entry for {} {}: {}", command, path, e);
+    }
+}
+
+/// Audit a command's result ("success" or "error: ...") and pass it through
+pub(crate) fn audited<T>(command: &str, path: &str, result: Result<T, String>) -> Result<T, String> {
+    let outcome = match &result {
+        Ok(_) => "success".to_string(),
+        Err(e) => format!("error: {}", e),
+    };
+    log_audit_event(command, path, &outcome);
+    result
+}
+
+/// Page through audit entries, newest first. `offset` skips that many of the newest entries.
+pub(crate) fn read_audit_entries(path: &Path, limit: u32, offset: u32) -> Result<Vec<AuditEntry>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let wanted = limit as usize + offset as usize;
+    let lines = read_last_lines(path, wanted)?;
+
+    Ok(lines.iter()
+        .rev()
+        .filter(|line| !line.trim().is_empty())
+        .skip(offset as usize)
+        .take(limit as usize)
+        .filter_map(|line| match serde_json::from_str::<AuditEntry>(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warn!("Skipping malformed audit log line: {}", e);
+                None
+            }
+        })
+        .collect())
+}
+
+/// Read audit log entries (newest first) for display, `limit` at a time
+#[tauri::command]
+pub(crate) fn read_audit_log(limit: u32, offset: u32) -> Result<Vec<AuditEntry>, String> {
+    read_audit_entries(&get_audit_log_path()?, limit, offset)
+}
+
+// ============================================================================
+// SYSTEM DIAGNOSTICS
+// ============================================================================
+
+#[derive(Serialize)]
+pub(crate) struct SystemInfo {
+    os_name: String,
+    os_version: String,
+    arch: String,
+    total_memory_mb: u64,
+    available_memory_mb: u64,
+    /// Free space on the filesystem holding the PBS_Admin data directory
+    pbs_admin_disk_free_bytes: u64,
+    cpu_count: u32,
+}
+
+/// Free bytes on the disk whose mount point most specifically contains `path`
+fn disk_free_bytes_for(path: &Path) -> u64 {
+    // The data folder may not exist yet - measure the closest existing ancestor
+    let existing = path.ancestors()
+        .find(|ancestor| ancestor.exists())
+        .and_then(|ancestor| ancestor.canonicalize().ok())
+        .unwrap_or_else(|| path.to_path_buf());
+
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    disks.list()
+        .iter()
+        .filter(|disk| existing.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| disk.available_space())
+        .unwrap_or(0)
+}
+
+/// Get OS, memory, CPU and PBS_Admin disk space information
+#[tauri::command]
+pub(crate) fn get_system_info() -> Result<SystemInfo, String> {
+    let mut system = sysinfo::System::new();
+    system.refresh_memory();
+    system.refresh_cpu_list(sysinfo::CpuRefreshKind::new());
+
+    let pbs_admin_path = resolve_pbs_admin_data_dir()?;
+
+    Ok(SystemInfo {
+        os_name: sysinfo::System::name().unwrap_or_else(|| std::env::consts::OS.to_string()),
+        os_version: sysinfo::System::os_version().unwrap_or_default(),
+        arch: std::env::consts::ARCH.to_string(),
+        total_memory_mb: system.total_memory() / 1_048_576,
+        available_memory_mb: system.available_memory() / 1_048_576,
+        pbs_admin_disk_free_bytes: disk_free_bytes_for(&pbs_admin_path),
+        cpu_count: system.cpus().len().max(1) as u32,
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct DependencyStatus {
+    name: String,
+    available: bool,
+    version: Option<String>,
+    path: Option<String>,
+}
+
+/// Look for `names` (first match wins) on `search_path` and ask the tool for its version
+fn dependency_status(
+    display_name: &str,
+    names: &[&str],
+    version_args: &[&str],
+    search_path: Option<&std::ffi::OsStr>,
+) -> DependencyStatus {
+    let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let mut found = names.iter()
+        .find_map(|name| which::which_in(name, search_path, &cwd).ok());
+
+    // LibreOffice is rarely on PATH outside Linux - also check where convert_docx_to_pdf looks
+    if found.is_none() && display_name == "soffice" {
+        found = LIBREOFFICE_CANDIDATE_PATHS.iter().map(PathBuf::from).find(|p| p.is_file());
+    }
+
+    let path = match found {
+        Some(path) => path,
+        None => return DependencyStatus {
+            name: display_name.to_string(),
+            available: false,
+            version: None,
+            path: None,
+        },
+    };
+
+    // Not every tool prints its version to stdout, so fall back to stderr
+    let version = Command::new(&path)
+        .args(version_args)
+        .output()
+        .ok()
+        .and_then(|output| {
+            let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            stdout.lines().chain(stderr.lines())
+                .map(|line| line.trim().to_string())
+                .find(|line| !line.is_empty())
+        });
+
+    DependencyStatus {
+        name: display_name.to_string(),
+        available: true,
+        version,
+        path: Some(path.to_string_lossy().to_string()),
+    }
+}
+
+/// Check the external tools the app relies on (pandoc, ffmpeg, LibreOffice and, on Windows, PowerShell)
+#[tauri::command]
+pub(crate) fn check_dependencies() -> Result<Vec<DependencyStatus>, String> {
+    let search_path = std::env::var_os("PATH");
+    let statuses = check_dependencies_in(search_path.as_deref());
+
+    for status in &statuses {
+        info!("Dependency {}: {}", status.name, if status.available { "available" } else { "missing" });
+    }
+
+    Ok(statuses)
+}
+
+/// Dependency statuses with tools looked up on `search_path` (a PATH-style list)
+fn check_dependencies_in(search_path: Option<&std::ffi::OsStr>) -> Vec<DependencyStatus> {
+    let mut statuses = vec![
+        dependency_status("pandoc", &["pandoc"], &["--version"], search_path),
+        dependency_status("ffmpeg", &["ffmpeg"], &["-version"], search_path),
+        dependency_status("soffice", &["soffice", "libreoffice"], &["--version"], search_path),
+    ];
+
+    if cfg!(target_os = "windows") {
+        statuses.push(dependency_status(
+            "powershell",
+            &["powershell"],
+            &["-NoProfile", "-Command", "$PSVersionTable.PSVersion.ToString()"],
+            search_path,
+        ));
+    }
+
+    statuses
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct HealthReport {
+    database_ok: bool,
+    filesystem_ok: bool,
+    temp_dir_ok: bool,
+    network_ok: bool,
+    errors: Vec<String>,
+}
+
+/// Open the database read-only and run a query. `SELECT 1` alone never touches the file,
+/// so the schema is counted to catch corrupt or non-SQLite files too.
+fn probe_database(db_path: &Path) -> Result<(), String> {
+    let conn = rusqlite::Connection::open_with_flags(db_path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open database: {}", e))?;
+    conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0))
+        .map_err(|e| format!("Database query failed: {}", e))?;
+    Ok(())
+}
+
+/// Check `dir` exists and a file can be created and removed in it
+fn probe_writable_dir(dir: &Path) -> Result<(), String> {
+    if !dir.is_dir() {
+        return Err(format!("Directory does not exist: {}", dir.display()));
+    }
+    let probe = dir.join(format!(".pbs_health_{}", std::process::id()));
+    fs::write(&probe, b"ok")
+        .map_err(|e| format!("Directory is not writable: {}: {}", dir.display(), e))?;
+    fs::remove_file(&probe)
+        .map_err(|e| format!("Failed to remove probe file in {}: {}", dir.display(), e))
+}
+
+/// Any HTTP response counts as reachable - only connection failures matter
+async fn probe_network(url: &str) -> Result<(), String> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    client.head(url).send().await
+        .map(|_| ())
+        .map_err(|e| format!("Network unreachable ({}): {}", url, e))
+}
+
+async fn run_health_check(db_path: Result<PathBuf, String>, data_dir: Result<PathBuf, String>, temp_dir: &Path, network_url: &str) -> HealthReport {
+    let mut errors = Vec::new();
+    let mut record = |result: Result<(), String>| match result {
+        Ok(()) => true,
+        Err(e) => {
+            errors.push(e);
+            false
+        }
+    };
+
+    let database_ok = record(db_path.and_then(|path| probe_database(&path)));
+    let filesystem_ok = record(data_dir.and_then(|dir| probe_writable_dir(&dir)));
+    let temp_dir_ok = record(fs::create_dir_all(temp_dir)
+        .map_err(|e| format!("Failed to create temp directory: {}", e))
+        .and_then(|_| probe_writable_dir(temp_dir)));
+    let network_ok = record(probe_network(network_url).await);
+
+    HealthReport { database_ok, filesystem_ok, temp_dir_ok, network_ok, errors }
+}
+
+/// Probe the database, data folder, temp folder and network so the UI can show what's broken
+#[tauri::command]
+pub(crate) async fn app_state_health_check() -> Result<HealthReport, String> {
+    let report = run_health_check(
+        get_database_path_internal(),
+        resolve_pbs_admin_data_dir(),
+        &std::env::temp_dir().join("PBS_Admin"),
+        "https://api.openai.com",
+    ).await;
+
+    for error in &report.errors {
+        warn!("Health check: {}", error);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        http_response, mock_tool, path_string, sample_database, scratch_dir, serve_http,
+    };
+
+    #[test]
+    fn oversized_logs_rotate_to_the_next_free_name() {
+        let dir = scratch_dir();
+        let log = dir.path().join("pbs_admin_2025-01-15.log");
+        fs::write(dir.path().join("pbs_admin_2025-01-15_1.log"), "older").unwrap();
+
+        // At the limit: left alone
+        fs::File::create(&log).unwrap().set_len(MAX_LOG_FILE_BYTES).unwrap();
+        rotate_log_file(&log).unwrap();
+        assert!(log.exists());
+
+        fs::File::create(&log).unwrap().set_len(MAX_LOG_FILE_BYTES + 1).unwrap();
+        rotate_log_file(&log).unwrap();
+        assert!(!log.exists());
+        assert_eq!(fs::metadata(dir.path().join("pbs_admin_2025-01-15_2.log")).unwrap().len(), MAX_LOG_FILE_BYTES + 1);
+        assert_eq!(fs::read_to_string(dir.path().join("pbs_admin_2025-01-15_1.log")).unwrap(), "older");
+
+        rotate_log_file(&dir.path().join("missing.log")).unwrap();
+    }
+
+    #[test]
+    fn read_last_lines_tails_large_files() {
+        let dir = scratch_dir();
+        let log = dir.path().join("big.log");
+        // Lines span several 8 KB read chunks
+        let content: String = (1..=5000).map(|i| format!("line {}\n", i)).collect();
+        fs::write(&log, content).unwrap();
+
+        assert_eq!(read_last_lines(&log, 3).unwrap(), ["line 4998", "line 4999", "line 5000"]);
+        assert_eq!(read_last_lines(&log, 2000).unwrap().first().unwrap(), "line 3001");
+        assert_eq!(read_last_lines(&log, 10_000).unwrap().len(), 5000);
+        assert!(read_last_lines(&log, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn get_system_info_reports_os_and_cpus() {
+        let info = get_system_info().unwrap();
+        assert!(!info.os_name.is_empty());
+        assert!(!info.arch.is_empty());
+        assert!(info.cpu_count >= 1);
+        assert!(info.total_memory_mb >= info.available_memory_mb);
+    }
+
+    #[test]
+    fn disk_free_bytes_measures_nearest_existing_ancestor() {
+        let dir = scratch_dir();
+        let existing = disk_free_bytes_for(dir.path());
+        assert!(existing > 0);
+
+        // A data folder that hasn't been created yet is measured on its parent's disk
+        let missing = dir.path().join("not").join("yet").join("created");
+        assert!(disk_free_bytes_for(&missing) > 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_dependencies_reports_tools_missing_from_path() {
+        let bin = tempfile::tempdir().unwrap();
+        let pandoc = mock_tool(bin.path(), "pandoc", "echo 'pandoc 3.1.13'");
+        mock_tool(bin.path(), "ffmpeg", "echo 'ffmpeg version 6.1' >&2; exit 1");
+        let only_pandoc = tempfile::tempdir().unwrap();
+        fs::copy(&pandoc, only_pandoc.path().join("pandoc")).unwrap();
+
+        let statuses = check_dependencies_in(Some(only_pandoc.path().as_os_str()));
+        let pandoc_status = statuses.iter().find(|s| s.name == "pandoc").unwrap();
+        assert!(pandoc_status.available);
+        assert_eq!(pandoc_status.version.as_deref(), Some("pandoc 3.1.13"));
+        assert_eq!(pandoc_status.path.as_deref(), Some(path_string(&only_pandoc.path().join("pandoc")).as_str()));
+        let ffmpeg_status = statuses.iter().find(|s| s.name == "ffmpeg").unwrap();
+        assert!(!ffmpeg_status.available);
+        assert_eq!(ffmpeg_status.version, None);
+        assert_eq!(ffmpeg_status.path, None);
+
+        // Version text printed only to stderr (and a non-zero exit) is still picked up
+        let statuses = check_dependencies_in(Some(bin.path().as_os_str()));
+        let ffmpeg_status = statuses.iter().find(|s| s.name == "ffmpeg").unwrap();
+        assert!(ffmpeg_status.available);
+        assert_eq!(ffmpeg_status.version.as_deref(), Some("ffmpeg version 6.1"));
+    }
+
+    #[test]
+    fn check_dependencies_with_empty_path_finds_nothing_on_path() {
+        let empty = tempfile::tempdir().unwrap();
+        let statuses = check_dependencies_in(Some(empty.path().as_os_str()));
+        let names: Vec<&str> = statuses.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(&names[..3], ["pandoc", "ffmpeg", "soffice"]);
+        assert!(statuses.iter().filter(|s| s.name != "soffice").all(|s| !s.available));
+    }
+
+    /// URL of a local port with nothing listening on it
+    fn refused_url() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    }
+
+    #[tokio::test]
+    async fn health_check_passes_against_real_temp_dirs() {
+        let dir = scratch_dir();
+        let db_path = dir.path().join("pbs_admin.db");
+        sample_database(&db_path);
+        let data_dir = dir.path().join("data");
+        fs::create_dir_all(&data_dir).unwrap();
+        let temp_dir = dir.path().join("temp").join("PBS_Admin");
+        let network = serve_http(|request| http_response(request, "401 Unauthorized", &[], b""));
+
+        let report = run_health_check(Ok(db_path), Ok(data_dir.clone()), &temp_dir, &network).await;
+
+        assert!(report.database_ok && report.filesystem_ok && report.temp_dir_ok && report.network_ok, "{:?}", report.errors);
+        assert!(report.errors.is_empty());
+        // Created on demand; probe files are cleaned up
+        assert!(temp_dir.is_dir());
+        assert_eq!(fs::read_dir(&temp_dir).unwrap().count(), 0);
+        assert_eq!(fs::read_dir(&data_dir).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn health_check_reports_each_failure() {
+        let dir = scratch_dir();
+        let not_a_db = dir.path().join("pbs_admin.db");
+        fs::write(&not_a_db, "this is not sqlite").unwrap();
+        // A file where the temp directory should be
+        let blocked_temp = dir.path().join("blocked");
+        fs::write(&blocked_temp, "").unwrap();
+
+        let report = run_health_check(
+            Ok(not_a_db),
+            Err("Could not find Documents folder".to_string()),
+            &blocked_temp,
+            &refused_url(),
+        ).await;
+
+        assert!(!report.database_ok && !report.filesystem_ok && !report.temp_dir_ok && !report.network_ok, "{:?}", report);
+        assert_eq!(report.errors.len(), 4);
+        assert!(report.errors[0].starts_with("Database query failed"), "{}", report.errors[0]);
+        assert_eq!(report.errors[1], "Could not find Documents folder");
+        assert!(report.errors[2].starts_with("Failed to create temp directory"), "{}", report.errors[2]);
+        assert!(report.errors[3].starts_with("Network unreachable"), "{}", report.errors[3]);
+    }
+
+    #[test]
+    fn writable_dir_probe_needs_an_existing_directory() {
+        let dir = scratch_dir();
+        assert!(probe_writable_dir(dir.path()).is_ok());
+        assert!(probe_writable_dir(&dir.path().join("missing")).unwrap_err().starts_with("Directory does not exist"));
+        assert!(probe_database(&dir.path().join("missing.db")).unwrap_err().starts_with("Failed to open database"));
+    }
+}
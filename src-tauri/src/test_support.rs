@@ -0,0 +1,158 @@
+// PBS Admin - Helpers shared by the unit tests
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::io::Write;
+
+use crate::system::{AuditEntry, get_audit_log_path, read_audit_entries};
+use crate::transcription::TranscriptionMetadata;
+
+/// Scratch directory under temp/PBS_Admin, which the path validators allow
+pub(crate) fn scratch_dir() -> tempfile::TempDir {
+    let root = std::env::temp_dir().join("PBS_Admin");
+    fs::create_dir_all(&root).unwrap();
+    tempfile::Builder::new().prefix("test_").tempdir_in(root).unwrap()
+}
+
+pub(crate) fn path_string(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+/// Minimal HTTP server for download tests. Each connection gets
+/// `respond(request_head)` and is then closed. Returns the base URL.
+pub(crate) fn serve_http<F>(respond: F) -> String
+where
+    F: Fn(&str) -> Vec<u8> + Send + 'static,
+{
+    use std::io::Read;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                match stream.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => request.extend_from_slice(&buffer[..n]),
+                }
+            }
+            let _ = stream.write_all(&respond(&String::from_utf8_lossy(&request)));
+        }
+    });
+    url
+}
+
+/// An HTTP/1.1 response with Content-Length; HEAD requests get the headers only
+pub(crate) fn http_response(request: &str, status: &str, headers: &[(&str, String)], body: &[u8]) -> Vec<u8> {
+    let mut response = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n", status, body.len());
+    for (name, value) in headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str("\r\n");
+    let mut bytes = response.into_bytes();
+    if !request.starts_with("HEAD ") {
+        bytes.extend_from_slice(body);
+    }
+    bytes
+}
+
+pub(crate) fn strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+/// Write an executable shell script standing in for an external tool
+#[cfg(unix)]
+pub(crate) fn mock_tool(dir: &Path, name: &str, script: &str) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = dir.join(name);
+    fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+/// Minimal PDF with `pages` pages, each showing "<label> <n>"
+pub(crate) fn sample_pdf(label: &str, pages: usize) -> lopdf::Document {
+    use lopdf::content::{Content, Operation};
+    use lopdf::{dictionary, Object, Stream};
+
+    let mut doc = lopdf::Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let font_id = doc.add_object(dictionary! {"Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Courier"});
+    let resources_id = doc.add_object(dictionary! {"Font" => dictionary! {"F1" => font_id}});
+    let mut kids: Vec<Object> = Vec::new();
+    for n in 0..pages {
+        let content = Content {
+            operations: vec![
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec!["F1".into(), 24.into()]),
+                Operation::new("Td", vec![100.into(), 600.into()]),
+                Operation::new("Tj", vec![Object::string_literal(format!("{} {}", label, n))]),
+                Operation::new("ET", vec![]),
+            ],
+        };
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+            "Resources" => resources_id,
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        });
+        kids.push(page_id.into());
+    }
+    doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+        "Type" => "Pages",
+        "Kids" => kids,
+        "Count" => pages as i64,
+    }));
+    let catalog_id = doc.add_object(dictionary! {"Type" => "Catalog", "Pages" => pages_id});
+    doc.trailer.set("Root", catalog_id);
+    doc
+}
+
+pub(crate) fn wav_bytes(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let mut body = b"WAVE".to_vec();
+    for (id, data) in chunks {
+        body.extend_from_slice(*id);
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            body.push(0);
+        }
+    }
+    [b"RIFF".to_vec(), (body.len() as u32).to_le_bytes().to_vec(), body].concat()
+}
+
+/// A small clinic database: clients with pets (foreign key) and a BLOB column
+pub(crate) fn sample_database(path: &Path) -> rusqlite::Connection {
+    let conn = rusqlite::Connection::open(path).unwrap();
+    conn.execute_batch("
+            PRAGMA foreign_keys = ON;
+            CREATE TABLE Client (id INTEGER PRIMARY KEY, name TEXT NOT NULL, balance REAL, notes TEXT);
+            CREATE TABLE Pet (id INTEGER PRIMARY KEY, clientId INTEGER NOT NULL REFERENCES Client(id), name TEXT, photo BLOB);
+            INSERT INTO Client VALUES (1, 'Jane Doe', 195.5, NULL), (2, 'O''Brien', 0, 'Prefers email');
+            INSERT INTO Pet VALUES (10, 1, 'Max', X'00FF10'), (11, 2, 'Luna', NULL);
+        ").unwrap();
+    conn
+}
+
+pub(crate) fn sample_transcription_metadata() -> TranscriptionMetadata {
+    TranscriptionMetadata {
+        client_name: "Jane Doe".to_string(),
+        session_date: "2025-01-15".to_string(),
+        clinician_name: "Dr Smith".to_string(),
+        duration_seconds: 3725.4,
+    }
+}
+
+/// Audit entries for `path` (newest first) from the real audit log
+pub(crate) fn audit_entries_for(path: &str) -> Vec<AuditEntry> {
+    read_audit_entries(&get_audit_log_path().unwrap(), 10_000, 0).unwrap()
+        .into_iter()
+        .filter(|entry| entry.path.contains(path))
+        .collect()
+}
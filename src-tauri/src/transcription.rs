@@ -0,0 +1,1810 @@
+// PBS Admin - Transcription commands
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{check_rate_limit, validate_write_path, write_file_atomically};
+use crate::audio::{
+    TRANSCRIPTION_MAX_UPLOAD_MB, audio_extension_for_mime, audio_mime_from_extension, check_ffmpeg,
+    get_audio_duration_with, sniff_audio_mime,
+};
+use crate::documents::run_pandoc_from_stdin_with;
+use crate::settings::{resolve_openai_api_key, resolve_pbs_admin_data_dir};
+
+// ============================================================================
+// TRANSCRIPTION EXPORT
+// ============================================================================
+
+/// Header details for an exported transcription document
+#[derive(Debug, Deserialize)]
+pub(crate) struct TranscriptionMetadata {
+    pub(crate) client_name: String,
+    pub(crate) session_date: String,
+    pub(crate) clinician_name: String,
+    pub(crate) duration_seconds: f64,
+}
+
+/// Format seconds as H:MM:SS (or M:SS under an hour)
+pub(crate) fn format_duration_clock(seconds: f64) -> String {
+    let total = seconds.max(0.0).round() as u64;
+    let (hours, minutes, secs) = (total / 3600, (total / 60) % 60, total % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, secs)
+    } else {
+        format!("{}:{:02}", minutes, secs)
+    }
+}
+
+/// Markdown body for a transcription document: session details followed by the transcript
+fn build_transcription_markdown(transcript: &str, metadata: &TranscriptionMetadata) -> String {
+    format!(
+        "**Client:** {}\n**Session date:** {}\n**Clinician:** {}\n**Duration:** {}\n\n## Transcript\n\n{}\n",
+        metadata.client_name,
+        metadata.session_date,
+        metadata.clinician_name,
+        format_duration_clock(metadata.duration_seconds),
+        transcript.trim()
+    )
+}
+
+/// Save a transcript as a formatted Word document (title, session details, transcript) via pandoc
+#[tauri::command]
+pub(crate) fn export_transcription_as_docx(
+    transcript: String,
+    metadata: TranscriptionMetadata,
+    output_path: String,
+    template_path: Option<String>,
+) -> Result<String, String> {
+    export_transcription_as_docx_with(std::ffi::OsStr::new("pandoc"), transcript, metadata, output_path, template_path)
+}
+
+pub(crate) fn export_transcription_as_docx_with(
+    pandoc: &std::ffi::OsStr,
+    transcript: String,
+    metadata: TranscriptionMetadata,
+    output_path: String,
+    template_path: Option<String>,
+) -> Result<String, String> {
+    let validated_output = validate_write_path(&output_path)?;
+    let is_docx = validated_output
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("docx"))
+        .unwrap_or(false);
+    if !is_docx {
+        return Err(format!("Output path must be a .docx file: {}", output_path));
+    }
+
+    let markdown = build_transcription_markdown(&transcript, &metadata);
+    let document_metadata = serde_json::json!({
+        "title": format!("Session Transcript - {}", metadata.client_name),
+        "author": metadata.clinician_name,
+        "date": metadata.session_date,
+    });
+
+    run_pandoc_from_stdin_with(
+        pandoc,
+        markdown,
+        validated_output.to_string_lossy().to_string(),
+        template_path,
+        Some(document_metadata),
+    )
+}
+
+// ============================================================================
+// TRANSCRIPTION RESULTS
+// ============================================================================
+
+// Transcription-related structures
+#[derive(Serialize, Deserialize)]
+struct TranscriptionResponse {
+    text: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct DiarizedSegment {
+    speaker: String,
+    text: String,
+    start: f64,
+    end: f64,
+}
+
+/// Timestamped segment from a whisper-1 `verbose_json` response
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct Segment {
+    start: f64,
+    end: f64,
+    text: String,
+}
+
+/// Word-level timestamp from a whisper-1 `verbose_json` response
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct WordTimestamp {
+    word: String,
+    start: f64,
+    end: f64,
+}
+
+#[derive(Serialize)]
+pub(crate) struct TranscribeResult {
+    pub(crate) text: String,
+    pub(crate) duration: f64,
+    pub(crate) segments: Option<Vec<DiarizedSegment>>,
+    /// Only populated for `verbose_json` responses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) verbose_segments: Option<Vec<Segment>>,
+    /// Only populated for `verbose_json` responses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) words: Option<Vec<WordTimestamp>>,
+}
+
+/// Parse a whisper-1 `verbose_json` response into text, duration, segments, and words
+fn parse_verbose_json_response(response_json: &serde_json::Value) -> (String, Option<f64>, Vec<Segment>, Vec<WordTimestamp>) {
+    let text = response_json["text"].as_str().unwrap_or("").trim().to_string();
+    let duration = response_json["duration"].as_f64();
+
+    let segments = response_json["segments"]
+        .as_array()
+        .map(|arr| {
+            arr.iter().filter_map(|seg| {
+                Some(Segment {
+                    start: seg["start"].as_f64()?,
+                    end: seg["end"].as_f64()?,
+                    text: seg["text"].as_str()?.trim().to_string(),
+                })
+            }).collect()
+        })
+        .unwrap_or_default();
+
+    let words = response_json["words"]
+        .as_array()
+        .map(|arr| {
+            arr.iter().filter_map(|w| {
+                Some(WordTimestamp {
+                    word: w["word"].as_str()?.to_string(),
+                    start: w["start"].as_f64()?,
+                    end: w["end"].as_f64()?,
+                })
+            }).collect()
+        })
+        .unwrap_or_default();
+
+    (text, duration, segments, words)
+}
+
+// ============================================================================
+// LONG RECORDING SEGMENTS
+// ============================================================================
+
+/// Length of each segment when a long recording is split for transcription
+const TRANSCRIPTION_SEGMENT_SECS: u64 = 600;
+
+/// Extra audio added to the end of each segment so words cut at a boundary
+/// appear complete in at least one segment
+const TRANSCRIPTION_SEGMENT_OVERLAP_SECS: u64 = 5;
+
+/// Split an audio file into overlapping segments of `segment_length_secs`
+/// (plus `TRANSCRIPTION_SEGMENT_OVERLAP_SECS`) using FFmpeg.
+/// Returns the segment paths in playback order.
+fn split_audio_file(path: &Path, segment_dir: &Path, segment_length_secs: u64) -> Result<Vec<PathBuf>, String> {
+    split_audio_file_with(std::ffi::OsStr::new("ffmpeg"), std::ffi::OsStr::new("ffprobe"), path, segment_dir, segment_length_secs)
+}
+
+fn split_audio_file_with(
+    ffmpeg: &std::ffi::OsStr,
+    ffprobe: &std::ffi::OsStr,
+    path: &Path,
+    segment_dir: &Path,
+    segment_length_secs: u64,
+) -> Result<Vec<PathBuf>, String> {
+    if segment_length_secs == 0 {
+        return Err("Segment length must be greater than zero".to_string());
+    }
+
+    fs::create_dir_all(segment_dir)
+        .map_err(|e| format!("Failed to create segment directory: {}", e))?;
+
+    let input = path.to_string_lossy().to_string();
+    let duration = get_audio_duration_with(ffprobe, &input)?;
+    let segment_secs = (segment_length_secs + TRANSCRIPTION_SEGMENT_OVERLAP_SECS).to_string();
+
+    let mut segments = Vec::new();
+    let mut start: u64 = 0;
+    while (start as f64) < duration {
+        let segment_path = segment_dir.join(format!("segment_{:03}.mp3", segments.len()));
+
+        let output = Command::new(ffmpeg)
+            .args([
+                "-ss", &start.to_string(),
+                "-t", &segment_secs,
+                "-i", &input,
+                "-ac", "1",           // mono
+                "-ab", "64k",         // 64kbps
+                "-ar", "16000",       // 16kHz
+                "-y",
+                &segment_path.to_string_lossy(),
+            ])
+            .output()
+            .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("FFmpeg split failed: {}", stderr));
+        }
+
+        segments.push(segment_path);
+        start += segment_length_secs;
+    }
+
+    if segments.is_empty() {
+        return Err("FFmpeg produced no output segments".to_string());
+    }
+
+    info!("Split {} into {} segments", input, segments.len());
+
+    Ok(segments)
+}
+
+/// How text repeated in the overlap between two segments is handled
+#[derive(Clone, Copy, PartialEq)]
+enum OverlapTrim {
+    /// Keep everything (duplicates at boundaries are left in)
+    None,
+    /// Drop the longest run of words at the start of a segment that repeats
+    /// the end of the previous one, plus segments/words inside the overlap
+    WordOverlap,
+}
+
+impl OverlapTrim {
+    fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value.unwrap_or("word_overlap") {
+            "word_overlap" => Ok(OverlapTrim::WordOverlap),
+            "none" => Ok(OverlapTrim::None),
+            other => Err(format!("Unsupported overlap trim strategy: {}. Use word_overlap or none.", other)),
+        }
+    }
+}
+
+/// Normalize a word for overlap comparison (case and punctuation insensitive)
+fn normalize_overlap_word(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+/// Remove the leading words of `next` that repeat the trailing words of `previous`
+fn trim_overlapping_text(previous: &str, next: &str) -> String {
+    // Short matches are too likely to be coincidence ("and the", "I think")
+    const MIN_OVERLAP_WORDS: usize = 3;
+    const MAX_OVERLAP_WORDS: usize = 60;
+
+    let prev_words: Vec<String> = previous.split_whitespace().map(normalize_overlap_word).collect();
+    let next_raw: Vec<&str> = next.split_whitespace().collect();
+    let next_words: Vec<String> = next_raw.iter().map(|w| normalize_overlap_word(w)).collect();
+
+    let max_len = prev_words.len().min(next_words.len()).min(MAX_OVERLAP_WORDS);
+    let overlap = (MIN_OVERLAP_WORDS..=max_len)
+        .rev()
+        .find(|&len| prev_words[prev_words.len() - len..] == next_words[..len])
+        .unwrap_or(0);
+
+    next_raw[overlap..].join(" ")
+}
+
+// ============================================================================
+// TRANSCRIPTION REQUESTS
+// ============================================================================
+
+/// Text fields of the transcription multipart form (the audio file part is added separately)
+fn transcription_form_fields(
+    model: &str,
+    response_format: &str,
+    language: Option<&str>,
+    speaker_names: Option<&[String]>,
+    prompt: Option<&str>,
+) -> Vec<(&'static str, String)> {
+    let mut fields = vec![
+        ("model", model.to_string()),
+        ("response_format", response_format.to_string()),
+    ];
+
+    // Without a language the API detects it itself
+    if let Some(language) = language {
+        fields.push(("language", language.to_string()));
+    }
+
+    if model == "whisper-1" {
+        // Request both segment- and word-level timestamps
+        fields.push(("timestamp_granularities[]", "segment".to_string()));
+        fields.push(("timestamp_granularities[]", "word".to_string()));
+
+        // Bias spelling toward domain vocabulary
+        if let Some(prompt) = prompt.filter(|p| !p.trim().is_empty()) {
+            fields.push(("prompt", prompt.to_string()));
+        }
+    } else {
+        fields.push(("chunking_strategy", "auto".to_string()));
+
+        // Add speaker names if provided (up to 4)
+        if let Some(names) = speaker_names {
+            for name in names.iter().take(4) {
+                fields.push(("known_speaker_names[]", name.clone()));
+            }
+        }
+
+        if prompt.is_some_and(|p| !p.trim().is_empty()) {
+            warn!("The diarization model doesn't accept a prompt; use verbose_json (whisper-1) for vocabulary prompts");
+        }
+    }
+
+    fields
+}
+
+/// Send a single audio file (already under the size limit) to the OpenAI transcription API
+#[allow(clippy::too_many_arguments)]
+async fn transcribe_single_file(
+    file_path: &str,
+    file_data: Vec<u8>,
+    language: Option<&str>,
+    api_key: &str,
+    speaker_names: Option<&[String]>,
+    model: &str,
+    response_format: &str,
+    prompt: Option<&str>,
+) -> Result<TranscribeResult, String> {
+    let file_size = file_data.len();
+
+    // Get file name from path
+    let file_name = Path::new(file_path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("audio.m4a")
+        .to_string();
+
+    // Determine MIME type from the audio content, then the file extension
+    let mime_type = sniff_audio_mime(&file_data[..file_data.len().min(512)])
+        .or_else(|| audio_mime_from_extension(&file_name))
+        .unwrap_or("audio/mpeg"); // default
+
+    // Create multipart form with diarization model
+    let part = reqwest::multipart::Part::bytes(file_data)
+        .file_name(file_name)
+        .mime_str(mime_type)
+        .map_err(|e| format!("Failed to set MIME type: {}", e))?;
+
+    let mut form = reqwest::multipart::Form::new().part("file", part);
+    for (name, value) in transcription_form_fields(model, response_format, language, speaker_names, prompt) {
+        form = form.text(name, value);
+    }
+
+    info!("Sending request to OpenAI Transcription API ({}, {})...", model, response_format);
+
+    // Send request to OpenAI API
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(300)) // 5 minute timeout for long audio
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+    let response = client
+        .post("https://api.openai.com/v1/audio/transcriptions")
+        .header("Authorization", format!("Bearer {}", api_key))
+        .multipart(form)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send request to OpenAI: {}", e))?;
+
+    // Check response status
+    if !response.status().is_success() {
+        let error_text = response.text().await
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(format!("OpenAI API error: {}", error_text));
+    }
+
+    // Plain-text responses have no JSON structure (and no timing information)
+    if response_format == "text" {
+        let text = response.text().await
+            .map_err(|e| format!("Failed to read response: {}", e))?
+            .trim()
+            .to_string();
+        let duration = file_size as f64 / 1_000_000.0 * 60.0;
+        info!("Transcription complete. Text: {} chars", text.len());
+        return Ok(TranscribeResult { text, duration, segments: None, verbose_segments: None, words: None });
+    }
+
+    // Parse JSON response
+    let response_json: serde_json::Value = response.json().await
+        .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+    info!("Transcription response received successfully");
+
+    if response_format == "verbose_json" {
+        let (text, duration, verbose_segments, words) = parse_verbose_json_response(&response_json);
+        // verbose_json reports the real duration - only estimate if it's missing
+        let duration = duration.unwrap_or_else(|| file_size as f64 / 1_000_000.0 * 60.0);
+        info!("Transcription complete. Text: {} chars, Duration: {:.0}s, Segments: {}, Words: {}",
+            text.len(), duration, verbose_segments.len(), words.len());
+        return Ok(TranscribeResult {
+            text,
+            duration,
+            segments: None,
+            verbose_segments: Some(verbose_segments),
+            words: Some(words),
+        });
+    }
+
+    let text = response_json["text"]
+        .as_str()
+        .unwrap_or("")
+        .to_string();
+
+    // Parse diarized segments
+    let segments: Option<Vec<DiarizedSegment>> = response_json["segments"]
+        .as_array()
+        .map(|arr| {
+            arr.iter().filter_map(|seg| {
+                Some(DiarizedSegment {
+                    speaker: seg["speaker"].as_str()?.to_string(),
+                    text: seg["text"].as_str()?.to_string(),
+                    start: seg["start"].as_f64()?,
+                    end: seg["end"].as_f64()?,
+                })
+            }).collect()
+        });
+
+    // Calculate duration from last segment end time, or estimate from file size
+    let duration = segments.as_ref()
+        .and_then(|segs| segs.last())
+        .map(|last| last.end)
+        .or_else(|| response_json["duration"].as_f64())
+        .unwrap_or_else(|| {
+            let mb = file_size as f64 / 1_000_000.0;
+            mb * 60.0
+        });
+
+    let segment_count = segments.as_ref().map(|s| s.len()).unwrap_or(0);
+    info!("Transcription complete. Text: {} chars, Duration: {:.0}s, Segments: {}",
+        text.len(), duration, segment_count);
+
+    Ok(TranscribeResult { text, duration, segments, verbose_segments: None, words: None })
+}
+
+/// Fold the transcription of one audio segment into the running result.
+/// `offset` is the segment's start time within the original recording.
+fn append_segment_transcription(
+    merged: &mut TranscribeResult,
+    part: TranscribeResult,
+    offset: f64,
+    is_first: bool,
+    trim: OverlapTrim,
+) {
+    // Timed entries that end inside the overlap were already covered by the previous segment
+    let overlap = TRANSCRIPTION_SEGMENT_OVERLAP_SECS as f64;
+    let keep = |end: f64| is_first || trim == OverlapTrim::None || end > overlap;
+
+    let text = if is_first || trim == OverlapTrim::None {
+        part.text.trim().to_string()
+    } else {
+        trim_overlapping_text(&merged.text, &part.text)
+    };
+    if !text.is_empty() {
+        if !merged.text.is_empty() {
+            merged.text.push(' ');
+        }
+        merged.text.push_str(&text);
+    }
+
+    if let Some(segments) = part.segments {
+        merged.segments.get_or_insert_with(Vec::new).extend(
+            segments.into_iter().filter(|s| keep(s.end)).map(|mut s| {
+                s.start += offset;
+                s.end += offset;
+                s
+            })
+        );
+    }
+    if let Some(segments) = part.verbose_segments {
+        merged.verbose_segments.get_or_insert_with(Vec::new).extend(
+            segments.into_iter().filter(|s| keep(s.end)).map(|mut s| {
+                s.start += offset;
+                s.end += offset;
+                s
+            })
+        );
+    }
+    if let Some(words) = part.words {
+        merged.words.get_or_insert_with(Vec::new).extend(
+            words.into_iter().filter(|w| keep(w.end)).map(|mut w| {
+                w.start += offset;
+                w.end += offset;
+                w
+            })
+        );
+    }
+
+    merged.duration = offset + part.duration;
+}
+
+/// Split an oversized recording with FFmpeg and transcribe the segments one after another
+#[allow(clippy::too_many_arguments)]
+async fn transcribe_in_segments(
+    file_path: &str,
+    segment_dir: &Path,
+    language: Option<&str>,
+    api_key: &str,
+    speaker_names: Option<&[String]>,
+    model: &str,
+    response_format: &str,
+    trim: OverlapTrim,
+    prompt: Option<&str>,
+) -> Result<TranscribeResult, String> {
+    let segment_paths = split_audio_file(Path::new(file_path), segment_dir, TRANSCRIPTION_SEGMENT_SECS)?;
+
+    let mut merged = TranscribeResult {
+        text: String::new(),
+        duration: 0.0,
+        segments: None,
+        verbose_segments: None,
+        words: None,
+    };
+
+    for (index, segment_path) in segment_paths.iter().enumerate() {
+        info!("Transcribing segment {}/{}", index + 1, segment_paths.len());
+
+        let segment_file = segment_path.to_string_lossy().to_string();
+        let segment_data = fs::read(segment_path)
+            .map_err(|e| format!("Failed to read audio segment: {}", e))?;
+
+        let part = transcribe_single_file(
+            &segment_file, segment_data, language, api_key, speaker_names, model, response_format, prompt,
+        ).await
+            .map_err(|e| format!("Segment {}/{}: {}", index + 1, segment_paths.len(), e))?;
+
+        let offset = (index as u64 * TRANSCRIPTION_SEGMENT_SECS) as f64;
+        append_segment_transcription(&mut merged, part, offset, index == 0, trim);
+    }
+
+    info!("Segmented transcription complete. Text: {} chars, Duration: {:.0}s",
+        merged.text.len(), merged.duration);
+
+    Ok(merged)
+}
+
+/// Length of the audio sample sent for language detection
+const LANGUAGE_DETECTION_SAMPLE_SECS: u64 = 30;
+
+/// Detections below this confidence carry a warning and aren't used automatically
+const LANGUAGE_DETECTION_MIN_CONFIDENCE: f64 = 0.6;
+
+#[derive(Debug, Serialize)]
+pub(crate) struct LanguageDetection {
+    /// ISO 639-1 code where known (e.g. "en"), otherwise the name Whisper reported
+    language_code: String,
+    /// 0.0 - 1.0, estimated from the segment log-probabilities
+    confidence: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warning: Option<String>,
+}
+
+/// Map the language name Whisper reports in verbose_json (e.g. "english") to its ISO 639-1 code
+fn whisper_language_code(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    let code = match name.as_str() {
+        "english" => "en",
+        "spanish" => "es",
+        "french" => "fr",
+        "german" => "de",
+        "italian" => "it",
+        "portuguese" => "pt",
+        "dutch" => "nl",
+        "swedish" => "sv",
+        "norwegian" => "no",
+        "danish" => "da",
+        "finnish" => "fi",
+        "polish" => "pl",
+        "czech" => "cs",
+        "greek" => "el",
+        "turkish" => "tr",
+        "russian" => "ru",
+        "ukrainian" => "uk",
+        "arabic" => "ar",
+        "hebrew" => "he",
+        "hindi" => "hi",
+        "chinese" => "zh",
+        "japanese" => "ja",
+        "korean" => "ko",
+        "vietnamese" => "vi",
+        "thai" => "th",
+        "indonesian" => "id",
+        "malay" => "ms",
+        "tagalog" => "tl",
+        _ => return name,
+    };
+    code.to_string()
+}
+
+/// Read the detected language from a whisper-1 verbose_json response.
+/// Confidence is exp(mean avg_logprob) scaled down by the mean no-speech probability.
+fn parse_language_detection(response: &serde_json::Value) -> Result<LanguageDetection, String> {
+    let language = response["language"].as_str()
+        .filter(|l| !l.trim().is_empty())
+        .ok_or_else(|| "Transcription response did not include a language".to_string())?;
+
+    let segments = response["segments"].as_array().map(Vec::as_slice).unwrap_or(&[]);
+    let mean = |field: &str| {
+        let values: Vec<f64> = segments.iter().filter_map(|s| s[field].as_f64()).collect();
+        if values.is_empty() { None } else { Some(values.iter().sum::<f64>() / values.len() as f64) }
+    };
+    let confidence = match mean("avg_logprob") {
+        Some(logprob) => (logprob.exp() * (1.0 - mean("no_speech_prob").unwrap_or(0.0))).clamp(0.0, 1.0),
+        None => 0.0,
+    };
+
+    let language_code = whisper_language_code(language);
+    let warning = (confidence < LANGUAGE_DETECTION_MIN_CONFIDENCE).then(|| format!(
+        "Low confidence ({:.0}%) detecting language '{}'; the recording may be mixed-language or mostly silence",
+        confidence * 100.0,
+        language_code
+    ));
+
+    Ok(LanguageDetection { language_code, confidence, warning })
+}
+
+async fn detect_language_with_key(sample_bytes: Vec<u8>, sample_mime: &str, api_key: &str) -> Result<LanguageDetection, String> {
+    const MAX_SAMPLE_SIZE: usize = TRANSCRIPTION_MAX_UPLOAD_MB as usize * 1024 * 1024;
+    if sample_bytes.is_empty() {
+        return Err("Audio sample is empty".to_string());
+    }
+    if sample_bytes.len() > MAX_SAMPLE_SIZE {
+        return Err("Audio sample exceeds the 25MB API limit - send only the first 30 seconds".to_string());
+    }
+
+    let extension = match sample_mime {
+        "audio/x-wav" => "wav",
+        other => audio_extension_for_mime(other).unwrap_or("mp3"),
+    };
+    let part = reqwest::multipart::Part::bytes(sample_bytes)
+        .file_name(format!("sample.{}", extension))
+        .mime_str(sample_mime)
+        .map_err(|e| format!("Failed to set MIME type: {}", e))?;
+
+    // No language or prompt, so Whisper detects the language itself
+    let form = reqwest::multipart::Form::new()
+        .text("model", "whisper-1")
+        .text("response_format", "verbose_json")
+        .part("file", part);
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(60))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+    let response = client
+        .post("https://api.openai.com/v1/audio/transcriptions")
+        .header("Authorization", format!("Bearer {}", api_key))
+        .multipart(form)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send request to OpenAI: {}", e))?;
+
+    if !response.status().is_success() {
+        let error_text = response.text().await
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(format!("OpenAI API error: {}", error_text));
+    }
+
+    let response_json: serde_json::Value = response.json().await
+        .map_err(|e| format!("Failed to parse response: {}", e))?;
+    parse_language_detection(&response_json)
+}
+
+/// Detect the spoken language of a short (~30 second) audio sample with Whisper
+#[tauri::command]
+pub(crate) async fn detect_language(sample_bytes: Vec<u8>, sample_mime: String) -> Result<LanguageDetection, String> {
+    let api_key = resolve_openai_api_key(None)?;
+    let detection = detect_language_with_key(sample_bytes, &sample_mime, &api_key).await?;
+    info!("Detected language: {} ({:.0}% confidence)", detection.language_code, detection.confidence * 100.0);
+    Ok(detection)
+}
+
+/// Cut the first `seconds` of a recording to a small mono MP3 with FFmpeg
+fn extract_audio_clip(path: &Path, clip_path: &Path, seconds: u64) -> Result<(), String> {
+    let output = Command::new("ffmpeg")
+        .args([
+            "-t", &seconds.to_string(),
+            "-i", &path.to_string_lossy(),
+            "-ac", "1",           // mono
+            "-ab", "64k",         // 64kbps
+            "-ar", "16000",       // 16kHz
+            "-y",
+            &clip_path.to_string_lossy(),
+        ])
+        .output()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("FFmpeg clip extraction failed: {}", stderr));
+    }
+    Ok(())
+}
+
+/// Pick a transcription language from the first 30 seconds of the recording.
+/// Returns None (let the transcription API detect per request) if FFmpeg is missing,
+/// detection fails, or the result isn't confident enough.
+async fn auto_detect_language(file_path: &str, api_key: &str) -> Option<String> {
+    if check_ffmpeg().is_err() {
+        info!("FFmpeg not available - leaving language detection to the transcription API");
+        return None;
+    }
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let clip_path = std::env::temp_dir()
+        .join("PBS_Admin")
+        .join(format!("language_sample_{}.mp3", timestamp));
+
+    let detection = async {
+        if let Some(parent) = clip_path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create temp directory: {}", e))?;
+        }
+        extract_audio_clip(Path::new(file_path), &clip_path, LANGUAGE_DETECTION_SAMPLE_SECS)?;
+        let sample = fs::read(&clip_path)
+            .map_err(|e| format!("Failed to read audio sample: {}", e))?;
+        detect_language_with_key(sample, "audio/mpeg", api_key).await
+    }.await;
+    let _ = fs::remove_file(&clip_path);
+
+    match detection {
+        Ok(detection) if detection.warning.is_none() => {
+            info!("Auto-detected language: {} ({:.0}% confidence)", detection.language_code, detection.confidence * 100.0);
+            Some(detection.language_code)
+        }
+        Ok(detection) => {
+            warn!("{}", detection.warning.unwrap_or_default());
+            None
+        }
+        Err(e) => {
+            warn!("Language detection failed: {}", e);
+            None
+        }
+    }
+}
+
+/// Whisper only reads roughly the last 224 tokens of a prompt; keep well under that
+const MAX_CLINICAL_PROMPT_CHARS: usize = 800;
+
+/// Build a Whisper prompt listing clinical terms (diagnoses, medications, ...) so they're
+/// spelled correctly. Blank and duplicate terms are dropped; the list is cut at a term boundary.
+fn build_clinical_prompt(terms: Vec<String>) -> String {
+    const PREFIX: &str = "Veterinary behaviour consultation. Terms: ";
+
+    let mut seen = std::collections::HashSet::new();
+    let mut prompt = String::from(PREFIX);
+    for term in terms.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+        if !seen.insert(term.to_lowercase()) {
+            continue;
+        }
+        let separator = if prompt.len() > PREFIX.len() { ", " } else { "" };
+        if prompt.len() + separator.len() + term.len() + 1 > MAX_CLINICAL_PROMPT_CHARS {
+            break;
+        }
+        prompt.push_str(separator);
+        prompt.push_str(term);
+    }
+
+    if prompt.len() == PREFIX.len() {
+        return String::new();
+    }
+    prompt.push('.');
+    prompt
+}
+
+/// Parse clinical_terms.txt: one term per line, blank lines and `#` comments ignored
+fn parse_clinical_terms(content: &str) -> Vec<String> {
+    content.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Read clinical_terms.txt from `dir` (empty if missing)
+pub(crate) fn read_clinical_terms_in(dir: &Path) -> Result<Vec<String>, String> {
+    let path = dir.join("clinical_terms.txt");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read clinical terms: {}", e))?;
+    Ok(parse_clinical_terms(&content))
+}
+
+/// Read the user's clinical vocabulary from clinical_terms.txt in the data directory (empty if missing)
+#[tauri::command]
+pub(crate) fn get_saved_clinical_terms() -> Result<Vec<String>, String> {
+    read_clinical_terms_in(&resolve_pbs_admin_data_dir()?)
+}
+
+/// Transcribe audio using OpenAI gpt-4o-transcribe-diarize API
+/// Uses native speaker diarization - no separate Claude call needed.
+/// Files over the 25MB API limit are split into overlapping 10-minute segments
+/// with FFmpeg (when installed) and transcribed sequentially.
+/// Without a `language`, it's detected from the first 30 seconds.
+/// `prompt` (whisper-1 / verbose_json only) biases spelling of clinical terms.
+#[tauri::command]
+pub(crate) async fn transcribe_audio(
+    file_path: String,
+    language: Option<String>,
+    api_key: Option<String>,
+    speaker_names: Option<Vec<String>>,
+    response_format: Option<String>,
+    overlap_trim: Option<String>,
+    prompt: Option<String>,
+) -> Result<TranscribeResult, String> {
+    // verbose_json (segment + word timestamps) is only offered by whisper-1;
+    // everything else goes through the diarization model
+    let response_format = response_format.unwrap_or_else(|| "diarized_json".to_string());
+    let model = match response_format.as_str() {
+        "diarized_json" | "json" | "text" => "gpt-4o-transcribe-diarize",
+        "verbose_json" => "whisper-1",
+        other => return Err(format!(
+            "Unsupported response format: {}. Use diarized_json, verbose_json, json, or text.",
+            other
+        )),
+    };
+    let trim = OverlapTrim::parse(overlap_trim.as_deref())?;
+
+    // Rate limit: 1 transcription per 30 seconds
+    check_rate_limit("transcription", Duration::from_secs(30))?;
+
+    info!("Transcribing audio file: {}", file_path);
+
+    // Use provided API key, then the OS keychain, then the (deprecated) environment variable
+    let api_key = resolve_openai_api_key(api_key)?;
+
+    // Detect once up front so every segment of a long recording uses the same language
+    let language = match language.filter(|l| !l.trim().is_empty()) {
+        Some(language) => Some(language),
+        None => auto_detect_language(&file_path, &api_key).await,
+    };
+
+    // Read audio file
+    let file_data = fs::read(&file_path)
+        .map_err(|e| format!("Failed to read audio file: {}", e))?;
+
+    let file_size = file_data.len();
+    info!("Audio file size: {} bytes ({:.1} MB)", file_size, file_size as f64 / 1_048_576.0);
+
+    // Check file size limit (OpenAI API has 25MB limit per request)
+    const MAX_FILE_SIZE: usize = TRANSCRIPTION_MAX_UPLOAD_MB as usize * 1024 * 1024; // 25MB
+    if file_size > MAX_FILE_SIZE {
+        let mb = file_size as f64 / 1_048_576.0;
+
+        // Without FFmpeg we can't split here - let the frontend handle it
+        if check_ffmpeg().is_err() {
+            return Err(format!(
+                "CHUNK_REQUIRED:{:.1}",
+                mb
+            ));
+        }
+        drop(file_data);
+
+        info!("File exceeds 25MB ({:.1} MB) - splitting into segments", mb);
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let segment_dir = std::env::temp_dir()
+            .join("PBS_Admin")
+            .join(format!("transcribe_segments_{}", timestamp));
+
+        let result = transcribe_in_segments(
+            &file_path,
+            &segment_dir,
+            language.as_deref(),
+            &api_key,
+            speaker_names.as_deref(),
+            model,
+            &response_format,
+            trim,
+            prompt.as_deref(),
+        ).await;
+
+        // Always clean up temp segments, even if a segment failed
+        let _ = fs::remove_dir_all(&segment_dir);
+
+        return result;
+    }
+
+    transcribe_single_file(
+        &file_path,
+        file_data,
+        language.as_deref(),
+        &api_key,
+        speaker_names.as_deref(),
+        model,
+        &response_format,
+        prompt.as_deref(),
+    ).await
+}
+
+/// Connection settings for Azure Speech Services speech-to-text
+#[derive(Debug, Deserialize)]
+pub(crate) struct AzureTranscribeConfig {
+    /// Full endpoint URL (leave empty to derive it from `region`)
+    endpoint: String,
+    api_key: String,
+    region: String,
+    language: String,
+}
+
+/// Parse an Azure speech-to-text (short audio, simple format) response.
+/// `Duration` is reported in 100-nanosecond ticks.
+fn parse_azure_transcription_response(response_json: &serde_json::Value) -> Result<TranscribeResult, String> {
+    let status = response_json["RecognitionStatus"].as_str().unwrap_or("Unknown");
+    if status != "Success" {
+        return Err(format!("Azure recognition failed: {}", status));
+    }
+
+    let text = response_json["DisplayText"]
+        .as_str()
+        .unwrap_or("")
+        .to_string();
+    let duration = response_json["Duration"].as_f64().unwrap_or(0.0) / 10_000_000.0;
+
+    Ok(TranscribeResult { text, duration, segments: None, verbose_segments: None, words: None })
+}
+
+/// Transcribe audio with Azure Speech Services (speech-to-text REST API for short audio).
+/// The endpoint takes the raw WAV/OGG body rather than a multipart form.
+#[tauri::command]
+pub(crate) async fn transcribe_audio_azure(
+    file_path: String,
+    config: AzureTranscribeConfig,
+) -> Result<TranscribeResult, String> {
+    // Rate limit: 1 transcription per 30 seconds
+    check_rate_limit("azure transcription", Duration::from_secs(30))?;
+
+    if config.api_key.is_empty() {
+        return Err("Azure Speech API key cannot be empty".to_string());
+    }
+
+    let endpoint = if config.endpoint.trim().is_empty() {
+        if config.region.trim().is_empty() {
+            return Err("Azure endpoint or region must be provided".to_string());
+        }
+        format!(
+            "https://{}.stt.speech.microsoft.com/speech/recognition/conversation/cognitiveservices/v1",
+            config.region.trim()
+        )
+    } else {
+        config.endpoint.trim().trim_end_matches('/').to_string()
+    };
+
+    // The short-audio API only accepts WAV (PCM) and OGG (Opus)
+    let lower = file_path.to_lowercase();
+    let content_type = if lower.ends_with(".wav") {
+        "audio/wav; codecs=audio/pcm; samplerate=16000"
+    } else if lower.ends_with(".ogg") {
+        "audio/ogg; codecs=opus"
+    } else {
+        return Err("Azure transcription supports WAV or OGG audio only".to_string());
+    };
+
+    info!("Transcribing audio file with Azure: {}", file_path);
+
+    let file_data = fs::read(&file_path)
+        .map_err(|e| format!("Failed to read audio file: {}", e))?;
+
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(300))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+    let response = client
+        .post(&endpoint)
+        .query(&[("language", config.language.as_str()), ("format", "simple")])
+        .header("Ocp-Apim-Subscription-Key", &config.api_key)
+        .header("Content-Type", content_type)
+        .header("Accept", "application/json")
+        .body(file_data)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send request to Azure: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(format!("Azure Speech API error ({}): {}", status, error_text));
+    }
+
+    let response_json: serde_json::Value = response.json().await
+        .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+    let result = parse_azure_transcription_response(&response_json)?;
+    info!("Azure transcription complete. Text: {} chars, Duration: {:.0}s",
+        result.text.len(), result.duration);
+
+    Ok(result)
+}
+
+/// Check that `model_path` exists and looks like a whisper.cpp GGML model
+#[cfg(any(feature = "local-whisper", test))]
+fn validate_ggml_model(model_path: &Path) -> Result<(), String> {
+    use std::io::Read;
+
+    if !model_path.is_file() {
+        return Err(format!("Whisper model file not found: {}", model_path.display()));
+    }
+
+    let mut magic = [0u8; 4];
+    let mut file = fs::File::open(model_path)
+        .map_err(|e| format!("Failed to open Whisper model: {}", e))?;
+    file.read_exact(&mut magic)
+        .map_err(|_| format!("Invalid Whisper model (file too small): {}", model_path.display()))?;
+
+    // whisper.cpp writes the magic 0x67676d6c as a little-endian u32 ("lmgg" on disk)
+    if &magic != b"ggml" && &magic != b"lmgg" {
+        return Err(format!(
+            "Invalid Whisper model (not a GGML file): {}",
+            model_path.display()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Convert any audio file to 16 kHz mono 16-bit WAV (the input whisper.cpp expects)
+#[cfg(any(feature = "local-whisper", test))]
+fn convert_to_whisper_wav(input_path: &Path, output_path: &Path) -> Result<(), String> {
+    let output = Command::new("ffmpeg")
+        .args([
+            "-i", &input_path.to_string_lossy(),
+            "-ar", "16000",       // 16kHz
+            "-ac", "1",           // mono
+            "-c:a", "pcm_s16le",
+            "-y",
+            &output_path.to_string_lossy(),
+        ])
+        .output()
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("FFmpeg conversion failed: {}", stderr));
+    }
+
+    Ok(())
+}
+
+/// Read the samples of a 16-bit PCM mono WAV file as f32 in [-1.0, 1.0]
+#[cfg(any(feature = "local-whisper", test))]
+fn read_wav_samples(path: &Path) -> Result<Vec<f32>, String> {
+    let data = fs::read(path)
+        .map_err(|e| format!("Failed to read WAV file: {}", e))?;
+
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err("Invalid WAV file".to_string());
+    }
+
+    // Walk the RIFF chunks looking for the sample data
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let chunk_id = &data[pos..pos + 4];
+        let chunk_len = u32::from_le_bytes([data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]]) as usize;
+        let body_start = pos + 8;
+        let body_end = (body_start + chunk_len).min(data.len());
+
+        if chunk_id == b"data" {
+            return Ok(data[body_start..body_end]
+                .chunks_exact(2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+                .collect());
+        }
+
+        // Chunks are padded to an even length
+        pos = body_start + chunk_len + (chunk_len & 1);
+    }
+
+    Err("WAV file has no data chunk".to_string())
+}
+
+/// Run whisper.cpp over 16 kHz mono samples
+#[cfg(feature = "local-whisper")]
+fn run_whisper_inference(model_path: &Path, samples: &[f32], language: &str) -> Result<TranscribeResult, String> {
+    use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+    let model_path = model_path.to_string_lossy();
+
+    let ctx = WhisperContext::new_with_params(&model_path, WhisperContextParameters::default())
+        .map_err(|e| format!("Failed to load Whisper model: {}", e))?;
+    let mut state = ctx.create_state()
+        .map_err(|e| format!("Failed to create Whisper state: {}", e))?;
+
+    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+    params.set_language(Some(if language.is_empty() { "auto" } else { language }));
+    params.set_print_special(false);
+    params.set_print_progress(false);
+    params.set_print_realtime(false);
+    params.set_print_timestamps(false);
+
+    state.full(params, samples)
+        .map_err(|e| format!("Whisper inference failed: {}", e))?;
+
+    let segment_count = state.full_n_segments()
+        .map_err(|e| format!("Failed to read Whisper output: {}", e))?;
+
+    let mut segments = Vec::new();
+    for i in 0..segment_count {
+        let text = state.full_get_segment_text(i)
+            .map_err(|e| format!("Failed to read Whisper segment: {}", e))?;
+        // whisper.cpp timestamps are in centiseconds
+        let start = state.full_get_segment_t0(i)
+            .map_err(|e| format!("Failed to read Whisper segment: {}", e))? as f64 / 100.0;
+        let end = state.full_get_segment_t1(i)
+            .map_err(|e| format!("Failed to read Whisper segment: {}", e))? as f64 / 100.0;
+        segments.push(Segment { start, end, text: text.trim().to_string() });
+    }
+
+    let text = segments.iter()
+        .map(|s| s.text.as_str())
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let duration = samples.len() as f64 / 16000.0;
+
+    Ok(TranscribeResult { text, duration, segments: None, verbose_segments: Some(segments), words: None })
+}
+
+/// Removes the temporary WAV when dropped, so it is cleaned up on every exit path (panics included)
+#[cfg(feature = "local-whisper")]
+struct TempWavFile(PathBuf);
+
+#[cfg(feature = "local-whisper")]
+impl Drop for TempWavFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Transcribe audio offline with a local whisper.cpp GGML model.
+/// Audio is converted to 16 kHz mono WAV with FFmpeg first.
+#[tauri::command]
+pub(crate) async fn transcribe_audio_local(
+    file_path: String,
+    model_path: String,
+    language: String,
+) -> Result<TranscribeResult, String> {
+    transcribe_with_local_whisper(file_path, model_path, language).await
+}
+
+/// Builds without whisper.cpp fail straight away, before any validation or FFmpeg conversion
+#[cfg(not(feature = "local-whisper"))]
+async fn transcribe_with_local_whisper(
+    _file_path: String,
+    _model_path: String,
+    _language: String,
+) -> Result<TranscribeResult, String> {
+    Err("Local transcription is not available in this build (enable the local-whisper feature).".to_string())
+}
+
+#[cfg(feature = "local-whisper")]
+async fn transcribe_with_local_whisper(
+    file_path: String,
+    model_path: String,
+    language: String,
+) -> Result<TranscribeResult, String> {
+    let model = PathBuf::from(&model_path);
+    validate_ggml_model(&model)?;
+
+    let input = PathBuf::from(&file_path);
+    if !input.is_file() {
+        return Err(format!("Audio file not found: {}", file_path));
+    }
+
+    check_ffmpeg()?;
+
+    info!("Transcribing audio locally: {} (model: {})", file_path, model_path);
+
+    tokio::task::spawn_blocking(move || {
+        let temp_dir = std::env::temp_dir().join("PBS_Admin");
+        fs::create_dir_all(&temp_dir)
+            .map_err(|e| format!("Failed to create temp directory: {}", e))?;
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let wav_file = TempWavFile(temp_dir.join(format!("whisper_input_{}.wav", timestamp)));
+
+        let result = convert_to_whisper_wav(&input, &wav_file.0)
+            .and_then(|_| read_wav_samples(&wav_file.0))
+            .and_then(|samples| run_whisper_inference(&model, &samples, &language));
+        drop(wav_file);
+
+        if let Ok(ref transcription) = result {
+            info!("Local transcription complete. Text: {} chars, Duration: {:.0}s",
+                transcription.text.len(), transcription.duration);
+        }
+
+        result
+    })
+    .await
+    .map_err(|e| format!("Local transcription task failed: {}", e))?
+}
+
+/// Format seconds as an SRT timestamp (HH:MM:SS,mmm)
+fn format_srt_timestamp(seconds: f64) -> String {
+    let total_ms = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        total_ms / 3_600_000,
+        (total_ms / 60_000) % 60,
+        (total_ms / 1000) % 60,
+        total_ms % 1000
+    )
+}
+
+/// Build SRT subtitle content from timestamped segments.
+/// Segments with empty text are skipped; zero-length segments are shown for one second.
+fn build_srt(segments: &[Segment]) -> String {
+    let mut srt = String::new();
+    let mut index = 0;
+
+    for segment in segments {
+        let text = segment.text.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        // Players drop cues with no duration, so give them a minimal one
+        let end = if segment.end > segment.start { segment.end } else { segment.start + 1.0 };
+
+        index += 1;
+        srt.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            index,
+            format_srt_timestamp(segment.start),
+            format_srt_timestamp(end),
+            text
+        ));
+    }
+
+    srt
+}
+
+/// Export timestamped transcription segments (from a verbose_json transcription) as an SRT file
+#[tauri::command]
+pub(crate) fn export_transcription_to_srt(segments: Vec<Segment>, output_path: String) -> Result<String, String> {
+    let validated_path = validate_write_path(&output_path)?;
+
+    let is_srt = validated_path
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("srt"))
+        .unwrap_or(false);
+    if !is_srt {
+        warn!("Writing SRT subtitles to a path without a .srt extension: {}", output_path);
+    }
+
+    if let Some(parent) = validated_path.parent() {
+        if !parent.exists() {
+            return Err(format!("Parent directory does not exist: {}", parent.display()));
+        }
+    }
+
+    let srt = build_srt(&segments);
+    write_file_atomically(&validated_path, srt.as_bytes())?;
+
+    info!("Exported {} segments to SRT: {}", segments.len(), output_path);
+
+    Ok(output_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::documents::read_docx_document_xml;
+    use crate::test_support::{
+        http_response, mock_tool, path_string, sample_transcription_metadata, scratch_dir,
+        serve_http, strings, wav_bytes,
+    };
+
+    /// Trimmed whisper-1 `verbose_json` response
+    const VERBOSE_JSON_FIXTURE: &str = r#"{
+        "task": "transcribe",
+        "language": "english",
+        "duration": 8.47,
+        "text": " Hello there. How is Max settling in?",
+        "segments": [
+            {"id": 0, "seek": 0, "start": 0.0, "end": 3.2, "text": " Hello there.", "avg_logprob": -0.2},
+            {"id": 1, "seek": 0, "start": 3.2, "end": 8.47, "text": " How is Max settling in?", "avg_logprob": -0.3},
+            {"id": 2, "text": "missing timings are skipped"}
+        ],
+        "words": [
+            {"word": "Hello", "start": 0.0, "end": 0.42},
+            {"word": "there", "start": 0.42, "end": 0.9}
+        ]
+    }"#;
+
+    #[test]
+    fn verbose_json_response_parsing() {
+        let response: serde_json::Value = serde_json::from_str(VERBOSE_JSON_FIXTURE).unwrap();
+
+        let (text, duration, segments, words) = parse_verbose_json_response(&response);
+
+        assert_eq!(text, "Hello there. How is Max settling in?");
+        assert_eq!(duration, Some(8.47));
+        assert_eq!(segments.len(), 2);
+        assert_eq!((segments[0].start, segments[0].end, segments[0].text.as_str()), (0.0, 3.2, "Hello there."));
+        assert_eq!((segments[1].start, segments[1].end, segments[1].text.as_str()), (3.2, 8.47, "How is Max settling in?"));
+        assert_eq!(words.len(), 2);
+        assert_eq!((words[1].word.as_str(), words[1].start, words[1].end), ("there", 0.42, 0.9));
+    }
+
+    #[test]
+    fn verbose_json_without_details_parses_empty() {
+        let (text, duration, segments, words) = parse_verbose_json_response(&serde_json::json!({"text": "hi"}));
+        assert_eq!(text, "hi");
+        assert_eq!(duration, None);
+        assert!(segments.is_empty() && words.is_empty());
+    }
+
+    #[test]
+    fn whisper_form_requests_timestamps() {
+        let fields = transcription_form_fields("whisper-1", "verbose_json", Some("en"), None, None);
+        assert_eq!(fields, vec![
+            ("model", "whisper-1".to_string()),
+            ("response_format", "verbose_json".to_string()),
+            ("language", "en".to_string()),
+            ("timestamp_granularities[]", "segment".to_string()),
+            ("timestamp_granularities[]", "word".to_string()),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn transcribe_audio_rejects_unknown_response_format() {
+        let err = transcribe_audio(String::new(), None, None, None, Some("srt".to_string()), None, None)
+            .await
+            .err()
+            .unwrap();
+        assert!(err.contains("Unsupported response format: srt"), "{}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn split_audio_file_runs_overlapping_ffmpeg_segments() {
+        let dir = scratch_dir();
+        let ffprobe = mock_tool(dir.path(), "ffprobe", "echo 1250.5");
+        // Records each invocation and creates the output file (the last argument)
+        let ffmpeg = mock_tool(dir.path(), "ffmpeg", r#"
+echo "$@" >> "$(dirname "$0")/ffmpeg_calls.txt"
+for last; do :; done
+: > "$last""#);
+        let audio = dir.path().join("consult.m4a");
+        fs::write(&audio, b"audio").unwrap();
+        let segment_dir = dir.path().join("segments");
+
+        let segments = split_audio_file_with(ffmpeg.as_os_str(), ffprobe.as_os_str(), &audio, &segment_dir, 600).unwrap();
+
+        let names: Vec<String> = segments.iter().map(|p| p.file_name().unwrap().to_string_lossy().to_string()).collect();
+        assert_eq!(names, ["segment_000.mp3", "segment_001.mp3", "segment_002.mp3"]);
+        assert!(segments.iter().all(|p| p.starts_with(&segment_dir) && p.exists()));
+
+        let calls = fs::read_to_string(dir.path().join("ffmpeg_calls.txt")).unwrap();
+        let calls: Vec<&str> = calls.lines().collect();
+        assert_eq!(calls.len(), 3);
+        for (call, start) in calls.iter().zip(["0", "600", "1200"]) {
+            // Each segment carries the 5 s overlap on top of its 600 s
+            assert!(call.starts_with(&format!("-ss {} -t 605 -i {}", start, path_string(&audio))), "{}", call);
+            assert!(call.contains("-ac 1 -ab 64k -ar 16000 -y"), "{}", call);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn split_audio_file_errors() {
+        let dir = scratch_dir();
+        let audio = dir.path().join("consult.m4a");
+        fs::write(&audio, b"audio").unwrap();
+        let ffprobe = mock_tool(dir.path(), "ffprobe", "echo 30");
+        let ffmpeg = mock_tool(dir.path(), "ffmpeg", "echo 'Invalid data found' >&2; exit 1");
+        let segment_dir = dir.path().join("segments");
+
+        let err = split_audio_file_with(ffmpeg.as_os_str(), ffprobe.as_os_str(), &audio, &segment_dir, 0).unwrap_err();
+        assert!(err.contains("greater than zero"));
+        let err = split_audio_file_with(ffmpeg.as_os_str(), ffprobe.as_os_str(), &audio, &segment_dir, 600).unwrap_err();
+        assert!(err.contains("Invalid data found"), "{}", err);
+        let bad_probe = mock_tool(dir.path(), "ffprobe-bad", "echo N/A");
+        let err = split_audio_file_with(ffmpeg.as_os_str(), bad_probe.as_os_str(), &audio, &segment_dir, 600).unwrap_err();
+        assert!(err.contains("Failed to parse duration"), "{}", err);
+    }
+
+    #[test]
+    fn overlap_trimming_drops_repeated_words() {
+        let previous = "The dog barks at visitors when they arrive at the front door";
+        assert_eq!(
+            trim_overlapping_text(previous, "at the Front door! Then he settles quickly."),
+            "Then he settles quickly."
+        );
+        // Two matching words could be coincidence, so nothing is trimmed
+        assert_eq!(trim_overlapping_text(previous, "front door again"), "front door again");
+        assert_eq!(trim_overlapping_text("", "fresh start"), "fresh start");
+    }
+
+    fn transcript_part(text: &str, segments: &[(f64, f64, &str)]) -> TranscribeResult {
+        TranscribeResult {
+            text: text.to_string(),
+            duration: segments.last().map(|s| s.1).unwrap_or(0.0),
+            segments: Some(segments.iter().map(|&(start, end, text)| DiarizedSegment {
+                speaker: "A".to_string(),
+                text: text.to_string(),
+                start,
+                end,
+            }).collect()),
+            verbose_segments: None,
+            words: None,
+        }
+    }
+
+    #[test]
+    fn segment_transcriptions_merge_with_offsets() {
+        let mut merged = transcript_part("", &[]);
+        merged.segments = None;
+
+        append_segment_transcription(
+            &mut merged,
+            transcript_part("Tell me about his walks in the park", &[(0.0, 598.0, "Tell me about"), (598.0, 604.0, "walks in the park")]),
+            0.0, true, OverlapTrim::WordOverlap,
+        );
+        append_segment_transcription(
+            &mut merged,
+            transcript_part("walks in the park. He pulls on the lead", &[(0.0, 4.0, "walks in the park."), (4.0, 12.0, "He pulls on the lead")]),
+            600.0, false, OverlapTrim::WordOverlap,
+        );
+
+        assert_eq!(merged.text, "Tell me about his walks in the park He pulls on the lead");
+        let segments = merged.segments.unwrap();
+        let times: Vec<(f64, f64)> = segments.iter().map(|s| (s.start, s.end)).collect();
+        // The second part's first segment ends inside the 5 s overlap and is dropped
+        assert_eq!(times, [(0.0, 598.0), (598.0, 604.0), (604.0, 612.0)]);
+        assert_eq!(merged.duration, 612.0);
+    }
+
+    #[test]
+    fn overlap_trim_strategy_parsing() {
+        assert!(OverlapTrim::parse(None).unwrap() == OverlapTrim::WordOverlap);
+        assert!(OverlapTrim::parse(Some("none")).unwrap() == OverlapTrim::None);
+        assert!(OverlapTrim::parse(Some("sentences")).is_err());
+    }
+
+    #[test]
+    fn ggml_model_validation() {
+        let dir = scratch_dir();
+        let missing = dir.path().join("ggml-base.en.bin");
+        assert!(validate_ggml_model(&missing).unwrap_err().contains("not found"));
+
+        let tiny = dir.path().join("tiny.bin");
+        fs::write(&tiny, b"gg").unwrap();
+        assert!(validate_ggml_model(&tiny).unwrap_err().contains("too small"));
+
+        let not_ggml = dir.path().join("model.onnx");
+        fs::write(&not_ggml, b"\x08\x07onnx-model").unwrap();
+        assert!(validate_ggml_model(&not_ggml).unwrap_err().contains("not a GGML file"));
+
+        // whisper.cpp stores the magic little-endian, so both byte orders are accepted
+        for (name, magic) in [("be.bin", b"ggml"), ("le.bin", b"lmgg")] {
+            let model = dir.path().join(name);
+            fs::write(&model, [&magic[..], b"rest of model"].concat()).unwrap();
+            validate_ggml_model(&model).unwrap();
+        }
+        assert!(validate_ggml_model(dir.path()).is_err());
+    }
+
+    #[cfg(not(feature = "local-whisper"))]
+    #[tokio::test]
+    async fn local_transcription_without_feature_fails_before_conversion() {
+        let dir = scratch_dir();
+        let audio = dir.path().join("consult.m4a");
+        fs::write(&audio, b"not checked").unwrap();
+        let temp_wavs = || fs::read_dir(std::env::temp_dir().join("PBS_Admin")).map(|entries| {
+            entries.filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().starts_with("whisper_input_")).count()
+        }).unwrap_or(0);
+        let before = temp_wavs();
+
+        // The model doesn't exist either: the feature error wins over every other check
+        let err = transcribe_audio_local(path_string(&audio), path_string(&dir.path().join("ggml.bin")), "en".to_string())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err, "Local transcription is not available in this build (enable the local-whisper feature).");
+        assert_eq!(temp_wavs(), before);
+    }
+
+    #[cfg(feature = "local-whisper")]
+    #[tokio::test]
+    async fn local_transcription_rejects_missing_model_before_inference() {
+        let dir = scratch_dir();
+        let audio = dir.path().join("consult.wav");
+        fs::write(&audio, b"RIFF").unwrap();
+        let model = dir.path().join("ggml-small.bin");
+
+        let err = transcribe_audio_local(path_string(&audio), path_string(&model), "en".to_string())
+            .await
+            .err()
+            .unwrap();
+        assert!(err.contains("Whisper model file not found"), "{}", err);
+
+        fs::write(&model, b"ggml model").unwrap();
+        let err = transcribe_audio_local(path_string(&dir.path().join("missing.wav")), path_string(&model), "en".to_string())
+            .await
+            .err()
+            .unwrap();
+        assert!(err.contains("Audio file not found"), "{}", err);
+    }
+
+    #[test]
+    fn wav_samples_are_read_from_the_data_chunk() {
+        let dir = scratch_dir();
+        let wav = dir.path().join("input.wav");
+        let samples: Vec<u8> = [0i16, 16384, -32768].iter().flat_map(|s| s.to_le_bytes()).collect();
+        // An odd-length chunk before the data checks padding is skipped
+        fs::write(&wav, wav_bytes(&[(b"fmt ", &[0u8; 16]), (b"LIST", b"abc"), (b"data", &samples)])).unwrap();
+
+        assert_eq!(read_wav_samples(&wav).unwrap(), vec![0.0, 0.5, -1.0]);
+
+        fs::write(&wav, wav_bytes(&[(b"fmt ", &[0u8; 16])])).unwrap();
+        assert!(read_wav_samples(&wav).unwrap_err().contains("no data chunk"));
+        fs::write(&wav, b"ID3 not a wav").unwrap();
+        assert_eq!(read_wav_samples(&wav).unwrap_err(), "Invalid WAV file");
+    }
+
+    fn segment(start: f64, end: f64, text: &str) -> Segment {
+        Segment { start, end, text: text.to_string() }
+    }
+
+    #[test]
+    fn srt_timestamps() {
+        assert_eq!(format_srt_timestamp(0.0), "00:00:00,000");
+        assert_eq!(format_srt_timestamp(3.2), "00:00:03,200");
+        assert_eq!(format_srt_timestamp(3725.0456), "01:02:05,046");
+        assert_eq!(format_srt_timestamp(-1.0), "00:00:00,000");
+    }
+
+    #[test]
+    fn srt_export_matches_reference() {
+        let dir = scratch_dir();
+        let output = dir.path().join("consult.srt");
+        let segments = vec![
+            segment(0.0, 3.2, " Hello there."),
+            segment(3.2, 3.2, "Zero length cue"),
+            segment(4.0, 5.0, "   "),
+            segment(61.5, 3725.046, "How is Max?"),
+        ];
+
+        export_transcription_to_srt(segments, path_string(&output)).unwrap();
+
+        let expected = "\
+1
+00:00:00,000 --> 00:00:03,200
+Hello there.
+
+2
+00:00:03,200 --> 00:00:04,200
+Zero length cue
+
+3
+00:01:01,500 --> 01:02:05,046
+How is Max?
+
+";
+        assert_eq!(fs::read_to_string(&output).unwrap(), expected);
+    }
+
+    #[test]
+    fn srt_export_without_srt_extension_still_writes() {
+        let dir = scratch_dir();
+        let output = dir.path().join("consult.txt");
+
+        export_transcription_to_srt(vec![segment(0.0, 1.0, "Hi")], path_string(&output)).unwrap();
+
+        assert!(fs::read_to_string(&output).unwrap().starts_with("1\n00:00:00,000 --> 00:00:01,000\nHi\n"));
+        assert!(export_transcription_to_srt(vec![], "/etc/consult.srt".to_string()).is_err());
+    }
+
+    /// Azure speech-to-text (short audio, simple format) response
+    const AZURE_RESPONSE_FIXTURE: &str = r#"{
+        "RecognitionStatus": "Success",
+        "DisplayText": "Max has been barking at the neighbours.",
+        "Offset": 1800000,
+        "Duration": 42500000
+    }"#;
+
+    #[test]
+    fn azure_response_parsing() {
+        let response: serde_json::Value = serde_json::from_str(AZURE_RESPONSE_FIXTURE).unwrap();
+
+        let result = parse_azure_transcription_response(&response).unwrap();
+
+        assert_eq!(result.text, "Max has been barking at the neighbours.");
+        // 100 ns ticks -> seconds
+        assert_eq!(result.duration, 4.25);
+        assert!(result.segments.is_none());
+
+        let no_match = serde_json::json!({"RecognitionStatus": "NoMatch", "Offset": 0, "Duration": 0});
+        assert_eq!(parse_azure_transcription_response(&no_match).err().unwrap(), "Azure recognition failed: NoMatch");
+    }
+
+    #[tokio::test]
+    async fn azure_transcription_posts_audio_with_subscription_key() {
+        let url = serve_http(|request| {
+            let lower = request.to_ascii_lowercase();
+            let ok = request.starts_with("POST /stt?language=en-AU&format=simple ")
+                && lower.contains("ocp-apim-subscription-key: azure-key")
+                && lower.contains("content-type: audio/wav");
+            if ok {
+                http_response(request, "200 OK", &[("Content-Type", "application/json".to_string())], AZURE_RESPONSE_FIXTURE.as_bytes())
+            } else {
+                http_response(request, "400 Bad Request", &[], request.as_bytes())
+            }
+        });
+        let dir = scratch_dir();
+        let audio = dir.path().join("consult.wav");
+        fs::write(&audio, b"RIFF....WAVE").unwrap();
+        let config = AzureTranscribeConfig {
+            endpoint: format!("{}/stt/", url),
+            api_key: "azure-key".to_string(),
+            region: String::new(),
+            language: "en-AU".to_string(),
+        };
+
+        // The only test calling transcribe_audio_azure, so its rate limit is never hit
+        let result = transcribe_audio_azure(path_string(&audio), config).await.unwrap();
+
+        assert_eq!(result.text, "Max has been barking at the neighbours.");
+        assert_eq!(result.duration, 4.25);
+    }
+
+    #[test]
+    fn transcription_markdown_has_header_and_body() {
+        let markdown = build_transcription_markdown("  Hello there.\n", &sample_transcription_metadata());
+        assert_eq!(
+            markdown,
+            "**Client:** Jane Doe\n**Session date:** 2025-01-15\n**Clinician:** Dr Smith\n**Duration:** 1:02:05\n\n## Transcript\n\nHello there.\n"
+        );
+        assert_eq!(format_duration_clock(59.6), "1:00");
+        assert_eq!(format_duration_clock(-5.0), "0:00");
+    }
+
+    #[test]
+    fn export_transcription_as_docx_requires_docx_output() {
+        let dir = scratch_dir();
+        let err = export_transcription_as_docx(
+            "text".to_string(),
+            sample_transcription_metadata(),
+            path_string(&dir.path().join("Transcript.pdf")),
+            None,
+        ).unwrap_err();
+        assert!(err.contains("must be a .docx"), "{}", err);
+    }
+
+    #[test]
+    fn export_transcription_as_docx_with_real_pandoc_when_installed() {
+        if which::which("pandoc").is_err() {
+            return;
+        }
+        let dir = scratch_dir();
+        let output = dir.path().join("Transcript.docx");
+        export_transcription_as_docx("Hello".to_string(), sample_transcription_metadata(), path_string(&output), None).unwrap();
+        assert!(read_docx_document_xml(&output).unwrap().contains("Hello"));
+    }
+
+    /// Trimmed whisper-1 verbose_json response for a 30 second English sample
+    const LANGUAGE_DETECTION_FIXTURE: &str = r#"{
+        "task": "transcribe",
+        "language": "english",
+        "duration": 30.0,
+        "text": "So Max has been barking at the door whenever visitors arrive.",
+        "segments": [
+            {"id": 0, "start": 0.0, "end": 4.2, "text": "So Max has been barking", "avg_logprob": -0.12, "no_speech_prob": 0.01},
+            {"id": 1, "start": 4.2, "end": 9.8, "text": "at the door whenever visitors arrive.", "avg_logprob": -0.18, "no_speech_prob": 0.03}
+        ]
+    }"#;
+
+    #[test]
+    fn language_detection_reads_fixture_response() {
+        let response: serde_json::Value = serde_json::from_str(LANGUAGE_DETECTION_FIXTURE).unwrap();
+        let detection = parse_language_detection(&response).unwrap();
+
+        assert_eq!(detection.language_code, "en");
+        let expected = (-0.15f64).exp() * (1.0 - 0.02);
+        assert!((detection.confidence - expected).abs() < 1e-9, "{}", detection.confidence);
+        assert!(detection.warning.is_none());
+    }
+
+    #[test]
+    fn language_detection_warns_below_threshold() {
+        let mut response: serde_json::Value = serde_json::from_str(LANGUAGE_DETECTION_FIXTURE).unwrap();
+        response["language"] = "welsh".into();
+        response["segments"][0]["avg_logprob"] = (-0.9).into();
+        response["segments"][1]["no_speech_prob"] = 0.6.into();
+
+        let detection = parse_language_detection(&response).unwrap();
+
+        // Unmapped names pass through as reported
+        assert_eq!(detection.language_code, "welsh");
+        assert!(detection.confidence < LANGUAGE_DETECTION_MIN_CONFIDENCE);
+        assert!(detection.warning.unwrap().contains("Low confidence"));
+    }
+
+    #[test]
+    fn language_detection_needs_a_language() {
+        let no_segments = parse_language_detection(&serde_json::json!({"language": "French"})).unwrap();
+        assert_eq!(no_segments.language_code, "fr");
+        assert_eq!(no_segments.confidence, 0.0);
+
+        assert_eq!(
+            parse_language_detection(&serde_json::json!({"text": "hi", "language": " "})).unwrap_err(),
+            "Transcription response did not include a language"
+        );
+    }
+
+    #[tokio::test]
+    async fn detect_language_rejects_bad_samples_before_uploading() {
+        assert_eq!(detect_language_with_key(Vec::new(), "audio/mpeg", "sk-test").await.unwrap_err(), "Audio sample is empty");
+        let oversized = vec![0u8; TRANSCRIPTION_MAX_UPLOAD_MB as usize * 1024 * 1024 + 1];
+        assert!(detect_language_with_key(oversized, "audio/mpeg", "sk-test").await.unwrap_err().contains("25MB"));
+    }
+
+    #[test]
+    fn whisper_form_includes_prompt_when_provided() {
+        let prompt = build_clinical_prompt(strings(&["fluoxetine", "separation anxiety"]));
+        let fields = transcription_form_fields("whisper-1", "verbose_json", None, None, Some(&prompt));
+        assert!(fields.contains(&("prompt", "Veterinary behaviour consultation. Terms: fluoxetine, separation anxiety.".to_string())));
+
+        let blank = transcription_form_fields("whisper-1", "verbose_json", None, None, Some("  "));
+        assert!(blank.iter().all(|(name, _)| *name != "prompt"));
+
+        // The diarization model has no prompt parameter
+        let diarize = transcription_form_fields("gpt-4o-transcribe-diarize", "diarized_json", None, None, Some(&prompt));
+        assert!(diarize.iter().all(|(name, _)| *name != "prompt"));
+    }
+
+    #[test]
+    fn clinical_prompt_drops_blanks_and_duplicates() {
+        assert_eq!(build_clinical_prompt(Vec::new()), "");
+        assert_eq!(build_clinical_prompt(strings(&[" ", ""])), "");
+        assert_eq!(
+            build_clinical_prompt(strings(&["Clomipramine", " clomipramine ", "", "noise phobia"])),
+            "Veterinary behaviour consultation. Terms: Clomipramine, noise phobia."
+        );
+    }
+
+    #[test]
+    fn clinical_prompt_stops_at_a_term_boundary() {
+        let terms: Vec<String> = (0..200).map(|i| format!("term{:03}", i)).collect();
+        let prompt = build_clinical_prompt(terms);
+
+        assert!(prompt.len() <= MAX_CLINICAL_PROMPT_CHARS, "{}", prompt.len());
+        assert!(prompt.ends_with('.'));
+        let last = prompt.trim_end_matches('.').rsplit(", ").next().unwrap();
+        assert_eq!(last.len(), "term000".len());
+    }
+
+    #[test]
+    fn saved_clinical_terms_skip_comments() {
+        let dir = scratch_dir();
+        assert!(read_clinical_terms_in(dir.path()).unwrap().is_empty());
+
+        fs::write(dir.path().join("clinical_terms.txt"), "# medications\nfluoxetine\n\n  trazodone  \r\n#diagnoses\nresource guarding\n").unwrap();
+        assert_eq!(read_clinical_terms_in(dir.path()).unwrap(), strings(&["fluoxetine", "trazodone", "resource guarding"]));
+    }
+}
@@ -183,6 +183,7 @@ export async function checkTemplateExists(templateName: string): Promise<boolean
     const files = await invoke<string[]>("list_files", {
       directory: templatesPath,
       pattern: templateName,
+      recursive: false,
     });
 
     return files.length > 0;
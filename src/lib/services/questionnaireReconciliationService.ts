@@ -107,7 +107,8 @@ export async function findQuestionnaireFile(
   try {
     const files = await invoke<string[]>('list_files', {
       directory: folderPath,
-      pattern: `questionnaire_${submissionId}`,
+      pattern: `questionnaire_${submissionId}*`,
+      recursive: false,
     });
     const jsonFiles = files.filter((f) => f.endsWith('.json'));
     if (jsonFiles.length === 0) {